@@ -0,0 +1,335 @@
+//! Active abilities. On top of their weapons, which fire on their own, each
+//! player carries one ability, picked in the menu before the run, that goes
+//! off on their ability key and then takes a long while to come back. A
+//! shockwave throws back every monster around the player and a second wind
+//! patches them up. The HUD shows how long until each is ready.
+
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::health::Health;
+use crate::input::{Action, Controls};
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::spatial::SpatialIndex;
+use crate::{Player, Position};
+
+
+const SHOCKWAVE_COOLDOWN_SECONDS: f32 = 30.0;
+const SHOCKWAVE_RADIUS: f32           = 200.0;
+const SHOCKWAVE_PUSH_SPEED: f32       = 600.0;
+const SHOCKWAVE_PUSH_SECONDS: f32     = 0.4;
+const SHOCKWAVE_RING_SECONDS: f32     = 0.4;
+const SHOCKWAVE_RING_DEPTH: f32       = 15.0;
+
+const SECOND_WIND_COOLDOWN_SECONDS: f32 = 45.0;
+const SECOND_WIND_HEAL: f32             = 40.0;
+
+// When the autopilot casts
+const AUTOPILOT_SHOCKWAVE_CROWD: usize = 8;
+const AUTOPILOT_HEAL_FRACTION: f32     = 0.5;
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+const HUD_FONT_SIZE: f32   = 20.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Ability {
+    Shockwave,
+    SecondWind,
+}
+
+impl Ability {
+    const ALL: [Ability; 2] = [Ability::Shockwave, Ability::SecondWind];
+
+    fn name(self) -> &'static str {
+        match self {
+            Ability::Shockwave => "shockwave",
+            Ability::SecondWind => "second wind",
+        }
+    }
+
+    fn next(self) -> Ability {
+        let i = Ability::ALL.iter().position(|&a| a == self).unwrap_or_default();
+        Ability::ALL[(i + 1) % Ability::ALL.len()]
+    }
+
+    fn cooldown_seconds(self) -> f32 {
+        match self {
+            Ability::Shockwave => SHOCKWAVE_COOLDOWN_SECONDS,
+            Ability::SecondWind => SECOND_WIND_COOLDOWN_SECONDS,
+        }
+    }
+}
+
+/// The ability picked for the next run. Kept between runs.
+pub struct ChosenAbility(pub Ability);
+
+/// A player's ability and how long until they can use it again.
+#[derive(Component)]
+pub struct AbilitySlot {
+    ability: Ability,
+    cooldown: Timer,
+}
+
+impl AbilitySlot {
+    fn new(ability: Ability) -> AbilitySlot {
+        // Ready from the start of the run
+        let mut cooldown = Timer::from_seconds(ability.cooldown_seconds(), false);
+        cooldown.set_elapsed(cooldown.duration());
+
+        AbilitySlot{
+            ability,
+            cooldown,
+        }
+    }
+
+    fn label(&self) -> String {
+        if self.cooldown.finished() {
+            format!("{}: ready", self.ability.name())
+        }
+        else {
+            let remaining = self.cooldown.duration().as_secs_f32() - self.cooldown.elapsed_secs();
+            format!("{}: {}s", self.ability.name(), remaining.ceil() as u32)
+        }
+    }
+}
+
+/// Pushes a monster straight away from a shockwave or a thorns pulse, easing
+/// off as it goes.
+#[derive(Component)]
+pub struct Knockback {
+    velocity: Vec2,
+    timer: Timer,
+}
+
+#[derive(Component)]
+pub struct ShockwaveRing(Timer);
+
+#[derive(Component)]
+pub struct AbilityPanelText;
+
+#[derive(Component)]
+pub struct AbilityHud;
+
+fn panel_label(ability: Ability) -> String {
+    format!("ability: {} (Q)", ability.name())
+}
+
+pub fn spawn_ability_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    chosen: Res<ChosenAbility>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(36.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(chosen.0),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(AbilityPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn ability_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut chosen: ResMut<ChosenAbility>,
+    mut texts: Query<&mut Text, With<AbilityPanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Q) {
+        return;
+    }
+
+    chosen.0 = chosen.0.next();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(chosen.0);
+    }
+}
+
+pub fn attach_ability_slots(
+    mut commands: Commands,
+    chosen: Res<ChosenAbility>,
+    players: Query<Entity, Added<Player>>,
+) {
+    for player in players.iter() {
+        commands.entity(player).insert(AbilitySlot::new(chosen.0));
+    }
+}
+
+pub fn cast_abilities(
+    time: Res<Time>,
+    controls: Controls,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    index: Res<SpatialIndex>,
+    mut players: Query<(&Player, &Position, &mut AbilitySlot, &mut Health, Option<&Autopilot>)>,
+) {
+    for (player, p, mut slot, mut health, autopilot) in players.iter_mut() {
+        if !slot.cooldown.tick(time.delta()).finished() {
+            continue;
+        }
+
+        // The autopilot saves each ability for when it's worth it
+        let does_cast = if autopilot.is_some() {
+            match slot.ability {
+                Ability::Shockwave => {
+                    index.overlap_circle(p.current, SHOCKWAVE_RADIUS).count() >= AUTOPILOT_SHOCKWAVE_CROWD
+                }
+                Ability::SecondWind => health.fraction() < AUTOPILOT_HEAL_FRACTION,
+            }
+        }
+        else {
+            controls.just_pressed(player.index, Action::UseAbility)
+        };
+        if !does_cast {
+            continue;
+        }
+
+        slot.cooldown.reset();
+        match slot.ability {
+            Ability::Shockwave => {
+                knock_back(&mut commands, &index, p.current, SHOCKWAVE_RADIUS, SHOCKWAVE_PUSH_SPEED);
+
+                commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: sprites.atlas(),
+                        sprite: TextureAtlasSprite{
+                            index: sprites.blast(),
+                            color: Color::rgba(0.8, 0.9, 1.0, 0.6),
+                            custom_size: Some(Vec2::ZERO),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_xyz(0.0, 0.0, SHOCKWAVE_RING_DEPTH),
+                        ..Default::default()
+                    })
+                    .insert(ShockwaveRing(Timer::from_seconds(SHOCKWAVE_RING_SECONDS, false)))
+                    .insert(Position::new(p.current));
+            }
+            Ability::SecondWind => health.heal(SECOND_WIND_HEAL),
+        }
+    }
+}
+
+/// Throw every monster within `radius` of `center` away from it, harder the
+/// closer they stand.
+pub fn knock_back(commands: &mut Commands, index: &SpatialIndex, center: Vec2, radius: f32, speed: f32) {
+    for (monster, monster_p) in index.overlap_circle(center, radius) {
+        let away = (monster_p - center).normalize_or_zero();
+        let strength = 1.0 - monster_p.distance(center) / radius;
+        commands.entity(monster).insert(Knockback{
+            velocity: away * speed * strength.max(0.0),
+            timer: Timer::from_seconds(SHOCKWAVE_PUSH_SECONDS, false),
+        });
+    }
+}
+
+pub fn apply_knockback(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut knocked: Query<(Entity, &mut Knockback, &mut Position)>,
+) {
+    for (entity, mut knockback, mut p) in knocked.iter_mut() {
+        let easing = 1.0 - knockback.timer.percent();
+        p.apply(knockback.velocity * easing * time.delta_seconds());
+
+        if knockback.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+pub fn expand_shockwaves(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut rings: Query<(Entity, &mut ShockwaveRing, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut ring, mut sprite) in rings.iter_mut() {
+        if ring.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let progress = ring.0.percent();
+        sprite.custom_size = Some(Vec2::splat(SHOCKWAVE_RADIUS * 2.0 * progress));
+        sprite.color.set_a(0.6 * (1.0 - progress));
+    }
+}
+
+pub fn spawn_ability_hud(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(12.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: assets.font(),
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(AbilityHud);
+}
+
+pub fn despawn_ability_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<AbilityHud>>,
+) {
+    for hud in huds.iter() {
+        commands.entity(hud).despawn_recursive();
+    }
+}
+
+pub fn update_ability_hud(
+    players: Query<(&Player, &AbilitySlot)>,
+    mut huds: Query<&mut Text, With<AbilityHud>>,
+) {
+    let mut slots: Vec<_> = players.iter().collect();
+    slots.sort_by_key(|(player, _)| player.index);
+
+    // Name each player only when there's more than one
+    let label = slots
+        .iter()
+        .map(|(player, slot)| {
+            if slots.len() > 1 {
+                format!("P{} {}", player.index + 1, slot.label())
+            }
+            else {
+                slot.label()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    for mut text in huds.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+}
@@ -0,0 +1,212 @@
+//! Adaptive difficulty, switched on in the menu with Y. It leaves the
+//! difficulty presets alone and instead checks on the run every few seconds:
+//! how the players' health is holding up and how fast they clear monsters
+//! next to how fast they arrive. Players who are struggling get the
+//! director's pressure eased off, and those coasting get it back. Pressure
+//! scales the steady spawn rate and how often formations and hordes come,
+//! and stays within bounds so it can soften a run but never take it over.
+//! Monsters folding into hordes don't count as arrivals, and a horde counts
+//! as one kill, since it takes several blasts to clear. Daily runs are played
+//! without it, so everyone on the day's board faces the same pressure.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::health::Health;
+use crate::daily::Daily;
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::storage::{self, Versioned};
+use crate::{MonsterStats, NewGameEvent, Player};
+
+
+const SETTING_FILE: &str = "adaptive.json";
+
+const CHECK_SECONDS: f32 = 5.0;
+const MIN_PRESSURE: f32  = 0.6;
+const MAX_PRESSURE: f32  = 1.2;
+const PRESSURE_STEP: f32 = 0.05;
+/// Health under which players are struggling however it's trending.
+const LOW_HEALTH: f32    = 0.35;
+/// Health over which players with nothing else going wrong are coasting.
+const HIGH_HEALTH: f32   = 0.8;
+/// Health lost between checks, as a share of max health, that's too fast.
+const HEALTH_DROP: f32   = 0.15;
+/// The share of arriving monsters players have to clear to keep up.
+const KEEP_UP_SHARE: f32 = 0.5;
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Adaptive {
+    #[default]
+    Off,
+    On,
+}
+
+impl Versioned for Adaptive {
+    const MIGRATIONS: &'static [storage::Migration] = &[];
+}
+
+impl Adaptive {
+    pub fn load() -> Adaptive {
+        if !storage::exists(SETTING_FILE) {
+            return Adaptive::default();
+        }
+
+        storage::load(SETTING_FILE).unwrap_or_else(|e| {
+            warn!("ignoring {}: {}", SETTING_FILE, e);
+            Adaptive::default()
+        })
+    }
+
+    fn save(self) {
+        if let Err(e) = storage::save(SETTING_FILE, &self) {
+            warn!("failed to save {}: {}", SETTING_FILE, e);
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Adaptive::Off => "off",
+            Adaptive::On => "on",
+        }
+    }
+
+    fn toggled(self) -> Adaptive {
+        match self {
+            Adaptive::Off => Adaptive::On,
+            Adaptive::On => Adaptive::Off,
+        }
+    }
+}
+
+/// How hard the director pushes this run, next to what the preset says.
+pub struct Pressure {
+    factor: f32,
+    check: Timer,
+    /// Where things stood at the last check.
+    last_health: Option<f32>,
+    last_kills: u32,
+    last_arrivals: u32,
+}
+
+impl Default for Pressure {
+    fn default() -> Pressure {
+        Pressure{
+            factor: 1.0,
+            check: Timer::from_seconds(CHECK_SECONDS, true),
+            last_health: None,
+            last_kills: 0,
+            last_arrivals: 0,
+        }
+    }
+}
+
+impl Pressure {
+    pub fn factor(&self) -> f32 {
+        self.factor
+    }
+}
+
+pub fn reset_pressure(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut pressure: ResMut<Pressure>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        *pressure = Pressure::default();
+    }
+}
+
+pub fn adapt_pressure(
+    time: Res<Time>,
+    adaptive: Res<Adaptive>,
+    daily: Res<Daily>,
+    mut pressure: ResMut<Pressure>,
+    monster_stats: Res<MonsterStats>,
+    players: Query<&Health, With<Player>>,
+) {
+    if *adaptive == Adaptive::Off || daily.run_day.is_some() || !pressure.check.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let healths: Vec<_> = players.iter().map(|health| health.fraction()).collect();
+    if healths.is_empty() {
+        return;
+    }
+    let health = healths.iter().sum::<f32>() / healths.len() as f32;
+    let trend = health - pressure.last_health.unwrap_or(health);
+
+    let killed = monster_stats.bodies_killed.saturating_sub(pressure.last_kills);
+    let arrived = monster_stats.arrived().saturating_sub(pressure.last_arrivals);
+    let is_falling_behind = (killed as f32) < arrived as f32 * KEEP_UP_SHARE;
+
+    pressure.last_health = Some(health);
+    pressure.last_kills = monster_stats.bodies_killed;
+    pressure.last_arrivals = monster_stats.arrived();
+
+    let step = if health < LOW_HEALTH || trend < -HEALTH_DROP || is_falling_behind {
+        -PRESSURE_STEP
+    }
+    else if health > HIGH_HEALTH && trend >= 0.0 && killed >= arrived {
+        PRESSURE_STEP
+    }
+    else {
+        return;
+    };
+    pressure.factor = (pressure.factor + step).clamp(MIN_PRESSURE, MAX_PRESSURE);
+}
+
+#[derive(Component)]
+pub struct AdaptivePanelText;
+
+fn panel_label(adaptive: Adaptive) -> String {
+    format!("adaptive difficulty: {} (Y)", adaptive.name())
+}
+
+pub fn spawn_adaptive_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    adaptive: Res<Adaptive>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(252.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*adaptive),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(AdaptivePanelText)
+        .insert(MenuRoot);
+}
+
+pub fn adaptive_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut adaptive: ResMut<Adaptive>,
+    mut texts: Query<&mut Text, With<AdaptivePanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Y) {
+        return;
+    }
+
+    *adaptive = adaptive.toggled();
+    adaptive.save();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*adaptive);
+    }
+}
@@ -0,0 +1,160 @@
+//! Bounded arenas. A stage can fence the fight in with walls around the start,
+//! broken by gates along each side. Monsters there don't appear on a ring
+//! around the players but just outside a gate, and find their way in through
+//! the flow field like around any other obstacle.
+
+use bevy::prelude::*;
+
+use crate::stage::Stages;
+use crate::y_sort::SortByY;
+use crate::{Body, Circle, Layers, NewGameEvent, Obstacle, Position, Shape};
+
+
+/// How far outside the wall monsters appear. The flow field grows to
+/// reach them.
+const GATE_SPAWN_MARGIN: f32 = 40.0;
+
+/// A gap in the arena wall.
+struct Gate {
+    /// The middle of the gap, on the wall's center line.
+    position: Vec2,
+    /// Straight out of the arena.
+    outward: Vec2,
+}
+
+/// The gates of the current run's arena, empty on open stages.
+#[derive(Default)]
+pub struct Arena {
+    gates: Vec<Gate>,
+    gate_width: f32,
+    wall: f32,
+    /// Half the size inside the walls, or zero on open stages.
+    half: Vec2,
+}
+
+impl Arena {
+    pub fn is_bounded(&self) -> bool {
+        !self.gates.is_empty()
+    }
+
+    /// Half the size of the area monsters appear in, out to just past the
+    /// gates, on bounded stages.
+    pub fn bounds(&self) -> Option<Vec2> {
+        self.is_bounded().then(|| self.half + Vec2::splat(self.wall + GATE_SPAWN_MARGIN))
+    }
+
+    /// Somewhere just outside a random gate, on bounded stages.
+    pub fn spawn_point(&self) -> Option<Vec2> {
+        if self.gates.is_empty() {
+            return None;
+        }
+
+        let gate = &self.gates[rand::random::<usize>() % self.gates.len()];
+        Some(self.outside(gate))
+    }
+
+    /// Somewhere just outside a random gate on the side facing `from`, on
+    /// bounded stages. Off the diagonal, both sides it sits between qualify.
+    pub fn spawn_point_from(&self, from: Vec2) -> Option<Vec2> {
+        let facing = |gate: &Gate| gate.outward.dot(from);
+        let best = self.gates.iter().map(facing).reduce(f32::max)?;
+        let sides: Vec<_> = self.gates.iter().filter(|gate| facing(gate) >= best - 0.01).collect();
+
+        let gate = sides[rand::random::<usize>() % sides.len()];
+        Some(self.outside(gate))
+    }
+
+    fn outside(&self, gate: &Gate) -> Vec2 {
+        let along = (rand::random::<f32>() - 0.5) * self.gate_width;
+        gate.position + gate.outward * (self.wall + GATE_SPAWN_MARGIN) + gate.outward.perp() * along
+    }
+
+    /// Pull a point in from past the walls, on bounded stages.
+    pub fn clamp_inside(&self, point: Vec2, margin: f32) -> Vec2 {
+        if self.gates.is_empty() {
+            return point;
+        }
+
+        let reach = (self.half - Vec2::splat(self.wall / 2.0 + margin)).max(Vec2::ZERO);
+        point.clamp(-reach, reach)
+    }
+}
+
+/// Build the walls and gates of the new run's stage, if it has an arena.
+pub fn build_arena(
+    mut commands: Commands,
+    stages: Res<Stages>,
+    mut arena: ResMut<Arena>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+) {
+    let event = match new_game_reader.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    *arena = Arena::default();
+    let stage = match stages.list.get(event.stage) {
+        Some(stage) => stage,
+        None => return,
+    };
+    let def = match &stage.arena {
+        Some(def) => def,
+        None => return,
+    };
+    arena.gate_width = def.gate_width;
+    arena.wall = def.wall;
+
+    let (r, g, b) = stage.obstacles.color;
+    let half = Vec2::new(def.width, def.height) / 2.0;
+    arena.half = half;
+    let sides = [
+        (Vec2::new(0.0, half.y), Vec2::X, half.x, Vec2::Y),
+        (Vec2::new(0.0, -half.y), Vec2::X, half.x, -Vec2::Y),
+        (Vec2::new(half.x, 0.0), Vec2::Y, half.y, Vec2::X),
+        (Vec2::new(-half.x, 0.0), Vec2::Y, half.y, -Vec2::X),
+    ];
+    for (middle, along, reach, outward) in sides {
+        // Gates spaced evenly along the side, with wall between and past them
+        let gates: Vec<f32> = (1..=def.gates_per_side)
+            .map(|i| -reach + 2.0 * reach * i as f32 / (def.gates_per_side + 1) as f32)
+            .collect();
+        for &gate in &gates {
+            arena.gates.push(Gate{
+                position: middle + along * gate,
+                outward,
+            });
+        }
+
+        // Each stretch of wall is a core swept by half the wall's thickness,
+        // so the cores stop that far short of the gates
+        let inset = (def.gate_width + def.wall) / 2.0;
+        let mut start = -reach;
+        let ends = gates.iter().map(|gate| (gate - inset, gate + inset));
+        for (end, next_start) in ends.chain([(reach, 0.0)]) {
+            if end > start {
+                let half_length = (end - start) / 2.0;
+                let center = middle + along * (start + half_length);
+                let core = along * half_length;
+
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite{
+                            color: Color::rgb(r, g, b),
+                            custom_size: Some(core.abs() * 2.0 + Vec2::splat(def.wall)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(Obstacle)
+                    .insert(SortByY{ layer: 0.0 })
+                    .insert(Position::new(center))
+                    .insert(
+                        Body::new(Circle::new(def.wall / 2.0), None)
+                            .shaped(Shape::Rect(core.abs()))
+                            .on_layers(Layers::OBSTACLE, Layers::PLAYER.with(Layers::GROUND)),
+                    );
+            }
+            start = next_start;
+        }
+    }
+}
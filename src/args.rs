@@ -0,0 +1,235 @@
+//! Command line options, read once before the app is built. Mostly useful for
+//! testing and speedrun setups that need a repeatable start.
+
+use std::env;
+
+use bevy::prelude::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::content::{self, Content};
+use crate::damage::DamageType;
+use crate::frame_limit::FrameCap;
+use crate::mods;
+use crate::synergy::Tag;
+
+
+pub const USAGE: &str = "\
+usage: swarm [options]
+    --seed <number>          fix the world layout seed for every run
+    --windowed <W>x<H>       open a window of the given size
+    --no-vsync               draw frames as fast as they come
+    --fps <cap>              30, 60, 120 or uncapped
+    --weapon <id>            starting weapon (blast, ember, or one from a mod)
+    --difficulty <level>     easy, normal or hard
+    --manual-fire            fire weapons with a key instead of automatically
+    --click-to-move          walk to where the left mouse button is held
+    --eight-way              snap movement to eight directions
+    --procedural-sprites     draw generated shapes instead of sprite files
+    --instanced-monsters     draw monsters with GPU instancing for huge swarms";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weapon {
+    Blast,
+    /// A smaller blast that burns instead of bludgeons.
+    Ember,
+    /// A blast weapon added by a mod, by its place among them.
+    Modded(u8),
+}
+
+impl Serialize for Weapon {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        content::serialize(*self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Weapon {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Weapon, D::Error> {
+        content::deserialize(deserializer)
+    }
+}
+
+impl Content for Weapon {
+    const SORT: &'static str = "weapon";
+
+    fn id(self) -> &'static str {
+        match self {
+            Weapon::Blast => "blast",
+            Weapon::Ember => "ember",
+            Weapon::Modded(id) => &mods::weapon(id).id,
+        }
+    }
+
+    fn all() -> Vec<Weapon> {
+        Weapon::ALL.into_iter().chain(mods::weapons()).collect()
+    }
+}
+
+impl Weapon {
+    const ALL: [Weapon; 2] = [Weapon::Blast, Weapon::Ember];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weapon::Blast => "blast",
+            Weapon::Ember => "ember",
+            Weapon::Modded(id) => &mods::weapon(id).name,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Weapon::Blast => "a burst that clears every monster it catches",
+            Weapon::Ember => "a tighter burst of flame that bombers and necromancers dread",
+            Weapon::Modded(id) => &mods::weapon(id).description,
+        }
+    }
+
+    pub fn damage_type(self) -> DamageType {
+        match self {
+            Weapon::Blast => DamageType::Physical,
+            Weapon::Ember => DamageType::Fire,
+            Weapon::Modded(id) => mods::weapon(id).damage_type,
+        }
+    }
+
+    pub fn tags(self) -> &'static [Tag] {
+        match self {
+            Weapon::Blast | Weapon::Ember => &[Tag::Area],
+            Weapon::Modded(id) => &mods::weapon(id).tags,
+        }
+    }
+
+    /// How big its blasts are next to the plain blast's.
+    pub fn blast_radius_factor(self) -> f32 {
+        match self {
+            Weapon::Blast => 1.0,
+            Weapon::Ember => 0.8,
+            Weapon::Modded(id) => mods::weapon(id).radius,
+        }
+    }
+
+    pub fn tint(self) -> Color {
+        match self {
+            Weapon::Modded(id) => match mods::weapon(id).tint {
+                Some((r, g, b)) => Color::rgb(r, g, b),
+                None => Color::WHITE,
+            },
+            Weapon::Ember => Color::rgb(1.0, 0.55, 0.2),
+            _ => Color::WHITE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn from_name(name: &str) -> Option<Difficulty> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn spawn_rate_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    pub fn monster_speed_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.85,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.2,
+        }
+    }
+}
+
+pub struct LaunchArgs {
+    pub seed: Option<u64>,
+    pub window_size: Option<(f32, f32)>,
+    pub no_vsync: bool,
+    pub frame_cap: FrameCap,
+    pub weapon: Weapon,
+    pub difficulty: Difficulty,
+    pub manual_fire: bool,
+    pub click_to_move: bool,
+    pub eight_way: bool,
+    pub procedural_sprites: bool,
+    pub instanced_monsters: bool,
+}
+
+impl LaunchArgs {
+    pub fn parse() -> Result<LaunchArgs, String> {
+        let mut args = LaunchArgs{
+            seed: None,
+            window_size: None,
+            no_vsync: false,
+            frame_cap: FrameCap::Uncapped,
+            weapon: Weapon::Blast,
+            difficulty: Difficulty::Normal,
+            manual_fire: false,
+            click_to_move: false,
+            eight_way: false,
+            procedural_sprites: false,
+            instanced_monsters: false,
+        };
+
+        let mut words = env::args().skip(1);
+        while let Some(flag) = words.next() {
+            let mut value = || words.next().ok_or(format!("{} needs a value", flag));
+
+            match flag.as_str() {
+                "--seed" => {
+                    let v = value()?;
+                    args.seed = Some(v.parse().map_err(|_| format!("bad seed '{}'", v))?);
+                }
+                "--windowed" => {
+                    let v = value()?;
+                    args.window_size = Some(parse_size(&v).ok_or(format!("bad window size '{}'", v))?);
+                }
+                "--fps" => {
+                    let v = value()?;
+                    args.frame_cap = FrameCap::from_name(&v).ok_or(format!("bad frame cap '{}'", v))?;
+                }
+                "--weapon" => {
+                    let v = value()?;
+                    args.weapon = Weapon::from_id(&v).ok_or(format!("unknown weapon '{}'", v))?;
+                }
+                "--difficulty" => {
+                    let v = value()?;
+                    args.difficulty = Difficulty::from_name(&v).ok_or(format!("unknown difficulty '{}'", v))?;
+                }
+                "--no-vsync" => args.no_vsync = true,
+                "--manual-fire" => args.manual_fire = true,
+                "--click-to-move" => args.click_to_move = true,
+                "--eight-way" => args.eight_way = true,
+                "--procedural-sprites" => args.procedural_sprites = true,
+                "--instanced-monsters" => args.instanced_monsters = true,
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// Parse a `1280x720` style window size.
+fn parse_size(text: &str) -> Option<(f32, f32)> {
+    let (width, height) = text.split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    let height: u32 = height.parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some((width as f32, height as f32))
+}
@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::stage::Stages;
+use crate::{AppState, Monster, NewGameEvent, Obstacle, Player, Position, RunDefaults, Velocity};
+
+
+const DEMO_IDLE_SECONDS: f32 = 20.0;
+
+const AUTOPILOT_SENSE_RADIUS: f32    = 260.0;
+const AUTOPILOT_OBSTACLE_WEIGHT: f32 = 2.0;
+const AUTOPILOT_WANDER_RATE: f32     = 4.0;
+const AUTOPILOT_CALM_THRESHOLD: f32  = 0.002;
+
+const HINT_FONT_SIZE: f32 = 18.0;
+
+/// Marks a player steered by the computer rather than the keyboard.
+#[derive(Component)]
+pub struct Autopilot {
+    heading: f32,
+}
+
+pub struct DemoMode {
+    active: bool,
+    idle: Timer,
+}
+
+impl DemoMode {
+    pub fn new() -> DemoMode {
+        DemoMode{
+            active: false,
+            idle: Timer::from_seconds(DEMO_IDLE_SECONDS, false),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+pub fn spawn_demo_hint(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "D: watch demo",
+                TextStyle {
+                    font: assets.font(),
+                    font_size: HINT_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MenuRoot);
+}
+
+pub fn start_demo(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut demo: ResMut<DemoMode>,
+    mut state: ResMut<State<AppState>>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    demo.active = false;
+
+    // Any other key counts as activity on the title screen
+    let requested = keyboard_input.just_pressed(KeyCode::D);
+    if keyboard_input.get_just_pressed().len() > 0 {
+        demo.idle.reset();
+    }
+    else {
+        demo.idle.tick(time.delta());
+    }
+
+    if (requested || demo.idle.finished()) && state.set(AppState::Playing).is_ok() {
+        demo.active = true;
+        demo.idle.reset();
+        new_game_writer.send(NewGameEvent::on_stage(stages.selected, &defaults));
+    }
+}
+
+pub fn stop_demo(
+    demo: Res<DemoMode>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !demo.active || keyboard_input.get_just_pressed().len() == 0 {
+        return;
+    }
+
+    // Escape may already have sent us back to the menu this frame
+    if state.set(AppState::Menu).is_ok() {
+        keyboard_input.clear();
+    }
+}
+
+pub fn attach_autopilot(
+    mut commands: Commands,
+    demo: Res<DemoMode>,
+    players: Query<Entity, Added<Player>>,
+) {
+    if !demo.active {
+        return;
+    }
+
+    for player in players.iter() {
+        commands.entity(player).insert(Autopilot{
+            heading: rand::random::<f32>() * std::f32::consts::TAU,
+        });
+    }
+}
+
+pub fn autopilot_direction(
+    time: Res<Time>,
+    mut pilots: Query<(&mut Autopilot, &Position, &mut Velocity), With<Player>>,
+    monsters: Query<&Position, With<Monster>>,
+    obstacles: Query<&Position, With<Obstacle>>,
+) {
+    for (mut pilot, p, mut v) in pilots.iter_mut() {
+        // Sum up repulsion from everything nearby, strongest when closest
+        let mut threat = Vec2::ZERO;
+        let sense_squared = AUTOPILOT_SENSE_RADIUS * AUTOPILOT_SENSE_RADIUS;
+        let mut repel = |other: Vec2, weight: f32| {
+            let away = p.current - other;
+            let distance_squared = away.length_squared();
+            if distance_squared > 0.0 && distance_squared < sense_squared {
+                threat += away / distance_squared * weight;
+            }
+        };
+
+        for m in monsters.iter() {
+            repel(m.current, 1.0);
+        }
+        for o in obstacles.iter() {
+            repel(o.current, AUTOPILOT_OBSTACLE_WEIGHT);
+        }
+
+        // Kite away from danger, otherwise wander on a slowly turning heading
+        if threat.length() > AUTOPILOT_CALM_THRESHOLD {
+            v.direction = threat.normalize_or_zero();
+            pilot.heading = v.direction.y.atan2(v.direction.x);
+        }
+        else {
+            let turn = rand::random::<f32>() * 2.0 - 1.0;
+            pilot.heading += turn * AUTOPILOT_WANDER_RATE * time.delta_seconds();
+            v.direction = Vec2::new(pilot.heading.cos(), pilot.heading.sin());
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Burrowers and the submerged phase. A burrower spends a while on the
+//! surface, then dives and tunnels toward the players as a faint shadow. It
+//! comes back up with a short, rumbling eruption before it can hurt anyone.
+//! While submerged a monster leaves every collision layer, so it can't be hit
+//! and doesn't collide. It can't hurt anyone either and isn't in the spatial
+//! index, so no weapon can aim at it.
+
+use bevy::prelude::*;
+
+use crate::{Body, Layers, MonsterKind, Velocity};
+
+
+const SURFACED_SECONDS: f32 = 3.0;
+const BURROWED_SECONDS: f32 = 2.5;
+const ERUPTING_SECONDS: f32 = 0.6;
+
+const BURROWED_ALPHA: f32         = 0.2;
+const ERUPTION_FLASH_SECONDS: f32 = 0.1;
+
+/// Keeps a monster out of reach of weapons, players and the crowd.
+#[derive(Component)]
+pub struct Submerged;
+
+#[derive(Clone, Copy, PartialEq)]
+enum BurrowPhase {
+    Surfaced,
+    Burrowed,
+    Erupting,
+}
+
+impl BurrowPhase {
+    fn seconds(self) -> f32 {
+        match self {
+            BurrowPhase::Surfaced => SURFACED_SECONDS,
+            BurrowPhase::Burrowed => BURROWED_SECONDS,
+            BurrowPhase::Erupting => ERUPTING_SECONDS,
+        }
+    }
+
+    fn next(self) -> BurrowPhase {
+        match self {
+            BurrowPhase::Surfaced => BurrowPhase::Burrowed,
+            BurrowPhase::Burrowed => BurrowPhase::Erupting,
+            BurrowPhase::Erupting => BurrowPhase::Surfaced,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Burrow {
+    phase: BurrowPhase,
+    timer: Timer,
+}
+
+impl Burrow {
+    pub fn new() -> Burrow {
+        Burrow{
+            phase: BurrowPhase::Surfaced,
+            timer: Timer::from_seconds(SURFACED_SECONDS, false),
+        }
+    }
+}
+
+pub fn cycle_burrows(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut burrowers: Query<(Entity, &MonsterKind, &mut Burrow, &mut Body, &mut Velocity, &mut TextureAtlasSprite)>,
+) {
+    for (entity, kind, mut burrow, mut body, mut v, mut sprite) in burrowers.iter_mut() {
+        if burrow.timer.tick(time.delta()).finished() {
+            burrow.phase = burrow.phase.next();
+            burrow.timer = Timer::from_seconds(burrow.phase.seconds(), false);
+
+            match burrow.phase {
+                BurrowPhase::Burrowed => {
+                    commands.entity(entity).insert(Submerged);
+                    body.layer = Layers::NONE;
+                    body.mask = Layers::NONE;
+                }
+                BurrowPhase::Surfaced => {
+                    commands.entity(entity).remove::<Submerged>();
+                    (body.layer, body.mask) = kind.collision_layers();
+                    sprite.color = kind.tint();
+                }
+                BurrowPhase::Erupting => {}
+            }
+        }
+
+        match burrow.phase {
+            BurrowPhase::Surfaced => {}
+            BurrowPhase::Burrowed => {
+                sprite.color = Color::rgba(0.3, 0.25, 0.2, BURROWED_ALPHA);
+            }
+            BurrowPhase::Erupting => {
+                // Hold still and rumble where it's about to come up
+                v.direction = Vec2::ZERO;
+                let is_lit = ((burrow.timer.elapsed_secs() / ERUPTION_FLASH_SECONDS) as u32).is_multiple_of(2);
+                sprite.color = if is_lit {
+                    Color::rgb(0.6, 0.45, 0.3)
+                }
+                else {
+                    Color::rgba(0.3, 0.25, 0.2, BURROWED_ALPHA)
+                };
+            }
+        }
+    }
+}
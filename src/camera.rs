@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+
+use crate::players::{player_centroid, player_positions};
+use crate::{Player, Position};
+
+
+const CAMERA_FRAMING_RADIUS: f32 = 250.0;
+const CAMERA_MAX_ZOOM: f32       = 1.6;
+const CAMERA_ZOOM_RATE: f32      = 3.0;
+
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Pins a UI node over an entity in the world, centered `offset` world units
+/// from its position.
+#[derive(Component)]
+pub struct ScreenAnchor {
+    pub target: Entity,
+    pub offset: Vec2,
+}
+
+pub struct CameraFocus {
+    pub center: Vec2,
+    pub zoom: f32,
+}
+
+impl Default for CameraFocus {
+    fn default() -> CameraFocus {
+        CameraFocus{
+            center: Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+pub fn frame_players(
+    time: Res<Time>,
+    mut focus: ResMut<CameraFocus>,
+    players: Query<&Position, With<Player>>,
+    mut cameras: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    let positions = player_positions(&players);
+
+    // Center on the midpoint of all players, holding still when there are none
+    let center = if let Some(c) = player_centroid(&positions) {
+        c
+    }
+    else {
+        return
+    };
+    focus.center = center;
+
+    // Zoom out to keep everyone on screen as they spread apart
+    let spread = positions.iter().fold(0.0, |max: f32, p| max.max(p.distance(center)));
+    let target_zoom = (spread / CAMERA_FRAMING_RADIUS).clamp(1.0, CAMERA_MAX_ZOOM);
+    let blend = (CAMERA_ZOOM_RATE * time.delta_seconds()).min(1.0);
+    focus.zoom += (target_zoom - focus.zoom) * blend;
+
+    for mut projection in cameras.iter_mut() {
+        projection.scale = focus.zoom;
+    }
+}
+
+/// The world position under a point on a window of `window` size, measured
+/// from its bottom left corner.
+pub fn screen_to_world(screen: Vec2, window: Vec2, focus: &CameraFocus) -> Vec2 {
+    (screen - window / 2.0) * focus.zoom + focus.center
+}
+
+pub fn anchor_to_world(
+    windows: Res<Windows>,
+    focus: Res<CameraFocus>,
+    targets: Query<&Position>,
+    mut anchors: Query<(&ScreenAnchor, &mut Style)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+
+    for (anchor, mut style) in anchors.iter_mut() {
+        let p = match targets.get(anchor.target) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+
+        // UI is laid out from the bottom left corner of the window
+        let screen = (p.current + anchor.offset - focus.center) / focus.zoom + window / 2.0;
+        let half_size = match (style.size.width, style.size.height) {
+            (Val::Px(width), Val::Px(height)) => Vec2::new(width, height) / 2.0,
+            _ => Vec2::ZERO,
+        };
+        style.position.left = Val::Px(screen.x - half_size.x);
+        style.position.bottom = Val::Px(screen.y - half_size.y);
+    }
+}
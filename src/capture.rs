@@ -0,0 +1,285 @@
+//! Renders the sprite layer into a CPU-side image so frames can be written to
+//! disk. Bevy keeps texture data on the CPU, so this composites sprites in
+//! depth order using the main camera's view rather than reading back the GPU.
+//! UI nodes are not included.
+
+// Clips and the capture hotkeys are only registered in native builds
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::thread;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use image::{Rgba, RgbaImage};
+
+use crate::camera::MainCamera;
+use crate::storage;
+
+
+pub const CAPTURE_DIRECTORY: &str = "captures";
+
+const CLIP_SECONDS: f32        = 5.0;
+const CLIP_FPS: f32            = 10.0;
+const CLIP_SCALE: f32          = 0.25;
+const CLIP_QUANTIZE_SPEED: i32 = 10;
+
+#[derive(SystemParam)]
+pub struct FrameSource<'w, 's> {
+    windows: Res<'w, Windows>,
+    clear_color: Res<'w, ClearColor>,
+    images: Res<'w, Assets<Image>>,
+    atlases: Res<'w, Assets<TextureAtlas>>,
+    cameras: Query<'w, 's, (&'static GlobalTransform, &'static OrthographicProjection), With<MainCamera>>,
+    sprites: Query<'w, 's, (&'static GlobalTransform, &'static Sprite, &'static Handle<Image>, &'static Visibility)>,
+    atlas_sprites: Query<'w, 's, (&'static GlobalTransform, &'static TextureAtlasSprite, &'static Handle<TextureAtlas>, &'static Visibility)>,
+}
+
+/// One sprite to paint, from either a plain or an atlas sprite.
+struct SpriteDraw<'a> {
+    transform: &'a GlobalTransform,
+    image: Option<&'a Image>,
+    region: Option<bevy::sprite::Rect>,
+    color: Color,
+    flip_x: bool,
+    flip_y: bool,
+    custom_size: Option<Vec2>,
+}
+
+fn to_rgba(color: Color) -> [f32; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
+
+impl<'w, 's> FrameSource<'w, 's> {
+    /// Composite the current frame at `scale` times the window size.
+    pub fn capture(&self, scale: f32) -> Option<RgbaImage> {
+        let window = self.windows.get_primary()?;
+        let (camera, projection) = self.cameras.iter().next()?;
+
+        let width = ((window.width() * scale) as u32).max(1);
+        let height = ((window.height() * scale) as u32).max(1);
+        let [r, g, b, _] = to_rgba(self.clear_color.0);
+        let mut frame = RgbaImage::from_pixel(width, height, Rgba([
+            (r * 255.0) as u8,
+            (g * 255.0) as u8,
+            (b * 255.0) as u8,
+            255,
+        ]));
+
+        let plain = self.sprites
+            .iter()
+            .filter(|(_, _, _, visibility)| visibility.is_visible)
+            .map(|(transform, sprite, handle, _)| SpriteDraw{
+                transform,
+                image: self.images.get(handle),
+                region: None,
+                color: sprite.color,
+                flip_x: sprite.flip_x,
+                flip_y: sprite.flip_y,
+                custom_size: sprite.custom_size,
+            });
+        let atlased = self.atlas_sprites
+            .iter()
+            .filter(|(_, _, _, visibility)| visibility.is_visible)
+            .filter_map(|(transform, sprite, handle, _)| {
+                let atlas = self.atlases.get(handle)?;
+                Some(SpriteDraw{
+                    transform,
+                    image: self.images.get(&atlas.texture),
+                    region: atlas.textures.get(sprite.index).copied(),
+                    color: sprite.color,
+                    flip_x: sprite.flip_x,
+                    flip_y: sprite.flip_y,
+                    custom_size: sprite.custom_size,
+                })
+            });
+
+        // Paint back to front
+        let mut sprites: Vec<SpriteDraw> = plain.chain(atlased).collect();
+        sprites.sort_by(|a, b| a.transform.translation.z.total_cmp(&b.transform.translation.z));
+
+        let pixels_per_unit = scale / projection.scale;
+        for sprite in sprites {
+            paint_sprite(&mut frame, camera, pixels_per_unit, &sprite);
+        }
+
+        Some(frame)
+    }
+}
+
+fn paint_sprite(
+    frame: &mut RgbaImage,
+    camera: &GlobalTransform,
+    pixels_per_unit: f32,
+    sprite: &SpriteDraw,
+) {
+    // Fall back to plain white for missing or unusual textures
+    let white = ([255u8, 255, 255, 255].as_slice(), 1, 1);
+    let (texels, texture_width, texture_height) = match sprite.image {
+        Some(image) if matches!(image.texture_descriptor.format, TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm) => {
+            let size = image.texture_descriptor.size;
+            (image.data.as_slice(), size.width, size.height)
+        }
+        _ => white,
+    };
+
+    // Atlas sprites only cover part of the texture
+    let (region_min, region_size) = match sprite.region {
+        Some(region) if texture_width > 1 => (region.min, region.max - region.min),
+        _ => (Vec2::ZERO, Vec2::new(texture_width as f32, texture_height as f32)),
+    };
+
+    let transform = sprite.transform;
+    let size = sprite.custom_size.unwrap_or(region_size) * transform.scale.truncate();
+    let center = Vec2::new(
+        (transform.translation.x - camera.translation.x) * pixels_per_unit + frame.width() as f32 / 2.0,
+        frame.height() as f32 / 2.0 - (transform.translation.y - camera.translation.y) * pixels_per_unit,
+    );
+    let extent = size * pixels_per_unit;
+    let min = center - extent / 2.0;
+    let max = center + extent / 2.0;
+
+    let x_range = (min.x.max(0.0) as u32)..(max.x.min(frame.width() as f32).max(0.0) as u32);
+    let y_range = (min.y.max(0.0) as u32)..(max.y.min(frame.height() as f32).max(0.0) as u32);
+    let tint = to_rgba(sprite.color);
+
+    for y in y_range {
+        for x in x_range.clone() {
+            // Nearest texel, honoring flips
+            let mut u = (x as f32 + 0.5 - min.x) / extent.x;
+            let mut v = (y as f32 + 0.5 - min.y) / extent.y;
+            if sprite.flip_x {
+                u = 1.0 - u;
+            }
+            if sprite.flip_y {
+                v = 1.0 - v;
+            }
+            let tx = ((region_min.x + u * region_size.x) as u32).min(texture_width - 1);
+            let ty = ((region_min.y + v * region_size.y) as u32).min(texture_height - 1);
+            let i = ((ty * texture_width + tx) * 4) as usize;
+
+            let alpha = texels[i + 3] as f32 / 255.0 * tint[3];
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            let pixel = frame.get_pixel_mut(x, y);
+            for c in 0..3 {
+                let source = texels[i + c] as f32 * tint[c];
+                pixel.0[c] = (source * alpha + pixel.0[c] as f32 * (1.0 - alpha)) as u8;
+            }
+        }
+    }
+}
+
+/// A timestamped path in the capture directory, creating the directory if needed.
+pub fn capture_path(prefix: &str, extension: &str) -> Option<PathBuf> {
+    if let Err(e) = fs::create_dir_all(CAPTURE_DIRECTORY) {
+        warn!("failed to create {}: {}", CAPTURE_DIRECTORY, e);
+        return None;
+    }
+
+    let stamp = storage::unix_millis();
+
+    Some(PathBuf::from(CAPTURE_DIRECTORY).join(format!("{}-{}.{}", prefix, stamp, extension)))
+}
+
+pub fn save_png(frame: &RgbaImage, prefix: &str) {
+    let path = match capture_path(prefix, "png") {
+        Some(path) => path,
+        None => return,
+    };
+
+    match frame.save(&path) {
+        Ok(()) => info!("saved {}", path.display()),
+        Err(e) => warn!("failed to save {}: {}", path.display(), e),
+    }
+}
+
+/// The last few seconds of low resolution frames, kept for clip capture.
+pub struct ClipBuffer {
+    frames: VecDeque<RgbaImage>,
+    timer: Timer,
+}
+
+impl ClipBuffer {
+    pub fn new() -> ClipBuffer {
+        ClipBuffer{
+            frames: VecDeque::new(),
+            timer: Timer::from_seconds(1.0 / CLIP_FPS, true),
+        }
+    }
+}
+
+pub fn record_clip(
+    time: Res<Time>,
+    mut clip: ResMut<ClipBuffer>,
+    frame_source: FrameSource,
+) {
+    clip.timer.tick(time.delta());
+    if !clip.timer.just_finished() {
+        return;
+    }
+
+    // Start over if the window was resized, since every frame must match
+    let frame = match frame_source.capture(CLIP_SCALE) {
+        Some(frame) => frame,
+        None => return,
+    };
+    if clip.frames.back().is_some_and(|f| f.dimensions() != frame.dimensions()) {
+        clip.frames.clear();
+    }
+
+    clip.frames.push_back(frame);
+    while clip.frames.len() > (CLIP_SECONDS * CLIP_FPS) as usize {
+        clip.frames.pop_front();
+    }
+}
+
+fn save_gif(frames: Vec<RgbaImage>, path: PathBuf) -> Result<(), String> {
+    let (width, height) = frames[0].dimensions();
+    let file = File::create(&path).map_err(|e| e.to_string())?;
+    let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[]).map_err(|e| e.to_string())?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+    for frame in frames {
+        let mut pixels = frame.into_raw();
+        let mut gif_frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut pixels, CLIP_QUANTIZE_SPEED);
+        gif_frame.delay = (100.0 / CLIP_FPS) as u16;
+        encoder.write_frame(&gif_frame).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+pub fn capture_hotkeys(
+    keyboard_input: Res<Input<KeyCode>>,
+    clip: Res<ClipBuffer>,
+    frame_source: FrameSource,
+) {
+    // F12 saves a full resolution screenshot
+    if keyboard_input.just_pressed(KeyCode::F12) {
+        if let Some(frame) = frame_source.capture(1.0) {
+            save_png(&frame, "screenshot");
+        }
+    }
+
+    // F11 saves the rolling clip, encoding off the main thread
+    if keyboard_input.just_pressed(KeyCode::F11) && !clip.frames.is_empty() {
+        let path = match capture_path("clip", "gif") {
+            Some(path) => path,
+            None => return,
+        };
+        let frames: Vec<RgbaImage> = clip.frames.iter().cloned().collect();
+
+        thread::spawn(move || {
+            match save_gif(frames, path.clone()) {
+                Ok(()) => info!("saved {}", path.display()),
+                Err(e) => warn!("failed to save {}: {}", path.display(), e),
+            }
+        });
+    }
+}
@@ -0,0 +1,143 @@
+//! Click to move, for trackpads and anyone who'd rather not hold keys down.
+//! With it on, holding the left mouse button sets a spot for the first player
+//! to walk to, and they keep going after the button is let go until they get
+//! there. The movement keys still work and cancel the walk. Picked in the
+//! menu with C or at launch with `--click-to-move`.
+
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::camera::{screen_to_world, CameraFocus};
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::{NewGameEvent, Player, Position, Velocity};
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+/// Close enough to the spot to stop walking.
+const ARRIVE_DISTANCE: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MoveScheme {
+    Keys,
+    Mouse,
+}
+
+impl MoveScheme {
+    fn name(self) -> &'static str {
+        match self {
+            MoveScheme::Keys => "keys",
+            MoveScheme::Mouse => "keys or mouse",
+        }
+    }
+
+    fn toggled(self) -> MoveScheme {
+        match self {
+            MoveScheme::Keys => MoveScheme::Mouse,
+            MoveScheme::Mouse => MoveScheme::Keys,
+        }
+    }
+}
+
+/// Where the first player is walking to.
+#[derive(Default)]
+pub struct ClickTarget(Option<Vec2>);
+
+#[derive(Component)]
+pub struct MovePanelText;
+
+fn panel_label(scheme: MoveScheme) -> String {
+    format!("moves with: {} (C)", scheme.name())
+}
+
+pub fn spawn_move_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    scheme: Res<MoveScheme>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(84.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*scheme),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MovePanelText)
+        .insert(MenuRoot);
+}
+
+pub fn move_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut scheme: ResMut<MoveScheme>,
+    mut texts: Query<&mut Text, With<MovePanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::C) {
+        return;
+    }
+
+    *scheme = scheme.toggled();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*scheme);
+    }
+}
+
+/// Runs after the keys, gamepads and touch stick have set each player's
+/// direction, and only steers when they've left the first player standing
+/// still.
+pub fn click_to_move(
+    scheme: Res<MoveScheme>,
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    focus: Res<CameraFocus>,
+    mut target: ResMut<ClickTarget>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut players: Query<(&Player, &Position, &mut Velocity), Without<Autopilot>>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        target.0 = None;
+    }
+    if *scheme != MoveScheme::Mouse {
+        return;
+    }
+
+    if mouse_input.pressed(MouseButton::Left) {
+        let window = windows.get_primary();
+        let cursor = window.and_then(|w| Some((w.cursor_position()?, Vec2::new(w.width(), w.height()))));
+        if let Some((cursor, size)) = cursor {
+            target.0 = Some(screen_to_world(cursor, size, &focus));
+        }
+    }
+
+    for (_, p, mut v) in players.iter_mut().filter(|(player, _, _)| player.index == 0) {
+        if v.direction != Vec2::ZERO {
+            target.0 = None;
+            continue;
+        }
+
+        let offset = match target.0 {
+            Some(spot) => spot - p.current,
+            None => continue,
+        };
+        if offset.length() <= ARRIVE_DISTANCE {
+            target.0 = None;
+            continue;
+        }
+        v.direction = offset.normalize();
+    }
+}
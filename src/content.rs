@@ -0,0 +1,88 @@
+//! Stable IDs for the game's content. Every monster kind, weapon and upgrade
+//! has a short lowercase ID, like `grunt` or `swiftness`. Stage files, saves,
+//! run records and mods refer to content by its ID, so a display name can
+//! change between versions without breaking what was written before. Mod
+//! content takes its ID from the mod, or from its name if the mod doesn't
+//! give one. Lookups ignore case and read spaces as underscores, so stage
+//! files that name kinds as `Grunt` still work.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserializer, EnumAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Serializer};
+
+
+/// One sort of content, looked up by ID.
+pub trait Content: Copy + 'static {
+    /// What this sort of content is called, for messages.
+    const SORT: &'static str;
+
+    fn id(self) -> &'static str;
+
+    /// The built-in content, then what mods added.
+    fn all() -> Vec<Self>;
+
+    fn from_id(id: &str) -> Option<Self> {
+        let id = to_id(id);
+        Self::all().into_iter().find(|content| content.id() == id)
+    }
+}
+
+/// The ID for `name`, lowercase with underscores between words.
+pub fn to_id(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+/// Write content as its ID.
+pub fn serialize<S: Serializer, T: Content>(content: T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(content.id())
+}
+
+/// Read content from its ID, either a bare word like an enum variant in RON
+/// or a string in JSON.
+pub fn deserialize<'de, D: Deserializer<'de>, T: Content>(deserializer: D) -> Result<T, D::Error> {
+    struct Word(String);
+
+    impl<'de> Deserialize<'de> for Word {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Word, D::Error> {
+            struct WordVisitor;
+
+            impl<'de> Visitor<'de> for WordVisitor {
+                type Value = Word;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("an ID")
+                }
+
+                fn visit_str<E: de::Error>(self, value: &str) -> Result<Word, E> {
+                    Ok(Word(value.to_string()))
+                }
+            }
+
+            deserializer.deserialize_identifier(WordVisitor)
+        }
+    }
+
+    struct ContentVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Content> Visitor<'de> for ContentVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a {} ID", T::SORT)
+        }
+
+        fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<T, A::Error> {
+            let (word, variant): (Word, _) = data.variant()?;
+            variant.unit_variant()?;
+            T::from_id(&word.0).ok_or_else(|| de::Error::custom(format!("unknown {} '{}'", T::SORT, word.0)))
+        }
+    }
+
+    // Going through enums lets RON read the ID as a bare word
+    deserializer.deserialize_enum(T::SORT, &[], ContentVisitor(PhantomData))
+}
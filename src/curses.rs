@@ -0,0 +1,179 @@
+//! Curses, optional handicaps picked in the menu before a run. Each one makes
+//! the run harder through the same factors as weather and difficulty, and in
+//! return raises the multiplier the run's score is worked out with.
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::effective_stats::StatsChangedEvent;
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::{NewGameEvent, RunDefaults};
+
+
+const SWIFT_FOES_SPEED_FACTOR: f32     = 1.5;
+const NARROW_BLASTS_RADIUS_FACTOR: f32 = 0.75;
+const HEAVY_FEET_SPEED_FACTOR: f32     = 0.85;
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Curse {
+    SwiftFoes,
+    NarrowBlasts,
+    HeavyFeet,
+}
+
+impl Curse {
+    const ALL: [Curse; 3] = [Curse::SwiftFoes, Curse::NarrowBlasts, Curse::HeavyFeet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Curse::SwiftFoes => "swift foes",
+            Curse::NarrowBlasts => "narrow blasts",
+            Curse::HeavyFeet => "heavy feet",
+        }
+    }
+
+    /// The menu key that toggles it.
+    fn key(self) -> KeyCode {
+        match self {
+            Curse::SwiftFoes => KeyCode::Key1,
+            Curse::NarrowBlasts => KeyCode::Key2,
+            Curse::HeavyFeet => KeyCode::Key3,
+        }
+    }
+
+    /// How much it adds to the score multiplier.
+    fn reward(self) -> f32 {
+        match self {
+            Curse::SwiftFoes => 0.5,
+            Curse::NarrowBlasts => 0.25,
+            Curse::HeavyFeet => 0.25,
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << Curse::ALL.iter().position(|&c| c == self).unwrap_or_default()
+    }
+}
+
+/// A set of curses. The run's curses are kept as a resource too.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Curses(u8);
+
+impl Curses {
+    pub fn has(self, curse: Curse) -> bool {
+        self.0 & curse.bit() != 0
+    }
+
+    fn toggled(self, curse: Curse) -> Curses {
+        Curses(self.0 ^ curse.bit())
+    }
+
+    /// Any set of curses, none included.
+    pub fn random(rng: &mut impl Rng) -> Curses {
+        Curses(rng.gen_range(0..1 << Curse::ALL.len()))
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Curse> {
+        Curse::ALL.into_iter().filter(move |&c| self.has(c))
+    }
+
+    pub fn reward_multiplier(self) -> f32 {
+        1.0 + self.iter().map(Curse::reward).sum::<f32>()
+    }
+
+    /// A run's score: its kills, scaled up by the curses it was played with.
+    pub fn score(self, kills: u32) -> u32 {
+        (kills as f32 * self.reward_multiplier()).round() as u32
+    }
+
+    pub fn monster_speed_factor(self) -> f32 {
+        if self.has(Curse::SwiftFoes) { SWIFT_FOES_SPEED_FACTOR } else { 1.0 }
+    }
+
+    pub fn blast_radius_factor(self) -> f32 {
+        if self.has(Curse::NarrowBlasts) { NARROW_BLASTS_RADIUS_FACTOR } else { 1.0 }
+    }
+
+    pub fn move_speed_factor(self) -> f32 {
+        if self.has(Curse::HeavyFeet) { HEAVY_FEET_SPEED_FACTOR } else { 1.0 }
+    }
+}
+
+#[derive(Component)]
+pub struct CursePanelText;
+
+fn panel_label(curses: Curses) -> String {
+    let toggles = Curse::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, &curse)| {
+            let mark = if curses.has(curse) { "x" } else { " " };
+            format!("[{}] {} ({})", mark, curse.name(), i + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    format!("curses: {}  score x{:.2}", toggles, curses.reward_multiplier())
+}
+
+pub fn spawn_curse_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    defaults: Res<RunDefaults>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(60.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(defaults.curses),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(CursePanelText)
+        .insert(MenuRoot);
+}
+
+pub fn curse_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut defaults: ResMut<RunDefaults>,
+    mut texts: Query<&mut Text, With<CursePanelText>>,
+) {
+    let toggled = Curse::ALL.into_iter().find(|curse| keyboard_input.just_pressed(curse.key()));
+    let curse = match toggled {
+        Some(curse) => curse,
+        None => return,
+    };
+
+    defaults.curses = defaults.curses.toggled(curse);
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(defaults.curses);
+    }
+}
+
+pub fn apply_curses(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut curses: ResMut<Curses>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+) {
+    if let Some(event) = new_game_reader.iter().last() {
+        *curses = event.curses;
+        stats_writer.send(StatsChangedEvent);
+    }
+}
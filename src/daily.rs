@@ -0,0 +1,93 @@
+//! The daily challenge. Its stage, seed and curses all come from the date, so
+//! everyone playing on the same day gets the same run, and its curses can't
+//! be changed. Results are summarised in their own file apart from other runs.
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::args::Difficulty;
+use crate::curses::Curses;
+use crate::storage;
+use crate::{NewGameEvent, RunDefaults};
+
+
+const SECONDS_PER_DAY: u64 = 86_400;
+const DAILY_SEED_SALT: u64 = 0x5757_4152_4d44_4159;
+
+/// Whether the daily challenge is picked in the menu, and the day of the
+/// current run if it is one.
+#[derive(Default)]
+pub struct Daily {
+    pub is_selected: bool,
+    pub run_day: Option<u64>,
+}
+
+/// Days since the Unix epoch, in UTC.
+pub fn today() -> u64 {
+    storage::unix_millis() / 1000 / SECONDS_PER_DAY
+}
+
+/// A day since the Unix epoch as a YYYY-MM-DD date.
+pub fn date_label(day: u64) -> String {
+    // Civil date from a day count, with years starting in March so the leap
+    // day falls at the end
+    let days = day as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+/// The daily challenge for `day`, on normal difficulty with the default
+/// weapon.
+pub fn daily_challenge(day: u64, stage_count: usize, defaults: &RunDefaults) -> NewGameEvent {
+    let mut rng = StdRng::seed_from_u64(day ^ DAILY_SEED_SALT);
+
+    NewGameEvent{
+        weapon: defaults.weapon,
+        difficulty: Difficulty::Normal,
+        stage: rng.gen_range(0..stage_count),
+        seed: rng.gen(),
+        curses: Curses::random(&mut rng),
+        daily: Some(day),
+        sandbox: false,
+        escort: false,
+    }
+}
+
+pub fn track_daily_run(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut daily: ResMut<Daily>,
+) {
+    if let Some(event) = new_game_reader.iter().last() {
+        daily.run_day = event.daily;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_the_epoch() {
+        assert_eq!(date_label(0), "1970-01-01");
+    }
+
+    #[test]
+    fn labels_a_leap_day() {
+        assert_eq!(date_label(11_016), "2000-02-29");
+    }
+
+    #[test]
+    fn skips_the_leap_day_in_a_century_year() {
+        assert_eq!(date_label(47_540), "2100-02-28");
+        assert_eq!(date_label(47_541), "2100-03-01");
+    }
+}
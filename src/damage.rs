@@ -0,0 +1,38 @@
+//! Damage types. Every weapon deals one type, and each monster kind takes more
+//! or less of each, so a fire build melts some kinds and barely scratches
+//! others. How much a hit lands is a factor on the kind: 1 for a full hit,
+//! under 1 for kinds that resist and over 1 for kinds that are weak to it.
+//! Blasts land in whole hits, so a factor of 0.5 lands every other blast on
+//! average and one of 1.5 lands two hits half the time. A kind that takes
+//! none of a type shrugs it off entirely.
+
+use serde::Deserialize;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Ice,
+    Arcane,
+}
+
+impl DamageType {
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Fire => "fire",
+            DamageType::Ice => "ice",
+            DamageType::Arcane => "arcane",
+        }
+    }
+}
+
+/// How many hits a blast lands on a kind taking `factor` of its type.
+pub fn roll_hits(factor: f32) -> u32 {
+    let factor = factor.max(0.0);
+    let whole = factor.floor();
+    let extra = rand::random::<f32>() < factor - whole;
+    whole as u32 + extra as u32
+}
@@ -0,0 +1,102 @@
+//! Dashing. Each player can dash on their dash key, shooting a short way in
+//! the direction they're holding, and then has to wait a moment before the
+//! next one. On a touch screen, a tap dashes the first player. Standing still,
+//! there's nowhere to dash to, so nothing happens.
+
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::death::Dying;
+use crate::input::{Action, Controls};
+use crate::touch::TouchTap;
+use crate::{Player, Position, Velocity};
+
+
+const DASH_SPEED: f32            = 600.0;
+const DASH_SECONDS: f32          = 0.15;
+const DASH_COOLDOWN_SECONDS: f32 = 1.5;
+
+/// How long until a player can dash again.
+#[derive(Component)]
+pub struct DashCooldown(Timer);
+
+impl DashCooldown {
+    fn new() -> DashCooldown {
+        // Ready from the start of the run
+        let mut timer = Timer::from_seconds(DASH_COOLDOWN_SECONDS, false);
+        timer.set_elapsed(timer.duration());
+
+        DashCooldown(timer)
+    }
+}
+
+/// A player partway through a dash.
+#[derive(Component)]
+pub struct Dashing {
+    velocity: Vec2,
+    timer: Timer,
+}
+
+impl Dashing {
+    pub fn new(direction: Vec2) -> Dashing {
+        Dashing{
+            velocity: direction.normalize_or_zero() * DASH_SPEED,
+            timer: Timer::from_seconds(DASH_SECONDS, false),
+        }
+    }
+}
+
+pub fn attach_dash_cooldowns(
+    mut commands: Commands,
+    players: Query<Entity, Added<Player>>,
+) {
+    for player in players.iter() {
+        commands.entity(player).insert(DashCooldown::new());
+    }
+}
+
+/// Players who could set off on a dash, with the way they're heading.
+type DashReady<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Player, &'static Velocity, &'static mut DashCooldown),
+    (Without<Dashing>, Without<Autopilot>, Without<Dying>),
+>;
+
+pub fn start_dashes(
+    time: Res<Time>,
+    controls: Controls,
+    mut commands: Commands,
+    mut taps: EventReader<TouchTap>,
+    mut players: DashReady,
+) {
+    let is_tapped = taps.iter().count() > 0;
+    for (entity, player, v, mut cooldown) in players.iter_mut() {
+        let does_dash = controls.just_pressed(player.index, Action::Dash)
+            || (is_tapped && player.index == 0);
+        if !cooldown.0.tick(time.delta()).finished() || !does_dash {
+            continue;
+        }
+        if v.direction == Vec2::ZERO {
+            continue;
+        }
+
+        cooldown.0.reset();
+        commands.entity(entity).insert(Dashing::new(v.direction));
+    }
+}
+
+/// Carry dashing players along on top of their usual movement.
+pub fn apply_dashes(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut dashing: Query<(Entity, &mut Dashing, &mut Position)>,
+) {
+    for (entity, mut dash, mut p) in dashing.iter_mut() {
+        p.apply_add(dash.velocity * time.delta_seconds());
+
+        if dash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Dashing>();
+        }
+    }
+}
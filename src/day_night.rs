@@ -0,0 +1,139 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::adaptive::Pressure;
+use crate::args::Difficulty;
+use crate::loading::GameAssets;
+use crate::{MonsterSpawnTimer, NewGameEvent, MONSTER_SPAWN_PERIOD_SECONDS};
+
+
+const DAY_LENGTH_SECONDS: f32 = 120.0;
+
+const NIGHT_SPAWN_RATE_BONUS: f32 = 1.0;
+const NIGHT_SPEED_BONUS: f32      = 0.3;
+const NIGHT_OVERLAY_ALPHA: f32    = 0.55;
+
+const HUD_FONT_SIZE: f32 = 24.0;
+
+#[derive(Default)]
+pub struct WorldClock {
+    elapsed: f32,
+}
+
+impl WorldClock {
+    fn day(&self) -> u32 {
+        (self.elapsed / DAY_LENGTH_SECONDS) as u32 + 1
+    }
+
+    /// 0.0 at midday, 1.0 at midnight.
+    pub fn darkness(&self) -> f32 {
+        let t = (self.elapsed / DAY_LENGTH_SECONDS).fract();
+        0.5 - 0.5 * (t * TAU).cos()
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.darkness() > 0.5
+    }
+
+    /// Monsters grow bolder in the dark.
+    pub fn monster_speed_factor(&self) -> f32 {
+        1.0 + NIGHT_SPEED_BONUS * self.darkness()
+    }
+}
+
+#[derive(Component)]
+pub struct NightOverlay;
+
+#[derive(Component)]
+pub struct PhaseText;
+
+pub fn spawn_day_night_hud(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::FlexEnd,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(NightOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(12.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: assets.font(),
+                            font_size: HUD_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PhaseText);
+        });
+}
+
+pub fn despawn_day_night_hud(
+    mut commands: Commands,
+    overlays: Query<Entity, With<NightOverlay>>,
+) {
+    for overlay in overlays.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+}
+
+pub fn reset_world_clock(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut clock: ResMut<WorldClock>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        clock.elapsed = 0.0;
+    }
+}
+
+pub fn advance_world_clock(
+    time: Res<Time>,
+    mut clock: ResMut<WorldClock>,
+    mut overlays: Query<&mut UiColor, With<NightOverlay>>,
+    mut phase_texts: Query<&mut Text, With<PhaseText>>,
+) {
+    clock.elapsed += time.delta_seconds();
+    let darkness = clock.darkness();
+
+    for mut color in overlays.iter_mut() {
+        color.0 = Color::rgba(0.02, 0.03, 0.15, darkness * NIGHT_OVERLAY_ALPHA);
+    }
+
+    let phase = if clock.is_night() { "Night" } else { "Day" };
+    for mut text in phase_texts.iter_mut() {
+        text.sections[0].value = format!("Day {} - {}", clock.day(), phase);
+    }
+}
+
+pub fn night_pressure(
+    clock: Res<WorldClock>,
+    difficulty: Res<Difficulty>,
+    pressure: Res<Pressure>,
+    mut spawn_timer: ResMut<MonsterSpawnTimer>,
+) {
+    // Spawn faster as night falls
+    let night = 1.0 + NIGHT_SPAWN_RATE_BONUS * clock.darkness();
+    let rate = difficulty.spawn_rate_factor() * pressure.factor() * night;
+    let period = MONSTER_SPAWN_PERIOD_SECONDS / rate;
+    spawn_timer.0.set_duration(Duration::from_secs_f32(period));
+}
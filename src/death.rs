@@ -0,0 +1,206 @@
+//! Getting caught plays out before the run ends. The caught player, or the
+//! pilgrim on an escort, spins away while the world slows to a crawl, then the
+//! game over screen comes up over the frozen arena. Demo runs skip the screen
+//! and restart straight away.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::autopilot::DemoMode;
+use crate::curses::Curses;
+use crate::daily::{daily_challenge, Daily};
+use crate::escort::Escort;
+use crate::leaderboard::Leaderboard;
+use crate::loading::GameAssets;
+use crate::menu::menu_text;
+use crate::nav::{FocusRing, MenuAction};
+use crate::run_stats::{GameOverEvent, RunStats};
+use crate::seed::{seed_label, SeedText};
+use crate::stage::Stages;
+use crate::{AppState, MonsterStats, NewGameEvent, RunDefaults, Velocity, WorldSeed, PLAYER_SPRITE_SCALE};
+
+
+const DEATH_SECONDS: f32    = 1.5;
+const DEATH_TIME_SCALE: f32 = 0.3;
+const DEATH_TURNS: f32      = 2.0;
+
+const GAME_OVER_TITLE_SIZE: f32 = 72.0;
+const GAME_OVER_TEXT_SIZE: f32  = 32.0;
+const GAME_OVER_HINT_SIZE: f32  = 18.0;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct PlayDeath;
+
+/// A caught player playing out their death. Input no longer moves them.
+#[derive(Component)]
+pub struct Dying(Timer);
+
+impl Dying {
+    pub fn new() -> Dying {
+        Dying(Timer::from_seconds(DEATH_SECONDS, false))
+    }
+}
+
+/// How fast the world moves, slowed while a death plays out.
+pub struct TimeScale(pub f32);
+
+#[derive(Component)]
+pub struct GameOverRoot;
+
+pub fn play_death(
+    time: Res<Time>,
+    mut time_scale: ResMut<TimeScale>,
+    mut dying: Query<(&mut Dying, &mut Velocity, &mut Transform, &mut TextureAtlasSprite)>,
+    demo: Res<DemoMode>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    mut state: ResMut<State<AppState>>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    if dying.is_empty() {
+        time_scale.0 = 1.0;
+        return;
+    }
+    time_scale.0 = DEATH_TIME_SCALE;
+
+    // Runs on real time, so the slowdown doesn't stretch it out
+    let mut is_over = false;
+    for (mut dying, mut velocity, mut transform, mut sprite) in dying.iter_mut() {
+        dying.0.tick(time.delta());
+        let progress = dying.0.percent();
+
+        velocity.direction = Vec2::ZERO;
+        transform.rotation = Quat::from_rotation_z(progress * DEATH_TURNS * TAU);
+        transform.scale = Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, 1.0) * (1.0 - progress);
+        sprite.color.set_a(1.0 - progress);
+
+        is_over |= dying.0.finished();
+    }
+
+    if !is_over {
+        return;
+    }
+
+    // The finished death is seen again next frame, so a game over held back
+    // by a transition already queued goes through then
+    if !demo.is_active() {
+        if let Err(e) = state.set(AppState::GameOver) {
+            warn!("holding back the game over: {}", e);
+            return;
+        }
+    }
+
+    time_scale.0 = 1.0;
+    game_over_writer.send(GameOverEvent);
+    if demo.is_active() {
+        new_game_writer.send(NewGameEvent::on_stage(stages.selected, &defaults));
+    }
+}
+
+pub fn spawn_game_over_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    run_stats: Res<RunStats>,
+    monster_stats: Res<MonsterStats>,
+    curses: Res<Curses>,
+    world_seed: Res<WorldSeed>,
+    escort: Res<Escort>,
+    leaderboard: Res<Leaderboard>,
+) {
+    let font = assets.font();
+    let seconds = run_stats.duration_seconds() as u32;
+    let mut summary = format!("Survived {}:{:02} with {} kills", seconds / 60, seconds % 60, monster_stats.killed());
+    if monster_stats.executed() > 0 {
+        summary.push_str(&format!(" ({} executed)", monster_stats.executed()));
+    }
+    if escort.is_active {
+        summary.push_str(&format!(", {} waypoints escorted", escort.waypoints));
+    }
+    let title = if escort.has_fallen { "PILGRIM LOST" } else { "CAUGHT" };
+
+    // How the run stands against the best on its seed and on any seed, for
+    // the runs that are scored
+    let best_on_seed = leaderboard.on_seed(world_seed.value).next();
+    let best_on_version = leaderboard.on_version().next();
+    let standing = match (best_on_seed, best_on_version) {
+        _ if escort.is_active => String::new(),
+        (Some(best), Some(top)) if leaderboard.is_latest(best) => {
+            format!("New best on this seed! Best on any seed {}", top.score)
+        }
+        (Some(best), Some(top)) => format!("Your best on this seed {}, on any seed {}", best.score, top.score),
+        _ => String::new(),
+    };
+    let score = format!("Score {} (x{:.2})", curses.score(monster_stats.killed()), curses.reward_multiplier());
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.3, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(GameOverRoot)
+        .insert(FocusRing::new(0, 0))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text(title, &font, GAME_OVER_TITLE_SIZE, Color::WHITE));
+            parent.spawn_bundle(menu_text(&summary, &font, GAME_OVER_TEXT_SIZE, Color::WHITE));
+            parent.spawn_bundle(menu_text(&score, &font, GAME_OVER_TEXT_SIZE, Color::WHITE));
+            if !standing.is_empty() {
+                parent.spawn_bundle(menu_text(&standing, &font, GAME_OVER_HINT_SIZE, Color::WHITE));
+            }
+            parent
+                .spawn_bundle(menu_text(&seed_label(world_seed.value), &font, GAME_OVER_HINT_SIZE, Color::rgb(0.6, 0.6, 0.6)))
+                .insert(SeedText);
+            parent.spawn_bundle(menu_text("Enter to try again, Escape for the menu", &font, GAME_OVER_HINT_SIZE, Color::rgb(0.6, 0.6, 0.6)));
+        });
+}
+
+pub fn despawn_game_over_screen(
+    mut commands: Commands,
+    roots: Query<Entity, With<GameOverRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn game_over_input(
+    mut actions: EventReader<MenuAction>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    daily: Res<Daily>,
+    escort: Res<Escort>,
+    mut state: ResMut<State<AppState>>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    match actions.iter().next() {
+        // Trying the daily again plays the same day's run, and an escort
+        // goes again as an escort
+        Some(MenuAction::Confirm(_)) => match state.set(AppState::Playing) {
+            Ok(()) => {
+                let setup = match daily.run_day {
+                    Some(day) => daily_challenge(day, stages.list.len(), &defaults),
+                    None => NewGameEvent::on_stage(stages.selected, &defaults),
+                };
+                new_game_writer.send(NewGameEvent{
+                    escort: escort.is_active,
+                    ..setup
+                });
+            }
+            Err(e) => warn!("ignoring the new run: {}", e),
+        },
+        Some(MenuAction::Cancel) => {
+            if let Err(e) = state.set(AppState::Menu) {
+                warn!("ignoring the return to the menu: {}", e);
+            }
+        }
+        None => {}
+    }
+}
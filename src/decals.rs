@@ -0,0 +1,135 @@
+//! Marks left on the ground by the fight: scorch marks where blasts went off
+//! and splatter where monsters fell. Decals fade out after a while, and only
+//! as many as the remains limit allows are kept, with the oldest going first.
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::remains::{oldest_over_limit, RemainsLimit};
+use crate::tween::{Ease, Tween, TweenTarget};
+use crate::{Blast, MonsterKind, NewGameEvent, Position};
+
+
+const DECAL_DEPTH: f32          = -20.0;
+const DECAL_SPREAD_SECONDS: f32 = 0.2;
+const DECAL_START_SCALE: f32    = 0.3;
+
+const SCORCH_SECONDS: f32 = 6.0;
+const SCORCH_ALPHA: f32   = 0.35;
+
+const SPLATTER_SECONDS: f32 = 10.0;
+const SPLATTER_ALPHA: f32   = 0.45;
+const SPLATTER_SCALE: f32   = 1.5;
+
+/// Sent to leave a mark on the ground.
+#[derive(Clone, Copy)]
+pub struct LeaveDecal {
+    position: Vec2,
+    color: Color,
+    radius: f32,
+    seconds: f32,
+}
+
+impl LeaveDecal {
+    fn scorch(position: Vec2, radius: f32) -> LeaveDecal {
+        LeaveDecal{
+            position,
+            color: Color::rgba(0.1, 0.08, 0.05, SCORCH_ALPHA),
+            radius,
+            seconds: SCORCH_SECONDS,
+        }
+    }
+
+    pub fn splatter(position: Vec2, kind: MonsterKind) -> LeaveDecal {
+        LeaveDecal{
+            position,
+            color: *kind.tint().set_a(SPLATTER_ALPHA),
+            radius: kind.radius() * SPLATTER_SCALE,
+            seconds: SPLATTER_SECONDS,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Decal {
+    lifetime: Timer,
+    alpha: f32,
+}
+
+pub fn scorch_ground(
+    blasts: Query<(&Blast, &Position), Added<Blast>>,
+    mut decals: EventWriter<LeaveDecal>,
+) {
+    for (blast, p) in blasts.iter() {
+        decals.send(LeaveDecal::scorch(p.current, blast.circle.radius));
+    }
+}
+
+pub fn update_decals(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    limit: Res<RemainsLimit>,
+    mut new_decals: EventReader<LeaveDecal>,
+    mut decals: Query<(Entity, &mut Decal, &mut TextureAtlasSprite)>,
+) {
+    // Fade the decals already down, keeping the ones still showing from
+    // oldest to newest
+    let mut showing = Vec::new();
+    for (entity, mut decal, mut sprite) in decals.iter_mut() {
+        decal.lifetime.tick(time.delta());
+        sprite.color.set_a(decal.alpha * (1.0 - decal.lifetime.percent()));
+
+        if decal.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+        else {
+            showing.push((entity, decal.lifetime.elapsed_secs()));
+        }
+    }
+
+    // Make room for new ones by dropping the oldest
+    let placed: Vec<LeaveDecal> = new_decals.iter().copied().collect();
+    for entity in oldest_over_limit(showing, placed.len(), limit.get()) {
+        commands.entity(entity).despawn();
+    }
+
+    for decal in placed.into_iter().rev().take(limit.get()) {
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.blast(),
+                    color: decal.color,
+                    custom_size: Some(Vec2::splat(decal.radius * 2.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, DECAL_DEPTH),
+                ..Default::default()
+            })
+            .insert(Decal{
+                lifetime: Timer::from_seconds(decal.seconds, false),
+                alpha: decal.color.a(),
+            })
+            .insert(Position::new(decal.position))
+            .insert(Tween::new(
+                TweenTarget::Scale { from: Vec3::splat(DECAL_START_SCALE), to: Vec3::ONE },
+                Ease::CubicOut,
+                DECAL_SPREAD_SECONDS,
+            ));
+    }
+}
+
+pub fn clear_decals(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    decals: Query<Entity, With<Decal>>,
+) {
+    if new_game_reader.iter().next().is_none() {
+        return;
+    }
+
+    for decal in decals.iter() {
+        commands.entity(decal).despawn();
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::camera::CameraFocus;
+use crate::{Position, WorldSeed};
+
+
+const DECORATION_CHUNK_SIZE: f32     = 512.0;
+const DECORATION_CHUNK_RADIUS: i32   = 2;
+const DECORATION_MIN_PER_CHUNK: u32  = 6;
+const DECORATION_MAX_PER_CHUNK: u32  = 14;
+const DECORATION_DEPTH: f32          = -50.0;
+
+#[derive(Clone, Copy)]
+enum DecorationKind {
+    Grass,
+    Rock,
+    Bones,
+}
+
+impl DecorationKind {
+    fn pick(rng: &mut StdRng) -> DecorationKind {
+        match rng.gen_range(0..10) {
+            0..=5 => DecorationKind::Grass,
+            6..=8 => DecorationKind::Rock,
+            _ => DecorationKind::Bones,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            DecorationKind::Grass => Color::rgb(0.3, 0.45, 0.22),
+            DecorationKind::Rock => Color::rgb(0.42, 0.42, 0.4),
+            DecorationKind::Bones => Color::rgb(0.85, 0.82, 0.72),
+        }
+    }
+
+    fn size(self) -> Vec2 {
+        match self {
+            DecorationKind::Grass => Vec2::new(6.0, 14.0),
+            DecorationKind::Rock => Vec2::new(16.0, 12.0),
+            DecorationKind::Bones => Vec2::new(14.0, 5.0),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Decoration;
+
+#[derive(Default)]
+pub struct DecorationChunks {
+    seed: u64,
+    loaded: HashMap<IVec2, Vec<Entity>>,
+}
+
+fn chunk_seed(seed: u64, chunk: IVec2) -> u64 {
+    seed
+        ^ (chunk.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (chunk.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    seed: u64,
+    chunk: IVec2,
+) -> Vec<Entity> {
+    let mut rng = StdRng::seed_from_u64(chunk_seed(seed, chunk));
+    let corner = chunk.as_vec2() * DECORATION_CHUNK_SIZE;
+    let count = rng.gen_range(DECORATION_MIN_PER_CHUNK..=DECORATION_MAX_PER_CHUNK);
+
+    (0..count)
+        .map(|_| {
+            let kind = DecorationKind::pick(&mut rng);
+            let offset = Vec2::new(rng.gen(), rng.gen()) * DECORATION_CHUNK_SIZE;
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite{
+                        color: kind.color(),
+                        custom_size: Some(kind.size()),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, DECORATION_DEPTH),
+                    ..Default::default()
+                })
+                .insert(Decoration)
+                .insert(Position::new(corner + offset))
+                .id()
+        })
+        .collect()
+}
+
+pub fn stream_decorations(
+    mut commands: Commands,
+    world_seed: Res<WorldSeed>,
+    mut chunks: ResMut<DecorationChunks>,
+    focus: Res<CameraFocus>,
+) {
+    let center = (focus.center / DECORATION_CHUNK_SIZE).floor().as_ivec2();
+
+    // A new seed means a new world, so drop everything generated for the old one
+    if chunks.seed != world_seed.value {
+        for (_, entities) in chunks.loaded.drain() {
+            for entity in entities {
+                commands.entity(entity).despawn();
+            }
+        }
+        chunks.seed = world_seed.value;
+    }
+
+    // Despawn chunks that fell out of range
+    chunks.loaded.retain(|chunk, entities| {
+        let distance = (*chunk - center).abs();
+        if distance.x <= DECORATION_CHUNK_RADIUS && distance.y <= DECORATION_CHUNK_RADIUS {
+            return true;
+        }
+
+        for entity in entities.iter() {
+            commands.entity(*entity).despawn();
+        }
+        false
+    });
+
+    // Spawn chunks that came into range
+    for y in -DECORATION_CHUNK_RADIUS..=DECORATION_CHUNK_RADIUS {
+        for x in -DECORATION_CHUNK_RADIUS..=DECORATION_CHUNK_RADIUS {
+            let chunk = center + IVec2::new(x, y);
+            if chunks.loaded.contains_key(&chunk) {
+                continue;
+            }
+
+            let entities = spawn_chunk(&mut commands, world_seed.value, chunk);
+            chunks.loaded.insert(chunk, entities);
+        }
+    }
+}
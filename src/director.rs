@@ -0,0 +1,504 @@
+//! The spawn director, which sends coordinated groups on top of the steady
+//! trickle from `spawn_monster`. Every so often it spawns a formation: a
+//! closing ring around a player, a wedge or a pincer of two columns. A
+//! controller entity moves each formation and its members hold their offsets
+//! from it until the formation breaks up and they chase on their own.
+//!
+//! Every few minutes it also runs a horde event. A banner warns which side
+//! the horde is coming from, then a wave of runners sweeps straight across
+//! the arena before turning to chase like the rest.
+//!
+//! On top of that it plays out the stage's timeline, spawning the groups and
+//! bosses the stage scripts at set times. Scripted spawns are the stage's
+//! design, so they go ahead even past the spawn budget. It also paces the
+//! shrines in `shrine`, which bring their own spawns while they charge.
+//!
+//! Formations and hordes come sooner or later with the pressure from
+//! `adaptive`, when it's on. In bounded arenas everything comes in through
+//! the gates on the side it's sent from, and formations don't ring players
+//! in, since a ring would stand in the walls.
+
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::adaptive::Pressure;
+use crate::arena::Arena;
+use crate::loading::GameAssets;
+use crate::players::{nearest_player, player_positions, random_player};
+use crate::stage::{spawn_boss, Stages, TimelineAction};
+use crate::threat::Threat;
+use crate::{
+    random_unit, spawn_monster_kind, MonsterKind, MonsterStats, NewGameEvent, Player, Position,
+    Velocity, MONSTER_SPAWN_DISTANCE,
+};
+
+
+const FORMATION_PERIOD_SECONDS: f32 = 40.0;
+const FORMATION_SECONDS: f32        = 10.0;
+const FORMATION_SIZE: usize         = 16;
+const FORMATION_SPACING: f32        = 28.0;
+const FORMATION_SLOT_SLACK: f32     = 20.0;
+const FORMATION_PACE: f32           = 0.8;
+
+const WAVE_PERIOD_SECONDS: f32  = 180.0;
+const WAVE_WARNING_SECONDS: f32 = 3.0;
+const WAVE_BURST_SECONDS: f32   = 0.2;
+const WAVE_BURSTS: u32          = 12;
+const WAVE_BURST_SIZE: u32      = 6;
+const WAVE_DISTANCE: f32        = 450.0;
+const WAVE_HALF_WIDTH: f32      = 500.0;
+const WAVE_SWEEP_SECONDS: f32   = 8.0;
+
+const BANNER_FONT_SIZE: f32 = 40.0;
+
+const TIMELINE_GROUP_DEPTH: f32 = 60.0;
+
+const SHRINE_PERIOD_SECONDS: f32 = 75.0;
+
+const RING_RADIUS: f32  = 380.0;
+const RING_CLOSING: f32 = 0.8;
+const PINCER_GAP: f32   = 260.0;
+
+#[derive(Clone, Copy)]
+enum FormationShape {
+    Ring,
+    Wedge,
+    Pincer,
+}
+
+impl FormationShape {
+    const ALL: [FormationShape; 3] = [FormationShape::Ring, FormationShape::Wedge, FormationShape::Pincer];
+
+    /// Where member `slot` sits relative to the controller, facing along
+    /// `heading`, with `progress` running from 0 to 1 over the formation's life.
+    fn offset(self, slot: usize, heading: Vec2, progress: f32) -> Vec2 {
+        let side = heading.perp();
+        let sign = 1.0 - 2.0 * (slot % 2) as f32;
+        match self {
+            FormationShape::Ring => {
+                let angle = slot as f32 / FORMATION_SIZE as f32 * TAU;
+                let radius = RING_RADIUS * (1.0 - RING_CLOSING * progress);
+                Vec2::new(angle.cos(), angle.sin()) * radius
+            }
+            FormationShape::Wedge => {
+                // Tip first, then alternating sides of each row
+                let row = slot.div_ceil(2) as f32;
+                (side * sign - heading) * row * FORMATION_SPACING
+            }
+            FormationShape::Pincer => {
+                // Two columns that squeeze together as they advance
+                let rank = (slot / 2) as f32;
+                side * sign * PINCER_GAP * (1.0 - progress) - heading * rank * FORMATION_SPACING
+            }
+        }
+    }
+}
+
+/// Moves a formation. Rings close in where they were spawned, the other
+/// shapes advance on the nearest player.
+#[derive(Component)]
+pub struct Formation {
+    shape: FormationShape,
+    age: Timer,
+}
+
+#[derive(Component)]
+pub struct FormationMember {
+    controller: Entity,
+    slot: usize,
+}
+
+/// A wave member still sweeping straight across the arena.
+#[derive(Component)]
+pub struct Sweeping {
+    direction: Vec2,
+    timer: Timer,
+}
+
+#[derive(Component)]
+pub struct WaveBanner;
+
+/// The steps of a horde event, in order.
+enum WaveStep {
+    Warning(Timer),
+    Spawning {
+        burst: Timer,
+        remaining: u32,
+    },
+}
+
+struct Wave {
+    /// The side the horde comes from.
+    from: Vec2,
+    step: WaveStep,
+}
+
+pub struct Director {
+    formation_timer: Timer,
+    wave_timer: Timer,
+    wave: Option<Wave>,
+    shrine_timer: Timer,
+    timeline_seconds: f32,
+    /// The next timeline event due.
+    timeline_next: usize,
+}
+
+impl Director {
+    pub fn new() -> Director {
+        Director{
+            formation_timer: Timer::from_seconds(FORMATION_PERIOD_SECONDS, true),
+            wave_timer: Timer::from_seconds(WAVE_PERIOD_SECONDS, true),
+            wave: None,
+            shrine_timer: Timer::from_seconds(SHRINE_PERIOD_SECONDS, true),
+            timeline_seconds: 0.0,
+            timeline_next: 0,
+        }
+    }
+
+    /// Keep formations, waves, shrines and the timeline from coming, for runs
+    /// without them.
+    pub fn hold(&mut self) {
+        self.formation_timer.reset();
+        self.wave_timer.reset();
+        self.shrine_timer.reset();
+        self.timeline_seconds = 0.0;
+    }
+
+    /// Whether it's time to put up another shrine.
+    pub fn is_shrine_due(&mut self, delta: Duration) -> bool {
+        self.shrine_timer.tick(delta).just_finished()
+    }
+}
+
+fn compass_name(from: Vec2) -> &'static str {
+    if from.y > 0.5 {
+        "north"
+    }
+    else if from.y < -0.5 {
+        "south"
+    }
+    else if from.x > 0.0 {
+        "east"
+    }
+    else {
+        "west"
+    }
+}
+
+pub fn reset_director(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut director: ResMut<Director>,
+    formations: Query<Entity, With<Formation>>,
+    mut banners: Query<&mut Text, With<WaveBanner>>,
+) {
+    if new_game_reader.iter().next().is_none() {
+        return;
+    }
+
+    *director = Director::new();
+    for formation in formations.iter() {
+        commands.entity(formation).despawn();
+    }
+    for mut text in banners.iter_mut() {
+        text.sections[0].value.clear();
+    }
+}
+
+pub fn spawn_formations(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    stages: Res<Stages>,
+    players: Query<&Position, With<Player>>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut threat: ResMut<Threat>,
+    pressure: Res<Pressure>,
+    arena: Res<Arena>,
+) {
+    director.formation_timer.tick(time.delta().mul_f32(pressure.factor()));
+    if !director.formation_timer.just_finished() {
+        return;
+    }
+
+    // Formations don't push past the budget
+    let kind = stages.current().roll_monster();
+    let cost = kind.threat() * FORMATION_SIZE as u32;
+    if !threat.can_afford(cost) {
+        return;
+    }
+
+    let target = if let Some(p) = random_player(&player_positions(&players)) {
+        p
+    }
+    else {
+        return
+    };
+
+    // Rings come first, and would stand in the walls of a bounded arena
+    let shapes = if arena.is_bounded() { &FormationShape::ALL[1..] } else { &FormationShape::ALL[..] };
+    let shape = shapes[rand::random::<usize>() % shapes.len()];
+    let (anchor, heading, speed) = match shape {
+        FormationShape::Ring => (target, Vec2::X, 0.0),
+        _ => {
+            // Slow enough for the members to keep up
+            let from = random_unit();
+            let anchor = arena
+                .spawn_point_from(from)
+                .unwrap_or(target + from * MONSTER_SPAWN_DISTANCE * 1.5);
+            (anchor, -from, kind.speed() * FORMATION_PACE)
+        }
+    };
+
+    let controller = commands
+        .spawn()
+        .insert(Formation{
+            shape,
+            age: Timer::from_seconds(FORMATION_SECONDS, false),
+        })
+        .insert(Position::new(anchor))
+        .insert(Velocity::new(heading, speed))
+        .id();
+
+    for slot in 0..FORMATION_SIZE {
+        let position = anchor + shape.offset(slot, heading, 0.0);
+        let member = spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+        commands.entity(member).insert(FormationMember{
+            controller,
+            slot,
+        });
+        monster_stats.record_spawn(kind);
+    }
+    threat.spend(cost);
+}
+
+pub fn run_horde_waves(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    players: Query<&Position, With<Player>>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut banners: Query<&mut Text, With<WaveBanner>>,
+    pressure: Res<Pressure>,
+    arena: Res<Arena>,
+) {
+    // Start a new wave from a random side
+    director.wave_timer.tick(time.delta().mul_f32(pressure.factor()));
+    if director.wave_timer.just_finished() && director.wave.is_none() {
+        let from = [Vec2::Y, -Vec2::Y, Vec2::X, -Vec2::X][rand::random::<usize>() % 4];
+        director.wave = Some(Wave{
+            from,
+            step: WaveStep::Warning(Timer::from_seconds(WAVE_WARNING_SECONDS, false)),
+        });
+
+        for mut text in banners.iter_mut() {
+            text.sections[0].value = format!("A horde approaches from the {}!", compass_name(from));
+        }
+    }
+
+    let wave = match director.wave.as_mut() {
+        Some(wave) => wave,
+        None => return,
+    };
+
+    match &mut wave.step {
+        WaveStep::Warning(timer) => {
+            if timer.tick(time.delta()).finished() {
+                wave.step = WaveStep::Spawning {
+                    burst: Timer::from_seconds(WAVE_BURST_SECONDS, true),
+                    remaining: WAVE_BURSTS,
+                };
+
+                for mut text in banners.iter_mut() {
+                    text.sections[0].value.clear();
+                }
+            }
+        }
+        WaveStep::Spawning { burst, remaining } => {
+            if !burst.tick(time.delta()).just_finished() {
+                return;
+            }
+
+            // Spread each burst along a line off to the horde's side of a player
+            if let Some(target) = random_player(&player_positions(&players)) {
+                let center = target + wave.from * WAVE_DISTANCE;
+                let across = wave.from.perp();
+                for _ in 0..WAVE_BURST_SIZE {
+                    let position = arena
+                        .spawn_point_from(wave.from)
+                        .unwrap_or_else(|| center + across * (rand::random::<f32>() * 2.0 - 1.0) * WAVE_HALF_WIDTH);
+                    let member = spawn_monster_kind(&mut commands, &sprites, MonsterKind::Runner, position, 1.0);
+                    commands.entity(member).insert(Sweeping{
+                        direction: -wave.from,
+                        timer: Timer::from_seconds(WAVE_SWEEP_SECONDS, false),
+                    });
+                    monster_stats.record_spawn(MonsterKind::Runner);
+                }
+            }
+
+            *remaining -= 1;
+            if *remaining == 0 {
+                director.wave = None;
+            }
+        }
+    }
+}
+
+pub fn run_timeline(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    stages: Res<Stages>,
+    players: Query<&Position, With<Player>>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut threat: ResMut<Threat>,
+    arena: Res<Arena>,
+) {
+    director.timeline_seconds += time.delta_seconds();
+
+    let timeline = &stages.current().timeline;
+    while let Some(event) = timeline.get(director.timeline_next) {
+        if event.at_seconds > director.timeline_seconds {
+            break;
+        }
+
+        let target = if let Some(p) = random_player(&player_positions(&players)) {
+            p
+        }
+        else {
+            return
+        };
+        director.timeline_next += 1;
+
+        match event.action {
+            TimelineAction::Group { kind, count, from } => {
+                for _ in 0..count {
+                    // A band off to one side, or all around, which in a
+                    // bounded arena means at the gates on that side or any
+                    let position = match from.direction() {
+                        Some(from) => arena.spawn_point_from(from).unwrap_or_else(|| {
+                            let across = (rand::random::<f32>() * 2.0 - 1.0) * WAVE_HALF_WIDTH;
+                            let depth = rand::random::<f32>() * TIMELINE_GROUP_DEPTH;
+                            target + from * (WAVE_DISTANCE + depth) + from.perp() * across
+                        }),
+                        None => arena
+                            .spawn_point()
+                            .unwrap_or_else(|| target + random_unit().normalize_or_zero() * MONSTER_SPAWN_DISTANCE),
+                    };
+                    spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+                    monster_stats.record_spawn(kind);
+                }
+                threat.spend(kind.threat() * count);
+            }
+            TimelineAction::Boss { kind, scale, hits } => {
+                let position = arena
+                    .spawn_point()
+                    .unwrap_or_else(|| target + random_unit() * MONSTER_SPAWN_DISTANCE);
+                spawn_boss(&mut commands, &sprites, kind, position, scale, hits);
+                monster_stats.record_spawn(kind);
+            }
+        }
+    }
+}
+
+pub fn steer_sweeping(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut sweepers: Query<(Entity, &mut Sweeping, &mut Velocity)>,
+) {
+    for (entity, mut sweeping, mut v) in sweepers.iter_mut() {
+        v.direction = sweeping.direction;
+
+        if sweeping.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Sweeping>();
+        }
+    }
+}
+
+pub fn spawn_wave_banner(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(120.0),
+                    left: Val::Percent(0.0),
+                    right: Val::Percent(0.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: assets.font(),
+                    font_size: BANNER_FONT_SIZE,
+                    color: Color::rgb(1.0, 0.4, 0.3),
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    ..Default::default()
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(WaveBanner);
+}
+
+pub fn despawn_wave_banner(
+    mut commands: Commands,
+    banners: Query<Entity, With<WaveBanner>>,
+) {
+    for banner in banners.iter() {
+        commands.entity(banner).despawn_recursive();
+    }
+}
+
+pub fn steer_formations(
+    time: Res<Time>,
+    mut commands: Commands,
+    players: Query<&Position, With<Player>>,
+    mut formations: Query<(Entity, &mut Formation, &Position, &mut Velocity), Without<FormationMember>>,
+    mut members: Query<(Entity, &FormationMember, &Position, &mut Velocity)>,
+) {
+    let targets = player_positions(&players);
+
+    for (entity, mut formation, p, mut v) in formations.iter_mut() {
+        formation.age.tick(time.delta());
+
+        // Keep heading for the nearest player
+        if let Some(nearest) = nearest_player(&targets, p.current) {
+            let direction = (nearest - p.current).normalize_or_zero();
+            if direction != Vec2::ZERO && v.speed > 0.0 {
+                v.direction = direction;
+            }
+        }
+
+        if formation.age.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for (entity, member, p, mut v) in members.iter_mut() {
+        let (formation, anchor, heading) = match formations.get(member.controller) {
+            Ok((_, formation, anchor, v)) => (formation, anchor.current, v.direction),
+            // The formation broke up, so chase like any other monster
+            Err(_) => {
+                commands.entity(entity).remove::<FormationMember>();
+                continue;
+            }
+        };
+
+        let slot = anchor + formation.shape.offset(member.slot, heading, formation.age.percent());
+        v.direction = ((slot - p.current) / FORMATION_SLOT_SLACK).clamp_length_max(1.0);
+    }
+}
@@ -0,0 +1,181 @@
+//! Discord Rich Presence. Reports the current stage, time survived and kill
+//! count while playing. The IPC connection lives on its own thread so a slow
+//! or missing Discord client never stalls a frame. Set `DISCORD_CLIENT_ID` to
+//! the application id to enable it.
+
+use std::env;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use discord_rich_presence::activity::{Activity, Timestamps};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+
+use crate::death::PlayDeath;
+use crate::run_stats::{GameOverEvent, RecordRun};
+use crate::stage::Stages;
+use crate::{AppState, MonsterStats, NewGameEvent};
+
+
+const CLIENT_ID_VARIABLE: &str = "DISCORD_CLIENT_ID";
+
+const KILL_MILESTONE: u32 = 100;
+
+pub struct DiscordPlugin;
+
+impl Plugin for DiscordPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Menu)
+                    .with_system(menu_presence),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(
+                        run_presence
+                            .after(PlayDeath)
+                            .before(RecordRun),
+                    ),
+            )
+            .insert_resource(DiscordPresence::connect());
+    }
+}
+
+struct Presence {
+    details: String,
+    state: String,
+    started_at: Option<i64>,
+}
+
+pub struct DiscordPresence {
+    sender: Option<Sender<Presence>>,
+}
+
+impl DiscordPresence {
+    fn connect() -> DiscordPresence {
+        let client_id = match env::var(CLIENT_ID_VARIABLE) {
+            Ok(id) => id,
+            Err(_) => {
+                info!("{} is not set, Discord presence disabled", CLIENT_ID_VARIABLE);
+                return DiscordPresence{
+                    sender: None,
+                };
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel::<Presence>();
+        thread::spawn(move || {
+            let mut client = DiscordIpcClient::new(&client_id);
+            let mut is_connected = false;
+
+            // Reconnect lazily, so Discord can be started after the game
+            for presence in receiver {
+                if !is_connected {
+                    is_connected = client.connect().is_ok();
+                }
+                if !is_connected {
+                    continue;
+                }
+
+                let mut activity = Activity::new()
+                    .details(presence.details.as_str())
+                    .state(presence.state.as_str());
+                if let Some(start) = presence.started_at {
+                    activity = activity.timestamps(Timestamps::new().start(start));
+                }
+
+                if let Err(e) = client.set_activity(activity) {
+                    warn!("failed to update Discord presence: {}", e);
+                    is_connected = false;
+                }
+            }
+        });
+
+        DiscordPresence{
+            sender: Some(sender),
+        }
+    }
+
+    fn send(&self, details: String, state: String, started_at: Option<i64>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Presence{
+                details,
+                state,
+                started_at,
+            });
+        }
+    }
+}
+
+/// Where the presence currently stands within a run.
+#[derive(Default)]
+struct RunProgress {
+    started: Option<Instant>,
+    started_at: i64,
+    milestone: (u64, u32),
+}
+
+impl RunProgress {
+    fn start(&mut self) {
+        self.started = Some(Instant::now());
+        self.started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn menu_presence(
+    presence: Res<DiscordPresence>,
+) {
+    presence.send(String::from("In the menu"), String::from("Picking a stage"), None);
+}
+
+fn run_presence(
+    presence: Res<DiscordPresence>,
+    mut progress: Local<RunProgress>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    monster_stats: Res<MonsterStats>,
+    stages: Res<Stages>,
+) {
+    let stage = &stages.current().name;
+    let is_new_game = new_game_reader.iter().next().is_some();
+
+    // Report the final tally, holding it until the next run reaches a milestone
+    if game_over_reader.iter().next().is_some() {
+        let survived = progress.started.map(|s| s.elapsed().as_secs()).unwrap_or_default();
+        presence.send(
+            format!("Caught on {}", stage),
+            format!("Survived {} with {} kills", format_duration(survived), monster_stats.killed()),
+            None,
+        );
+        progress.start();
+        progress.milestone = (0, 0);
+        return;
+    }
+    else if is_new_game || progress.started.is_none() {
+        progress.start();
+        progress.milestone = (u64::MAX, 0);
+    }
+
+    // Update on each new minute survived or kill milestone
+    let minute = progress.started.map(|s| s.elapsed().as_secs() / 60).unwrap_or_default();
+    let milestone = (minute, monster_stats.killed() / KILL_MILESTONE);
+    if milestone == progress.milestone {
+        return;
+    }
+    progress.milestone = milestone;
+
+    presence.send(
+        format!("Surviving on {}", stage),
+        format!("{} kills", monster_stats.killed()),
+        Some(progress.started_at),
+    );
+}
@@ -0,0 +1,54 @@
+//! Player stats with every modifier applied, cached on each player. They are
+//! only recomputed when something that feeds them changes, so systems that
+//! read them every frame don't each re-derive the same numbers.
+
+use bevy::prelude::*;
+
+use crate::curses::Curses;
+use crate::progression::Progression;
+use crate::weather::Weather;
+use crate::{Player, Velocity, BLAST_RADIUS, PLAYER_SPEED};
+
+
+/// Sent when a modifier that feeds player stats changes.
+pub struct StatsChangedEvent;
+
+#[derive(Component)]
+pub struct EffectiveStats {
+    pub move_speed: f32,
+    pub blast_radius: f32,
+}
+
+impl EffectiveStats {
+    fn derive(weather: &Weather, curses: Curses, progression: &Progression) -> EffectiveStats {
+        EffectiveStats{
+            move_speed: PLAYER_SPEED * curses.move_speed_factor() * progression.move_speed_factor(),
+            blast_radius: BLAST_RADIUS
+                * weather.blast_radius_factor()
+                * curses.blast_radius_factor()
+                * progression.blast_radius_factor(),
+        }
+    }
+}
+
+pub fn refresh_effective_stats(
+    mut commands: Commands,
+    mut changes: EventReader<StatsChangedEvent>,
+    weather: Res<Weather>,
+    curses: Res<Curses>,
+    progression: Res<Progression>,
+    mut players: Query<(Entity, &mut Velocity, Option<&EffectiveStats>), With<Player>>,
+) {
+    let is_stale = changes.iter().count() > 0;
+
+    // New players get their stats straight away, everyone else only on a change
+    for (entity, mut velocity, stats) in players.iter_mut() {
+        if stats.is_some() && !is_stale {
+            continue;
+        }
+
+        let stats = EffectiveStats::derive(&weather, *curses, &progression);
+        velocity.speed = stats.move_speed;
+        commands.entity(entity).insert(stats);
+    }
+}
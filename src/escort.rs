@@ -0,0 +1,258 @@
+//! The escort mode, started with G from the menu on the selected stage. A
+//! pilgrim sets off from beside the players and walks a winding road one
+//! waypoint at a time, slowly and without stopping. Monsters go for whichever
+//! is closer, the pilgrim or a player, and the run is lost if the pilgrim
+//! falls just as if a player had.
+
+use bevy::math::Mat2;
+use bevy::prelude::*;
+
+use crate::arena::Arena;
+use crate::burrowing::Submerged;
+use crate::camera::ScreenAnchor;
+use crate::death::Dying;
+use crate::feed::Announcement;
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::run_stats::RunStats;
+use crate::stage::Stages;
+use crate::{
+    collide_circles, random_unit, AppState, Attackers, Circle, NewGameEvent, Position, RunDefaults, Velocity,
+    MONSTER_CONTACT_DAMAGE, PLAYER_SPRITE_SCALE,
+};
+
+
+const PILGRIM_HEALTH: f32       = 100.0;
+const PILGRIM_SPEED: f32        = 30.0;
+const PILGRIM_RADIUS: f32       = 14.0;
+const PILGRIM_START_OFFSET: f32 = 60.0;
+const PILGRIM_COLOR: Color      = Color::rgb(0.95, 0.9, 0.6);
+
+const ROAD_LEG: f32       = 400.0;
+/// How far the road can bend at each waypoint, in radians.
+const ROAD_TURN: f32      = 0.8;
+const WAYPOINT_REACH: f32 = 8.0;
+
+const BAR_WIDTH: f32  = 40.0;
+const BAR_HEIGHT: f32 = 5.0;
+const BAR_GAP: f32    = 28.0;
+
+/// Whether the current run is an escort, and how it's going.
+#[derive(Default)]
+pub struct Escort {
+    pub is_active: bool,
+    pub waypoints: u32,
+    pub has_fallen: bool,
+}
+
+#[derive(Component)]
+pub struct Pilgrim {
+    waypoint: Vec2,
+    heading: Vec2,
+}
+
+#[derive(Component)]
+pub struct PilgrimBar;
+
+#[derive(Component)]
+pub struct PilgrimBarFill;
+
+pub fn start_escort(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    mut state: ResMut<State<AppState>>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::G) {
+        return;
+    }
+    if let Err(e) = state.set(AppState::Playing) {
+        warn!("ignoring the escort run: {}", e);
+        return;
+    }
+
+    keyboard_input.clear_just_pressed(KeyCode::G);
+    new_game_writer.send(NewGameEvent{
+        escort: true,
+        ..NewGameEvent::on_stage(stages.selected, &defaults)
+    });
+}
+
+/// Set off a new pilgrim for an escort run, and clear away the last one.
+pub fn setup_escort(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut escort: ResMut<Escort>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    pilgrims: Query<Entity, With<Pilgrim>>,
+    bars: Query<Entity, With<PilgrimBar>>,
+) {
+    let event = match new_game_reader.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    for pilgrim in pilgrims.iter() {
+        commands.entity(pilgrim).despawn();
+    }
+    for bar in bars.iter() {
+        commands.entity(bar).despawn_recursive();
+    }
+
+    *escort = Escort{
+        is_active: event.escort,
+        ..Default::default()
+    };
+    if !escort.is_active {
+        return;
+    }
+
+    let heading = random_unit().normalize_or_zero();
+    let start = -heading.perp() * PILGRIM_START_OFFSET;
+    let pilgrim = commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.player(),
+                color: PILGRIM_COLOR,
+                ..Default::default()
+            },
+            transform: Transform {
+                scale: Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, 1.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Pilgrim{
+            waypoint: start + heading * ROAD_LEG,
+            heading,
+        })
+        .insert(Health::new(PILGRIM_HEALTH))
+        .insert(Position::new(start))
+        .insert(Velocity::new(heading, PILGRIM_SPEED))
+        .id();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(PilgrimBar)
+        .insert(ScreenAnchor{
+            target: pilgrim,
+            offset: Vec2::Y * BAR_GAP,
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    color: Color::rgb(0.3, 0.9, 0.3).into(),
+                    ..Default::default()
+                })
+                .insert(PilgrimBarFill);
+        });
+}
+
+/// Keep the pilgrim heading for its next waypoint, and lay the road on past
+/// each one it reaches.
+pub fn walk_pilgrims(
+    arena: Res<Arena>,
+    mut escort: ResMut<Escort>,
+    mut announcements: EventWriter<Announcement>,
+    mut pilgrims: Query<(&mut Pilgrim, &Position, &mut Velocity), Without<Dying>>,
+) {
+    for (mut pilgrim, p, mut v) in pilgrims.iter_mut() {
+        // The arena may have gone up after the road was laid
+        pilgrim.waypoint = arena.clamp_inside(pilgrim.waypoint, PILGRIM_RADIUS);
+        if p.current.distance(pilgrim.waypoint) < WAYPOINT_REACH {
+            escort.waypoints += 1;
+            announcements.send(Announcement(format!("The pilgrim reached waypoint {}", escort.waypoints)));
+
+            // Bend the road a little, or turn back from an arena wall
+            let turn = (rand::random::<f32>() * 2.0 - 1.0) * ROAD_TURN;
+            let mut heading = Mat2::from_angle(turn) * pilgrim.heading;
+            let mut waypoint = arena.clamp_inside(p.current + heading * ROAD_LEG, PILGRIM_RADIUS);
+            if waypoint.distance(p.current) < ROAD_LEG / 2.0 {
+                heading = -heading;
+                waypoint = arena.clamp_inside(p.current + heading * ROAD_LEG, PILGRIM_RADIUS);
+            }
+            pilgrim.heading = heading;
+            pilgrim.waypoint = waypoint;
+        }
+
+        v.direction = (pilgrim.waypoint - p.current).normalize_or_zero();
+    }
+}
+
+/// Monsters hurt the pilgrim on contact, just as they do players, and a
+/// fallen pilgrim ends the run.
+pub fn hurt_pilgrims(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut escort: ResMut<Escort>,
+    mut run_stats: ResMut<RunStats>,
+    monsters: Attackers,
+    submerged: Query<(), With<Submerged>>,
+    dying: Query<(), With<Dying>>,
+    mut pilgrims: Query<(Entity, &Position, &mut Health), With<Pilgrim>>,
+) {
+    // The run is already ending
+    if !dying.is_empty() {
+        return;
+    }
+
+    let circle = Circle::new(PILGRIM_RADIUS);
+    for (entity, p, mut health) in pilgrims.iter_mut() {
+        health.tick(&time);
+        if !health.can_be_hurt() {
+            continue;
+        }
+
+        let attacker = monsters
+            .iter()
+            .filter(|m| m.2.is_none() && submerged.get(m.3).is_err())
+            .find(|monster| collide_circles((&circle, p.current), (&monster.0.circle, monster.1.current)).0);
+        let kind = match attacker {
+            Some(monster) => monster.4,
+            None => continue,
+        };
+
+        if health.hurt(MONSTER_CONTACT_DAMAGE) {
+            commands.entity(entity).insert(Dying::new());
+            escort.has_fallen = true;
+            run_stats.record_death(format!("{} caught the pilgrim", kind.name()));
+        }
+    }
+}
+
+pub fn update_pilgrim_bars(
+    mut commands: Commands,
+    pilgrims: Query<&Health, With<Pilgrim>>,
+    bars: Query<(Entity, &ScreenAnchor, &Children), With<PilgrimBar>>,
+    mut fills: Query<&mut Style, With<PilgrimBarFill>>,
+) {
+    for (bar, anchor, children) in bars.iter() {
+        let health = match pilgrims.get(anchor.target) {
+            Ok(health) => health,
+            Err(_) => {
+                commands.entity(bar).despawn_recursive();
+                continue;
+            }
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut style) = fills.get_mut(child) {
+                style.size.width = Val::Percent(100.0 * health.fraction());
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! The experience bar across the top of the screen, with the current level
+//! under its left end. Rather than jumping, the fill slides up to each new
+//! amount and glows brighter while it does, so every kill's experience reads
+//! as it lands. On a level up it runs to the end and starts again from empty.
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::progression::Progression;
+
+
+const BAR_HEIGHT: f32    = 6.0;
+const FILL_RATE: f32     = 8.0;
+const FILL_COLOR: Color  = Color::rgb(0.3, 0.6, 1.0);
+const GLOW_COLOR: Color  = Color::rgb(0.7, 0.9, 1.0);
+/// How far behind the fill has to be to glow fully.
+const GLOW_GAP: f32      = 0.1;
+
+const HUD_FONT_SIZE: f32  = 20.0;
+const LEVEL_TEXT_TOP: f32 = 12.0;
+
+#[derive(Component)]
+pub struct ExperienceBar;
+
+/// The fill, with how full it's drawn and the level it's drawn for.
+#[derive(Component)]
+pub struct ExperienceFill {
+    shown: f32,
+    level: u32,
+}
+
+#[derive(Component)]
+pub struct LevelText;
+
+fn level_label(level: u32) -> String {
+    format!("Level {}", level)
+}
+
+pub fn spawn_experience_bar(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    progression: Res<Progression>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Px(BAR_HEIGHT)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(ExperienceBar)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0 * progression.experience_fraction()), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    color: FILL_COLOR.into(),
+                    ..Default::default()
+                })
+                .insert(ExperienceFill{
+                    shown: progression.experience_fraction(),
+                    level: progression.level(),
+                });
+        });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(LEVEL_TEXT_TOP),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                level_label(progression.level()),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ExperienceBar)
+        .insert(LevelText);
+}
+
+pub fn despawn_experience_bar(
+    mut commands: Commands,
+    bars: Query<Entity, With<ExperienceBar>>,
+) {
+    for bar in bars.iter() {
+        commands.entity(bar).despawn_recursive();
+    }
+}
+
+pub fn update_experience_bar(
+    time: Res<Time>,
+    progression: Res<Progression>,
+    mut fills: Query<(&mut ExperienceFill, &mut Style, &mut UiColor)>,
+    mut texts: Query<&mut Text, With<LevelText>>,
+) {
+    let target = progression.experience_fraction();
+
+    for (mut fill, mut style, mut color) in fills.iter_mut() {
+        // Run out to the end before starting on the new level
+        let goal = if fill.level < progression.level() { 1.0 } else { target };
+        fill.shown += (goal - fill.shown) * (FILL_RATE * time.delta_seconds()).min(1.0);
+        if fill.level < progression.level() && fill.shown > 0.99 {
+            fill.level = progression.level();
+            fill.shown = 0.0;
+        }
+        // A new run starts over from nothing
+        if fill.level > progression.level() {
+            fill.level = progression.level();
+            fill.shown = target;
+        }
+
+        let glow = ((goal - fill.shown) / GLOW_GAP).clamp(0.0, 1.0);
+        let (from, to) = (Vec4::from(FILL_COLOR), Vec4::from(GLOW_COLOR));
+        color.0 = from.lerp(to, glow).into();
+        style.size.width = Val::Percent(100.0 * fill.shown);
+    }
+
+    if progression.is_changed() {
+        for mut text in texts.iter_mut() {
+            text.sections[0].value = level_label(progression.level());
+        }
+    }
+}
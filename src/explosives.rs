@@ -0,0 +1,140 @@
+//! Bombers and their explosions. A bomber that gets close to a player lights
+//! its fuse, flashes, and goes off. Blasting one sets it off early. Either way
+//! the explosion is a blast of its own that kills the monsters it catches,
+//! setting off other bombers in turn, and hurts any player caught in it. A
+//! bomber only ever goes off once, even if it's blasted as its fuse runs out.
+
+use bevy::prelude::*;
+
+use crate::death::Dying;
+use crate::health::Health;
+use crate::horde::Horde;
+use crate::loading::GameAssets;
+use crate::run_stats::RunStats;
+use crate::{collide_circles, Blast, Body, Layers, MonsterKind, MonsterStats, Player, Position};
+
+
+const FUSE_RANGE: f32         = 60.0;
+const FUSE_SECONDS: f32       = 0.8;
+const FUSE_FLASH_SECONDS: f32 = 0.1;
+
+const EXPLOSION_RADIUS: f32 = 70.0;
+const EXPLOSION_DAMAGE: f32 = 30.0;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct LightFuses;
+
+/// A bomber about to go off.
+#[derive(Component)]
+pub struct Fuse(Timer);
+
+impl Fuse {
+    /// Whether it's run out, so the bomber has gone off this frame.
+    pub fn is_spent(&self) -> bool {
+        self.0.finished()
+    }
+}
+
+pub fn spawn_explosion(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    position: Vec2,
+) {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.blast(),
+                color: Color::rgb(1.0, 0.6, 0.2),
+                custom_size: Some(Vec2::splat(EXPLOSION_RADIUS * 2.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Blast::new(EXPLOSION_RADIUS, position).hitting(Layers::MONSTERS.with(Layers::PLAYER)))
+        .insert(Position::new(position));
+}
+
+pub fn light_fuses(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut monster_stats: ResMut<MonsterStats>,
+    players: Query<&Position, With<Player>>,
+    hordes: Query<&Horde>,
+    mut bombers: Query<(Entity, &MonsterKind, &Position, Option<&mut Fuse>, &mut TextureAtlasSprite)>,
+) {
+    for (entity, kind, p, fuse, mut sprite) in bombers.iter_mut() {
+        if kind.behavior() != MonsterKind::Bomber {
+            continue;
+        }
+
+        let mut fuse = match fuse {
+            Some(fuse) => fuse,
+            None => {
+                let is_close = players
+                    .iter()
+                    .any(|player| player.current.distance_squared(p.current) <= FUSE_RANGE.powi(2));
+                if is_close {
+                    commands.entity(entity).insert(Fuse(Timer::from_seconds(FUSE_SECONDS, false)));
+                }
+                continue;
+            }
+        };
+
+        // Flash white while the fuse burns
+        let alpha = sprite.color.a();
+        let is_lit = ((fuse.0.elapsed_secs() / FUSE_FLASH_SECONDS) as u32).is_multiple_of(2);
+        sprite.color = if is_lit {
+            Color::WHITE
+        }
+        else {
+            kind.tint()
+        };
+        sprite.color.set_a(alpha);
+
+        if fuse.0.tick(time.delta()).finished() {
+            let members = hordes.get(entity).map(|h| h.members()).unwrap_or(1);
+            commands.entity(entity).despawn();
+            monster_stats.record_detonations(*kind, members);
+            spawn_explosion(&mut commands, &sprites, p.current);
+        }
+    }
+}
+
+pub fn explosion_damage(
+    mut commands: Commands,
+    blasts: Query<(&Blast, &Position)>,
+    mut players: Query<(Entity, &Body, &Position, &mut Health), With<Player>>,
+    dying: Query<(), With<Dying>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    // The run is already ending
+    if !dying.is_empty() {
+        return;
+    }
+
+    // Only explosions have players in their mask
+    for (explosion, explosion_p) in blasts.iter().filter(|(b, _)| b.mask.intersects(Layers::PLAYER)) {
+        for (entity, body, p, mut health) in players.iter_mut() {
+            if !health.can_be_hurt() {
+                continue;
+            }
+
+            let (did_collide, _) = collide_circles(
+                (&explosion.circle, explosion_p.current),
+                (&body.circle, p.current),
+            );
+            if !did_collide {
+                continue;
+            }
+
+            run_stats.record_damage(EXPLOSION_DAMAGE);
+            if health.hurt(EXPLOSION_DAMAGE) {
+                commands.entity(entity).insert(Dying::new());
+                run_stats.record_death(format!("{} explosion", MonsterKind::Bomber.name()));
+                return;
+            }
+        }
+    }
+}
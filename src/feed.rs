@@ -0,0 +1,153 @@
+//! The announcement feed in the top right corner. Boss arrivals and run
+//! milestones, each minute survived and every hundred kills, show up as short
+//! lines that fade out after a few seconds.
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::run_stats::RunStats;
+use crate::stage::Boss;
+use crate::{MonsterKind, MonsterStats, NewGameEvent};
+
+
+const FEED_ENTRY_SECONDS: f32 = 4.0;
+const FEED_FADE_SECONDS: f32  = 1.0;
+const FEED_MAX_ENTRIES: usize = 5;
+const FEED_FONT_SIZE: f32     = 20.0;
+
+const MILESTONE_SECONDS: u32 = 60;
+const MILESTONE_KILLS: u32   = 100;
+
+/// Sent with a line to show in the feed.
+pub struct Announcement(pub String);
+
+#[derive(Component)]
+pub struct FeedRoot;
+
+#[derive(Component)]
+pub struct FeedEntry(Timer);
+
+/// The milestones already announced this run.
+#[derive(Default)]
+pub struct Milestones {
+    minutes: u32,
+    kills: u32,
+}
+
+pub fn spawn_feed(
+    mut commands: Commands,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(FeedRoot);
+}
+
+pub fn despawn_feed(
+    mut commands: Commands,
+    roots: Query<Entity, With<FeedRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn announce_bosses(
+    bosses: Query<&MonsterKind, Added<Boss>>,
+    mut announcements: EventWriter<Announcement>,
+) {
+    for kind in bosses.iter() {
+        announcements.send(Announcement(format!("A giant {} has arrived", kind.name())));
+    }
+}
+
+pub fn announce_milestones(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut milestones: Local<Milestones>,
+    run_stats: Res<RunStats>,
+    monster_stats: Res<MonsterStats>,
+    mut announcements: EventWriter<Announcement>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        *milestones = Milestones::default();
+        return;
+    }
+
+    let minutes = run_stats.duration_seconds() as u32 / MILESTONE_SECONDS;
+    if minutes > milestones.minutes {
+        milestones.minutes = minutes;
+        let plural = if minutes == 1 { "" } else { "s" };
+        announcements.send(Announcement(format!("Survived {} minute{}", minutes, plural)));
+    }
+
+    let kills = monster_stats.killed() / MILESTONE_KILLS * MILESTONE_KILLS;
+    if kills > milestones.kills {
+        milestones.kills = kills;
+        announcements.send(Announcement(format!("{} kills", kills)));
+    }
+}
+
+pub fn update_feed(
+    time: Res<Time>,
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut announcements: EventReader<Announcement>,
+    roots: Query<(Entity, Option<&Children>), With<FeedRoot>>,
+    mut entries: Query<(&mut FeedEntry, &mut Text)>,
+) {
+    let is_new_game = new_game_reader.iter().next().is_some();
+
+    for (root, children) in roots.iter() {
+        let children = children.map(|c| c.iter().copied().collect()).unwrap_or_else(Vec::new);
+
+        // Fade entries out at the end of their time, and drop the oldest
+        // once the feed is over full
+        for (i, &child) in children.iter().enumerate() {
+            let (mut entry, mut text) = match entries.get_mut(child) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let is_excess = children.len() - i > FEED_MAX_ENTRIES;
+            if is_new_game || is_excess || entry.0.tick(time.delta()).finished() {
+                commands.entity(child).despawn_recursive();
+                continue;
+            }
+
+            let remaining = entry.0.duration().as_secs_f32() - entry.0.elapsed_secs();
+            text.sections[0].style.color.set_a((remaining / FEED_FADE_SECONDS).min(1.0));
+        }
+
+        for announcement in announcements.iter() {
+            let entry = commands
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        announcement.0.clone(),
+                        TextStyle {
+                            font: assets.font(),
+                            font_size: FEED_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(FeedEntry(Timer::from_seconds(FEED_ENTRY_SECONDS, false)))
+                .id();
+            commands.entity(root).add_child(entry);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+
+use crate::arena::Arena;
+use crate::escort::Pilgrim;
+use crate::{Body, Obstacle, Player, Position, Shape, MONSTER_BODY_RADIUS};
+
+
+const FLOW_FIELD_CELL_SIZE: f32   = 32.0;
+const FLOW_FIELD_HALF_EXTENT: i32 = 16;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBORS: [((i32, i32), u32); 8] = [
+    ((1, 0), STRAIGHT_COST),
+    ((-1, 0), STRAIGHT_COST),
+    ((0, 1), STRAIGHT_COST),
+    ((0, -1), STRAIGHT_COST),
+    ((1, 1), DIAGONAL_COST),
+    ((1, -1), DIAGONAL_COST),
+    ((-1, 1), DIAGONAL_COST),
+    ((-1, -1), DIAGONAL_COST),
+];
+
+pub struct FlowField {
+    targets: Vec<IVec2>,
+    obstacle_count: usize,
+    /// Cells from the middle of the grid to its edge.
+    half_extent: i32,
+    origin: IVec2,
+    blocked: Vec<bool>,
+    costs: Vec<u32>,
+    directions: Vec<Vec2>,
+}
+
+impl FlowField {
+    pub fn new() -> FlowField {
+        let size = FLOW_FIELD_HALF_EXTENT * 2 + 1;
+        let cells = (size * size) as usize;
+
+        FlowField{
+            targets: Vec::new(),
+            obstacle_count: 0,
+            half_extent: FLOW_FIELD_HALF_EXTENT,
+            origin: IVec2::ZERO,
+            blocked: vec![false; cells],
+            costs: vec![u32::MAX; cells],
+            directions: vec![Vec2::ZERO; cells],
+        }
+    }
+
+    fn size(&self) -> i32 {
+        self.half_extent * 2 + 1
+    }
+
+    fn cell_of(point: Vec2) -> IVec2 {
+        (point / FLOW_FIELD_CELL_SIZE).round().as_ivec2()
+    }
+
+    fn center_of(cell: IVec2) -> Vec2 {
+        cell.as_vec2() * FLOW_FIELD_CELL_SIZE
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let local = cell - self.origin;
+        if local.x < 0 || local.y < 0 || local.x >= self.size() || local.y >= self.size() {
+            None
+        }
+        else {
+            Some((local.y * self.size() + local.x) as usize)
+        }
+    }
+
+    /// Steering direction toward the nearest target for a point, or `None` when
+    /// the point is outside the field, unreachable, or already in a target cell.
+    pub fn direction_at(&self, point: Vec2) -> Option<Vec2> {
+        let i = self.index(Self::cell_of(point))?;
+        if self.directions[i] == Vec2::ZERO {
+            None
+        }
+        else {
+            Some(self.directions[i])
+        }
+    }
+
+    fn rebuild(&mut self, targets: Vec<IVec2>, obstacles: &[(f32, Shape, Vec2)], bounds: Option<Vec2>) {
+        // Center the grid between the targets, or over the whole of a bounded
+        // arena so monsters find their way in from the gates
+        let (center, half_extent) = match bounds {
+            Some(bounds) => {
+                let reach = (bounds.max_element() / FLOW_FIELD_CELL_SIZE).ceil() as i32 + 1;
+                (IVec2::ZERO, reach.max(FLOW_FIELD_HALF_EXTENT))
+            }
+            None => {
+                let sum = targets.iter().fold(IVec2::ZERO, |sum, t| sum + *t);
+                (sum / targets.len() as i32, FLOW_FIELD_HALF_EXTENT)
+            }
+        };
+        // Every cell is marked afresh below, so resizing is enough
+        self.half_extent = half_extent;
+        let cells = (self.size() * self.size()) as usize;
+        self.blocked.resize(cells, false);
+        self.costs.resize(cells, u32::MAX);
+        self.directions.resize(cells, Vec2::ZERO);
+
+        self.targets = targets;
+        self.obstacle_count = obstacles.len();
+        self.origin = center - IVec2::splat(self.half_extent);
+
+        // Mark cells a monster body can't occupy
+        for y in 0..self.size() {
+            for x in 0..self.size() {
+                let cell = self.origin + IVec2::new(x, y);
+                let center = Self::center_of(cell);
+                let i = (y * self.size() + x) as usize;
+
+                self.blocked[i] = obstacles.iter().any(|(radius, shape, position)| {
+                    let reach = radius + MONSTER_BODY_RADIUS;
+                    center.distance_squared(shape.closest_point(*position, center)) < reach * reach
+                });
+                self.costs[i] = u32::MAX;
+                self.directions[i] = Vec2::ZERO;
+            }
+        }
+
+        // Flood costs outward from every target that lands on the grid
+        let mut frontier = BinaryHeap::new();
+        for target in self.targets.iter() {
+            if let Some(start) = self.index(*target) {
+                self.costs[start] = 0;
+                frontier.push(Reverse((0, target.x, target.y)));
+            }
+        }
+
+        while let Some(Reverse((cost, x, y))) = frontier.pop() {
+            let cell = IVec2::new(x, y);
+            let i = self.index(cell).unwrap();
+            if cost > self.costs[i] {
+                continue;
+            }
+
+            for ((dx, dy), step_cost) in NEIGHBORS {
+                let step = IVec2::new(dx, dy);
+                let next = cell + step;
+                let j = match self.index(next) {
+                    Some(j) if !self.blocked[j] => j,
+                    _ => continue,
+                };
+
+                // Don't cut corners around blocked cells
+                if step.x != 0 && step.y != 0 && (self.is_blocked(cell + IVec2::new(step.x, 0)) || self.is_blocked(cell + IVec2::new(0, step.y))) {
+                    continue;
+                }
+
+                let next_cost = cost + step_cost;
+                if next_cost < self.costs[j] {
+                    self.costs[j] = next_cost;
+                    frontier.push(Reverse((next_cost, next.x, next.y)));
+                }
+            }
+        }
+
+        // Point each reachable cell at its cheapest neighbor
+        for y in 0..self.size() {
+            for x in 0..self.size() {
+                let cell = self.origin + IVec2::new(x, y);
+                let i = (y * self.size() + x) as usize;
+                if self.costs[i] == 0 || self.costs[i] == u32::MAX {
+                    continue;
+                }
+
+                let mut best = (self.costs[i], IVec2::ZERO);
+                for ((dx, dy), _) in NEIGHBORS {
+                    let step = IVec2::new(dx, dy);
+                    if let Some(j) = self.index(cell + step) {
+                        if self.costs[j] < best.0 {
+                            best = (self.costs[j], step);
+                        }
+                    }
+                }
+
+                self.directions[i] = best.1.as_vec2().normalize_or_zero();
+            }
+        }
+    }
+
+    fn is_blocked(&self, cell: IVec2) -> bool {
+        self.index(cell).is_none_or(|i| self.blocked[i])
+    }
+}
+
+pub fn update_flow_field(
+    mut field: ResMut<FlowField>,
+    players: Query<&Position, With<Player>>,
+    pilgrims: Query<&Position, With<Pilgrim>>,
+    obstacles: Query<(&Body, &Position), With<Obstacle>>,
+    added_obstacles: Query<(), Added<Obstacle>>,
+    arena: Res<Arena>,
+) {
+    let targets: Vec<IVec2> = players
+        .iter()
+        .chain(pilgrims.iter())
+        .map(|p| FlowField::cell_of(p.current))
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    // Only rebuild when a player changes cells or obstacles come and go
+    let obstacles: Vec<(f32, Shape, Vec2)> = obstacles
+        .iter()
+        .map(|(b, p)| (b.circle.radius, b.shape, p.current))
+        .collect();
+    if field.targets == targets && field.obstacle_count == obstacles.len() && added_obstacles.is_empty() && !arena.is_changed() {
+        return;
+    }
+
+    field.rebuild(targets, &obstacles, arena.bounds());
+}
@@ -0,0 +1,139 @@
+//! A cap on the frame rate, for laptops that would rather not run flat out.
+//! The cap is picked in the menu with F or at launch with `--fps`, and while
+//! the window is in the background the game drops to a handful of frames a
+//! second whatever the cap, so it barely simulates anything until it's back.
+
+// The cap sleeps, which browsers don't allow, so it's native only
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+const BACKGROUND_FPS: f32 = 10.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FrameCap {
+    Thirty,
+    Sixty,
+    OneTwenty,
+    Uncapped,
+}
+
+impl FrameCap {
+    pub fn from_name(name: &str) -> Option<FrameCap> {
+        match name {
+            "30" => Some(FrameCap::Thirty),
+            "60" => Some(FrameCap::Sixty),
+            "120" => Some(FrameCap::OneTwenty),
+            "uncapped" => Some(FrameCap::Uncapped),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FrameCap::Thirty => "30",
+            FrameCap::Sixty => "60",
+            FrameCap::OneTwenty => "120",
+            FrameCap::Uncapped => "uncapped",
+        }
+    }
+
+    fn fps(self) -> Option<f32> {
+        match self {
+            FrameCap::Thirty => Some(30.0),
+            FrameCap::Sixty => Some(60.0),
+            FrameCap::OneTwenty => Some(120.0),
+            FrameCap::Uncapped => None,
+        }
+    }
+
+    fn next(self) -> FrameCap {
+        match self {
+            FrameCap::Thirty => FrameCap::Sixty,
+            FrameCap::Sixty => FrameCap::OneTwenty,
+            FrameCap::OneTwenty => FrameCap::Uncapped,
+            FrameCap::Uncapped => FrameCap::Thirty,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct FrameCapText;
+
+fn panel_label(cap: FrameCap) -> String {
+    format!("frame cap: {} (F)", cap.name())
+}
+
+pub fn spawn_frame_cap_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    cap: Res<FrameCap>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(108.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*cap),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(FrameCapText)
+        .insert(MenuRoot);
+}
+
+pub fn frame_cap_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut cap: ResMut<FrameCap>,
+    mut texts: Query<&mut Text, With<FrameCapText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F) {
+        return;
+    }
+
+    *cap = cap.next();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*cap);
+    }
+}
+
+/// Runs last in the frame and sleeps off whatever is left of it.
+pub fn limit_frame_rate(
+    cap: Res<FrameCap>,
+    windows: Res<Windows>,
+    mut frame_start: Local<Option<Instant>>,
+) {
+    let is_focused = windows.get_primary().is_none_or(|w| w.is_focused());
+    let fps = if is_focused { cap.fps() } else { Some(BACKGROUND_FPS) };
+
+    if let (Some(fps), Some(start)) = (fps, *frame_start) {
+        let elapsed = start.elapsed();
+        let frame = Duration::from_secs_f32(1.0 / fps);
+        if elapsed < frame {
+            thread::sleep(frame - elapsed);
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
@@ -0,0 +1,35 @@
+//! Gamepad movement. Each connected gamepad steers a player, in the order
+//! they were connected, with its left stick. Unlike the keys, a stick pushed
+//! part way moves its player at part speed, shaped by the deadzone and curve
+//! in the bindings.
+
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::input::{pad_for, Bindings};
+use crate::{Player, Velocity};
+
+
+/// Runs after the keys, and takes over from them while a stick is pushed.
+pub fn gamepad_direction(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<Bindings>,
+    mut players: Query<(&Player, &mut Velocity), Without<Autopilot>>,
+) {
+    for (player, mut v) in players.iter_mut() {
+        let pad = match pad_for(&gamepads, player.index) {
+            Some(pad) => pad,
+            None => continue,
+        };
+
+        let stick = Vec2::new(
+            axes.get(GamepadAxis(pad, GamepadAxisType::LeftStickX)).unwrap_or_default(),
+            axes.get(GamepadAxis(pad, GamepadAxisType::LeftStickY)).unwrap_or_default(),
+        );
+        let direction = bindings.stick_direction(stick);
+        if direction != Vec2::ZERO {
+            v.direction = direction;
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! A ghost of the best run on each stage. The first player's route is sampled
+//! through every run, and a run that outlasts the stage's best replaces it on
+//! disk. Later runs on that stage show a faint copy of the player walking the
+//! saved route in step with the run clock, to race against. Demo and training
+//! runs neither record nor show one.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::DemoMode;
+use crate::camera::CameraFocus;
+use crate::loading::GameAssets;
+use crate::run_stats::{GameOverEvent, RunStats, RUN_DIRECTORY};
+use crate::stage::Stages;
+use crate::storage::{self, Versioned};
+use crate::{NewGameEvent, Player, Position};
+
+
+const GHOST_SAMPLE_SECONDS: f32 = 0.25;
+const GHOST_COLOR: Color        = Color::rgba(0.8, 0.9, 1.0, 0.3);
+const GHOST_SPRITE_SCALE: f32   = 4.0;
+/// Under the players and monsters, over the ground.
+const GHOST_DEPTH: f32          = -1.0;
+
+#[derive(Default, Serialize, Deserialize)]
+struct GhostPath {
+    duration_seconds: f32,
+    /// The first player's position every `GHOST_SAMPLE_SECONDS`.
+    points: Vec<Vec2>,
+}
+
+impl GhostPath {
+    /// Where the ghost stood `seconds` into its run, if it was still alive.
+    fn position_at(&self, seconds: f32) -> Option<Vec2> {
+        let step = seconds / GHOST_SAMPLE_SECONDS;
+        let index = step as usize;
+        let from = *self.points.get(index)?;
+        let to = self.points.get(index + 1).copied().unwrap_or(from);
+        Some(from.lerp(to, step.fract()))
+    }
+}
+
+impl Versioned for GhostPath {
+    const MIGRATIONS: &'static [storage::Migration] = &[];
+}
+
+fn ghost_file(stage: &str) -> String {
+    let name: String = stage
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    format!("{}/ghost-{}.json", RUN_DIRECTORY, name)
+}
+
+fn load_ghost(file: &str) -> Option<GhostPath> {
+    if !storage::exists(file) {
+        return None;
+    }
+
+    storage::load(file)
+        .map_err(|e| warn!("ignoring {}: {}", file, e))
+        .ok()
+}
+
+/// The stage's best route and the one being walked now.
+pub struct Ghosts {
+    file: Option<String>,
+    best: Option<GhostPath>,
+    recording: Vec<Vec2>,
+    timer: Timer,
+}
+
+impl Default for Ghosts {
+    fn default() -> Ghosts {
+        Ghosts{
+            file: None,
+            best: None,
+            recording: Vec::new(),
+            timer: Timer::from_seconds(GHOST_SAMPLE_SECONDS, true),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Ghost;
+
+pub fn reset_ghost(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    stages: Res<Stages>,
+    demo: Res<DemoMode>,
+    mut ghosts: ResMut<Ghosts>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    old: Query<Entity, With<Ghost>>,
+) {
+    let event = match new_game_reader.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    for ghost in old.iter() {
+        commands.entity(ghost).despawn();
+    }
+
+    *ghosts = Ghosts::default();
+    if demo.is_active() || event.sandbox {
+        return;
+    }
+    let file = match stages.list.get(event.stage) {
+        Some(stage) => ghost_file(&stage.name),
+        None => return,
+    };
+    ghosts.best = load_ghost(&file);
+    ghosts.file = Some(file);
+
+    if ghosts.best.is_some() {
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.player(),
+                    color: GHOST_COLOR,
+                    ..Default::default()
+                },
+                transform: Transform {
+                    scale: Vec3::new(GHOST_SPRITE_SCALE, GHOST_SPRITE_SCALE, 1.0),
+                    ..Default::default()
+                },
+                visibility: Visibility{
+                    is_visible: false,
+                },
+                ..Default::default()
+            })
+            .insert(Ghost);
+    }
+}
+
+pub fn record_ghost(
+    time: Res<Time>,
+    stats: Res<RunStats>,
+    mut ghosts: ResMut<Ghosts>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    players: Query<(&Player, &Position)>,
+) {
+    if ghosts.file.is_none() {
+        return;
+    }
+
+    if ghosts.timer.tick(time.delta()).just_finished() || ghosts.recording.is_empty() {
+        if let Some((_, p)) = players.iter().find(|(player, _)| player.index == 0) {
+            ghosts.recording.push(p.current);
+        }
+    }
+
+    if game_over_reader.iter().next().is_none() {
+        return;
+    }
+
+    // Only a run that outlasts the best one replaces it
+    let duration_seconds = stats.duration_seconds();
+    if ghosts.best.as_ref().is_some_and(|best| best.duration_seconds >= duration_seconds) {
+        return;
+    }
+
+    let path = GhostPath{
+        duration_seconds,
+        points: std::mem::take(&mut ghosts.recording),
+    };
+    let file = ghosts.file.take().unwrap();
+    if let Err(e) = storage::save(&file, &path) {
+        warn!("failed to save {}: {}", file, e);
+    }
+    ghosts.best = Some(path);
+}
+
+pub fn replay_ghost(
+    stats: Res<RunStats>,
+    ghosts: Res<Ghosts>,
+    focus: Res<CameraFocus>,
+    mut sprites: Query<(&mut Transform, &mut Visibility), With<Ghost>>,
+) {
+    let position = ghosts
+        .best
+        .as_ref()
+        .and_then(|best| best.position_at(stats.duration_seconds()));
+
+    for (mut transform, mut visibility) in sprites.iter_mut() {
+        visibility.is_visible = position.is_some();
+        if let Some(position) = position {
+            transform.translation = (position - focus.center).extend(GHOST_DEPTH);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Flashes of light where blasts go off. Each blast and explosion throws out
+//! a soft glow in its own color that quickly fades. Glows are stronger and
+//! wider at night, when they have more dark to light up.
+
+use bevy::prelude::*;
+
+use crate::day_night::WorldClock;
+use crate::loading::GameAssets;
+use crate::tween::{Ease, Tween, TweenTarget};
+use crate::{Blast, NewGameEvent, Position};
+
+
+const GLOW_SECONDS: f32     = 0.35;
+const GLOW_DEPTH: f32       = 1.0;
+const GLOW_DAY_ALPHA: f32   = 0.2;
+const GLOW_NIGHT_ALPHA: f32 = 0.6;
+const GLOW_DAY_SCALE: f32   = 1.6;
+const GLOW_NIGHT_SCALE: f32 = 2.4;
+
+#[derive(Component)]
+pub struct Glow;
+
+pub fn flash_blasts(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    clock: Res<WorldClock>,
+    blasts: Query<(&Blast, &Position, &TextureAtlasSprite), Added<Blast>>,
+) {
+    let darkness = clock.darkness();
+    let alpha = GLOW_DAY_ALPHA + (GLOW_NIGHT_ALPHA - GLOW_DAY_ALPHA) * darkness;
+    let scale = GLOW_DAY_SCALE + (GLOW_NIGHT_SCALE - GLOW_DAY_SCALE) * darkness;
+
+    for (blast, p, blast_sprite) in blasts.iter() {
+        let mut color = blast_sprite.color;
+        color.set_a(alpha);
+        let mut faded = color;
+        faded.set_a(0.0);
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.blast(),
+                    color,
+                    custom_size: Some(Vec2::splat(blast.circle.radius * 2.0 * scale)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, GLOW_DEPTH),
+                ..Default::default()
+            })
+            .insert(Glow)
+            .insert(Position::new(p.current))
+            .insert(Tween::new(TweenTarget::SpriteColor { from: color, to: faded }, Ease::QuadOut, GLOW_SECONDS).then_despawn());
+    }
+}
+
+pub fn clear_glows(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    glows: Query<Entity, With<Glow>>,
+) {
+    if new_game_reader.iter().next().is_none() {
+        return;
+    }
+
+    for glow in glows.iter() {
+        commands.entity(glow).despawn();
+    }
+}
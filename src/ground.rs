@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+
+use crate::camera::CameraFocus;
+use crate::stage::Stages;
+use crate::Position;
+
+
+const GROUND_TILE_SIZE: f32   = 64.0;
+const GROUND_HALF_EXTENT: i32 = 20;
+const GROUND_DEPTH: f32       = -100.0;
+
+#[derive(Component)]
+pub struct GroundTile {
+    offset: IVec2,
+}
+
+pub fn spawn_ground(
+    mut commands: Commands,
+) {
+    for y in -GROUND_HALF_EXTENT..=GROUND_HALF_EXTENT {
+        for x in -GROUND_HALF_EXTENT..=GROUND_HALF_EXTENT {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite{
+                        custom_size: Some(Vec2::splat(GROUND_TILE_SIZE)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, GROUND_DEPTH),
+                    ..Default::default()
+                })
+                .insert(GroundTile{
+                    offset: IVec2::new(x, y),
+                })
+                .insert(Position::default());
+        }
+    }
+}
+
+pub fn scroll_ground(
+    stages: Res<Stages>,
+    focus: Res<CameraFocus>,
+    mut tiles: Query<(&GroundTile, &mut Position, &mut Sprite)>,
+) {
+    let center = (focus.center / GROUND_TILE_SIZE).round().as_ivec2();
+
+    let ground = &stages.current().ground;
+    let light = Color::rgb(ground.light.0, ground.light.1, ground.light.2);
+    let dark = Color::rgb(ground.dark.0, ground.dark.1, ground.dark.2);
+
+    // Wrap the fixed set of tiles around whichever cell the camera looks at
+    for (tile, mut p, mut sprite) in tiles.iter_mut() {
+        let cell = center + tile.offset;
+        p.current = cell.as_vec2() * GROUND_TILE_SIZE;
+        sprite.color = if (cell.x + cell.y).rem_euclid(2) == 0 {
+            light
+        }
+        else {
+            dark
+        };
+    }
+}
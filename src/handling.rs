@@ -0,0 +1,149 @@
+//! How the players' movement responds to input. Rather than jumping straight
+//! to full speed in whatever direction is held, a player speeds up toward it
+//! and slides to a stop once nothing is held, which reads better on screen
+//! and takes a little of the twitch out of dodging.
+//!
+//! Every input already moves at the same speed on diagonals as along the
+//! axes. Players who'd rather have grid-like control can snap movement to
+//! eight directions, picked in the menu with E or at launch with `--eight-way`.
+//! Keys only ever give eight anyway, so snapping matters for sticks, touch and
+//! click to move.
+
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+const ACCELERATION_SECONDS: f32 = 0.12;
+const STOP_SECONDS: f32         = 0.18;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Directions {
+    Free,
+    EightWay,
+}
+
+impl Directions {
+    fn name(self) -> &'static str {
+        match self {
+            Directions::Free => "free",
+            Directions::EightWay => "8-way",
+        }
+    }
+
+    fn toggled(self) -> Directions {
+        match self {
+            Directions::Free => Directions::EightWay,
+            Directions::EightWay => Directions::Free,
+        }
+    }
+
+    /// Turn `direction` to the nearest allowed one, keeping its length.
+    pub fn snap(self, direction: Vec2) -> Vec2 {
+        if self == Directions::Free || direction == Vec2::ZERO {
+            return direction;
+        }
+
+        let angle = (direction.y.atan2(direction.x) / FRAC_PI_4).round() * FRAC_PI_4;
+        Vec2::new(angle.cos(), angle.sin()) * direction.length()
+    }
+}
+
+/// How quickly players get going and come to a stop.
+pub struct Handling {
+    /// From standing still to full speed.
+    pub acceleration_seconds: f32,
+    /// From full speed to standing still.
+    pub stop_seconds: f32,
+}
+
+impl Default for Handling {
+    fn default() -> Handling {
+        Handling{
+            acceleration_seconds: ACCELERATION_SECONDS,
+            stop_seconds: STOP_SECONDS,
+        }
+    }
+}
+
+/// The velocity a player is actually moving at, chasing the one their
+/// `Velocity` asks for.
+#[derive(Component, Default)]
+pub struct Momentum {
+    velocity: Vec2,
+}
+
+impl Momentum {
+    /// Ease toward `wanted`, for an entity whose top speed is `top_speed`,
+    /// and return the velocity to move at.
+    pub fn steer(&mut self, wanted: Vec2, top_speed: f32, handling: &Handling, seconds: f32) -> Vec2 {
+        let span = if wanted == Vec2::ZERO {
+            handling.stop_seconds
+        }
+        else {
+            handling.acceleration_seconds
+        };
+        let step = if span > 0.0 { top_speed / span * seconds } else { f32::INFINITY };
+
+        self.velocity += (wanted - self.velocity).clamp_length_max(step);
+        self.velocity
+    }
+}
+
+#[derive(Component)]
+pub struct DirectionsPanelText;
+
+fn panel_label(directions: Directions) -> String {
+    format!("directions: {} (E)", directions.name())
+}
+
+pub fn spawn_directions_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    directions: Res<Directions>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(180.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*directions),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(DirectionsPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn directions_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut directions: ResMut<Directions>,
+    mut texts: Query<&mut Text, With<DirectionsPanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    *directions = directions.toggled();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*directions);
+    }
+}
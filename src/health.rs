@@ -0,0 +1,190 @@
+//! Player health and the low health warning. Monsters wear players down on
+//! contact instead of catching them outright, with a short grace period after
+//! each hit. Below a quarter of their health, a red vignette pulses at the
+//! screen edges in time with a heartbeat until the player is healed.
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::loading::GameAssets;
+use crate::Player;
+
+
+const HURT_GRACE_SECONDS: f32 = 0.75;
+
+const LOW_HEALTH_FRACTION: f32 = 0.25;
+const HEARTBEAT_SECONDS: f32   = 0.8;
+
+const VIGNETTE_SIZE: u32      = 64;
+const VIGNETTE_INNER: f32     = 0.55;
+const VIGNETTE_FALLOFF: f32   = 0.65;
+const VIGNETTE_MAX_ALPHA: f32 = 0.7;
+
+#[derive(Component)]
+pub struct Health {
+    current: f32,
+    max: f32,
+    grace: Timer,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Health {
+        // No grace period to begin with
+        let mut grace = Timer::from_seconds(HURT_GRACE_SECONDS, false);
+        grace.set_elapsed(grace.duration());
+
+        Health{
+            current: max,
+            max,
+            grace,
+        }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+
+    /// Whether the grace period after the last hit is over.
+    pub fn can_be_hurt(&self) -> bool {
+        self.grace.finished()
+    }
+
+    pub fn tick(&mut self, time: &Time) {
+        self.grace.tick(time.delta());
+    }
+
+    /// Take damage and start a grace period. Returns true if it was lethal.
+    pub fn hurt(&mut self, amount: f32) -> bool {
+        self.current = (self.current - amount).max(0.0);
+        self.grace.reset();
+
+        self.current == 0.0
+    }
+
+    /// Raise max health, healing by the same amount.
+    pub fn raise_max(&mut self, amount: f32) {
+        self.max += amount;
+        self.current += amount;
+    }
+
+    pub fn restore(&mut self) {
+        self.current = self.max;
+    }
+
+    /// How much healing it would take to top up.
+    pub fn missing(&self) -> f32 {
+        self.max - self.current
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+}
+
+#[derive(Component)]
+pub struct Vignette;
+
+pub struct Heartbeat {
+    timer: Timer,
+    is_beating: bool,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Heartbeat {
+        Heartbeat{
+            timer: Timer::from_seconds(HEARTBEAT_SECONDS, true),
+            is_beating: false,
+        }
+    }
+}
+
+/// Red edges fading to a clear center, stretched over the whole screen.
+fn generate_vignette() -> Image {
+    let radius = VIGNETTE_SIZE as f32 / 2.0;
+    let mut data = Vec::with_capacity((VIGNETTE_SIZE * VIGNETTE_SIZE * 4) as usize);
+
+    for y in 0..VIGNETTE_SIZE {
+        for x in 0..VIGNETTE_SIZE {
+            let offset = Vec2::new(x as f32 + 0.5 - radius, y as f32 + 0.5 - radius) / radius;
+            let strength = ((offset.length() - VIGNETTE_INNER) / VIGNETTE_FALLOFF).clamp(0.0, 1.0);
+            data.extend_from_slice(&[200, 0, 0, (strength * strength * 255.0) as u8]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: VIGNETTE_SIZE,
+            height: VIGNETTE_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn spawn_vignette(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            image: images.add(generate_vignette()).into(),
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Vignette);
+}
+
+pub fn despawn_vignette(
+    mut commands: Commands,
+    vignettes: Query<Entity, With<Vignette>>,
+) {
+    for vignette in vignettes.iter() {
+        commands.entity(vignette).despawn_recursive();
+    }
+}
+
+pub fn warn_low_health(
+    time: Res<Time>,
+    audio: Res<Audio>,
+    sounds: Res<Assets<AudioSource>>,
+    assets: Res<GameAssets>,
+    mut heartbeat: Local<Heartbeat>,
+    players: Query<&Health, With<Player>>,
+    mut vignettes: Query<&mut UiColor, With<Vignette>>,
+) {
+    let is_low = players.iter().any(|h| h.fraction() < LOW_HEALTH_FRACTION);
+
+    // Thump on each beat, then let the vignette fade until the next one
+    let alpha = if is_low {
+        let is_beat = if !heartbeat.is_beating {
+            heartbeat.is_beating = true;
+            heartbeat.timer.reset();
+            true
+        }
+        else {
+            heartbeat.timer.tick(time.delta()).just_finished()
+        };
+
+        // Queuing a sound that never loaded would keep it queued for good
+        if let Some(sound) = assets.heartbeat(&sounds).filter(|_| is_beat) {
+            audio.play(sound);
+        }
+
+        VIGNETTE_MAX_ALPHA * (1.0 - heartbeat.timer.percent())
+    }
+    else {
+        heartbeat.is_beating = false;
+        0.0
+    };
+
+    for mut color in vignettes.iter_mut() {
+        color.0 = Color::rgba(1.0, 1.0, 1.0, alpha);
+    }
+}
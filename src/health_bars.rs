@@ -0,0 +1,97 @@
+//! Health bars over the monsters that take more than one blast, hordes and
+//! bosses. Ordinary monsters go down in one and don't get a bar, to keep the
+//! swarm readable.
+
+use bevy::prelude::*;
+
+use crate::camera::ScreenAnchor;
+use crate::horde::Horde;
+use crate::Body;
+
+
+const BAR_WIDTH: f32  = 32.0;
+const BAR_HEIGHT: f32 = 4.0;
+const BAR_GAP: f32    = 6.0;
+
+/// On a monster that already has a bar.
+#[derive(Component)]
+pub struct HasHealthBar;
+
+#[derive(Component)]
+pub struct HealthBar;
+
+#[derive(Component)]
+pub struct HealthBarFill;
+
+pub fn attach_health_bars(
+    mut commands: Commands,
+    monsters: Query<(Entity, &Horde), Without<HasHealthBar>>,
+) {
+    for (monster, _) in monsters.iter().filter(|(_, h)| h.max_health() > 1) {
+        commands.entity(monster).insert(HasHealthBar);
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                    ..Default::default()
+                },
+                color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..Default::default()
+            })
+            .insert(HealthBar)
+            .insert(ScreenAnchor{
+                target: monster,
+                offset: Vec2::ZERO,
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                            ..Default::default()
+                        },
+                        color: Color::rgb(0.9, 0.2, 0.2).into(),
+                        ..Default::default()
+                    })
+                    .insert(HealthBarFill);
+            });
+    }
+}
+
+pub fn update_health_bars(
+    mut commands: Commands,
+    monsters: Query<(&Horde, &Body)>,
+    mut bars: Query<(Entity, &mut ScreenAnchor, &Children), With<HealthBar>>,
+    mut fills: Query<&mut Style, With<HealthBarFill>>,
+) {
+    for (bar, mut anchor, children) in bars.iter_mut() {
+        // The monster went down
+        let (horde, body) = match monsters.get(anchor.target) {
+            Ok(monster) => monster,
+            Err(_) => {
+                commands.entity(bar).despawn_recursive();
+                continue;
+            }
+        };
+
+        // Hordes grow, so keep the bar just above the body
+        anchor.offset = Vec2::Y * (body.circle.radius + BAR_GAP);
+
+        let fraction = horde.health() as f32 / horde.max_health().max(1) as f32;
+        for &child in children.iter() {
+            if let Ok(mut style) = fills.get_mut(child) {
+                style.size.width = Val::Percent(100.0 * fraction);
+            }
+        }
+    }
+}
+
+pub fn clear_health_bars(
+    mut commands: Commands,
+    bars: Query<Entity, With<HealthBar>>,
+) {
+    for bar in bars.iter() {
+        commands.entity(bar).despawn_recursive();
+    }
+}
@@ -0,0 +1,57 @@
+//! Monsters flash white for a moment when a blast hurts them without taking
+//! them down, so it's clear the hits on a horde or boss are landing.
+
+use bevy::prelude::*;
+
+
+const HIT_FLASH_SECONDS: f32 = 0.1;
+
+/// Sent when a monster takes a hit it survives.
+pub struct MonsterHurtEvent(pub Entity);
+
+/// A monster mid flash, with the color to go back to.
+#[derive(Component)]
+pub struct HitFlash {
+    timer: Timer,
+    restore: Color,
+}
+
+pub fn flash_hurt_monsters(
+    mut commands: Commands,
+    mut hurt_reader: EventReader<MonsterHurtEvent>,
+    mut monsters: Query<(&mut TextureAtlasSprite, Option<&mut HitFlash>)>,
+) {
+    for MonsterHurtEvent(entity) in hurt_reader.iter() {
+        let (mut sprite, flash) = match monsters.get_mut(*entity) {
+            Ok(monster) => monster,
+            Err(_) => continue,
+        };
+
+        // A second hit mid flash just draws it out
+        match flash {
+            Some(mut flash) => flash.timer.reset(),
+            None => {
+                commands.entity(*entity).insert(HitFlash{
+                    timer: Timer::from_seconds(HIT_FLASH_SECONDS, false),
+                    restore: sprite.color,
+                });
+                let alpha = sprite.color.a();
+                sprite.color = Color::WHITE;
+                sprite.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+pub fn restore_hit_flashes(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut flashes: Query<(Entity, &mut HitFlash, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut flash, mut sprite) in flashes.iter_mut() {
+        if flash.timer.tick(time.delta()).finished() {
+            sprite.color = flash.restore;
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Hordes keep the pressure rising past the spawn budget without adding
+//! entities. Once it's spent, each new spawn is folded into an existing
+//! monster. That monster grows into a horde: a bigger, heavier body that takes
+//! several blasts to break and counts as all of its members when it does.
+//! Bosses take several blasts the same way, as a horde of one.
+
+use bevy::prelude::*;
+
+use crate::stage::Boss;
+use crate::{Body, Monster, MonsterKind, MONSTER_BODY_RADIUS};
+
+
+const HORDE_MAX_MEMBERS: u32     = 12;
+const HORDE_MEMBERS_PER_HIT: u32 = 3;
+
+#[derive(Component)]
+pub struct Horde {
+    members: u32,
+    health: u32,
+    max_health: u32,
+    hit_by: Vec<Entity>,
+}
+
+impl Horde {
+    /// A single monster that takes `health` blasts to bring down.
+    pub fn tough(health: u32) -> Horde {
+        Horde{
+            members: 1,
+            health,
+            max_health: health,
+            hit_by: Vec::new(),
+        }
+    }
+
+    pub fn members(&self) -> u32 {
+        self.members
+    }
+
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
+    pub fn max_health(&self) -> u32 {
+        self.max_health
+    }
+
+    pub fn was_hit_by(&self, blast: Entity) -> bool {
+        self.hit_by.contains(&blast)
+    }
+
+    /// Take `hits` from `blast`, ignoring repeat hits from the same blast.
+    /// Returns true on the hit that breaks the horde.
+    pub fn hit(&mut self, blast: Entity, hits: u32) -> bool {
+        if self.health == 0 || self.hit_by.contains(&blast) {
+            return false;
+        }
+
+        self.hit_by.push(blast);
+        self.health = self.health.saturating_sub(hits);
+        self.health == 0
+    }
+}
+
+pub type HordeCandidates<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static MonsterKind, &'static mut Body, &'static mut Transform, Option<&'static mut Horde>),
+    (With<Monster>, Without<Boss>),
+>;
+
+/// Fold one spawn into the world's monsters. Returns the kind of the horde
+/// that took it, or none if no monster had room.
+pub fn absorb_spawn(
+    commands: &mut Commands,
+    candidates: &mut HordeCandidates,
+) -> Option<MonsterKind> {
+    // Grow an existing horde if one has room, otherwise start a new one
+    let mut chosen = candidates
+        .iter()
+        .filter_map(|(entity, _, _, _, horde)| Some((entity, horde?.members)))
+        .filter(|(_, members)| *members < HORDE_MAX_MEMBERS)
+        .min_by_key(|(_, members)| *members)
+        .map(|(entity, _)| entity);
+    if chosen.is_none() {
+        let loners: Vec<Entity> = candidates
+            .iter()
+            .filter(|(_, _, _, _, horde)| horde.is_none())
+            .map(|(entity, _, _, _, _)| entity)
+            .collect();
+        if !loners.is_empty() {
+            chosen = Some(loners[rand::random::<usize>() % loners.len()]);
+        }
+    }
+
+    let (entity, kind, mut body, mut transform, horde) = candidates.get_mut(chosen?).ok()?;
+
+    let members = match horde {
+        Some(mut horde) => {
+            horde.members += 1;
+            horde.health = 1 + horde.members / HORDE_MEMBERS_PER_HIT;
+            horde.max_health = horde.health;
+            horde.members
+        }
+        None => {
+            let health = 1 + 2 / HORDE_MEMBERS_PER_HIT;
+            commands.entity(entity).insert(Horde{
+                members: 2,
+                health,
+                max_health: health,
+                hit_by: Vec::new(),
+            });
+            2
+        }
+    };
+
+    // Area and mass grow with the number of members
+    let radius = kind.radius() * (members as f32).sqrt();
+    let sprite_scale = 2.0 * radius / MONSTER_BODY_RADIUS;
+    body.circle.radius = radius;
+    body.mass = Some(kind.mass() * members as f32);
+    transform.scale = Vec3::new(sprite_scale, sprite_scale, 1.0);
+
+    Some(*kind)
+}
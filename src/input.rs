@@ -0,0 +1,455 @@
+//! Input actions and the keys and gamepad buttons bound to them. Gameplay,
+//! menus and the level up and shop screens ask `Controls` whether an action
+//! is held or was just pressed rather than reading keys directly, so the
+//! bindings can change without touching them. Bindings are read from
+//! `controls.ron` when it exists, and any action it leaves out keeps its
+//! default:
+//!
+//! ```ron
+//! (
+//!     players: [
+//!         (up: Up, down: Down, left: Left, right: Right, fire: Space, ability: RShift, dash: RControl),
+//!         (up: W, down: S, left: A, right: D, fire: LShift, ability: Q, dash: E),
+//!     ],
+//!     pause: Tab,
+//!     confirm: Return,
+//!     cancel: Escape,
+//!     choices: (Key1, Key2, Key3),
+//!     reroll: R,
+//!     skip: S,
+//!     banish: B,
+//!     buttons: (
+//!         up: DPadUp, down: DPadDown, left: DPadLeft, right: DPadRight,
+//!         fire: RightTrigger, ability: LeftTrigger, dash: RightTrigger2, pause: Start,
+//!         confirm: South, cancel: East, reroll: North, skip: West, banish: Select,
+//!     ),
+//!     stick_deadzone: 0.15,
+//!     stick_curve: 1.5,
+//! )
+//! ```
+//!
+//! Each gamepad plays for a player, in the order they were connected, and
+//! the buttons are the same on every pad. Pausing and the menu actions are
+//! shared, so any player's keys or pad can use them. A gamepad's left stick
+//! moves its player at a speed that follows how far it's pushed. Pushes
+//! inside the deadzone are ignored, and the curve bends the rest, with higher
+//! values giving finer control near the middle.
+//!
+//! Keys are rebound on the controls screen, opened from the menu with I,
+//! which writes them back to `controls.ron`. Gamepad buttons and the stick
+//! are only set in the file. The menu's setting shortcuts, like Y for
+//! adaptive difficulty, and the debug and capture keys stay where they are.
+
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+
+const BINDINGS_FILE: &str = "controls.ron";
+
+const STICK_DEADZONE: f32 = 0.15;
+const STICK_CURVE: f32    = 1.5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+    UseAbility,
+    Dash,
+    Pause,
+    Confirm,
+    Cancel,
+    /// Take the level up offer or ware in this slot.
+    Choose(usize),
+    Reroll,
+    Skip,
+    Banish,
+}
+
+impl Action {
+    /// Each player's own actions, in the order the controls screen lists them.
+    pub const PER_PLAYER: [Action; 7] = [
+        Action::Up, Action::Down, Action::Left, Action::Right, Action::Fire, Action::UseAbility, Action::Dash,
+    ];
+    /// The actions every player shares, in the order the controls screen
+    /// lists them.
+    pub const SHARED: [Action; 9] = [
+        Action::Pause, Action::Confirm, Action::Cancel, Action::Choose(0), Action::Choose(1),
+        Action::Choose(2), Action::Reroll, Action::Skip, Action::Banish,
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Action::Up => "up".to_string(),
+            Action::Down => "down".to_string(),
+            Action::Left => "left".to_string(),
+            Action::Right => "right".to_string(),
+            Action::Fire => "fire".to_string(),
+            Action::UseAbility => "ability".to_string(),
+            Action::Dash => "dash".to_string(),
+            Action::Pause => "pause".to_string(),
+            Action::Confirm => "confirm".to_string(),
+            Action::Cancel => "cancel".to_string(),
+            Action::Choose(slot) => format!("choice {}", slot + 1),
+            Action::Reroll => "reroll".to_string(),
+            Action::Skip => "skip".to_string(),
+            Action::Banish => "banish".to_string(),
+        }
+    }
+
+    /// Whether every player shares the action rather than having their own.
+    fn is_shared(self) -> bool {
+        !matches!(
+            self,
+            Action::Up | Action::Down | Action::Left | Action::Right | Action::Fire | Action::UseAbility | Action::Dash
+        )
+    }
+}
+
+/// The keys one player moves and fights with.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PlayerKeys {
+    up: KeyCode,
+    down: KeyCode,
+    left: KeyCode,
+    right: KeyCode,
+    fire: KeyCode,
+    ability: KeyCode,
+    /// Left out of older control files, so it falls back to the default.
+    #[serde(default)]
+    dash: Option<KeyCode>,
+}
+
+impl PlayerKeys {
+    /// Arrows for the first player and WASD for the second.
+    fn default_for(player: usize) -> PlayerKeys {
+        if player == 0 {
+            PlayerKeys{
+                up: KeyCode::Up,
+                down: KeyCode::Down,
+                left: KeyCode::Left,
+                right: KeyCode::Right,
+                fire: KeyCode::Space,
+                ability: KeyCode::RShift,
+                dash: Some(KeyCode::RControl),
+            }
+        }
+        else {
+            PlayerKeys{
+                up: KeyCode::W,
+                down: KeyCode::S,
+                left: KeyCode::A,
+                right: KeyCode::D,
+                fire: KeyCode::LShift,
+                ability: KeyCode::Q,
+                dash: Some(KeyCode::E),
+            }
+        }
+    }
+}
+
+/// The gamepad buttons for each action, the same on every pad. Offers and
+/// wares are picked with the directions and confirm.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct PadButtons {
+    up: GamepadButtonType,
+    down: GamepadButtonType,
+    left: GamepadButtonType,
+    right: GamepadButtonType,
+    fire: GamepadButtonType,
+    ability: GamepadButtonType,
+    dash: GamepadButtonType,
+    pause: GamepadButtonType,
+    confirm: GamepadButtonType,
+    cancel: GamepadButtonType,
+    reroll: GamepadButtonType,
+    skip: GamepadButtonType,
+    banish: GamepadButtonType,
+}
+
+impl Default for PadButtons {
+    fn default() -> PadButtons {
+        PadButtons{
+            up: GamepadButtonType::DPadUp,
+            down: GamepadButtonType::DPadDown,
+            left: GamepadButtonType::DPadLeft,
+            right: GamepadButtonType::DPadRight,
+            fire: GamepadButtonType::RightTrigger,
+            ability: GamepadButtonType::LeftTrigger,
+            dash: GamepadButtonType::RightTrigger2,
+            pause: GamepadButtonType::Start,
+            confirm: GamepadButtonType::South,
+            cancel: GamepadButtonType::East,
+            reroll: GamepadButtonType::North,
+            skip: GamepadButtonType::West,
+            banish: GamepadButtonType::Select,
+        }
+    }
+}
+
+impl PadButtons {
+    fn button(&self, action: Action) -> Option<GamepadButtonType> {
+        match action {
+            Action::Up => Some(self.up),
+            Action::Down => Some(self.down),
+            Action::Left => Some(self.left),
+            Action::Right => Some(self.right),
+            Action::Fire => Some(self.fire),
+            Action::UseAbility => Some(self.ability),
+            Action::Dash => Some(self.dash),
+            Action::Pause => Some(self.pause),
+            Action::Confirm => Some(self.confirm),
+            Action::Cancel => Some(self.cancel),
+            Action::Choose(_) => None,
+            Action::Reroll => Some(self.reroll),
+            Action::Skip => Some(self.skip),
+            Action::Banish => Some(self.banish),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bindings {
+    players: Vec<PlayerKeys>,
+    pause: KeyCode,
+    confirm: KeyCode,
+    cancel: KeyCode,
+    choices: [KeyCode; 3],
+    reroll: KeyCode,
+    skip: KeyCode,
+    banish: KeyCode,
+    buttons: PadButtons,
+    stick_deadzone: f32,
+    stick_curve: f32,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings{
+            players: vec![PlayerKeys::default_for(0), PlayerKeys::default_for(1)],
+            pause: KeyCode::Tab,
+            confirm: KeyCode::Return,
+            cancel: KeyCode::Escape,
+            choices: [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3],
+            reroll: KeyCode::R,
+            skip: KeyCode::S,
+            banish: KeyCode::B,
+            buttons: PadButtons::default(),
+            stick_deadzone: STICK_DEADZONE,
+            stick_curve: STICK_CURVE,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load() -> Bindings {
+        let text = match storage::read_to_string(BINDINGS_FILE) {
+            Ok(text) => text,
+            Err(_) => return Bindings::default(),
+        };
+
+        ron::from_str(&text).unwrap_or_else(|e| {
+            warn!("ignoring {}: {}", BINDINGS_FILE, e);
+            Bindings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let written = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|text| storage::write(BINDINGS_FILE, &text));
+        if let Err(e) = written {
+            warn!("failed to save {}: {}", BINDINGS_FILE, e);
+        }
+    }
+
+    /// Bind `key` to `action` for `player`, or for everyone if it's shared.
+    pub fn set_key(&mut self, player: usize, action: Action, key: KeyCode) {
+        while self.players.len() <= player {
+            self.players.push(PlayerKeys::default_for(self.players.len()));
+        }
+
+        let keys = &mut self.players[player];
+        match action {
+            Action::Up => keys.up = key,
+            Action::Down => keys.down = key,
+            Action::Left => keys.left = key,
+            Action::Right => keys.right = key,
+            Action::Fire => keys.fire = key,
+            Action::UseAbility => keys.ability = key,
+            Action::Dash => keys.dash = Some(key),
+            Action::Pause => self.pause = key,
+            Action::Confirm => self.confirm = key,
+            Action::Cancel => self.cancel = key,
+            Action::Choose(slot) => {
+                if let Some(choice) = self.choices.get_mut(slot) {
+                    *choice = key;
+                }
+            }
+            Action::Reroll => self.reroll = key,
+            Action::Skip => self.skip = key,
+            Action::Banish => self.banish = key,
+        }
+    }
+
+    /// The key bound to `action` for `player`, if any. Shared actions have
+    /// the same key for every player.
+    fn key(&self, player: usize, action: Action) -> Option<KeyCode> {
+        let keys = self.players.get(player).cloned().unwrap_or_else(|| PlayerKeys::default_for(player));
+        match action {
+            Action::Up => Some(keys.up),
+            Action::Down => Some(keys.down),
+            Action::Left => Some(keys.left),
+            Action::Right => Some(keys.right),
+            Action::Fire => Some(keys.fire),
+            Action::UseAbility => Some(keys.ability),
+            Action::Dash => keys.dash.or(PlayerKeys::default_for(player).dash),
+            Action::Pause => Some(self.pause),
+            Action::Confirm => Some(self.confirm),
+            Action::Cancel => Some(self.cancel),
+            Action::Choose(slot) => self.choices.get(slot).copied(),
+            Action::Reroll => Some(self.reroll),
+            Action::Skip => Some(self.skip),
+            Action::Banish => Some(self.banish),
+        }
+    }
+
+    /// What to call the first player's key for `action` in hints.
+    pub fn key_name(&self, action: Action) -> String {
+        self.player_key_name(0, action)
+    }
+
+    /// What to call `player`'s key for `action`.
+    pub fn player_key_name(&self, player: usize, action: Action) -> String {
+        let name = match self.key(player, action) {
+            Some(key) => format!("{:?}", key),
+            None => return "-".to_string(),
+        };
+        // Number keys read better as their number
+        match name.strip_prefix("Key") {
+            Some(digit) if !digit.is_empty() => digit.to_string(),
+            _ => name,
+        }
+    }
+
+    /// The keys that choose an offer or ware, like `1/2/3`.
+    pub fn choices_name(&self) -> String {
+        let names: Vec<_> = (0..self.choices.len()).map(|slot| self.key_name(Action::Choose(slot))).collect();
+        names.join("/")
+    }
+
+    /// A stick's raw position as a direction no longer than one, zero inside
+    /// the deadzone.
+    pub fn stick_direction(&self, stick: Vec2) -> Vec2 {
+        let push = stick.length().min(1.0);
+        if push <= self.stick_deadzone {
+            return Vec2::ZERO;
+        }
+
+        let past_deadzone = (push - self.stick_deadzone) / (1.0 - self.stick_deadzone).max(f32::EPSILON);
+        stick.normalize() * past_deadzone.powf(self.stick_curve)
+    }
+}
+
+/// The gamepad steering `player`, by the order pads were connected in.
+pub fn pad_for(gamepads: &Gamepads, player: usize) -> Option<Gamepad> {
+    let mut pads: Vec<Gamepad> = gamepads.iter().copied().collect();
+    pads.sort_by_key(|pad| pad.0);
+    pads.get(player).copied()
+}
+
+/// The keyboard and gamepads, read through the bindings.
+#[derive(SystemParam)]
+pub struct Controls<'w, 's> {
+    keyboard_input: ResMut<'w, Input<KeyCode>>,
+    button_input: ResMut<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+    bindings: Res<'w, Bindings>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Controls<'_, '_> {
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// The keyboard, for the keys that aren't bound to actions.
+    pub fn keyboard(&mut self) -> &mut Input<KeyCode> {
+        &mut self.keyboard_input
+    }
+
+    /// The buttons `action` is bound to for `player`: their own pad's, or
+    /// every pad's for shared actions.
+    fn buttons(&self, player: usize, action: Action) -> Vec<GamepadButton> {
+        let button = match self.bindings.buttons.button(action) {
+            Some(button) => button,
+            None => return Vec::new(),
+        };
+
+        if action.is_shared() {
+            self.gamepads.iter().map(|&pad| GamepadButton(pad, button)).collect()
+        }
+        else {
+            pad_for(&self.gamepads, player).map(|pad| GamepadButton(pad, button)).into_iter().collect()
+        }
+    }
+
+    pub fn pressed(&self, player: usize, action: Action) -> bool {
+        self.bindings.key(player, action).is_some_and(|key| self.keyboard_input.pressed(key))
+            || self.button_input.any_pressed(self.buttons(player, action))
+    }
+
+    pub fn just_pressed(&self, player: usize, action: Action) -> bool {
+        self.bindings.key(player, action).is_some_and(|key| self.keyboard_input.just_pressed(key))
+            || self.button_input.any_just_pressed(self.buttons(player, action))
+    }
+
+    /// Forget that `action` was just pressed, so a screen it opens or closes
+    /// doesn't see it too.
+    pub fn clear_just_pressed(&mut self, player: usize, action: Action) {
+        if let Some(key) = self.bindings.key(player, action) {
+            self.keyboard_input.clear_just_pressed(key);
+        }
+        for button in self.buttons(player, action) {
+            self.button_input.clear_just_pressed(button);
+        }
+    }
+
+    /// Forget every press this frame.
+    pub fn clear(&mut self) {
+        self.keyboard_input.clear();
+        self.button_input.clear();
+    }
+
+    /// The direction `player` is holding on keys or the d-pad, normalized or
+    /// zero. Right wins over left and up over down.
+    pub fn direction(&self, player: usize) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+
+        if self.pressed(player, Action::Right) {
+            direction.x = 1.0;
+        }
+        else if self.pressed(player, Action::Left) {
+            direction.x = -1.0;
+        }
+
+        if self.pressed(player, Action::Up) {
+            direction.y = 1.0;
+        }
+        else if self.pressed(player, Action::Down) {
+            direction.y = -1.0;
+        }
+
+        direction.normalize_or_zero()
+    }
+}
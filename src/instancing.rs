@@ -0,0 +1,386 @@
+//! Instanced monster rendering for very large swarms. Rather than one sprite
+//! per monster, a single quad is drawn once per monster from an instance
+//! buffer rebuilt from `Position` every frame. Monsters come out as tinted
+//! discs. Enabled with `--instanced-monsters`.
+
+use bevy::core::FloatOrd;
+use bevy::core_pipeline::Transparent2d;
+use bevy::ecs::system::lifetimeless::{Read, SQuery, SRes};
+use bevy::ecs::system::SystemParamItem;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::{GpuBufferInfo, Indices};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_component::{ExtractComponent, ExtractComponentPlugin};
+use bevy::render::render_phase::{
+    AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+    SetItemPipeline, TrackedRenderPass,
+};
+use bevy::render::render_resource::{
+    BlendState, Buffer, BufferInitDescriptor, BufferUsages, ColorTargetState, ColorWrites,
+    FragmentState, FrontFace, MultisampleState, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPipelineCache, RenderPipelineDescriptor, SpecializedPipeline, SpecializedPipelines,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+use bevy::render::renderer::RenderDevice;
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::VisibleEntities;
+use bevy::render::{RenderApp, RenderStage};
+use bevy::sprite::{
+    Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, Mesh2dUniform, SetMesh2dBindGroup,
+    SetMesh2dViewBindGroup,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::camera::CameraFocus;
+use crate::{Body, Monster, MonsterKind, Position};
+
+
+const MONSTER_INSTANCE_COLOR: Color = Color::rgb(0.85, 0.25, 0.25);
+
+const INSTANCED_MONSTER_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 4_117_309_882_615_004_731);
+
+const INSTANCED_MONSTER_SHADER: &str = r"
+#import bevy_sprite::mesh2d_view_bind_group
+[[group(0), binding(0)]]
+var<uniform> view: View;
+#import bevy_sprite::mesh2d_struct
+[[group(1), binding(0)]]
+var<uniform> mesh: Mesh2d;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] offset: vec4<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] local: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    // The quad is one unit wide, so scale it up to the monster's diameter
+    let world = vertex.position.xy * vertex.offset.w * 2.0 + vertex.offset.xy;
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * mesh.model * vec4<f32>(world, vertex.offset.z, 1.0);
+    out.local = vertex.position.xy * 2.0;
+    out.color = vertex.color;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] local: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    if (length(in.local) > 1.0) {
+        discard;
+    }
+    return in.color;
+}
+";
+
+pub struct InstancedMonstersPlugin;
+
+impl Plugin for InstancedMonstersPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            INSTANCED_MONSTER_SHADER_HANDLE,
+            Shader::from_wgsl(INSTANCED_MONSTER_SHADER),
+        );
+
+        app
+            .add_plugin(ExtractComponentPlugin::<MonsterInstances>::default())
+            .add_startup_system(spawn_instance_renderer)
+            .add_system(hide_monster_sprites)
+            .add_system_to_stage(CoreStage::PostUpdate, build_monster_instances);
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Transparent2d, DrawInstancedMonsters>()
+            .init_resource::<InstancedMonsterPipeline>()
+            .init_resource::<SpecializedPipelines<InstancedMonsterPipeline>>()
+            .add_system_to_stage(RenderStage::Prepare, prepare_instance_buffers)
+            .add_system_to_stage(RenderStage::Queue, queue_instanced_monsters);
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct InstanceData {
+    offset: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Every monster to draw this frame, held by the single renderer entity.
+#[derive(Component, Clone, Default)]
+pub struct MonsterInstances(Vec<InstanceData>);
+
+impl ExtractComponent for MonsterInstances {
+    type Query = &'static MonsterInstances;
+    type Filter = ();
+
+    fn extract_component(item: bevy::ecs::query::QueryItem<Self::Query>) -> Self {
+        item.clone()
+    }
+}
+
+fn spawn_instance_renderer(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut quad = Mesh::new(PrimitiveTopology::TriangleList);
+    quad.set_attribute(Mesh::ATTRIBUTE_POSITION, vec![
+        [-0.5, -0.5, 0.0],
+        [0.5, -0.5, 0.0],
+        [0.5, 0.5, 0.0],
+        [-0.5, 0.5, 0.0],
+    ]);
+    quad.set_indices(Some(Indices::U32(vec![0, 1, 2, 0, 2, 3])));
+
+    commands.spawn_bundle((
+        MonsterInstances::default(),
+        Mesh2dHandle(meshes.add(quad)),
+        Transform::default(),
+        GlobalTransform::default(),
+        Visibility::default(),
+        ComputedVisibility::default(),
+    ));
+}
+
+/// Monsters still get sprites when spawned, which this path keeps hidden.
+fn hide_monster_sprites(
+    mut monsters: Query<&mut Visibility, Added<Monster>>,
+) {
+    for mut visibility in monsters.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
+
+fn build_monster_instances(
+    focus: Res<CameraFocus>,
+    mut renderers: Query<&mut MonsterInstances>,
+    monsters: Query<(&Position, &Body, &MonsterKind), With<Monster>>,
+) {
+    // Same camera-relative placement as the sprites get
+    let offset = focus.center;
+    let base = MONSTER_INSTANCE_COLOR.as_rgba_f32();
+
+    for mut instances in renderers.iter_mut() {
+        instances.0.clear();
+        instances.0.extend(monsters.iter().map(|(p, body, kind)| {
+            let tint = kind.tint().as_rgba_f32();
+            let position = p.current - offset;
+            InstanceData{
+                offset: [position.x, position.y, 0.0, body.circle.radius],
+                color: [base[0] * tint[0], base[1] * tint[1], base[2] * tint[2], base[3] * tint[3]],
+            }
+        }));
+    }
+}
+
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+fn prepare_instance_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &MonsterInstances)>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, instances) in query.iter() {
+        // wgpu rejects empty vertex buffers, so skip drawing entirely
+        if instances.0.is_empty() {
+            continue;
+        }
+
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("monster instance buffer"),
+            contents: bytemuck::cast_slice(instances.0.as_slice()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        commands.entity(entity).insert(InstanceBuffer{
+            buffer,
+            length: instances.0.len(),
+        });
+    }
+}
+
+struct InstancedMonsterPipeline {
+    mesh2d_pipeline: Mesh2dPipeline,
+}
+
+impl FromWorld for InstancedMonsterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        InstancedMonsterPipeline{
+            mesh2d_pipeline: Mesh2dPipeline::from_world(world),
+        }
+    }
+}
+
+impl SpecializedPipeline for InstancedMonsterPipeline {
+    type Key = Mesh2dPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let shader = INSTANCED_MONSTER_SHADER_HANDLE.typed::<Shader>();
+
+        RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: shader.clone(),
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: vec![
+                    // The quad only carries positions
+                    VertexBufferLayout {
+                        array_stride: VertexFormat::Float32x3.size(),
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: vec![VertexAttribute {
+                            format: VertexFormat::Float32x3,
+                            offset: 0,
+                            shader_location: 0,
+                        }],
+                    },
+                    VertexBufferLayout {
+                        array_stride: std::mem::size_of::<InstanceData>() as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: vec![
+                            VertexAttribute {
+                                format: VertexFormat::Float32x4,
+                                offset: 0,
+                                shader_location: 1,
+                            },
+                            VertexAttribute {
+                                format: VertexFormat::Float32x4,
+                                offset: VertexFormat::Float32x4.size(),
+                                shader_location: 2,
+                            },
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                shader,
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            layout: Some(vec![
+                self.mesh2d_pipeline.view_layout.clone(),
+                self.mesh2d_pipeline.mesh_layout.clone(),
+            ]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: key.primitive_topology(),
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("instanced_monster_pipeline".into()),
+        }
+    }
+}
+
+fn queue_instanced_monsters(
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    instanced_pipeline: Res<InstancedMonsterPipeline>,
+    mut pipelines: ResMut<SpecializedPipelines<InstancedMonsterPipeline>>,
+    mut pipeline_cache: ResMut<RenderPipelineCache>,
+    msaa: Res<Msaa>,
+    renderers: Query<&Mesh2dUniform, With<MonsterInstances>>,
+    mut views: Query<(&VisibleEntities, &mut RenderPhase<Transparent2d>)>,
+) {
+    if renderers.is_empty() {
+        return;
+    }
+
+    let draw_function = draw_functions
+        .read()
+        .get_id::<DrawInstancedMonsters>()
+        .unwrap();
+    let key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples)
+        | Mesh2dPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList);
+    let pipeline = pipelines.specialize(&mut pipeline_cache, &instanced_pipeline, key);
+
+    for (visible_entities, mut phase) in views.iter_mut() {
+        for entity in visible_entities.entities.iter() {
+            if let Ok(uniform) = renderers.get(*entity) {
+                phase.add(Transparent2d {
+                    entity: *entity,
+                    draw_function,
+                    pipeline,
+                    sort_key: FloatOrd(uniform.transform.w_axis.z),
+                    batch_range: None,
+                });
+            }
+        }
+    }
+}
+
+type DrawInstancedMonsters = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetMesh2dBindGroup<1>,
+    DrawQuadInstanced,
+);
+
+struct DrawQuadInstanced;
+
+impl EntityRenderCommand for DrawQuadInstanced {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Mesh2dHandle>>,
+        SQuery<Read<InstanceBuffer>>,
+    );
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, instance_query): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        // No buffer means no monsters this frame
+        let instances = match instance_query.get(item) {
+            Ok(instances) => instances,
+            Err(_) => return RenderCommandResult::Success,
+        };
+        let gpu_mesh = match mesh_query.get(item).ok().and_then(|h| meshes.into_inner().get(&h.0)) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instances.buffer.slice(..));
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed { buffer, index_format, count } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instances.length as u32);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..instances.length as u32);
+            }
+        }
+
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,106 @@
+//! Squash and stretch. Players and monsters wobble a little when they finish
+//! spawning, take a hit or turn sharply, which keeps the swarm looking alive
+//! without new art. The wobble is laid over the scale other systems set: it's
+//! applied late in the frame and taken back off at the start of the next.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::hit_flash::MonsterHurtEvent;
+use crate::{Body, Spawning, Velocity};
+
+
+const SQUASH_SECONDS: f32 = 0.35;
+const SQUASH_WOBBLES: f32 = 1.5;
+
+/// Positive amounts squash wide, negative ones stretch tall.
+const SPAWN_STRETCH: f32 = -0.3;
+const HIT_SQUASH: f32    = 0.25;
+const TURN_STRETCH: f32  = -0.12;
+/// Turns sharper than this wobble, as the cosine of the angle.
+const TURN_THRESHOLD: f32 = 0.3;
+
+#[derive(Component)]
+pub struct Squash {
+    timer: Timer,
+    amount: f32,
+    last_direction: Vec2,
+    /// The scale laid over the transform this frame.
+    applied: Vec2,
+}
+
+impl Squash {
+    fn start(&mut self, amount: f32) {
+        self.amount = amount;
+        self.timer.reset();
+    }
+}
+
+pub fn attach_squash(
+    mut commands: Commands,
+    bodies: Query<(Entity, &Velocity, &Body), Added<Velocity>>,
+) {
+    for (entity, v, _) in bodies.iter() {
+        let mut timer = Timer::from_seconds(SQUASH_SECONDS, false);
+        timer.tick(timer.duration());
+        commands.entity(entity).insert(Squash{
+            timer,
+            amount: 0.0,
+            last_direction: v.direction,
+            applied: Vec2::ONE,
+        });
+    }
+}
+
+/// Take the last frame's wobble back off, so other systems see the scale
+/// they set.
+pub fn unsquash(
+    mut squashed: Query<(&mut Squash, &mut Transform)>,
+) {
+    for (mut squash, mut t) in squashed.iter_mut() {
+        t.scale.x /= squash.applied.x;
+        t.scale.y /= squash.applied.y;
+        squash.applied = Vec2::ONE;
+    }
+}
+
+pub fn squash_and_stretch(
+    time: Res<Time>,
+    spawned: RemovedComponents<Spawning>,
+    mut hurt_reader: EventReader<MonsterHurtEvent>,
+    mut squashed: Query<(&mut Squash, &Velocity, &mut Transform)>,
+) {
+    for entity in spawned.iter() {
+        if let Ok((mut squash, _, _)) = squashed.get_mut(entity) {
+            squash.start(SPAWN_STRETCH);
+        }
+    }
+    for MonsterHurtEvent(entity) in hurt_reader.iter() {
+        if let Ok((mut squash, _, _)) = squashed.get_mut(*entity) {
+            squash.start(HIT_SQUASH);
+        }
+    }
+
+    for (mut squash, v, mut t) in squashed.iter_mut() {
+        // Only turns between two real headings count, not starting or stopping
+        let is_sharp_turn = v.direction != Vec2::ZERO
+            && squash.last_direction != Vec2::ZERO
+            && v.direction.dot(squash.last_direction) < TURN_THRESHOLD;
+        if is_sharp_turn && squash.timer.finished() {
+            squash.start(TURN_STRETCH);
+        }
+        squash.last_direction = v.direction;
+
+        if squash.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        // A wobble that dies away, keeping the area about the same
+        let progress = squash.timer.percent();
+        let wobble = squash.amount * (1.0 - progress) * (progress * SQUASH_WOBBLES * TAU).cos();
+        squash.applied = Vec2::new(1.0 + wobble, 1.0 - wobble);
+        t.scale.x *= squash.applied.x;
+        t.scale.y *= squash.applied.y;
+    }
+}
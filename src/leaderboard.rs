@@ -0,0 +1,133 @@
+//! Best scores, kept on disk per seed and per game version. Balance changes
+//! between versions, so scores only count against others from the same
+//! version. Within one, the game over screen shows the best score on the
+//! run's seed alongside the best on any seed. Demo and escort runs aren't
+//! scored.
+
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::autopilot::DemoMode;
+use crate::curses::Curses;
+use crate::escort::Escort;
+use crate::run_stats::{GameOverEvent, RunStats, RUN_DIRECTORY};
+use crate::stage::Stages;
+use crate::storage::{self, Versioned};
+use crate::{MonsterStats, WorldSeed};
+
+
+const LEADERBOARD_FILE: &str = "leaderboard.json";
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Most runs kept for each version, dropping the lowest scores past it.
+const VERSION_ENTRIES: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScoreEntry {
+    pub finished_at: u64,
+    pub version: String,
+    pub seed: u64,
+    pub stage: String,
+    pub score: u32,
+    pub duration_seconds: f32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<ScoreEntry>,
+    /// When the last run scored this session finished.
+    #[serde(skip)]
+    latest: Option<u64>,
+}
+
+impl Versioned for Leaderboard {
+    const MIGRATIONS: &'static [storage::Migration] = &[];
+}
+
+fn leaderboard_file() -> String {
+    format!("{}/{}", RUN_DIRECTORY, LEADERBOARD_FILE)
+}
+
+impl Leaderboard {
+    pub fn load() -> Leaderboard {
+        let file = leaderboard_file();
+        if !storage::exists(&file) {
+            return Leaderboard::default();
+        }
+
+        storage::load(&file).unwrap_or_else(|e| {
+            warn!("ignoring {}: {}", file, e);
+            Leaderboard::default()
+        })
+    }
+
+    fn save(&self) {
+        let file = leaderboard_file();
+        if let Err(e) = storage::save(&file, self) {
+            warn!("failed to save {}: {}", file, e);
+        }
+    }
+
+    fn add(&mut self, entry: ScoreEntry) {
+        self.latest = Some(entry.finished_at);
+        self.entries.push(entry);
+
+        // Highest first, so trimming a version drops its lowest scores
+        self.entries.sort_by_key(|e| Reverse(e.score));
+        let mut kept = 0;
+        self.entries.retain(|e| {
+            if e.version != GAME_VERSION {
+                return true;
+            }
+            kept += 1;
+            kept <= VERSION_ENTRIES
+        });
+    }
+
+    /// The best runs in this version, highest first.
+    pub fn on_version(&self) -> impl Iterator<Item = &ScoreEntry> {
+        self.entries.iter().filter(|e| e.version == GAME_VERSION)
+    }
+
+    /// The best runs on `seed` in this version, highest first.
+    pub fn on_seed(&self, seed: u64) -> impl Iterator<Item = &ScoreEntry> {
+        self.on_version().filter(move |e| e.seed == seed)
+    }
+
+    /// The last run scored this session.
+    pub fn latest(&self) -> Option<&ScoreEntry> {
+        self.entries.iter().find(|e| self.latest == Some(e.finished_at))
+    }
+
+    /// Whether `entry` is the run that just ended.
+    pub fn is_latest(&self, entry: &ScoreEntry) -> bool {
+        self.latest().is_some_and(|latest| latest.finished_at == entry.finished_at)
+    }
+}
+
+pub fn record_score(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut leaderboard: ResMut<Leaderboard>,
+    demo: Res<DemoMode>,
+    escort: Res<Escort>,
+    stages: Res<Stages>,
+    world_seed: Res<WorldSeed>,
+    curses: Res<Curses>,
+    monster_stats: Res<MonsterStats>,
+    run_stats: Res<RunStats>,
+) {
+    if game_over_reader.iter().next().is_none() || demo.is_active() || escort.is_active {
+        return;
+    }
+
+    leaderboard.add(ScoreEntry{
+        finished_at: storage::unix_millis() / 1000,
+        version: GAME_VERSION.to_string(),
+        seed: world_seed.value,
+        stage: stages.current().name.clone(),
+        score: curses.score(monster_stats.killed()),
+        duration_seconds: run_stats.duration_seconds(),
+    });
+    leaderboard.save();
+}
@@ -0,0 +1,132 @@
+//! Lifesteal. Players with the upgrade heal for a share of the damage their
+//! blasts deal, up to a cap each second so a blast into a packed crowd can't
+//! refill them outright. Each heal floats up off the player as a small green
+//! number.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::progression::Progression;
+use crate::{NewGameEvent, Player, Position};
+
+
+const HEAL_WINDOW_SECONDS: f32 = 1.0;
+
+const HEAL_NUMBER_FONT_SIZE: f32 = 14.0;
+const HEAL_NUMBER_COLOR: Color   = Color::rgb(0.4, 1.0, 0.4);
+const HEAL_NUMBER_OFFSET: f32    = 20.0;
+const HEAL_NUMBER_RISE: f32      = 30.0;
+const HEAL_NUMBER_SECONDS: f32   = 0.8;
+/// Over the players and monsters.
+const HEAL_NUMBER_DEPTH: f32     = 30.0;
+
+/// Sent for damage a player deals to monsters, in player health points.
+pub struct DamageDealtEvent {
+    pub player: usize,
+    pub amount: f32,
+}
+
+/// How much each player has healed through lifesteal in the current second.
+pub struct Lifesteal {
+    healed: BTreeMap<usize, f32>,
+    window: Timer,
+}
+
+impl Default for Lifesteal {
+    fn default() -> Lifesteal {
+        Lifesteal{
+            healed: BTreeMap::new(),
+            window: Timer::from_seconds(HEAL_WINDOW_SECONDS, true),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct HealNumber {
+    lifetime: Timer,
+}
+
+pub fn steal_life(
+    time: Res<Time>,
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    progression: Res<Progression>,
+    mut lifesteal: ResMut<Lifesteal>,
+    mut damage_reader: EventReader<DamageDealtEvent>,
+    mut players: Query<(&Player, &Position, &mut Health)>,
+) {
+    if lifesteal.window.tick(time.delta()).just_finished() {
+        lifesteal.healed.clear();
+    }
+
+    let (fraction, cap) = progression.lifesteal();
+    let mut stolen: BTreeMap<usize, f32> = BTreeMap::new();
+    for event in damage_reader.iter() {
+        *stolen.entry(event.player).or_default() += event.amount * fraction;
+    }
+
+    for (player, p, mut health) in players.iter_mut() {
+        let healed = lifesteal.healed.entry(player.index).or_default();
+        let amount = stolen
+            .get(&player.index)
+            .copied()
+            .unwrap_or_default()
+            .min(cap - *healed)
+            .min(health.missing());
+        if amount <= 0.0 {
+            continue;
+        }
+
+        *healed += amount;
+        health.heal(amount);
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    format!("+{:.1}", amount),
+                    TextStyle {
+                        font: assets.font(),
+                        font_size: HEAL_NUMBER_FONT_SIZE,
+                        color: HEAL_NUMBER_COLOR,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Bottom,
+                    },
+                ),
+                transform: Transform::from_xyz(0.0, 0.0, HEAL_NUMBER_DEPTH),
+                ..Default::default()
+            })
+            .insert(Position::new(p.current + Vec2::Y * HEAL_NUMBER_OFFSET))
+            .insert(HealNumber{
+                lifetime: Timer::from_seconds(HEAL_NUMBER_SECONDS, false),
+            });
+    }
+}
+
+/// Heal numbers rise and fade, and a new run clears any left along with the
+/// lifesteal budgets.
+pub fn float_heal_numbers(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut lifesteal: ResMut<Lifesteal>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut numbers: Query<(Entity, &mut HealNumber, &mut Position, &mut Text)>,
+) {
+    let is_new_game = new_game_reader.iter().next().is_some();
+    if is_new_game {
+        *lifesteal = Lifesteal::default();
+    }
+
+    for (entity, mut number, mut p, mut text) in numbers.iter_mut() {
+        if is_new_game || number.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        p.current.y += HEAL_NUMBER_RISE / HEAL_NUMBER_SECONDS * time.delta_seconds();
+        text.sections[0].style.color.set_a(number.lifetime.percent_left());
+    }
+}
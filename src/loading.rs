@@ -0,0 +1,397 @@
+//! Loads everything the game draws with once, before the title screen, and
+//! keeps strong handles in `GameAssets` so spawns and restarts never go back
+//! to the asset server. A progress bar shows while they load, so slow disks
+//! don't start a run with invisible entities. Checks the sprite files the
+//! game needs as well. Missing or unreadable sprites are replaced by
+//! generated placeholders under the same handle, so everything that loads them
+//! by path keeps working, and a file that shows up later replaces its
+//! placeholder without a restart. With `--procedural-sprites` the files are
+//! skipped and every sprite is generated. Mods can swap any of the sprite
+//! files for their own.
+//!
+//! The heartbeat sound is optional and isn't waited for. Until its file has
+//! loaded, the low health warning pulses without a sound.
+//!
+//! Gameplay sprites are packed into one texture atlas so the swarm draws in a
+//! few batches. The atlas is rebuilt whenever one of its sprites changes.
+
+use std::f32::consts::{PI, TAU};
+use std::path::{Path, PathBuf};
+
+use bevy::asset::{HandleId, LoadState};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::mods;
+use crate::{AppState, UI_FONT};
+
+
+const ASSET_DIRECTORY: &str = "assets";
+const HEARTBEAT_SOUND: &str = "sounds/heartbeat.ogg";
+const MISSING_RETRY_SECONDS: f32 = 2.0;
+
+const LOADING_BAR_WIDTH: f32         = 320.0;
+const LOADING_BAR_HEIGHT: f32        = 12.0;
+const LOADING_BAR_TRACK_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const LOADING_BAR_FILL_COLOR: Color  = Color::rgb(0.9, 0.9, 0.9);
+
+enum SpriteShape {
+    Circle,
+    Polygon(u32),
+    Ring(f32),
+}
+
+struct RequiredSprite {
+    path: &'static str,
+    size: u32,
+    color: [u8; 4],
+    shape: SpriteShape,
+}
+
+const REQUIRED_SPRITES: [RequiredSprite; 3] = [
+    RequiredSprite{
+        path: "player.png",
+        size: 10,
+        color: [255, 255, 255, 255],
+        shape: SpriteShape::Circle,
+    },
+    RequiredSprite{
+        path: "monster.png",
+        size: 10,
+        color: [220, 60, 60, 255],
+        shape: SpriteShape::Polygon(5),
+    },
+    RequiredSprite{
+        path: "blast.png",
+        size: 16,
+        color: [255, 200, 80, 160],
+        shape: SpriteShape::Ring(0.6),
+    },
+];
+
+struct SpriteSlot {
+    sprite: &'static RequiredSprite,
+    /// The built-in file, or a mod's in its place.
+    file: PathBuf,
+    handle: Handle<Image>,
+    is_placeholder: bool,
+    index: usize,
+}
+
+/// Strong handles to every required sprite, keeping them loaded, the atlas
+/// they are packed into, the UI font and sounds.
+pub struct GameAssets {
+    slots: Vec<SpriteSlot>,
+    atlas: Handle<TextureAtlas>,
+    font: Handle<Font>,
+    heartbeat: Handle<AudioSource>,
+    retry: Timer,
+    is_procedural: bool,
+}
+
+impl GameAssets {
+    pub fn new(is_procedural: bool) -> GameAssets {
+        GameAssets{
+            slots: Vec::new(),
+            atlas: Handle::weak(HandleId::random::<TextureAtlas>()),
+            font: Handle::default(),
+            heartbeat: Handle::default(),
+            retry: Timer::from_seconds(MISSING_RETRY_SECONDS, true),
+            is_procedural,
+        }
+    }
+
+    pub fn atlas(&self) -> Handle<TextureAtlas> {
+        self.atlas.clone()
+    }
+
+    pub fn font(&self) -> Handle<Font> {
+        self.font.clone()
+    }
+
+    /// The heartbeat sound, if its file has loaded.
+    pub fn heartbeat(&self, sounds: &Assets<AudioSource>) -> Option<Handle<AudioSource>> {
+        sounds.get(&self.heartbeat).map(|_| self.heartbeat.clone())
+    }
+
+    fn index(&self, path: &str) -> usize {
+        self.slots
+            .iter()
+            .find(|s| s.sprite.path == path)
+            .map(|s| s.index)
+            .unwrap_or_default()
+    }
+
+    pub fn player(&self) -> usize {
+        self.index("player.png")
+    }
+
+    pub fn monster(&self) -> usize {
+        self.index("monster.png")
+    }
+
+    pub fn blast(&self) -> usize {
+        self.index("blast.png")
+    }
+}
+
+impl SpriteShape {
+    /// Whether a point, relative to the center and scaled so the shape's
+    /// outer radius is 1, falls inside the shape.
+    fn contains(&self, point: Vec2) -> bool {
+        let distance = point.length();
+        match *self {
+            SpriteShape::Circle => distance <= 1.0,
+            SpriteShape::Polygon(sides) => {
+                // Distance to the edge varies with the angle within each side, with a corner pointing up
+                let sector = TAU / sides as f32;
+                let angle = (point.y.atan2(point.x) - PI / 2.0).rem_euclid(sector) - sector / 2.0;
+                distance <= (sector / 2.0).cos() / angle.cos()
+            }
+            SpriteShape::Ring(inner) => distance <= 1.0 && distance >= inner,
+        }
+    }
+}
+
+/// A flat colored shape, used in place of a sprite file.
+fn generate_image(sprite: &RequiredSprite) -> Image {
+    let size = sprite.size;
+    let radius = size as f32 / 2.0;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            // Image rows run top to bottom, so flip to keep shapes upright
+            let offset = Vec2::new(x as f32 + 0.5 - radius, radius - y as f32 - 0.5);
+            if sprite.shape.contains(offset / radius) {
+                data.extend_from_slice(&sprite.color);
+            }
+            else {
+                data.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// The filled part of the progress bar.
+#[derive(Component)]
+pub struct LoadingBar;
+
+#[derive(Component)]
+pub struct LoadingRoot;
+
+pub fn spawn_loading_bar(
+    mut commands: Commands,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(LoadingRoot)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(LOADING_BAR_WIDTH), Val::Px(LOADING_BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    color: LOADING_BAR_TRACK_COLOR.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            color: LOADING_BAR_FILL_COLOR.into(),
+                            ..Default::default()
+                        })
+                        .insert(LoadingBar);
+                });
+        });
+}
+
+pub fn despawn_loading_bar(
+    mut commands: Commands,
+    roots: Query<Entity, With<LoadingRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn start_loading(
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut assets: ResMut<GameAssets>,
+) {
+    // Edited files reload in place while the game runs
+    if let Err(e) = asset_server.watch_for_changes() {
+        warn!("asset hot reloading unavailable: {:?}", e);
+    }
+
+    assets.font = asset_server.load(UI_FONT);
+    assets.heartbeat = asset_server.load(HEARTBEAT_SOUND);
+
+    let is_procedural = assets.is_procedural;
+    assets.slots = REQUIRED_SPRITES
+        .iter()
+        .map(|sprite| {
+            let file = match mods::sprite_override(sprite.path) {
+                Some(file) => file.to_path_buf(),
+                None => PathBuf::from(sprite.path),
+            };
+            SpriteSlot{
+                sprite,
+                handle: if is_procedural {
+                    images.add(generate_image(sprite))
+                }
+                else {
+                    asset_server.load(file.as_path())
+                },
+                file,
+                is_placeholder: false,
+                index: 0,
+            }
+        })
+        .collect();
+}
+
+pub fn finish_loading(
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut sprites: ResMut<GameAssets>,
+    mut state: ResMut<State<AppState>>,
+    mut bars: Query<&mut Style, With<LoadingBar>>,
+) {
+    // Text can go without its file, so only wait for it to settle
+    let mut pending = match asset_server.get_load_state(&sprites.font) {
+        LoadState::Loaded | LoadState::Failed => 0,
+        _ => 1,
+    };
+
+    // Generated sprites have nothing to load
+    if !sprites.is_procedural {
+        for slot in sprites.slots.iter_mut() {
+            match asset_server.get_load_state(&slot.handle) {
+                LoadState::Loaded => {}
+                LoadState::Failed => {
+                    if !slot.is_placeholder {
+                        warn!("{} is missing or unreadable, using a placeholder", slot.file.display());
+                        images.set_untracked(&slot.handle, generate_image(slot.sprite));
+                        slot.is_placeholder = true;
+                    }
+                }
+                _ => pending += 1,
+            }
+        }
+    }
+
+    // Spawning needs the packed atlas as well
+    if atlases.get(&sprites.atlas).is_none() {
+        pending += 1;
+    }
+
+    // The font, every sprite and the atlas
+    let total = sprites.slots.len() + 2;
+    let progress = (total - pending) as f32 / total as f32;
+    for mut style in bars.iter_mut() {
+        style.size.width = Val::Percent(progress * 100.0);
+    }
+
+    // Nothing is left pending next frame either, so a menu held back by a
+    // transition already queued opens then
+    if pending == 0 {
+        if let Err(e) = state.set(AppState::Menu) {
+            warn!("holding back the menu: {}", e);
+        }
+    }
+}
+
+pub fn retry_missing_sprites(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut sprites: ResMut<GameAssets>,
+) {
+    sprites.retry.tick(time.delta());
+    let should_retry = sprites.retry.just_finished();
+
+    for slot in sprites.slots.iter_mut().filter(|s| s.is_placeholder) {
+        match asset_server.get_load_state(&slot.handle) {
+            // The real file replaced the placeholder
+            LoadState::Loaded => {
+                info!("loaded {}", slot.file.display());
+                slot.is_placeholder = false;
+            }
+            // Try again once the file exists
+            LoadState::Failed if should_retry && Path::new(ASSET_DIRECTORY).join(&slot.file).exists() => {
+                let _ = asset_server.load::<Image, _>(slot.file.as_path());
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn refresh_sprite_atlas(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprites: ResMut<GameAssets>,
+) {
+    // Only rebuild when one of our sprites was added or changed
+    let is_stale = image_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            sprites.slots.iter().any(|s| s.handle == *handle)
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    if !is_stale {
+        return;
+    }
+
+    // Wait until every sprite has something to pack
+    let mut builder = TextureAtlasBuilder::default();
+    for slot in sprites.slots.iter() {
+        match images.get(&slot.handle) {
+            Some(image) => builder.add_texture(slot.handle.clone(), image),
+            None => return,
+        }
+    }
+
+    let atlas = match builder.finish(&mut images) {
+        Ok(atlas) => atlas,
+        Err(e) => {
+            warn!("failed to pack sprite atlas: {:?}", e);
+            return;
+        }
+    };
+
+    for slot in sprites.slots.iter_mut() {
+        slot.index = atlas.get_texture_index(&slot.handle).unwrap_or_default();
+    }
+
+    // Replace in place so existing sprites pick up the new atlas
+    let id = sprites.atlas.id;
+    sprites.atlas = atlases.set(id, atlas);
+}
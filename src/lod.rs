@@ -0,0 +1,54 @@
+//! Level of detail for the monster simulation. Monsters far from the camera
+//! are marked `Distant`: they pass through each other, and only re-steer and
+//! check for walls every few frames, staggered so the work is spread out.
+//! They return to full simulation as soon as they come back into range.
+
+use bevy::prelude::*;
+
+use crate::camera::CameraFocus;
+use crate::{Monster, Position};
+
+
+const LOD_FAR_DISTANCE: f32  = 900.0;
+const LOD_NEAR_DISTANCE: f32 = 750.0;
+const LOD_STEER_INTERVAL: u32 = 8;
+
+/// Marks a monster running at reduced simulation detail.
+#[derive(Component)]
+pub struct Distant;
+
+#[derive(Default)]
+pub struct SimulationLod {
+    frame: u32,
+}
+
+impl SimulationLod {
+    /// Whether a distant entity gets its turn to update this frame.
+    pub fn is_turn_of(&self, entity: Entity) -> bool {
+        entity.id() % LOD_STEER_INTERVAL == self.frame % LOD_STEER_INTERVAL
+    }
+}
+
+pub fn assign_lod_tiers(
+    mut commands: Commands,
+    mut lod: ResMut<SimulationLod>,
+    focus: Res<CameraFocus>,
+    monsters: Query<(Entity, &Position, Option<&Distant>), With<Monster>>,
+) {
+    lod.frame = lod.frame.wrapping_add(1);
+
+    // Range grows with zoom so nothing on screen is ever demoted, and the gap
+    // between the two distances stops monsters flickering between tiers
+    let far_squared = (LOD_FAR_DISTANCE * focus.zoom).powi(2);
+    let near_squared = (LOD_NEAR_DISTANCE * focus.zoom).powi(2);
+
+    for (entity, p, distant) in monsters.iter() {
+        let distance_squared = p.current.distance_squared(focus.center);
+        if distant.is_none() && distance_squared > far_squared {
+            commands.entity(entity).insert(Distant);
+        }
+        else if distant.is_some() && distance_squared < near_squared {
+            commands.entity(entity).remove::<Distant>();
+        }
+    }
+}
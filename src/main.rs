@@ -1,46 +1,333 @@
-use bevy::prelude::*;
-use rand;
+#![allow(clippy::too_many_arguments)]
 
+// LAN play needs UDP sockets and Discord needs its local IPC pipe, and
+// neither exists in the browser
+#[cfg(all(target_arch = "wasm32", any(feature = "lan", feature = "discord", feature = "online")))]
+compile_error!("the lan, discord and online features are native only");
 
-const PLAYER_SPEED: f32       = 100.0;
-const PLAYER_BODY_RADIUS: f32 = 18.0;
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
 
-const MONSTER_SPEED: f32       = 50.0;
-const MONSTER_BODY_RADIUS: f32 = 10.0;
-const MONSTER_BODY_MASS: f32   = 10.0;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+mod abilities;
+mod adaptive;
+mod arena;
+mod args;
+mod autopilot;
+mod burrowing;
+mod camera;
+mod capture;
+mod click_move;
+mod content;
+mod curses;
+mod daily;
+mod damage;
+mod dash;
+mod day_night;
+mod death;
+mod decals;
+mod decorations;
+mod director;
+#[cfg(feature = "discord")]
+mod discord;
+mod effective_stats;
+mod escort;
+mod experience_bar;
+mod explosives;
+mod feed;
+mod flow_field;
+mod frame_limit;
+mod gamepad;
+mod ghost;
+mod glow;
+mod ground;
+mod handling;
+mod health;
+mod health_bars;
+mod hit_flash;
+mod horde;
+mod input;
+mod instancing;
+mod juice;
+mod leaderboard;
+mod lifesteal;
+mod loading;
+mod lod;
+mod merchant;
+mod menu;
+mod mods;
+mod nav;
+mod necromancy;
+#[cfg(feature = "lan")]
+mod net;
+#[cfg(feature = "online")]
+mod online;
+mod pause;
+mod particles;
+mod photo;
+mod players;
+mod progression;
+#[cfg(feature = "rapier")]
+mod rapier;
+mod ranged;
+#[cfg(debug_assertions)]
+mod ray_probe;
+mod rebind;
+mod remains;
+mod run_stats;
+mod sandbox;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod seed;
+mod shadows;
+mod shield;
+mod shrine;
+mod spatial;
+mod stage;
+mod storage;
+mod synergy;
+mod targeting;
+mod telemetry;
+mod threat;
+mod trails;
+mod touch;
+mod tween;
+mod weather;
+mod window;
+mod y_sort;
+
+use abilities::{
+    ability_panel_input, apply_knockback, attach_ability_slots, cast_abilities, despawn_ability_hud,
+    expand_shockwaves, knock_back, spawn_ability_hud, spawn_ability_panel, update_ability_hud, Ability, ChosenAbility,
+};
+use adaptive::{adapt_pressure, adaptive_panel_input, reset_pressure, spawn_adaptive_panel, Adaptive, Pressure};
+use arena::{build_arena, Arena};
+use args::{Difficulty, LaunchArgs, Weapon, USAGE};
+use autopilot::{
+    attach_autopilot, autopilot_direction, spawn_demo_hint, start_demo, stop_demo, Autopilot,
+    DemoMode,
+};
+use burrowing::{cycle_burrows, Burrow, Submerged};
+#[cfg(not(target_arch = "wasm32"))]
+use capture::{capture_hotkeys, record_clip, ClipBuffer};
+use camera::{anchor_to_world, frame_players, CameraFocus, MainCamera};
+use click_move::{click_to_move, move_panel_input, spawn_move_panel, ClickTarget, MoveScheme};
+use content::Content;
+use curses::{apply_curses, curse_panel_input, spawn_curse_panel, Curses};
+use daily::{track_daily_run, Daily};
+use damage::DamageType;
+use dash::{apply_dashes, attach_dash_cooldowns, start_dashes};
+use day_night::{
+    advance_world_clock, despawn_day_night_hud, night_pressure, reset_world_clock,
+    spawn_day_night_hud, WorldClock,
+};
+use death::{
+    despawn_game_over_screen, game_over_input, play_death, spawn_game_over_screen, Dying, PlayDeath,
+    TimeScale,
+};
+use decals::{clear_decals, scorch_ground, update_decals, LeaveDecal};
+use decorations::{stream_decorations, DecorationChunks};
+use effective_stats::{refresh_effective_stats, EffectiveStats, StatsChangedEvent};
+use director::{
+    despawn_wave_banner, reset_director, run_horde_waves, run_timeline, spawn_formations, spawn_wave_banner,
+    steer_formations, steer_sweeping, Director,
+};
+use escort::{hurt_pilgrims, setup_escort, start_escort, update_pilgrim_bars, walk_pilgrims, Escort, Pilgrim};
+use experience_bar::{despawn_experience_bar, spawn_experience_bar, update_experience_bar};
+use explosives::{explosion_damage, light_fuses, spawn_explosion, Fuse, LightFuses};
+use feed::{announce_bosses, announce_milestones, despawn_feed, spawn_feed, update_feed, Announcement};
+use flow_field::{update_flow_field, FlowField};
+#[cfg(not(target_arch = "wasm32"))]
+use frame_limit::limit_frame_rate;
+use frame_limit::{frame_cap_panel_input, spawn_frame_cap_panel};
+use gamepad::gamepad_direction;
+use ghost::{record_ghost, replay_ghost, reset_ghost, Ghosts};
+use glow::{clear_glows, flash_blasts};
+use ground::{scroll_ground, spawn_ground};
+use handling::{directions_panel_input, spawn_directions_panel, Directions, Handling, Momentum};
+use health::{despawn_vignette, spawn_vignette, warn_low_health, Health};
+use health_bars::{attach_health_bars, clear_health_bars, update_health_bars};
+use hit_flash::{flash_hurt_monsters, restore_hit_flashes, MonsterHurtEvent};
+use horde::{absorb_spawn, Horde, HordeCandidates};
+use input::{Action, Bindings, Controls};
+use juice::{attach_squash, squash_and_stretch, unsquash};
+use leaderboard::{record_score, Leaderboard};
+use lifesteal::{float_heal_numbers, steal_life, DamageDealtEvent, Lifesteal};
+use loading::{
+    despawn_loading_bar, finish_loading, refresh_sprite_atlas, retry_missing_sprites, spawn_loading_bar,
+    start_loading, GameAssets,
+};
+use lod::{assign_lod_tiers, Distant, SimulationLod};
+use merchant::{
+    collect_coins, despawn_coin_hud, despawn_shop_screen, drop_coins, shop_input, spawn_coin_hud,
+    spawn_shop_screen, update_coin_hud, visit_merchant, CoinDrop, MerchantVisits, Purse,
+};
+use menu::{despawn_menu, menu_input, return_to_menu, spawn_menu};
+use mods::log_mods;
+use nav::{highlight_focus, navigate_menus, MenuAction, NavigateMenus};
+use necromancy::{clear_corpses, fade_corpses, raise_dead, spawn_corpse, Raised};
+use particles::{burst_on_hits, emit_particles, kick_up_dust, update_particles, ParticleBurst, ParticlePool};
+use pause::{despawn_pause_screen, pause_game, resume_game, spawn_pause_screen};
+use photo::{enter_photo_mode, hide_hud, photo_camera, photo_controls, show_hud, HiddenHud};
+use players::{nearest_player, player_positions, random_player};
+use progression::{
+    despawn_level_up_screen, gain_experience, level_up_input, reset_progression, spawn_level_up_screen,
+    Progression,
+};
+use ranged::{clear_spit, spit_at_players, spit_collision, SpitCooldown};
+use rebind::{despawn_rebind_screen, open_controls, rebind_keys, spawn_controls_panel, spawn_rebind_screen};
+use remains::{remains_panel_input, spawn_remains_panel, RemainsLimit};
+use run_stats::{record_run, track_run_time, GameOverEvent, RecordRun, RunStats};
+use sandbox::{hold_sandbox, sandbox_input, setup_sandbox, start_sandbox, strike_dummies, Sandbox};
+use seed::{copy_seed, seed_panel_input, spawn_seed_panel, Clipboard};
+use shadows::{attach_shadows, update_shadows};
+use shield::{break_shields, Shield};
+use shrine::{charge_shrines, open_chests, place_shrines};
+use spatial::{index_monsters, index_obstacles, IndexMonsters, SpatialIndex};
+use stage::{spawn_bosses, BossSchedule, ObstacleShape, Stages};
+use synergy::{offer_panel_input, spawn_offer_panel, Offers};
+use targeting::{spawn_weapon_panel, weapon_panel_input, BlastAiming, FireMode, WeaponAim};
+use telemetry::{record_telemetry, spawn_telemetry_panel, telemetry_panel_input, Telemetry};
+use threat::{measure_threat, MeasureThreat, Threat};
+use touch::{despawn_touch_stick, draw_touch_stick, read_touches, spawn_touch_stick, touch_to_move, TouchControls, TouchTap};
+use trails::{attach_trails, clear_trails, detach_trails, update_trails};
+use tween::{tween_sprite_colors, tween_text_colors, tween_transforms, tween_ui_colors, Ease, Tween, TweenTarget};
+use weather::{change_weather, despawn_weather_hud, reset_weather, spawn_weather_hud, Weather};
+#[cfg(not(target_arch = "wasm32"))]
+use window::set_window_icon;
+use window::window_descriptor;
+use y_sort::{sort_by_y, SortByY};
+
+
+const PLAYER_SPEED: f32        = 100.0;
+const PLAYER_BODY_RADIUS: f32  = 18.0;
+const PLAYER_BODY_MASS: f32    = 40.0;
+const PLAYER_SPACING: f32      = 80.0;
+const PLAYER_SPRITE_SCALE: f32 = 4.0;
+const PLAYER_MAX_HEALTH: f32   = 100.0;
+const MAX_PLAYERS: usize       = 2;
+
+const MONSTER_SPEED: f32          = 50.0;
+const MONSTER_BODY_RADIUS: f32    = 10.0;
+const MONSTER_BODY_MASS: f32      = 10.0;
+const MONSTER_CONTACT_DAMAGE: f32 = 20.0;
+const THORNS_RADIUS: f32          = 12.0;
+const THORNS_PULSE_RADIUS: f32    = 90.0;
+const THORNS_PULSE_SPEED: f32     = 400.0;
+const MONSTER_AGGRO_RADIUS: f32   = 450.0;
+const MONSTER_WANDER_SECONDS: f32 = 3.0;
+const MONSTER_WANDER_PACE: f32    = 0.4;
+const MONSTER_FLYING_DEPTH: f32   = 10.0;
 
 const MONSTER_SPAWN_DISTANCE: f32       = 300.0;
-const MONSTER_SPAWN_LIMIT: u32          = 300;
+/// Tries at finding a kind that fits the spawn budget.
+const MONSTER_SPAWN_ROLLS: u32          = 3;
 const MONSTER_SPAWN_PERIOD_SECONDS: f32 = 0.6;
+const MONSTER_SPAWN_IN_SECONDS: f32     = 0.5;
 
 const BLAST_RADIUS: f32               = 50.0;
 const BLAST_LIFETIME_SECONDS: f32     = 0.3;
 const BLAST_SPAWN_PERIOD_SECONDS: f32 = 3.0;
+const BLAST_STAGGER_SECONDS: f32      = 0.1;
+/// What one blast hit on a monster is worth, in player health points.
+const BLAST_HIT_DAMAGE: f32           = 10.0;
 
-const COLLISION_DISPLACEMENT_FACTOR: f32 = 0.2;
+const UI_FONT: &str = "fonts/DejaVuSans.ttf";
 
-#[derive(Default)]
+const COLLISION_DISPLACEMENT_FACTOR: f32 = 0.2;
+const CROWD_PRESSURE_FACTOR: f32         = 0.6;
+const PUSHER_NUDGE_FACTOR: f32           = 1.2;
+
+/// Spawn and kill counts for the current run, broken down by monster kind and
+/// by the weapon credited with each kill. Kills by explosions count by kind
+/// but aren't credited to any weapon. Bombers that blow themselves up aren't
+/// kills and are counted apart.
+#[derive(Default, Serialize)]
 struct MonsterStats {
-    spawned: u32,
-    killed: u32,
+    spawned_by_kind: BTreeMap<MonsterKind, u32>,
+    killed_by_kind: BTreeMap<MonsterKind, u32>,
+    killed_by_weapon: BTreeMap<Weapon, u32>,
+    detonated_by_kind: BTreeMap<MonsterKind, u32>,
+    /// Kills that came from Execute, also counted in the kills above.
+    executed_by_kind: BTreeMap<MonsterKind, u32>,
+    /// Spawns that joined a horde rather than arriving on their own, also
+    /// counted in the spawns above.
+    absorbed_by_kind: BTreeMap<MonsterKind, u32>,
+    /// Kills counting each horde once, however many members it had.
+    #[serde(skip)]
+    bodies_killed: u32,
 }
 
 impl MonsterStats {
     fn clear(&mut self) {
-        self.spawned = 0;
-        self.killed = 0;
+        *self = MonsterStats::default();
     }
 
-    fn count(&self) -> u32 {
-        if self.spawned > self.killed {
-            self.spawned - self.killed
-        }
-        else {
-            0
+    fn record_spawn(&mut self, kind: MonsterKind) {
+        *self.spawned_by_kind.entry(kind).or_default() += 1;
+    }
+
+    fn record_kills(&mut self, weapon: Option<Weapon>, kind: MonsterKind, count: u32) {
+        *self.killed_by_kind.entry(kind).or_default() += count;
+        self.bodies_killed += 1;
+        if let Some(weapon) = weapon {
+            *self.killed_by_weapon.entry(weapon).or_default() += count;
         }
     }
+
+    fn record_detonations(&mut self, kind: MonsterKind, count: u32) {
+        *self.detonated_by_kind.entry(kind).or_default() += count;
+    }
+
+    fn record_execution(&mut self, kind: MonsterKind, count: u32) {
+        *self.executed_by_kind.entry(kind).or_default() += count;
+    }
+
+    fn record_absorption(&mut self, kind: MonsterKind) {
+        self.record_spawn(kind);
+        *self.absorbed_by_kind.entry(kind).or_default() += 1;
+    }
+
+    fn spawned(&self) -> u32 {
+        self.spawned_by_kind.values().sum()
+    }
+
+    /// Spawns that came in as monsters of their own, not into a horde.
+    fn arrived(&self) -> u32 {
+        self.spawned() - self.absorbed_by_kind.values().sum::<u32>()
+    }
+
+    fn killed(&self) -> u32 {
+        self.killed_by_kind.values().sum()
+    }
+
+    fn executed(&self) -> u32 {
+        self.executed_by_kind.values().sum()
+    }
 }
 
+/// Seeds the world layout of the current run.
+#[derive(Default)]
+struct WorldSeed {
+    value: u64,
+}
+
+/// How runs are set up unless the player picks otherwise, from the command
+/// line.
+struct RunDefaults {
+    weapon: Weapon,
+    difficulty: Difficulty,
+    seed: Option<u64>,
+    curses: Curses,
+}
+
+struct PlayerCount(usize);
+
 struct MonsterSpawnTimer(Timer);
 
 impl MonsterSpawnTimer {
@@ -52,8 +339,43 @@ impl MonsterSpawnTimer {
     }
 }
 
+/// Each player's blast cooldown when firing by hand. It matches the automatic
+/// period but starts from each press.
+#[derive(Default)]
+struct ManualCooldowns(Vec<Timer>);
+
+impl ManualCooldowns {
+    fn for_player(&mut self, index: usize) -> &mut Timer {
+        // Ready to fire straight away
+        while self.0.len() <= index {
+            let mut timer = Timer::from_seconds(BLAST_SPAWN_PERIOD_SECONDS, false);
+            timer.set_elapsed(timer.duration());
+            self.0.push(timer);
+        }
+
+        &mut self.0[index]
+    }
+}
+
+/// Weapons the players carry this run.
+struct Loadout(Vec<Weapon>);
+
 struct BlastSpawnTimer(Timer);
 
+/// Blasts in a volley that haven't gone off yet. Each goes off a little after
+/// the one before, so a ring of them ripples out rather than landing at once.
+#[derive(Default)]
+struct BlastVolleys(Vec<QueuedBlast>);
+
+struct QueuedBlast {
+    player: usize,
+    weapon: Weapon,
+    delay: Timer,
+    radius: f32,
+    origin: Vec2,
+    target: Vec2,
+}
+
 impl BlastSpawnTimer {
     fn new() -> BlastSpawnTimer {
         let mut timer = Timer::from_seconds(BLAST_SPAWN_PERIOD_SECONDS, true);
@@ -67,30 +389,348 @@ impl BlastSpawnTimer {
 struct Blast {
     lifetime: Timer,
     circle: Circle,
+    /// Where the blast was fired from, for hits that care about direction.
+    origin: Vec2,
+    /// The layers of the bodies it hits.
+    mask: Layers,
+    /// The player who fired it, if any.
+    shooter: Option<usize>,
+    /// The weapon whose damage type it deals, credited with its kills if a
+    /// player fired it.
+    weapon: Weapon,
+    /// Struck back by thorns rather than fired, so its kills aren't the
+    /// weapon's.
+    is_thorns: bool,
+    /// Monsters that resisted it, so it doesn't roll on them again.
+    shrugged_off: Vec<Entity>,
 }
 
 impl Blast {
-    fn new() -> Blast {
+    fn new(radius: f32, origin: Vec2) -> Blast {
         Blast{
             lifetime: Timer::from_seconds(BLAST_LIFETIME_SECONDS, false),
-            circle: Circle::new(BLAST_RADIUS),
+            circle: Circle::new(radius),
+            origin,
+            mask: Layers::MONSTERS,
+            shooter: None,
+            weapon: Weapon::Blast,
+            is_thorns: false,
+            shrugged_off: Vec::new(),
         }
     }
+
+    fn hitting(mut self, mask: Layers) -> Blast {
+        self.mask = mask;
+        self
+    }
+
+    fn fired_by(mut self, player: usize, weapon: Weapon) -> Blast {
+        self.shooter = Some(player);
+        self.weapon = weapon;
+        self
+    }
+
+    fn struck_by_thorns(mut self) -> Blast {
+        self.is_thorns = true;
+        self
+    }
+
+    /// The weapon its kills count for, if a player fired it.
+    fn credited_weapon(&self) -> Option<Weapon> {
+        self.shooter.filter(|_| !self.is_thorns).map(|_| self.weapon)
+    }
 }
 
 #[derive(Component)]
-struct Player;
+struct Player {
+    index: usize,
+}
 
 #[derive(Component)]
 struct Monster;
 
+/// Where a monster came in the run's order of spawns. Seeds its wandering, so
+/// that doesn't hang on how entities happen to be allocated.
+#[derive(Component, Clone, Copy)]
+struct SpawnNumber(u64);
+
+/// How many monsters this run has numbered.
+#[derive(Default)]
+struct SpawnCount(u64);
+
+/// A monster still fading in. It can't hurt anyone until it has finished.
+#[derive(Component)]
+struct Spawning(Timer);
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum MonsterKind {
+    Grunt,
+    Runner,
+    Brute,
+    Necromancer,
+    Shielded,
+    Bomber,
+    Burrower,
+    Flyer,
+    Spitter,
+    /// A kind added by a mod, by its place among them.
+    Modded(u8),
+}
+
+/// Written as the kind's ID, so saves and data files can name mod kinds.
+impl Serialize for MonsterKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        content::serialize(*self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MonsterKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MonsterKind, D::Error> {
+        content::deserialize(deserializer)
+    }
+}
+
+impl Content for MonsterKind {
+    const SORT: &'static str = "monster kind";
+
+    fn id(self) -> &'static str {
+        match self {
+            MonsterKind::Grunt => "grunt",
+            MonsterKind::Runner => "runner",
+            MonsterKind::Brute => "brute",
+            MonsterKind::Necromancer => "necromancer",
+            MonsterKind::Shielded => "shielded",
+            MonsterKind::Bomber => "bomber",
+            MonsterKind::Burrower => "burrower",
+            MonsterKind::Flyer => "flyer",
+            MonsterKind::Spitter => "spitter",
+            MonsterKind::Modded(id) => &mods::monster(id).id,
+        }
+    }
+
+    fn all() -> Vec<MonsterKind> {
+        MonsterKind::ALL.into_iter().chain(mods::monsters()).collect()
+    }
+}
+
+impl MonsterKind {
+    /// The built-in kinds.
+    const ALL: [MonsterKind; 9] = [
+        MonsterKind::Grunt,
+        MonsterKind::Runner,
+        MonsterKind::Brute,
+        MonsterKind::Necromancer,
+        MonsterKind::Shielded,
+        MonsterKind::Bomber,
+        MonsterKind::Burrower,
+        MonsterKind::Flyer,
+        MonsterKind::Spitter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MonsterKind::Grunt => "Grunt",
+            MonsterKind::Runner => "Runner",
+            MonsterKind::Brute => "Brute",
+            MonsterKind::Necromancer => "Necromancer",
+            MonsterKind::Shielded => "Shielded",
+            MonsterKind::Bomber => "Bomber",
+            MonsterKind::Burrower => "Burrower",
+            MonsterKind::Flyer => "Flyer",
+            MonsterKind::Spitter => "Spitter",
+            MonsterKind::Modded(id) => &mods::monster(id).name,
+        }
+    }
+
+    /// The built-in kind whose behavior this kind has.
+    fn behavior(self) -> MonsterKind {
+        match self {
+            MonsterKind::Modded(id) => mods::monster(id).base,
+            kind => kind,
+        }
+    }
+
+    fn speed(self) -> f32 {
+        match self {
+            MonsterKind::Grunt => MONSTER_SPEED,
+            MonsterKind::Runner => MONSTER_SPEED * 1.6,
+            MonsterKind::Brute => MONSTER_SPEED * 0.6,
+            MonsterKind::Necromancer => MONSTER_SPEED * 0.7,
+            MonsterKind::Shielded => MONSTER_SPEED * 0.8,
+            MonsterKind::Bomber => MONSTER_SPEED * 1.3,
+            MonsterKind::Burrower => MONSTER_SPEED * 1.1,
+            MonsterKind::Flyer => MONSTER_SPEED * 0.9,
+            MonsterKind::Spitter => MONSTER_SPEED * 0.8,
+            MonsterKind::Modded(id) => mods::monster(id).base.speed() * mods::monster(id).speed,
+        }
+    }
+
+    fn radius(self) -> f32 {
+        match self {
+            MonsterKind::Grunt => MONSTER_BODY_RADIUS,
+            MonsterKind::Runner => MONSTER_BODY_RADIUS * 0.8,
+            MonsterKind::Brute => MONSTER_BODY_RADIUS * 1.6,
+            MonsterKind::Necromancer => MONSTER_BODY_RADIUS * 1.2,
+            MonsterKind::Shielded => MONSTER_BODY_RADIUS * 1.2,
+            MonsterKind::Bomber => MONSTER_BODY_RADIUS * 0.9,
+            MonsterKind::Burrower => MONSTER_BODY_RADIUS,
+            MonsterKind::Flyer => MONSTER_BODY_RADIUS * 0.9,
+            MonsterKind::Spitter => MONSTER_BODY_RADIUS,
+            MonsterKind::Modded(id) => mods::monster(id).base.radius() * mods::monster(id).radius,
+        }
+    }
+
+    /// How close a player has to be before this kind gives chase.
+    fn aggro_radius(self) -> f32 {
+        match self {
+            MonsterKind::Grunt => MONSTER_AGGRO_RADIUS,
+            MonsterKind::Runner => MONSTER_AGGRO_RADIUS * 1.5,
+            MonsterKind::Brute => MONSTER_AGGRO_RADIUS * 0.8,
+            MonsterKind::Necromancer => MONSTER_AGGRO_RADIUS * 1.2,
+            MonsterKind::Shielded => MONSTER_AGGRO_RADIUS,
+            MonsterKind::Bomber => MONSTER_AGGRO_RADIUS * 1.2,
+            MonsterKind::Burrower => MONSTER_AGGRO_RADIUS * 1.5,
+            MonsterKind::Flyer => MONSTER_AGGRO_RADIUS * 2.0,
+            MonsterKind::Spitter => MONSTER_AGGRO_RADIUS,
+            MonsterKind::Modded(id) => mods::monster(id).base.aggro_radius() * mods::monster(id).aggro_radius,
+        }
+    }
+
+    fn mass(self) -> f32 {
+        match self {
+            MonsterKind::Grunt => MONSTER_BODY_MASS,
+            MonsterKind::Runner => MONSTER_BODY_MASS * 0.6,
+            MonsterKind::Brute => MONSTER_BODY_MASS * 4.0,
+            MonsterKind::Necromancer => MONSTER_BODY_MASS * 1.5,
+            MonsterKind::Shielded => MONSTER_BODY_MASS * 2.0,
+            MonsterKind::Bomber => MONSTER_BODY_MASS * 0.8,
+            MonsterKind::Burrower => MONSTER_BODY_MASS * 1.2,
+            MonsterKind::Flyer => MONSTER_BODY_MASS * 0.5,
+            MonsterKind::Spitter => MONSTER_BODY_MASS,
+            MonsterKind::Modded(id) => mods::monster(id).base.mass() * mods::monster(id).mass,
+        }
+    }
+
+    /// What this kind costs out of the spawn budget.
+    fn threat(self) -> u32 {
+        match self {
+            MonsterKind::Grunt => 1,
+            MonsterKind::Runner => 1,
+            MonsterKind::Brute => 4,
+            MonsterKind::Necromancer => 6,
+            MonsterKind::Shielded => 3,
+            MonsterKind::Bomber => 2,
+            MonsterKind::Burrower => 2,
+            MonsterKind::Flyer => 2,
+            MonsterKind::Spitter => 3,
+            MonsterKind::Modded(id) => mods::monster(id).threat.unwrap_or_else(|| mods::monster(id).base.threat()),
+        }
+    }
+
+    /// How much of a hit of `damage` lands on this kind.
+    fn damage_taken(self, damage: DamageType) -> f32 {
+        match (self, damage) {
+            (MonsterKind::Brute, DamageType::Arcane) => 1.5,
+            (MonsterKind::Necromancer, DamageType::Arcane) => 0.5,
+            (MonsterKind::Necromancer, DamageType::Fire) => 1.5,
+            (MonsterKind::Shielded, DamageType::Ice) => 1.5,
+            (MonsterKind::Bomber, DamageType::Fire) => 2.0,
+            (MonsterKind::Burrower, DamageType::Fire) => 0.5,
+            (MonsterKind::Flyer, DamageType::Ice) => 1.5,
+            (MonsterKind::Spitter, DamageType::Ice) => 0.5,
+            (MonsterKind::Modded(id), damage) => match mods::monster(id).damage_taken.get(&damage) {
+                Some(&factor) => factor,
+                None => mods::monster(id).base.damage_taken(damage),
+            },
+            _ => 1.0,
+        }
+    }
+
+    fn tint(self) -> Color {
+        match self {
+            MonsterKind::Grunt => Color::WHITE,
+            MonsterKind::Runner => Color::rgb(0.75, 1.0, 0.75),
+            MonsterKind::Brute => Color::rgb(1.0, 0.65, 0.65),
+            MonsterKind::Necromancer => Color::rgb(0.7, 0.5, 1.0),
+            MonsterKind::Shielded => Color::rgb(0.6, 0.8, 1.0),
+            MonsterKind::Bomber => Color::rgb(1.0, 0.75, 0.4),
+            MonsterKind::Burrower => Color::rgb(0.85, 0.7, 0.55),
+            MonsterKind::Flyer => Color::rgb(0.7, 0.95, 1.0),
+            MonsterKind::Spitter => Color::rgb(0.6, 1.0, 0.4),
+            MonsterKind::Modded(id) => match mods::monster(id).tint {
+                Some((r, g, b)) => Color::rgb(r, g, b),
+                None => mods::monster(id).base.tint(),
+            },
+        }
+    }
+
+    fn is_flying(self) -> bool {
+        self.behavior() == MonsterKind::Flyer
+    }
+
+    /// The layer this kind sits on and the layers it collides with. Flying
+    /// kinds pass over obstacles and the crowd on the ground.
+    fn collision_layers(self) -> (Layers, Layers) {
+        if self.is_flying() {
+            (Layers::AIR, Layers::AIR)
+        }
+        else {
+            (Layers::GROUND, Layers::PLAYER.with(Layers::GROUND).with(Layers::OBSTACLE))
+        }
+    }
+
+    /// Draw flying kinds above everything on the ground.
+    fn depth(self) -> f32 {
+        if self.is_flying() {
+            MONSTER_FLYING_DEPTH
+        }
+        else {
+            0.0
+        }
+    }
+}
+
+#[derive(Component)]
+struct Obstacle;
+
 #[derive(Component, Default, PartialEq)]
 struct Position {
     current: Vec2,
     change: Vec2,
 }
 
-struct NewGameEvent;
+/// Starts a run with the given setup.
+#[derive(Clone, Copy)]
+struct NewGameEvent {
+    weapon: Weapon,
+    difficulty: Difficulty,
+    stage: usize,
+    seed: u64,
+    curses: Curses,
+    /// The day of the daily challenge, if this is one.
+    daily: Option<u64>,
+    /// Whether this is a training arena run.
+    sandbox: bool,
+    /// Whether there's a pilgrim to escort.
+    escort: bool,
+}
+
+impl NewGameEvent {
+    /// A run on `stage` with the default setup and a fresh seed, unless the
+    /// seed was fixed at launch.
+    fn on_stage(stage: usize, defaults: &RunDefaults) -> NewGameEvent {
+        NewGameEvent{
+            weapon: defaults.weapon,
+            difficulty: defaults.difficulty,
+            stage,
+            seed: defaults.seed.unwrap_or_else(rand::random),
+            curses: defaults.curses,
+            daily: None,
+            sandbox: false,
+            escort: false,
+        }
+    }
+}
 
 impl Position {
     fn new(current: Vec2) -> Position {
@@ -139,10 +779,40 @@ impl Velocity {
     }
 }
 
+/// Collision categories. A body sits on a layer and its mask lists the layers
+/// it collides with. Two bodies only push each other apart when each is in
+/// the other's mask, and blasts only hit the layers in their own mask.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Layers(u8);
+
+impl Layers {
+    const NONE: Layers     = Layers(0);
+    const PLAYER: Layers   = Layers(1);
+    const GROUND: Layers   = Layers(1 << 1);
+    const AIR: Layers      = Layers(1 << 2);
+    const OBSTACLE: Layers = Layers(1 << 3);
+    const MONSTERS: Layers = Layers::GROUND.with(Layers::AIR);
+
+    const fn with(self, other: Layers) -> Layers {
+        Layers(self.0 | other.0)
+    }
+
+    fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
 #[derive(Component)]
 struct Body {
     circle: Circle,
+    shape: Shape,
     mass: Option<f32>,
+    is_firm_when_still: bool,
+    /// Shoves lighter bodies out of its way as it moves, rather than only
+    /// passing its motion on.
+    is_pusher: bool,
+    layer: Layers,
+    mask: Layers,
     collision: Collision,
 }
 
@@ -150,23 +820,81 @@ impl Body {
     fn new(circle: Circle, mass: Option<f32>) -> Body {
         Body{
             circle,
+            shape: Shape::Point,
             mass,
+            is_firm_when_still: false,
+            is_pusher: false,
+            layer: Layers::NONE,
+            mask: Layers::NONE,
             collision: Collision{
                 displacement: Vec2::ZERO,
+                pressure: Vec2::ZERO,
                 is_firm: false,
             },
         }
     }
+
+    fn firm_when_still(mut self) -> Body {
+        self.is_firm_when_still = true;
+        self
+    }
+
+    fn pusher(mut self) -> Body {
+        self.is_pusher = true;
+        self
+    }
+
+    /// Whether the body holds its ground this frame. Judged from how it
+    /// actually moved rather than where it's trying to go, so a player still
+    /// sliding to a halt can be shoved.
+    fn is_holding_firm(&self, position: &Position) -> bool {
+        self.is_firm_when_still && position.change == Vec2::ZERO
+    }
+
+    /// How much of `push`, this body's motion into another of `other_mass`
+    /// along `away` from it, the other picks up. Pushers move what they walk
+    /// into aside, more so the lighter it is next to them.
+    fn push_on(&self, change: Vec2, away: Vec2, push: f32, other_mass: f32) -> Vec2 {
+        let mass = match self.mass {
+            Some(mass) if self.is_pusher => mass,
+            _ => return away * (push * CROWD_PRESSURE_FACTOR),
+        };
+
+        // Off to whichever side of the path the other is on
+        let mut side = change.normalize_or_zero().perp();
+        if side.dot(away) < 0.0 {
+            side = -side;
+        }
+        let share = mass / (mass + other_mass);
+        (away + side).normalize_or_zero() * (push * share * PUSHER_NUDGE_FACTOR)
+    }
+
+    fn shaped(mut self, shape: Shape) -> Body {
+        self.shape = shape;
+        self
+    }
+
+    fn on_layers(mut self, layer: Layers, mask: Layers) -> Body {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+
+    fn collides_with(&self, other: &Body) -> bool {
+        self.layer.intersects(other.mask) && other.layer.intersects(self.mask)
+    }
 }
 
 struct Collision {
     displacement: Vec2,
+    pressure: Vec2,
     is_firm: bool,
 }
 
 impl Collision {
     fn clear(&mut self) {
         self.displacement = Vec2::ZERO;
+        self.pressure = Vec2::ZERO;
         self.is_firm = false;
     }
 }
@@ -183,9 +911,55 @@ impl Circle {
     }
 }
 
+/// What a body's circle is swept along. A plain circle sweeps a point, a
+/// capsule a segment and a box an axis-aligned rectangle, with the circle
+/// rounding off the ends and corners.
+#[derive(Clone, Copy)]
+enum Shape {
+    Point,
+    /// Half the segment, from its middle to one end.
+    Segment(Vec2),
+    /// Half the rectangle's size.
+    Rect(Vec2),
+}
+
+impl Shape {
+    /// The point of the shape centered on `center` that is closest to `point`.
+    fn closest_point(self, center: Vec2, point: Vec2) -> Vec2 {
+        match self {
+            Shape::Point => center,
+            Shape::Segment(half) => {
+                if half == Vec2::ZERO {
+                    return center;
+                }
+                let t = (point - center).dot(half) / half.length_squared();
+                center + half * t.clamp(-1.0, 1.0)
+            }
+            Shape::Rect(half) => center + (point - center).clamp(-half, half),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum AppState {
+    AssetLoading,
+    Menu,
+    Playing,
+    GameOver,
+    PhotoMode,
+    LevelUp,
+    Shop,
+    Paused,
+    Rebinding,
+    #[cfg(feature = "lan")]
+    Client,
+}
+
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum Movement {
     Input,
+    Gamepad,
+    Touch,
     Player,
     Monster,
     Damage,
@@ -193,77 +967,656 @@ pub enum Movement {
 }
 
 fn main() {
-    App::new()
+    // Before anything that names content, the launch options included
+    mods::load_mods();
+
+    let args = match LaunchArgs::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut app = App::new();
+
+    app
+        .insert_resource(window_descriptor(&args))
         .add_plugins(DefaultPlugins)
         .add_startup_system(setup)
-        .add_system(
-            player_direction
-                .label(Movement::Input)
-                .before(Movement::Player),
+        .add_startup_system(log_mods)
+        .add_startup_system(spawn_ground)
+        .add_state(AppState::AssetLoading)
+        .add_system_set(
+            SystemSet::on_enter(AppState::AssetLoading)
+                .with_system(start_loading)
+                .with_system(spawn_loading_bar),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::AssetLoading)
+                .with_system(finish_loading),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::AssetLoading)
+                .with_system(despawn_loading_bar),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Menu)
+                .with_system(spawn_menu)
+                .with_system(spawn_demo_hint)
+                .with_system(spawn_weapon_panel)
+                .with_system(spawn_ability_panel)
+                .with_system(spawn_move_panel)
+                .with_system(spawn_frame_cap_panel)
+                .with_system(spawn_seed_panel)
+                .with_system(spawn_remains_panel)
+                .with_system(spawn_directions_panel)
+                .with_system(spawn_curse_panel)
+                .with_system(spawn_telemetry_panel)
+                .with_system(spawn_offer_panel)
+                .with_system(spawn_adaptive_panel)
+                .with_system(spawn_controls_panel),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Menu)
+                .with_system(navigate_menus)
+                .with_system(menu_input)
+                .with_system(start_demo)
+                .with_system(weapon_panel_input)
+                .with_system(ability_panel_input)
+                .with_system(move_panel_input)
+                .with_system(frame_cap_panel_input)
+                .with_system(seed_panel_input)
+                .with_system(remains_panel_input)
+                .with_system(directions_panel_input)
+                .with_system(curse_panel_input)
+                .with_system(telemetry_panel_input)
+                .with_system(offer_panel_input)
+                .with_system(adaptive_panel_input)
+                .with_system(start_sandbox)
+                .with_system(start_escort)
+                .with_system(open_controls),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Rebinding)
+                .with_system(spawn_rebind_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Rebinding)
+                .with_system(
+                    navigate_menus
+                        .label(NavigateMenus),
+                )
+                .with_system(
+                    rebind_keys
+                        .before(NavigateMenus),
+                ),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Rebinding)
+                .with_system(despawn_rebind_screen),
         )
-        .add_system(
-            movement
-                .label(Movement::Player),
+        .add_system_set(
+            SystemSet::on_exit(AppState::Menu)
+                .with_system(despawn_menu),
         )
-        .add_system(
-            monster_direction
-                .label(Movement::Monster)
-                .after(Movement::Player),
+        .add_system_set(
+            SystemSet::on_enter(AppState::Playing)
+                .with_system(spawn_day_night_hud)
+                .with_system(spawn_weather_hud)
+                .with_system(spawn_vignette)
+                .with_system(spawn_wave_banner)
+                .with_system(spawn_ability_hud)
+                .with_system(spawn_coin_hud)
+                .with_system(spawn_experience_bar)
+                .with_system(spawn_touch_stick)
+                .with_system(spawn_feed),
         )
-        .add_system(
-            damage_collision
-                .label(Movement::Damage)
-                .after(Movement::Monster),
+        .add_system_set(
+            SystemSet::on_exit(AppState::Playing)
+                .with_system(despawn_day_night_hud)
+                .with_system(despawn_weather_hud)
+                .with_system(despawn_vignette)
+                .with_system(despawn_wave_banner)
+                .with_system(despawn_ability_hud)
+                .with_system(despawn_coin_hud)
+                .with_system(despawn_experience_bar)
+                .with_system(despawn_touch_stick)
+                .with_system(despawn_feed)
+                .with_system(clear_health_bars),
         )
-        .add_system(
-            spread_collision
-                .label(Movement::Spread)
-                .after(Movement::Damage),
+        .add_system_set(
+            SystemSet::on_enter(AppState::GameOver)
+                .with_system(spawn_game_over_screen),
         )
-        .add_system(
-            blast_collision
-                .after(Movement::Spread),
+        .add_system_set(
+            SystemSet::on_update(AppState::GameOver)
+                .with_system(navigate_menus)
+                .with_system(game_over_input)
+                .with_system(copy_seed),
         )
-        .add_system(spawn_monster)
-        .add_system(spawn_blast)
-        .add_system(blast_lifetime)
-        .add_system(new_game)
+        .add_system_set(
+            SystemSet::on_exit(AppState::GameOver)
+                .with_system(despawn_game_over_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(
+                    player_direction
+                        .label(Movement::Input)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    autopilot_direction
+                        .label(Movement::Input)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    click_to_move
+                        .after(Movement::Touch)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    read_touches
+                        .before(Movement::Input),
+                )
+                .with_system(
+                    touch_to_move
+                        .label(Movement::Touch)
+                        .after(Movement::Gamepad)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    gamepad_direction
+                        .label(Movement::Gamepad)
+                        .after(Movement::Input)
+                        .before(Movement::Player),
+                )
+                .with_system(draw_touch_stick)
+                .with_system(
+                    movement
+                        .label(Movement::Player),
+                )
+                .with_system(
+                    update_flow_field
+                        .after(Movement::Player)
+                        .before(Movement::Monster),
+                )
+                .with_system(
+                    assign_lod_tiers
+                        .before(Movement::Monster),
+                )
+                .with_system(
+                    monster_direction
+                        .label(Movement::Monster)
+                        .after(Movement::Player),
+                )
+                .with_system(
+                    damage_collision
+                        .label(Movement::Damage)
+                        .after(Movement::Monster),
+                )
+                .with_system(
+                    blast_collision
+                        .after(Movement::Spread)
+                        .after(LightFuses),
+                )
+                .with_system(break_shields)
+                .with_system(flash_hurt_monsters)
+                .with_system(
+                    steal_life
+                        .after(Movement::Damage),
+                )
+                .with_system(float_heal_numbers)
+                .with_system(restore_hit_flashes)
+                .with_system(burst_on_hits)
+                .with_system(kick_up_dust)
+                .with_system(emit_particles)
+                .with_system(update_particles)
+                .with_system(
+                    explosion_damage
+                        .after(Movement::Spread),
+                )
+                .with_system(
+                    light_fuses
+                        .label(LightFuses),
+                )
+                .with_system(
+                    cycle_burrows
+                        .after(Movement::Monster)
+                        .before(Movement::Damage),
+                )
+                .with_system(
+                    measure_threat
+                        .label(MeasureThreat),
+                )
+                .with_system(
+                    spawn_monster
+                        .after(MeasureThreat),
+                )
+                .with_system(spawn_bosses)
+                .with_system(
+                    spawn_formations
+                        .after(MeasureThreat),
+                )
+                .with_system(run_horde_waves)
+                .with_system(run_timeline)
+                .with_system(place_shrines)
+                .with_system(
+                    charge_shrines
+                        .after(MeasureThreat),
+                )
+                .with_system(open_chests)
+                .with_system(
+                    steer_sweeping
+                        .after(Movement::Monster)
+                        .before(Movement::Damage),
+                )
+                .with_system(
+                    steer_formations
+                        .after(Movement::Monster)
+                        .before(Movement::Damage),
+                )
+                .with_system(reset_director)
+                .with_system(
+                    raise_dead
+                        .after(Movement::Monster)
+                        .before(Movement::Damage),
+                )
+                .with_system(fade_corpses)
+                .with_system(clear_corpses)
+                .with_system(scorch_ground)
+                .with_system(update_decals)
+                .with_system(flash_blasts)
+                .with_system(attach_shadows)
+                .with_system(attach_squash)
+                .with_system(update_shadows)
+                .with_system(clear_glows)
+                .with_system(attach_trails)
+                .with_system(detach_trails)
+                .with_system(update_trails)
+                .with_system(clear_trails)
+                .with_system(clear_decals)
+                .with_system(
+                    index_monsters
+                        .label(IndexMonsters)
+                        .after(Movement::Spread),
+                )
+                .with_system(
+                    index_obstacles
+                        .label(IndexMonsters),
+                )
+                .with_system(
+                    spawn_blast
+                        .after(IndexMonsters),
+                )
+                .with_system(detonate_volleys)
+                .with_system(
+                    spit_at_players
+                        .after(IndexMonsters),
+                )
+                .with_system(
+                    spit_collision
+                        .after(IndexMonsters)
+                        .after(Movement::Player),
+                )
+                .with_system(clear_spit)
+                .with_system(attach_ability_slots)
+                .with_system(attach_dash_cooldowns)
+                .with_system(
+                    start_dashes
+                        .after(Movement::Touch)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    apply_dashes
+                        .after(Movement::Player)
+                        .before(Movement::Monster),
+                )
+                .with_system(
+                    cast_abilities
+                        .after(IndexMonsters),
+                )
+                .with_system(
+                    apply_knockback
+                        .after(Movement::Monster)
+                        .before(Movement::Damage),
+                )
+                .with_system(expand_shockwaves)
+                .with_system(update_ability_hud)
+                .with_system(update_coin_hud)
+                .with_system(update_experience_bar)
+                .with_system(drop_coins)
+                .with_system(collect_coins)
+                .with_system(
+                    visit_merchant
+                        .after(Movement::Spread),
+                )
+                .with_system(blast_lifetime)
+                .with_system(monster_spawn_in)
+                .with_system(
+                    new_game
+                        .after(RecordRun),
+                )
+                .with_system(reset_world_clock)
+                .with_system(advance_world_clock)
+                .with_system(reset_pressure)
+                .with_system(adapt_pressure)
+                .with_system(night_pressure)
+                .with_system(reset_weather)
+                .with_system(apply_curses)
+                .with_system(track_daily_run)
+                .with_system(reset_progression)
+                .with_system(gain_experience)
+                .with_system(setup_sandbox)
+                .with_system(setup_escort)
+                .with_system(
+                    walk_pilgrims
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    hurt_pilgrims
+                        .after(Movement::Monster),
+                )
+                .with_system(update_pilgrim_bars)
+                .with_system(hold_sandbox)
+                .with_system(announce_bosses)
+                .with_system(announce_milestones)
+                .with_system(update_feed)
+                .with_system(attach_health_bars)
+                .with_system(update_health_bars)
+                .with_system(
+                    anchor_to_world
+                        .after(Movement::Spread),
+                )
+                .with_system(sandbox_input)
+                .with_system(
+                    strike_dummies
+                        .after(IndexMonsters),
+                )
+                .with_system(change_weather)
+                .with_system(refresh_effective_stats)
+                .with_system(warn_low_health)
+                .with_system(track_run_time)
+                .with_system(
+                    play_death
+                        .label(PlayDeath)
+                        .after(Movement::Input)
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    record_run
+                        .label(RecordRun)
+                        .after(PlayDeath),
+                )
+                .with_system(
+                    record_score
+                        .after(PlayDeath)
+                        .before(RecordRun),
+                )
+                .with_system(
+                    record_telemetry
+                        .after(PlayDeath)
+                        .before(RecordRun),
+                )
+                .with_system(reset_ghost)
+                .with_system(build_arena)
+                .with_system(
+                    record_ghost
+                        .after(PlayDeath),
+                )
+                .with_system(
+                    replay_ghost
+                        .after(Movement::Spread),
+                )
+                .with_system(
+                    monster_speed
+                        .before(Movement::Player),
+                )
+                .with_system(
+                    frame_players
+                        .after(Movement::Spread),
+                )
+                .with_system(enter_photo_mode)
+                .with_system(pause_game)
+                .with_system(attach_autopilot)
+                .with_system(stop_demo)
+                .with_system(return_to_menu),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::LevelUp)
+                .with_system(spawn_level_up_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelUp)
+                .with_system(navigate_menus)
+                .with_system(level_up_input),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::LevelUp)
+                .with_system(despawn_level_up_screen),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Shop)
+                .with_system(spawn_shop_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Shop)
+                .with_system(navigate_menus)
+                .with_system(shop_input),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Shop)
+                .with_system(despawn_shop_screen),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Paused)
+                .with_system(spawn_pause_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Paused)
+                .with_system(navigate_menus)
+                .with_system(resume_game)
+                .with_system(copy_seed),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Paused)
+                .with_system(despawn_pause_screen),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::PhotoMode)
+                .with_system(hide_hud),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::PhotoMode)
+                .with_system(photo_camera)
+                .with_system(photo_controls),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::PhotoMode)
+                .with_system(show_hud),
+        )
+        .add_system(retry_missing_sprites)
+        .add_system(refresh_sprite_atlas)
+        .add_system(scroll_ground)
+        .add_system(highlight_focus)
+        .add_system(tween_transforms)
+        .add_system(tween_sprite_colors)
+        .add_system(tween_ui_colors)
+        .add_system(tween_text_colors)
+        .add_system(stream_decorations)
+        .insert_resource(GameAssets::new(args.procedural_sprites))
+        .insert_resource(Stages::load())
+        .insert_resource(Bindings::load())
+        .insert_resource(Leaderboard::load())
+        .insert_resource(Telemetry::load())
+        .insert_resource(Offers::load())
+        .insert_resource(Adaptive::load())
+        .insert_resource(Pressure::default())
+        .insert_resource(PlayerCount(1))
+        .insert_resource(DemoMode::new())
+        .insert_resource(HiddenHud::default())
+        .insert_resource(CameraFocus::default())
+        .insert_resource(BossSchedule::default())
+        .insert_resource(Director::new())
+        .insert_resource(WorldClock::default())
+        .insert_resource(Weather::new())
         .insert_resource(MonsterStats::default())
+        .insert_resource(SpawnCount::default())
+        .insert_resource(RunStats::default())
         .insert_resource(MonsterSpawnTimer::new())
         .insert_resource(BlastSpawnTimer::new())
+        .insert_resource(BlastVolleys::default())
+        .insert_resource(Lifesteal::default())
+        .insert_resource(Purse::default())
+        .insert_resource(Threat::default())
+        .insert_resource(Arena::default())
+        .insert_resource(MerchantVisits::default())
+        .insert_resource(FlowField::new())
+        .insert_resource(SimulationLod::default())
+        .insert_resource(TimeScale(1.0))
+        .insert_resource(SpatialIndex::default())
+        .insert_resource(WeaponAim::default())
+        .insert_resource(ChosenAbility(Ability::Shockwave))
+        .insert_resource(if args.manual_fire {
+            FireMode::Manual
+        }
+        else {
+            FireMode::Auto
+        })
+        .insert_resource(if args.click_to_move {
+            MoveScheme::Mouse
+        }
+        else {
+            MoveScheme::Keys
+        })
+        .insert_resource(ClickTarget::default())
+        .insert_resource(Ghosts::default())
+        .insert_resource(Handling::default())
+        .insert_resource(if args.eight_way {
+            Directions::EightWay
+        }
+        else {
+            Directions::Free
+        })
+        .insert_resource(RemainsLimit::default())
+        .insert_non_send_resource(Clipboard::new())
+        .insert_resource(args.frame_cap)
+        .insert_resource(TouchControls::default())
+        .insert_resource(RunDefaults{
+            weapon: args.weapon,
+            difficulty: args.difficulty,
+            seed: args.seed,
+            curses: Curses::default(),
+        })
+        .insert_resource(Curses::default())
+        .insert_resource(Daily::default())
+        .insert_resource(Progression::default())
+        .insert_resource(Sandbox::default())
+        .insert_resource(Escort::default())
+        .insert_resource(ParticlePool::default())
+        .insert_resource(WorldSeed::default())
+        .insert_resource(Loadout(vec![args.weapon]))
+        .insert_resource(args.difficulty)
+        .insert_resource(DecorationChunks::default())
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
-                .with_system(position_translation),
+                .with_system(position_translation)
+                .with_system(sort_by_y)
+                .with_system(squash_and_stretch.before(TransformSystem::TransformPropagate)),
         )
+        .add_system_to_stage(CoreStage::PreUpdate, unsquash)
+        .add_system_to_stage(CoreStage::PreUpdate, number_monsters)
         .add_event::<NewGameEvent>()
-        .run();
+        .add_event::<GameOverEvent>()
+        .add_event::<StatsChangedEvent>()
+        .add_event::<Announcement>()
+        .add_event::<MonsterHurtEvent>()
+        .add_event::<DamageDealtEvent>()
+        .add_event::<CoinDrop>()
+        .add_event::<ParticleBurst>()
+        .add_event::<LeaveDecal>()
+        .add_event::<MenuAction>()
+        .add_event::<TouchTap>();
+
+    if args.instanced_monsters {
+        app.add_plugin(instancing::InstancedMonstersPlugin);
+    }
+
+    // Clips are encoded on a thread of their own and the frame cap sleeps,
+    // neither of which browsers allow, and the page sets its own icon
+    #[cfg(not(target_arch = "wasm32"))]
+    app
+        .add_system(record_clip)
+        .add_system(capture_hotkeys)
+        .add_system_to_stage(CoreStage::Last, limit_frame_rate)
+        .insert_resource(ClipBuffer::new())
+        .add_startup_system(set_window_icon);
+
+    // Rapier settles contacts in place of spread_collision when it's built in
+    #[cfg(not(feature = "rapier"))]
+    app.add_system_set(
+        SystemSet::on_update(AppState::Playing)
+            .with_system(
+                spread_collision
+                    .label(Movement::Spread)
+                    .after(Movement::Damage),
+            ),
+    );
+    #[cfg(feature = "rapier")]
+    app.add_plugin(rapier::RapierPlugin);
+
+    #[cfg(debug_assertions)]
+    app.add_plugin(ray_probe::RayProbePlugin);
+
+    #[cfg(feature = "lan")]
+    app.add_plugin(net::LanPlugin);
+
+    #[cfg(feature = "discord")]
+    app.add_plugin(discord::DiscordPlugin);
+    #[cfg(feature = "online")]
+    app.add_plugin(online::OnlinePlugin);
+    #[cfg(feature = "scripting")]
+    app.add_plugin(scripting::ScriptingPlugin);
+
+    app.run();
 }
 
 fn setup(
     mut commands: Commands,
-    mut new_game_writer: EventWriter<NewGameEvent>,
 ) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-
-    new_game_writer.send(NewGameEvent);
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
 }
 
 fn new_game(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    sprites: Res<GameAssets>,
     mut new_game_reader: EventReader<NewGameEvent>,
     players: Query<Entity, With<Player>>,
     monsters: Query<Entity, With<Monster>>,
     blasts: Query<Entity, With<Blast>>,
+    obstacles: Query<Entity, With<Obstacle>>,
     mut monster_stats: ResMut<MonsterStats>,
     mut monster_spawn_timer: ResMut<MonsterSpawnTimer>,
     mut blast_spawn_timer: ResMut<BlastSpawnTimer>,
+    mut world_seed: ResMut<WorldSeed>,
+    mut stages: ResMut<Stages>,
+    mut loadout: ResMut<Loadout>,
+    mut difficulty: ResMut<Difficulty>,
+    mut boss_schedule: ResMut<BossSchedule>,
+    player_count: Res<PlayerCount>,
 ) {
     // Only fire if event was sent
-    if !new_game_reader.iter().next().is_some() {
-        return;
+    let setup = if let Some(event) = new_game_reader.iter().last() {
+        *event
     }
+    else {
+        return
+    };
 
     // Clear state
     for player in players.iter() {
@@ -275,22 +1628,100 @@ fn new_game(
     for blast in blasts.iter() {
         commands.entity(blast).despawn();
     }
+    for obstacle in obstacles.iter() {
+        commands.entity(obstacle).despawn();
+    }
     monster_stats.clear();
+    boss_schedule.clear();
+
+    // Apply the run's setup
+    stages.selected = setup.stage;
+    loadout.0 = vec![setup.weapon];
+    *difficulty = setup.difficulty;
+    world_seed.value = setup.seed;
+    let mut rng = StdRng::seed_from_u64(world_seed.value);
+
+    // Create players side by side around the origin
+    let first_x = -PLAYER_SPACING * (player_count.0 - 1) as f32 / 2.0;
+    for index in 0..player_count.0 {
+        let tint = if index == 0 {
+            Color::WHITE
+        }
+        else {
+            Color::rgb(0.6, 0.8, 1.0)
+        };
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.player(),
+                    color: tint,
+                    ..Default::default()
+                },
+                transform: Transform {
+                    scale: Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, 1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Player{
+                index,
+            })
+            .insert(SortByY{ layer: 0.0 })
+            .insert(Position::new(Vec2::new(first_x + PLAYER_SPACING * index as f32, 0.0)))
+            .insert(Velocity::new(Vec2::ZERO, PLAYER_SPEED))
+            .insert(Momentum::default())
+            .insert(
+                Body::new(Circle::new(PLAYER_BODY_RADIUS), Some(PLAYER_BODY_MASS))
+                    .on_layers(Layers::PLAYER, Layers::PLAYER.with(Layers::GROUND).with(Layers::OBSTACLE))
+                    .firm_when_still()
+                    .pusher(),
+            )
+            .insert(Health::new(PLAYER_MAX_HEALTH));
+    }
 
-    // Create player
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("player.png"),
-            transform: Transform {
-                scale: Vec3::new(4.0, 4.0, 1.0),
+    // Scatter the stage's obstacles, keeping the starting area clear
+    let layout = &stages.current().obstacles;
+    for _ in 0..layout.count {
+        let distance = layout.clear_radius + rng.gen::<f32>() * (layout.spawn_radius - layout.clear_radius);
+        let angle = rng.gen::<f32>() * TAU;
+        let position = Vec2::new(angle.cos(), angle.sin()) * distance;
+        let (r, g, b) = layout.color;
+
+        // Drawn as a rectangle around the shape, turned to match capsules
+        let (shape, core_size, heading) = match layout.shape {
+            ObstacleShape::Circle => (Shape::Point, Vec2::ZERO, 0.0),
+            ObstacleShape::Capsule { length } => {
+                let heading = rng.gen::<f32>() * TAU;
+                let half = Vec2::new(heading.cos(), heading.sin()) * length / 2.0;
+                (Shape::Segment(half), Vec2::new(length, 0.0), heading)
+            }
+            ObstacleShape::Box { width, height } => {
+                let size = Vec2::new(width, height);
+                (Shape::Rect(size / 2.0), size, 0.0)
+            }
+        };
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite{
+                    color: Color::rgb(r, g, b),
+                    custom_size: Some(core_size + Vec2::splat(layout.radius * 2.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_rotation(Quat::from_rotation_z(heading)),
                 ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Player)
-        .insert(Position::new(Vec2::ZERO))
-        .insert(Velocity::new(Vec2::ZERO, PLAYER_SPEED))
-        .insert(Body::new(Circle::new(PLAYER_BODY_RADIUS), None));
+            })
+            .insert(Obstacle)
+            .insert(SortByY{ layer: 0.0 })
+            .insert(Position::new(position))
+            .insert(
+                Body::new(Circle::new(layout.radius), None)
+                    .shaped(shape)
+                    .on_layers(Layers::OBSTACLE, Layers::PLAYER.with(Layers::GROUND)),
+            );
+    }
 
     // Reset and unpause spawn timers
     monster_spawn_timer.0.reset();
@@ -306,47 +1737,167 @@ fn random_unit() -> Vec2 {
     Vec2::new(x, y).normalize_or_zero()
 }
 
+/// Number monsters in the order they spawned, from zero each run. Runs
+/// before the frame's updates, so monsters spawned last frame have a number
+/// by the time they steer.
+fn number_monsters(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut count: ResMut<SpawnCount>,
+    monsters: Query<Entity, (With<Monster>, Without<SpawnNumber>)>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        count.0 = 0;
+    }
+
+    for entity in monsters.iter() {
+        commands.entity(entity).insert(SpawnNumber(count.0));
+        count.0 += 1;
+    }
+}
+
+/// A slow heading for an idle monster. It changes every few seconds and is
+/// the same for the same seed, monster and time.
+fn wander_direction(seed: u64, number: SpawnNumber, seconds: f32) -> Vec2 {
+    let period = (seconds / MONSTER_WANDER_SECONDS) as u64;
+    let mut rng = StdRng::seed_from_u64(seed ^ number.0.rotate_left(32) ^ period);
+    let angle = rng.gen::<f32>() * TAU;
+
+    Vec2::new(angle.cos(), angle.sin()) * MONSTER_WANDER_PACE
+}
+
 fn spawn_blast(
     time: Res<Time>,
+    controls: Controls,
     mut spawn_timer: ResMut<BlastSpawnTimer>,
+    mut manual_cooldowns: Local<ManualCooldowns>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    player: Query<&Position, With<Player>>,
+    sprites: Res<GameAssets>,
+    players: Query<(&Player, &Position, &EffectiveStats, Option<&Autopilot>)>,
+    loadout: Res<Loadout>,
+    fire_mode: Res<FireMode>,
+    aiming: BlastAiming,
+    progression: Res<Progression>,
+    mut volleys: ResMut<BlastVolleys>,
 ) {
     spawn_timer.0.tick(time.delta());
-    if !spawn_timer.0.just_finished() {
-        return;
-    }
 
-    let target = if let Some(p) = player.iter().next() {
-        p.current
+    // Every player gets their own blast from each weapon
+    for (player, p, stats, autopilot) in players.iter() {
+        // Fire on the shared timer, or on the player's key once their own
+        // cooldown is up
+        let does_fire = if *fire_mode == FireMode::Manual && autopilot.is_none() {
+            let cooldown = manual_cooldowns.for_player(player.index);
+            let is_ready = cooldown.tick(time.delta()).finished();
+            if is_ready && controls.just_pressed(player.index, Action::Fire) {
+                cooldown.reset();
+                true
+            }
+            else {
+                false
+            }
+        }
+        else {
+            spawn_timer.0.just_finished()
+        };
+        if !does_fire {
+            continue;
+        }
+
+        for &weapon in &loadout.0 {
+            let radius = stats.blast_radius * weapon.blast_radius_factor();
+            let target = aiming.target(weapon, player.index, p.current, radius);
+            spawn_blast_at(&mut commands, &sprites, player.index, weapon, radius, p.current, target);
+
+            // The rest of a volley rings the player, starting from the aimed
+            // blast's side and going off one after another
+            let count = progression.blast_count();
+            let facing = (target - p.current).try_normalize().unwrap_or(Vec2::X);
+            for i in 1..count {
+                let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                volleys.0.push(QueuedBlast{
+                    player: player.index,
+                    weapon,
+                    delay: Timer::from_seconds(BLAST_STAGGER_SECONDS * i as f32, false),
+                    radius,
+                    origin: p.current,
+                    target: p.current + bevy::math::Mat2::from_angle(angle) * facing * radius,
+                });
+            }
+        }
     }
-    else {
-        return
-    };
+}
 
+fn spawn_blast_at(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    player: usize,
+    weapon: Weapon,
+    radius: f32,
+    origin: Vec2,
+    target: Vec2,
+) -> Entity {
+    spawn_blast_with(commands, sprites, Blast::new(radius, origin).fired_by(player, weapon), target)
+}
+
+fn spawn_blast_with(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    blast: Blast,
+    target: Vec2,
+) -> Entity {
+    let weapon = blast.weapon;
     commands
-        .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("blast.png"),
-            sprite: Sprite{
-                custom_size: Some(Vec2::splat(BLAST_RADIUS * 2.0)),
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.blast(),
+                custom_size: Some(Vec2::splat(blast.circle.radius * 2.0)),
                 ..Default::default()
             },
             ..Default::default()
         })
-        .insert(Blast::new())
-        .insert(Position::new(target));
+        .insert(blast)
+        .insert(Position::new(target))
+        .insert(Tween::new(
+            TweenTarget::SpriteColor { from: weapon.tint(), to: *weapon.tint().set_a(0.0) },
+            Ease::QuadIn,
+            BLAST_LIFETIME_SECONDS,
+        ))
+        .id()
+}
+
+/// Set off the queued blasts whose turn has come. A new run drops any left.
+fn detonate_volleys(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut volleys: ResMut<BlastVolleys>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        volleys.0.clear();
+        return;
+    }
+
+    volleys.0.retain_mut(|blast| {
+        if !blast.delay.tick(time.delta()).finished() {
+            return true;
+        }
+        spawn_blast_at(&mut commands, &sprites, blast.player, blast.weapon, blast.radius, blast.origin, blast.target);
+        false
+    });
 }
 
 fn blast_lifetime(
     time: Res<Time>,
     mut commands: Commands,
-    mut blasts: Query<(&mut Blast, &mut Sprite, Entity)>,
+    mut blasts: Query<(&mut Blast, Entity)>,
 ) {
     for mut blast in blasts.iter_mut() {
         blast.0.lifetime.tick(time.delta());
         if blast.0.lifetime.just_finished() {
-            commands.entity(blast.2).despawn();
+            commands.entity(blast.1).despawn();
             continue;
         }
     }
@@ -356,69 +1907,178 @@ fn spawn_monster(
     time: Res<Time>,
     mut spawn_timer: ResMut<MonsterSpawnTimer>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    player: Query<&Position, With<Player>>,
+    sprites: Res<GameAssets>,
+    players: Query<&Position, With<Player>>,
     mut monster_stats: ResMut<MonsterStats>,
+    stages: Res<Stages>,
+    mut threat: ResMut<Threat>,
+    arena: Res<Arena>,
+    mut horde_candidates: HordeCandidates,
 ) {
     spawn_timer.0.tick(time.delta());
     if !spawn_timer.0.just_finished() {
         return;
     }
 
-    if monster_stats.count() >= MONSTER_SPAWN_LIMIT {
-        return;
-    }
+    // Roll for a kind that fits what's left of the budget. Past it, new
+    // monsters join a horde instead of adding entities
+    let kind = (0..MONSTER_SPAWN_ROLLS)
+        .map(|_| stages.current().roll_monster())
+        .find(|kind| threat.can_afford(kind.threat()));
+    let kind = match kind {
+        Some(kind) => kind,
+        None => {
+            if let Some(kind) = absorb_spawn(&mut commands, &mut horde_candidates) {
+                monster_stats.record_absorption(kind);
+            }
+            return;
+        }
+    };
 
-    let target = if let Some(p) = player.iter().next() {
-        p.current
+    let target = if let Some(p) = random_player(&player_positions(&players)) {
+        p
     }
     else {
         return
     };
 
-    let direction = random_unit();
-    let position = target + (direction * MONSTER_SPAWN_DISTANCE);
+    // Bounded arenas send monsters in through their gates
+    let position = arena
+        .spawn_point()
+        .unwrap_or_else(|| target + random_unit() * MONSTER_SPAWN_DISTANCE);
 
-    commands
-        .spawn_bundle(SpriteBundle {
-            texture: asset_server.load("monster.png"),
-            transform: Transform {
-                scale: Vec3::new(2.0, 2.0, 1.0),
-                ..Default::default()
-            },
+    spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+
+    monster_stats.record_spawn(kind);
+    threat.spend(kind.threat());
+}
+
+fn spawn_monster_kind(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    kind: MonsterKind,
+    position: Vec2,
+    scale: f32,
+) -> Entity {
+    let radius = kind.radius() * scale;
+    let (layer, mask) = kind.collision_layers();
+
+    // Starts invisible and grows in while spawning
+    let mut monster = commands.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: sprites.atlas(),
+        sprite: TextureAtlasSprite{
+            index: sprites.monster(),
+            color: *kind.tint().set_a(0.0),
             ..Default::default()
-        })
+        },
+        transform: Transform {
+            translation: Vec3::new(0.0, 0.0, kind.depth()),
+            scale: Vec3::new(0.0, 0.0, 1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    monster
         .insert(Monster)
+        .insert(Spawning(Timer::from_seconds(MONSTER_SPAWN_IN_SECONDS, false)))
+        .insert(kind)
+        .insert(SortByY{ layer: kind.depth() })
         .insert(Position::new(position))
-        .insert(Velocity::new(Vec2::ZERO, MONSTER_SPEED))
-        .insert(Body::new(Circle::new(MONSTER_BODY_RADIUS), Some(MONSTER_BODY_MASS)));
+        .insert(Velocity::new(Vec2::ZERO, kind.speed()))
+        .insert(Body::new(Circle::new(radius), Some(kind.mass() * scale * scale)).on_layers(layer, mask));
+
+    match kind.behavior() {
+        MonsterKind::Shielded => {
+            monster.insert(Shield::new());
+        }
+        MonsterKind::Burrower => {
+            monster.insert(Burrow::new());
+        }
+        MonsterKind::Spitter => {
+            monster.insert(SpitCooldown::new());
+        }
+        _ => {}
+    }
 
-    monster_stats.spawned += 1;
+    monster.id()
+}
+
+fn monster_spawn_in(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut monsters: Query<(Entity, &mut Spawning, &Body, &mut Transform, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut spawning, body, mut transform, mut sprite) in monsters.iter_mut() {
+        spawning.0.tick(time.delta());
+        let progress = spawning.0.percent();
+
+        // Follow the body so hordes that grow mid spawn stay in step
+        let sprite_scale = 2.0 * body.circle.radius / MONSTER_BODY_RADIUS * progress;
+        transform.scale = Vec3::new(sprite_scale, sprite_scale, 1.0);
+        sprite.color.set_a(progress);
+
+        if spawning.0.finished() {
+            commands.entity(entity).remove::<Spawning>();
+        }
+    }
 }
 
 fn movement(
     time: Res<Time>,
-    mut query: Query<(&Velocity, &mut Position)>,
+    time_scale: Res<TimeScale>,
+    handling: Res<Handling>,
+    directions: Res<Directions>,
+    mut query: Query<(&Velocity, &mut Position, Option<&mut Momentum>)>,
 ) {
-    for (v, mut p) in query.iter_mut() {
+    let seconds = time.delta_seconds() * time_scale.0;
+
+    for (v, mut p, momentum) in query.iter_mut() {
+        // Players ease toward the velocity they ask for, in one of the
+        // directions allowed
+        if let Some(mut momentum) = momentum {
+            let wanted = directions.snap(v.change_for_seconds(1.0));
+            let velocity = momentum.steer(wanted, v.speed, &handling, seconds);
+            if velocity != Vec2::ZERO {
+                p.apply(velocity * seconds);
+            }
+            else {
+                p.change = Vec2::ZERO;
+            }
+            continue;
+        }
+
+        // Standing still, so last frame's move mustn't linger as a push
         if v.is_zero() {
+            p.change = Vec2::ZERO;
             continue;
         }
 
-        p.apply(v.change_for_seconds(time.delta_seconds()));
+        p.apply(v.change_for_seconds(seconds));
+    }
+}
+
+fn monster_speed(
+    clock: Res<WorldClock>,
+    weather: Res<Weather>,
+    difficulty: Res<Difficulty>,
+    curses: Res<Curses>,
+    mut monsters: Query<(&MonsterKind, &mut Velocity), With<Monster>>,
+) {
+    let factor = clock.monster_speed_factor()
+        * weather.monster_speed_factor()
+        * difficulty.monster_speed_factor()
+        * curses.monster_speed_factor();
+
+    for (kind, mut v) in monsters.iter_mut() {
+        v.speed = kind.speed() * factor;
     }
 }
 
 fn position_translation(
-    player: Query<&Position, With<Player>>,
+    focus: Res<CameraFocus>,
     mut query: Query<(&Position, &mut Transform)>,
 ) {
-    let offset = if let Some(p) = player.iter().next() {
-        p.current
-    }
-    else {
-        return
-    };
+    let offset = focus.center;
 
     for (p, mut t) in query.iter_mut() {
         t.translation.x = p.current.x - offset.x;
@@ -426,104 +2086,313 @@ fn position_translation(
     }
 }
 
-fn monster_direction(
-    player: Query<&Position, With<Player>>,
-    mut monsters: Query<(&Position, &mut Velocity), With<Monster>>,
-) {
-    let target = if let Some(p) = player.iter().next() {
-        p.current
-    }
-    else {
-        return
-    };
+/// Monsters picking a heading, with what decides how often and where they
+/// wander.
+type SteeredMonsters<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Position,
+        &'static MonsterKind,
+        &'static mut Velocity,
+        Option<&'static Distant>,
+        Option<&'static SpawnNumber>,
+    ),
+>;
 
-    for (p, mut v) in monsters.iter_mut() {
-        let difference = target - p.current;
-        v.direction = difference.normalize_or_zero();
-    }
-}
-
-fn player_direction(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut velocities: Query<&mut Velocity, With<Player>>,
+fn monster_direction(
+    flow_field: Res<FlowField>,
+    lod: Res<SimulationLod>,
+    world_seed: Res<WorldSeed>,
+    run_stats: Res<RunStats>,
+    players: Query<&Position, With<Player>>,
+    pilgrims: Query<&Position, With<Pilgrim>>,
+    mut monsters: SteeredMonsters,
 ) {
-    // Pull one player velocity out of the query
-    if let Some(mut v) = velocities.iter_mut().next() {
-        // Start with no direction
-        let mut direction = Vec2::ZERO;
+    // A pilgrim draws monsters like another player
+    let mut targets = player_positions(&players);
+    targets.extend(pilgrims.iter().map(|p| p.current));
 
-        // Read horizontal direction, preferring right
-        if keyboard_input.pressed(KeyCode::Right) {
-            direction.x = 1.0;
-        }
-        else if keyboard_input.pressed(KeyCode::Left) {
-            direction.x = -1.0;
+    for (entity, p, kind, mut v, distant, number) in monsters.iter_mut() {
+        // Distant monsters keep their heading between turns
+        if distant.is_some() && !lod.is_turn_of(entity) {
+            continue;
         }
 
-        // Read vertical direction, preferring up
-        if keyboard_input.pressed(KeyCode::Up) {
-            direction.y = 1.0;
+        // Wander until a player comes within range
+        let is_aggro = nearest_player(&targets, p.current)
+            .is_some_and(|nearest| nearest.distance_squared(p.current) <= kind.aggro_radius().powi(2));
+        if !is_aggro {
+            if let Some(&number) = number {
+                v.direction = wander_direction(world_seed.value, number, run_stats.duration_seconds());
+            }
+            continue;
         }
-        else if keyboard_input.pressed(KeyCode::Down) {
-            direction.y = -1.0;
+
+        // Follow the flow field around obstacles, heading straight for the
+        // nearest player when close or flying over them
+        let around = if kind.is_flying() {
+            None
         }
+        else {
+            flow_field.direction_at(p.current)
+        };
+        v.direction = match around {
+            Some(direction) => direction,
+            None => match nearest_player(&targets, p.current) {
+                Some(nearest) => (nearest - p.current).normalize_or_zero(),
+                None => Vec2::ZERO,
+            },
+        };
+    }
+}
 
-        // Set normalized (or zero) direction
-        v.direction = direction.normalize_or_zero();
+fn player_direction(
+    controls: Controls,
+    mut velocities: Query<(&Player, &mut Velocity), Without<Autopilot>>,
+) {
+    // Each player reads its own bindings
+    for (player, mut v) in velocities.iter_mut() {
+        v.direction = controls.direction(player.index);
     }
 }
 
+/// Monsters that might land a hit, along with what kind they are.
+type Attackers<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Body, &'static Position, Option<&'static Spawning>, Entity, &'static MonsterKind),
+    With<Monster>,
+>;
+
 fn damage_collision(
-    players: Query<(&Body, &Position), With<Player>>,
-    monsters: Query<(&Body, &Position), With<Monster>>,
-    mut new_game_writer: EventWriter<NewGameEvent>,
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    index: Res<SpatialIndex>,
+    progression: Res<Progression>,
+    mut players: Query<(&Body, &Position, Entity, &mut Health, &Player)>,
+    monsters: Attackers,
+    submerged: Query<(), With<Submerged>>,
+    dying: Query<(), With<Dying>>,
+    mut run_stats: ResMut<RunStats>,
 ) {
-    for player in players.iter() {
-        for monster in monsters.iter() {
-            let (did_collide, _) = collide_circles(
-                (&player.0.circle, player.1.current),
-                (&monster.0.circle, monster.1.current),
-            );
+    // The run is already ending
+    if !dying.is_empty() {
+        return;
+    }
+
+    for mut player in players.iter_mut() {
+        player.3.tick(&time);
+        if !player.3.can_be_hurt() {
+            continue;
+        }
+
+        // Monsters still spawning in or submerged are harmless
+        let attackers: Vec<(Vec2, MonsterKind)> = monsters
+            .iter()
+            .filter(|m| m.2.is_none() && submerged.get(m.3).is_err())
+            .filter(|monster| {
+                let (did_collide, _) = collide_circles(
+                    (&player.0.circle, player.1.current),
+                    (&monster.0.circle, monster.1.current),
+                );
+                did_collide
+            })
+            .map(|monster| (monster.1.current, *monster.4))
+            .collect();
+
+        let kind = match attackers.first() {
+            Some(&(_, kind)) => kind,
+            None => continue,
+        };
+
+        run_stats.record_damage(MONSTER_CONTACT_DAMAGE);
+        if player.3.hurt(MONSTER_CONTACT_DAMAGE) {
+            commands.entity(player.2).insert(Dying::new());
+            run_stats.record_death(kind.name().to_string());
+            return;
+        }
 
-            if did_collide {
-                new_game_writer.send(NewGameEvent);
-                return;
+        // Thorns strike back at whatever landed the hit, and now and then
+        // throw back everything close
+        if let Some(pulse_chance) = progression.thorns_pulse_chance() {
+            for (attacker, _) in attackers {
+                let thorns = Blast::new(THORNS_RADIUS, player.1.current)
+                    .fired_by(player.4.index, Weapon::Blast)
+                    .struck_by_thorns();
+                spawn_blast_with(&mut commands, &sprites, thorns, attacker);
+            }
+            if rand::random::<f32>() < pulse_chance {
+                knock_back(&mut commands, &index, player.1.current, THORNS_PULSE_RADIUS, THORNS_PULSE_SPEED);
             }
         }
     }
 }
 
+/// Monsters blasts can land on, with the hordes and fuses some carry.
+type BlastTargets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Body,
+        &'static Position,
+        &'static MonsterKind,
+        Entity,
+        Option<&'static mut Horde>,
+        Option<&'static Fuse>,
+    ),
+>;
+
 fn blast_collision(
     mut commands: Commands,
-    blasts: Query<(&Blast, &Position)>,
-    monsters: Query<(&Body, &Position, Entity), With<Monster>>,
+    sprites: Res<GameAssets>,
+    mut blasts: Query<(&mut Blast, &Position, Entity)>,
+    mut monsters: BlastTargets,
+    raised: Query<(), With<Raised>>,
+    mut shields: Query<(&mut Shield, &Velocity)>,
     mut monster_stats: ResMut<MonsterStats>,
+    mut hurt_writer: EventWriter<MonsterHurtEvent>,
+    mut bursts: EventWriter<ParticleBurst>,
+    mut decals: EventWriter<LeaveDecal>,
+    mut damage_writer: EventWriter<DamageDealtEvent>,
+    mut coin_writer: EventWriter<CoinDrop>,
+    progression: Res<Progression>,
 ) {
-    for blast in blasts.iter() {
-        for monster in monsters.iter() {
+    let execute_threshold = progression.execute_threshold();
+
+    // Monsters already killed this frame, and bombers whose fuse just ran
+    // out, are gone and can't be blasted again
+    let mut spent = Vec::new();
+
+    for mut blast in blasts.iter_mut() {
+        for mut monster in monsters.iter_mut() {
+            if spent.contains(&monster.3) || monster.5.is_some_and(|fuse| fuse.is_spent()) {
+                continue;
+            }
+
             let (did_collide, _) = collide_circles(
                 (&blast.0.circle, blast.1.current),
                 (&monster.0.circle, monster.1.current),
             );
+            if !did_collide || !blast.0.mask.intersects(monster.0.layer) {
+                continue;
+            }
+
+            // Shields soak up blasts fired at them from the front
+            if let Ok((mut shield, v)) = shields.get_mut(monster.3) {
+                if shield.blocks(blast.2, monster.1.current, v.direction, blast.0.origin) {
+                    continue;
+                }
+            }
+
+            // Each blast lands on a monster only once
+            if blast.0.shrugged_off.contains(&monster.3)
+                || monster.4.as_ref().is_some_and(|horde| horde.was_hit_by(blast.2))
+            {
+                continue;
+            }
+
+            // Resistant kinds shrug off some blasts and weak ones take extra hits
+            let hits = damage::roll_hits(monster.2.damage_taken(blast.0.weapon.damage_type()));
+            if hits == 0 {
+                blast.0.shrugged_off.push(monster.3);
+                continue;
+            }
+            if let Some(player) = blast.0.shooter {
+                damage_writer.send(DamageDealtEvent{
+                    player,
+                    amount: BLAST_HIT_DAMAGE * hits as f32,
+                });
+            }
 
-            if did_collide {
-                commands.entity(monster.2).despawn();
-                monster_stats.killed += 1;
+            // A horde takes several blasts and counts all its members. One
+            // left weak enough is executed outright
+            let mut is_executed = false;
+            let members = match monster.4.as_mut() {
+                Some(horde) => {
+                    if !horde.hit(blast.2, hits) {
+                        if (horde.health() as f32) >= execute_threshold * horde.max_health() as f32 {
+                            hurt_writer.send(MonsterHurtEvent(monster.3));
+                            continue;
+                        }
+                        is_executed = true;
+                    }
+
+                    // Tough monsters pay out for every blast they took
+                    coin_writer.send(CoinDrop{
+                        position: monster.1.current,
+                        count: horde.max_health(),
+                    });
+                    horde.members()
+                }
+                None => 1,
+            };
+
+            commands.entity(monster.3).despawn();
+            spent.push(monster.3);
+            monster_stats.record_kills(blast.0.credited_weapon(), *monster.2, members);
+            if is_executed {
+                monster_stats.record_execution(*monster.2, members);
+                bursts.send(ParticleBurst::execution(monster.1.current));
+            }
+            bursts.send(ParticleBurst::ichor(monster.1.current, *monster.2));
+            decals.send(LeaveDecal::splatter(monster.1.current, *monster.2));
+            if raised.get(monster.3).is_err() {
+                spawn_corpse(&mut commands, &sprites, *monster.2, monster.1.current);
+            }
+
+            // Blasted bombers go off where they stand
+            if monster.2.behavior() == MonsterKind::Bomber {
+                spawn_explosion(&mut commands, &sprites, monster.1.current);
             }
         }
     }
 }
 
+/// Bodies that push each other apart, and whether they're far enough out to
+/// be simulated less.
+type SpreadBodies<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Body, &'static mut Position, Option<&'static Distant>, Entity),
+>;
+
+#[cfg_attr(feature = "rapier", allow(dead_code))]
 fn spread_collision(
-    mut bodies: Query<(&mut Body, &mut Position)>,
+    lod: Res<SimulationLod>,
+    mut bodies: SpreadBodies,
 ) {
+    // Anchor bodies that hold their ground while standing still
+    for (mut body, p, _, _) in bodies.iter_mut() {
+        if body.is_holding_firm(&p) {
+            body.collision.is_firm = true;
+        }
+    }
+
     // Detect collisions and accumulate displacements
     let mut combinations = bodies.iter_combinations_mut();
     while let Some([mut a, mut b]) = combinations.fetch_next() {
+        // Distant monsters are allowed to overlap each other, and only
+        // check for walls on their turn
+        if a.2.is_some() || b.2.is_some() {
+            let is_crowd = a.0.layer.intersects(Layers::MONSTERS) && b.0.layer.intersects(Layers::MONSTERS);
+            let is_waiting = (a.2.is_some() && !lod.is_turn_of(a.3)) || (b.2.is_some() && !lod.is_turn_of(b.3));
+            if is_crowd || is_waiting {
+                continue;
+            }
+        }
+
+        // Only bodies on each other's masks push each other around
+        if !a.0.collides_with(&b.0) {
+            continue;
+        }
+
         // Detect overlap
-        let (did_collide, overlap) = collide_circles(
-            (&a.0.circle, a.1.current),
-            (&b.0.circle, b.1.current),
+        let (did_collide, overlap) = collide_bodies(
+            (&a.0, a.1.current),
+            (&b.0, b.1.current),
         );
 
         // No work if no collision
@@ -537,6 +2406,17 @@ fn spread_collision(
             continue;
         }
 
+        // Pass along the part of each body's motion that drives into the other
+        let normal = overlap.normalize_or_zero();
+        let a_push = a.1.change.dot(-normal);
+        let b_push = b.1.change.dot(normal);
+        if let Some(b_mass) = b.0.mass.filter(|_| a_push > 0.0) {
+            b.0.collision.pressure += a.0.push_on(a.1.change, -normal, a_push, b_mass);
+        }
+        if let Some(a_mass) = a.0.mass.filter(|_| b_push > 0.0) {
+            a.0.collision.pressure += b.0.push_on(b.1.change, normal, b_push, a_mass);
+        }
+
         // Handle immovable a
         if a.0.mass.is_none() || a.0.collision.is_firm {
             b.0.collision.displacement = -overlap;
@@ -569,10 +2449,35 @@ fn spread_collision(
             body.1.apply_add(body.0.collision.displacement * COLLISION_DISPLACEMENT_FACTOR);
         }
 
+        // Firm bodies don't yield to pressure from the crowd behind
+        if body.0.collision.pressure != Vec2::ZERO && !body.0.collision.is_firm {
+            body.1.apply_add(body.0.collision.pressure);
+        }
+
         body.0.collision.clear();
     }
 }
 
+/// Like `collide_circles`, for bodies of any shape. Only plain circles
+/// collide with other shapes, since those are the only bodies that move.
+fn collide_bodies(
+    a: (&Body, Vec2),
+    b: (&Body, Vec2),
+) -> (bool, Vec2) {
+    // Test against the closest point of the other body's shape
+    match (a.0.shape, b.0.shape) {
+        (Shape::Point, shape) => collide_circles(
+            (&a.0.circle, a.1),
+            (&b.0.circle, shape.closest_point(b.1, a.1)),
+        ),
+        (shape, Shape::Point) => collide_circles(
+            (&a.0.circle, shape.closest_point(a.1, b.1)),
+            (&b.0.circle, b.1),
+        ),
+        _ => (false, Vec2::ZERO),
+    }
+}
+
 fn collide_circles(
     a: (&Circle, Vec2),
     b: (&Circle, Vec2),
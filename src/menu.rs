@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+
+use crate::daily::{daily_challenge, date_label, today, Daily};
+use crate::input::{Action, Controls};
+use crate::loading::GameAssets;
+use crate::nav::{FocusRing, Focusable, MenuAction, NAV_COLOR, NAV_FOCUSED_COLOR};
+use crate::stage::Stages;
+use crate::tween::{Ease, Tween, TweenTarget};
+use crate::{AppState, NewGameEvent, PlayerCount, RunDefaults, MAX_PLAYERS};
+
+
+const MENU_TITLE_SIZE: f32 = 72.0;
+const MENU_ENTRY_SIZE: f32 = 32.0;
+const MENU_HINT_SIZE: f32  = 18.0;
+
+const MENU_BACKDROP: Color     = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const MENU_FADE_SECONDS: f32 = 0.25;
+
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component)]
+pub struct PlayerCountText;
+
+fn player_count_label(count: usize) -> String {
+    format!("< Players: {} >", count)
+}
+
+pub fn menu_text(
+    value: &str,
+    font: &Handle<Font>,
+    font_size: f32,
+    color: Color,
+) -> TextBundle {
+    TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }
+}
+
+pub fn spawn_menu(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    stages: Res<Stages>,
+    daily: Res<Daily>,
+    player_count: Res<PlayerCount>,
+) {
+    let font = assets.font();
+
+    // Every stage, then the daily challenge
+    let count = stages.list.len() + 1;
+    let cursor = if daily.is_selected { count - 1 } else { stages.selected };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(MenuRoot)
+        .insert(FocusRing::new(cursor, count))
+        .insert(Tween::new(
+            TweenTarget::UiColor { from: Color::NONE, to: MENU_BACKDROP },
+            Ease::Linear,
+            MENU_FADE_SECONDS,
+        ))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text("SWARM", &font, MENU_TITLE_SIZE, Color::WHITE));
+
+            // The daily challenge comes after every stage
+            let daily_name = format!("Daily {}", date_label(today()));
+            let names = stages.list.iter().map(|stage| stage.name.as_str()).chain([daily_name.as_str()]);
+            for (index, name) in names.enumerate() {
+                let color = if index == cursor {
+                    NAV_FOCUSED_COLOR
+                }
+                else {
+                    NAV_COLOR
+                };
+
+                parent
+                    .spawn_bundle(menu_text(name, &font, MENU_ENTRY_SIZE, color))
+                    .insert(Focusable(index));
+            }
+
+            parent
+                .spawn_bundle(menu_text(&player_count_label(player_count.0), &font, MENU_ENTRY_SIZE, Color::WHITE))
+                .insert(PlayerCountText);
+
+            parent.spawn_bundle(menu_text("Up/Down to choose a stage or the daily, Left/Right for players, Enter to start, T to train, G to escort", &font, MENU_HINT_SIZE, NAV_COLOR));
+        });
+}
+
+pub fn despawn_menu(
+    mut commands: Commands,
+    roots: Query<Entity, With<MenuRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut stages: ResMut<Stages>,
+    mut daily: ResMut<Daily>,
+    mut state: ResMut<State<AppState>>,
+    mut player_count: ResMut<PlayerCount>,
+    defaults: Res<RunDefaults>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+    mut actions: EventReader<MenuAction>,
+    rings: Query<&FocusRing, With<MenuRoot>>,
+    mut player_count_texts: Query<&mut Text, With<PlayerCountText>>,
+) {
+    // The focused entry is a stage, or the daily challenge after them all
+    for ring in rings.iter() {
+        daily.is_selected = ring.focus == stages.list.len();
+        if !daily.is_selected {
+            stages.selected = ring.focus;
+        }
+    }
+
+    // Choose how many players share the keyboard
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        player_count.0 = (player_count.0 % MAX_PLAYERS) + 1;
+    }
+    else if keyboard_input.just_pressed(KeyCode::Left) {
+        player_count.0 = ((player_count.0 + MAX_PLAYERS - 2) % MAX_PLAYERS) + 1;
+    }
+
+    for mut text in player_count_texts.iter_mut() {
+        text.sections[0].value = player_count_label(player_count.0);
+    }
+
+    // Start a run on the selected stage
+    let is_confirmed = actions.iter().any(|action| matches!(action, MenuAction::Confirm(_)));
+    if !is_confirmed {
+        return;
+    }
+    if let Err(e) = state.set(AppState::Playing) {
+        warn!("ignoring the new run: {}", e);
+        return;
+    }
+
+    let setup = if daily.is_selected {
+        daily_challenge(today(), stages.list.len(), &defaults)
+    }
+    else {
+        NewGameEvent::on_stage(stages.selected, &defaults)
+    };
+    new_game_writer.send(setup);
+}
+
+pub fn return_to_menu(
+    mut controls: Controls,
+    mut state: ResMut<State<AppState>>,
+) {
+    if controls.just_pressed(0, Action::Cancel) {
+        controls.clear_just_pressed(0, Action::Cancel);
+        if let Err(e) = state.set(AppState::Menu) {
+            warn!("ignoring the return to the menu: {}", e);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Coins and the wandering merchant. Hordes and bosses scatter coins when they
+//! break, one for each blast they took, and players pick them up by walking
+//! over them into a purse they all share. Now and then a merchant wanders in
+//! near the players for a while. Touching them pauses the run on a small shop
+//! selling a heal, an extra level up reroll or a random upgrade. Demo and
+//! sandbox runs get no merchant.
+
+use bevy::prelude::*;
+
+use crate::autopilot::DemoMode;
+use crate::death::Dying;
+use crate::effective_stats::StatsChangedEvent;
+use crate::health::Health;
+use crate::input::{Action, Bindings, Controls};
+use crate::loading::GameAssets;
+use crate::menu::menu_text;
+use crate::nav::{FocusRing, Focusable, MenuAction, NAV_COLOR};
+use crate::players::random_player;
+use crate::progression::{grant_upgrade, Progression};
+use crate::{random_unit, AppState, NewGameEvent, Player, Position, PLAYER_SPRITE_SCALE};
+
+
+const COIN_SIZE: f32          = 8.0;
+const COIN_COLOR: Color       = Color::rgb(1.0, 0.85, 0.2);
+const COIN_SCATTER: f32       = 16.0;
+const COIN_PICKUP_RADIUS: f32 = 28.0;
+/// Under the players and monsters, over the ground.
+const COIN_DEPTH: f32         = -1.0;
+
+const MERCHANT_CHECK_SECONDS: f32 = 60.0;
+const MERCHANT_CHANCE: f32        = 0.5;
+const MERCHANT_DISTANCE: f32      = 220.0;
+const MERCHANT_STAY_SECONDS: f32  = 40.0;
+const MERCHANT_SPEED: f32         = 25.0;
+const MERCHANT_TURN_SECONDS: f32  = 3.0;
+const MERCHANT_REACH: f32         = 28.0;
+const MERCHANT_COLOR: Color       = Color::rgb(1.0, 0.8, 0.3);
+
+const SHOP_HEAL: f32     = 40.0;
+const HEAL_PRICE: u32    = 5;
+const REROLL_PRICE: u32  = 3;
+const UPGRADE_PRICE: u32 = 12;
+
+const HUD_FONT_SIZE: f32   = 20.0;
+const SHOP_TITLE_SIZE: f32 = 56.0;
+const SHOP_ITEM_SIZE: f32  = 28.0;
+const SHOP_HINT_SIZE: f32  = 18.0;
+
+/// Sent when something breaks that leaves coins behind.
+pub struct CoinDrop {
+    pub position: Vec2,
+    pub count: u32,
+}
+
+/// Coins picked up this run, shared by all players.
+#[derive(Default)]
+pub struct Purse {
+    coins: u32,
+}
+
+#[derive(Component)]
+pub struct Coin;
+
+/// When the next chance of a merchant visit comes up.
+pub struct MerchantVisits(Timer);
+
+impl Default for MerchantVisits {
+    fn default() -> MerchantVisits {
+        MerchantVisits(Timer::from_seconds(MERCHANT_CHECK_SECONDS, true))
+    }
+}
+
+impl MerchantVisits {
+    /// Keep the merchant from coming, for runs without one.
+    pub fn hold(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[derive(Component)]
+pub struct Merchant {
+    heading: Vec2,
+    turn: Timer,
+    stay: Timer,
+}
+
+#[derive(Clone, Copy)]
+enum Ware {
+    Heal,
+    Reroll,
+    Upgrade,
+}
+
+impl Ware {
+    const ALL: [Ware; 3] = [Ware::Heal, Ware::Reroll, Ware::Upgrade];
+
+    fn price(self) -> u32 {
+        match self {
+            Ware::Heal => HEAL_PRICE,
+            Ware::Reroll => REROLL_PRICE,
+            Ware::Upgrade => UPGRADE_PRICE,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Ware::Heal => "heal everyone for 40",
+            Ware::Reroll => "one more level up reroll",
+            Ware::Upgrade => "a random upgrade",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CoinHud;
+
+#[derive(Component)]
+pub struct ShopRoot;
+
+#[derive(Component)]
+pub struct ShopPurseText;
+
+fn purse_label(purse: &Purse) -> String {
+    format!("coins: {}", purse.coins)
+}
+
+fn ware_label(slot: usize, ware: Ware) -> String {
+    format!("{}: {} - {} coins", slot + 1, ware.description(), ware.price())
+}
+
+/// Scatter dropped coins and clear them away, with the purse and any
+/// merchant, for a new run.
+pub fn drop_coins(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut purse: ResMut<Purse>,
+    mut visits: ResMut<MerchantVisits>,
+    mut drops: EventReader<CoinDrop>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    coins: Query<Entity, With<Coin>>,
+    merchants: Query<Entity, With<Merchant>>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        *purse = Purse::default();
+        *visits = MerchantVisits::default();
+        for entity in coins.iter().chain(merchants.iter()) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for drop in drops.iter() {
+        for _ in 0..drop.count {
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: sprites.atlas(),
+                    sprite: TextureAtlasSprite{
+                        index: sprites.blast(),
+                        color: COIN_COLOR,
+                        custom_size: Some(Vec2::splat(COIN_SIZE)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, COIN_DEPTH),
+                    ..Default::default()
+                })
+                .insert(Coin)
+                .insert(Position::new(drop.position + random_unit() * COIN_SCATTER));
+        }
+    }
+}
+
+pub fn collect_coins(
+    mut commands: Commands,
+    mut purse: ResMut<Purse>,
+    players: Query<&Position, With<Player>>,
+    coins: Query<(Entity, &Position), With<Coin>>,
+) {
+    for (coin, coin_p) in coins.iter() {
+        if players.iter().any(|p| p.current.distance(coin_p.current) < COIN_PICKUP_RADIUS) {
+            commands.entity(coin).despawn();
+            purse.coins += 1;
+        }
+    }
+}
+
+/// Bring a merchant in now and then, let them wander and see them off once
+/// they've stayed long enough. A player who reaches them opens the shop.
+pub fn visit_merchant(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    demo: Res<DemoMode>,
+    mut visits: ResMut<MerchantVisits>,
+    mut state: ResMut<State<AppState>>,
+    players: Query<&Position, (With<Player>, Without<Merchant>)>,
+    mut merchants: Query<(Entity, &mut Merchant, &mut Position)>,
+    dying: Query<(), With<Dying>>,
+) {
+    let positions: Vec<Vec2> = players.iter().map(|p| p.current).collect();
+
+    let is_due = visits.0.tick(time.delta()).just_finished();
+    if is_due && merchants.is_empty() && !demo.is_active() && rand::random::<f32>() < MERCHANT_CHANCE {
+        if let Some(target) = random_player(&positions) {
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: sprites.atlas(),
+                    sprite: TextureAtlasSprite{
+                        index: sprites.player(),
+                        color: MERCHANT_COLOR,
+                        ..Default::default()
+                    },
+                    transform: Transform {
+                        scale: Vec3::new(PLAYER_SPRITE_SCALE, PLAYER_SPRITE_SCALE, 1.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Merchant{
+                    heading: random_unit().normalize_or_zero(),
+                    turn: Timer::from_seconds(MERCHANT_TURN_SECONDS, true),
+                    stay: Timer::from_seconds(MERCHANT_STAY_SECONDS, false),
+                })
+                .insert(Position::new(target + random_unit().normalize_or_zero() * MERCHANT_DISTANCE));
+        }
+    }
+
+    for (entity, mut merchant, mut p) in merchants.iter_mut() {
+        let is_reached = positions.iter().any(|&player| player.distance(p.current) < MERCHANT_REACH);
+        if is_reached && dying.is_empty() && state.push(AppState::Shop).is_ok() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if merchant.stay.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if merchant.turn.tick(time.delta()).just_finished() {
+            merchant.heading = random_unit().normalize_or_zero();
+        }
+        p.apply(merchant.heading * MERCHANT_SPEED * time.delta_seconds());
+    }
+}
+
+pub fn spawn_coin_hud(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    purse: Res<Purse>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(36.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                purse_label(&purse),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: HUD_FONT_SIZE,
+                    color: COIN_COLOR,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(CoinHud);
+}
+
+pub fn despawn_coin_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<CoinHud>>,
+) {
+    for hud in huds.iter() {
+        commands.entity(hud).despawn_recursive();
+    }
+}
+
+pub fn update_coin_hud(
+    purse: Res<Purse>,
+    mut huds: Query<&mut Text, With<CoinHud>>,
+) {
+    if !purse.is_changed() {
+        return;
+    }
+
+    for mut text in huds.iter_mut() {
+        text.sections[0].value = purse_label(&purse);
+    }
+}
+
+pub fn spawn_shop_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    purse: Res<Purse>,
+    bindings: Res<Bindings>,
+) {
+    let font = assets.font();
+    let hint = format!(
+        "{} or {} to buy, {} to leave",
+        bindings.choices_name(), bindings.key_name(Action::Confirm), bindings.key_name(Action::Cancel),
+    );
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.2, 0.12, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(ShopRoot)
+        .insert(FocusRing::new(0, Ware::ALL.len()))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text("MERCHANT", &font, SHOP_TITLE_SIZE, Color::WHITE));
+            for (slot, &ware) in Ware::ALL.iter().enumerate() {
+                parent
+                    .spawn_bundle(menu_text(&ware_label(slot, ware), &font, SHOP_ITEM_SIZE, NAV_COLOR))
+                    .insert(Focusable(slot));
+            }
+            parent
+                .spawn_bundle(menu_text(&purse_label(&purse), &font, SHOP_HINT_SIZE, COIN_COLOR))
+                .insert(ShopPurseText);
+            parent.spawn_bundle(menu_text(&hint, &font, SHOP_HINT_SIZE, Color::rgb(0.6, 0.6, 0.6)));
+        });
+}
+
+pub fn despawn_shop_screen(
+    mut commands: Commands,
+    roots: Query<Entity, With<ShopRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn shop_input(
+    mut controls: Controls,
+    mut actions: EventReader<MenuAction>,
+    mut purse: ResMut<Purse>,
+    mut progression: ResMut<Progression>,
+    mut state: ResMut<State<AppState>>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+    mut players: Query<&mut Health, With<Player>>,
+    living: Query<Entity, (With<Player>, Without<Dying>)>,
+    mut texts: Query<&mut Text, With<ShopPurseText>>,
+) {
+    let mut is_leaving = false;
+    let mut confirmed = None;
+    for action in actions.iter() {
+        match action {
+            MenuAction::Confirm(slot) => confirmed = Some(*slot),
+            MenuAction::Cancel => is_leaving = true,
+        }
+    }
+
+    if is_leaving {
+        if state.pop().is_ok() {
+            controls.clear();
+        }
+        return;
+    }
+
+    // A ware's number buys it straight away, or Enter buys the focused one
+    let ware = (0..Ware::ALL.len())
+        .find(|&slot| controls.just_pressed(0, Action::Choose(slot)))
+        .or(confirmed)
+        .and_then(|slot| Ware::ALL.get(slot).copied())
+        .filter(|ware| ware.price() <= purse.coins);
+    let ware = match ware {
+        Some(ware) => ware,
+        None => return,
+    };
+
+    purse.coins -= ware.price();
+    match ware {
+        Ware::Heal => {
+            for player in living.iter() {
+                if let Ok(mut health) = players.get_mut(player) {
+                    health.heal(SHOP_HEAL);
+                }
+            }
+        }
+        Ware::Reroll => progression.add_reroll(),
+        Ware::Upgrade => {
+            let upgrade = progression.random_upgrade();
+            grant_upgrade(&mut progression, upgrade, &mut players, &mut stats_writer);
+        }
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = purse_label(&purse);
+    }
+}
@@ -0,0 +1,342 @@
+//! Mods, read from `mods/` once at startup, before anything names a monster
+//! kind, weapon or upgrade. Every RON file there, or in a folder of its own
+//! under it, can add monster kinds, weapons and passives and swap the built-in
+//! sprites for its own. What a mod adds is built on something the game already
+//! knows how to play with numbers of its own: a kind takes the behavior of a
+//! built-in kind, a weapon fires blasts of a damage type and a passive raises
+//! the stats the built-in upgrades do. Everything added has an ID like the
+//! built-in content, given by the mod or made from its name. Added kinds can be
+//! named in stage files by their ID and join every stage's spawn table with
+//! their weight. In builds with the `scripting` feature, kinds and weapons can
+//! also run a script of their own.
+//!
+//! The content is kept for the whole session, so looking it up needs no
+//! resource and works from anywhere the built-in tables do.
+//! Browser builds have no `mods/` to read and play with the built-in
+//! content alone.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::args::Weapon;
+use crate::content::{to_id, Content};
+use crate::damage::DamageType;
+use crate::progression::Upgrade;
+use crate::synergy::Tag;
+use crate::MonsterKind;
+
+
+const MOD_DIRECTORY: &str = "mods";
+/// Most monster kinds, weapons or passives mods can add of each.
+const MOST_ADDED: usize   = u8::MAX as usize + 1;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// What one mod file adds.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ModDef {
+    monsters: Vec<MonsterDef>,
+    weapons: Vec<WeaponDef>,
+    passives: Vec<PassiveDef>,
+    /// Built-in sprite files, like `"monster.png"`, to replace with files
+    /// next to the mod's.
+    sprites: Vec<(String, String)>,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// A monster kind with the behavior of a built-in one. Sizes and speeds are
+/// multiples of the built-in kind's.
+#[derive(Deserialize)]
+pub struct MonsterDef {
+    /// Made from the name if left out.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub base: MonsterKind,
+    #[serde(default = "one")]
+    pub speed: f32,
+    #[serde(default = "one")]
+    pub radius: f32,
+    #[serde(default = "one")]
+    pub aggro_radius: f32,
+    #[serde(default = "one")]
+    pub mass: f32,
+    /// How much of a hit of each damage type lands, the base kind's for
+    /// types left out.
+    #[serde(default)]
+    pub damage_taken: HashMap<DamageType, f32>,
+    /// What it costs out of the spawn budget, the base kind's if left out.
+    #[serde(default)]
+    pub threat: Option<u32>,
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32)>,
+    /// How often it turns up on every stage next to the stage's own kinds.
+    #[serde(default)]
+    pub spawn_weight: u32,
+    /// A script steering it, in builds with the `scripting` feature.
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+}
+
+/// A weapon that fires blasts of its own size and color.
+#[derive(Deserialize)]
+pub struct WeaponDef {
+    /// Picked with `--weapon`. Made from the name if left out.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default = "one")]
+    pub radius: f32,
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32)>,
+    /// A script run as each blast goes off, in builds with the `scripting`
+    /// feature.
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+}
+
+/// An upgrade raising stats by this much for each level taken.
+#[derive(Deserialize)]
+pub struct PassiveDef {
+    /// Made from the name if left out.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// Added to the move speed factor.
+    #[serde(default)]
+    pub move_speed: f32,
+    /// Added to the blast radius factor.
+    #[serde(default)]
+    pub blast_radius: f32,
+    #[serde(default)]
+    pub max_health: f32,
+}
+
+#[derive(Default)]
+struct Registry {
+    names: Vec<String>,
+    monsters: Vec<MonsterDef>,
+    weapons: Vec<WeaponDef>,
+    passives: Vec<PassiveDef>,
+    sprites: Vec<(String, PathBuf)>,
+    problems: Vec<String>,
+}
+
+/// The loaded mods, or none while they're still loading.
+fn registry() -> &'static Registry {
+    static NONE: Registry = Registry{
+        names: Vec::new(),
+        monsters: Vec::new(),
+        weapons: Vec::new(),
+        passives: Vec::new(),
+        sprites: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    REGISTRY.get().unwrap_or(&NONE)
+}
+
+fn directory_entries(directory: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(directory) {
+        Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ron")
+}
+
+/// The mod files in `mods/` and the folders directly under it.
+fn mod_files() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for path in directory_entries(Path::new(MOD_DIRECTORY)) {
+        if path.is_dir() {
+            paths.extend(directory_entries(&path).into_iter().filter(|p| is_ron(p)));
+        }
+        else if is_ron(&path) {
+            paths.push(path);
+        }
+    }
+
+    // Sort by path so mods load in the same order every time
+    paths.sort();
+    paths
+}
+
+/// The ID a mod gave, or one made from the name, in the usual form.
+fn pick_id(id: &str, name: &str) -> String {
+    to_id(if id.is_empty() { name } else { id })
+}
+
+impl Registry {
+    /// Add what `def` brings, skipping anything that clashes with content
+    /// already there.
+    fn add(&mut self, path: &Path, def: ModDef) {
+        // Sprites and scripts sit next to the mod file
+        let folder = path.parent().unwrap_or_else(|| Path::new(MOD_DIRECTORY));
+        let mut has_script = false;
+
+        for mut monster in def.monsters {
+            monster.id = pick_id(&monster.id, &monster.name);
+            let is_taken = MonsterKind::from_id(&monster.id).is_some()
+                || self.monsters.iter().any(|m| m.id == monster.id);
+            if monster.id.is_empty() || is_taken || self.monsters.len() == MOST_ADDED {
+                self.problems.push(format!("{}: skipping monster kind '{}'", path.display(), monster.id));
+            }
+            else {
+                has_script |= monster.script.is_some();
+                monster.script = monster.script.map(|script| folder.join(script));
+                self.monsters.push(monster);
+            }
+        }
+
+        for mut weapon in def.weapons {
+            weapon.id = pick_id(&weapon.id, &weapon.name);
+            let is_taken = Weapon::from_id(&weapon.id).is_some()
+                || self.weapons.iter().any(|w| w.id == weapon.id);
+            if weapon.id.is_empty() || is_taken || self.weapons.len() == MOST_ADDED {
+                self.problems.push(format!("{}: skipping weapon '{}'", path.display(), weapon.id));
+            }
+            else {
+                has_script |= weapon.script.is_some();
+                weapon.script = weapon.script.map(|script| folder.join(script));
+                self.weapons.push(weapon);
+            }
+        }
+
+        for mut passive in def.passives {
+            passive.id = pick_id(&passive.id, &passive.name);
+            let is_taken = Upgrade::from_id(&passive.id).is_some()
+                || self.passives.iter().any(|p| p.id == passive.id);
+            if passive.id.is_empty() || is_taken || self.passives.len() == MOST_ADDED {
+                self.problems.push(format!("{}: skipping passive '{}'", path.display(), passive.id));
+            }
+            else {
+                self.passives.push(passive);
+            }
+        }
+
+        if has_script && !cfg!(feature = "scripting") {
+            self.problems.push(format!("{}: scripts need a build with the scripting feature", path.display()));
+        }
+
+        for (sprite, file) in def.sprites {
+            match fs::canonicalize(folder.join(&file)) {
+                Ok(file) => self.sprites.push((sprite, file)),
+                Err(e) => self.problems.push(format!("{}: skipping sprite {}: {}", path.display(), file, e)),
+            }
+        }
+    }
+}
+
+/// Read every mod. Nothing a mod adds can be looked up until they all are.
+pub fn load_mods() {
+    let mut loaded = Registry::default();
+    for path in mod_files() {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ron::from_str::<ModDef>(&text).map_err(|e| e.to_string()));
+
+        match parsed {
+            Ok(def) => {
+                loaded.add(&path, def);
+                loaded.names.push(path.display().to_string());
+            }
+            Err(e) => loaded.problems.push(format!("skipping mod {}: {}", path.display(), e)),
+        }
+    }
+
+    let _ = REGISTRY.set(loaded);
+}
+
+/// Report what was loaded, once logging is up.
+pub fn log_mods() {
+    let registry = registry();
+    for name in &registry.names {
+        info!("loaded mod {}", name);
+    }
+    for problem in &registry.problems {
+        warn!("{}", problem);
+    }
+}
+
+pub fn monster(index: u8) -> &'static MonsterDef {
+    &registry().monsters[index as usize]
+}
+
+pub fn monsters() -> impl Iterator<Item = MonsterKind> {
+    (0..registry().monsters.len()).map(|index| MonsterKind::Modded(index as u8))
+}
+
+/// Every kind mods added, with how often it spawns.
+pub fn monster_weights() -> impl Iterator<Item = (MonsterKind, u32)> {
+    registry()
+        .monsters
+        .iter()
+        .enumerate()
+        .map(|(index, m)| (MonsterKind::Modded(index as u8), m.spawn_weight))
+}
+
+pub fn weapon(index: u8) -> &'static WeaponDef {
+    &registry().weapons[index as usize]
+}
+
+pub fn weapons() -> impl Iterator<Item = Weapon> {
+    (0..registry().weapons.len()).map(|index| Weapon::Modded(index as u8))
+}
+
+pub fn passive(index: u8) -> &'static PassiveDef {
+    &registry().passives[index as usize]
+}
+
+pub fn passives() -> impl Iterator<Item = Upgrade> {
+    (0..registry().passives.len()).map(|index| Upgrade::Modded(index as u8))
+}
+
+/// Every kind mods added that has a script.
+#[cfg(feature = "scripting")]
+pub fn monster_scripts() -> impl Iterator<Item = (MonsterKind, &'static Path)> {
+    registry()
+        .monsters
+        .iter()
+        .enumerate()
+        .filter_map(|(index, m)| Some((MonsterKind::Modded(index as u8), m.script.as_deref()?)))
+}
+
+/// Every weapon mods added that has a script.
+#[cfg(feature = "scripting")]
+pub fn weapon_scripts() -> impl Iterator<Item = (Weapon, &'static Path)> {
+    registry()
+        .weapons
+        .iter()
+        .enumerate()
+        .filter_map(|(index, w)| Some((Weapon::Modded(index as u8), w.script.as_deref()?)))
+}
+
+/// The file replacing the built-in sprite `path`, if a mod has one.
+pub fn sprite_override(path: &str) -> Option<&'static Path> {
+    registry()
+        .sprites
+        .iter()
+        .rev()
+        .find(|(sprite, _)| sprite == path)
+        .map(|(_, file)| file.as_path())
+}
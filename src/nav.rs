@@ -0,0 +1,109 @@
+//! Keyboard navigation shared by the menu screens. A screen's root carries a
+//! focus ring over its entries: the first player's up and down move the focus,
+//! wrapping at either end, confirm picks the focused entry and cancel backs
+//! out. Screens react to the actions rather than reading the keys themselves,
+//! and the focused entry is highlighted for them. Navigation only runs in the
+//! menu states, so confirm and cancel are left alone while playing.
+
+use bevy::prelude::*;
+
+use crate::input::{Action, Controls};
+use crate::tween::{Ease, Tween, TweenTarget};
+
+
+pub const NAV_COLOR: Color         = Color::rgb(0.6, 0.6, 0.6);
+pub const NAV_FOCUSED_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+
+const NAV_FOCUS_SECONDS: f32 = 0.15;
+
+/// Which of a screen's entries has focus. Screens without entries still
+/// carry one to hear confirm and cancel.
+#[derive(Component)]
+pub struct FocusRing {
+    pub focus: usize,
+    count: usize,
+}
+
+impl FocusRing {
+    pub fn new(focus: usize, count: usize) -> FocusRing {
+        FocusRing{
+            focus: focus.min(count.saturating_sub(1)),
+            count,
+        }
+    }
+
+    /// Change the number of entries, keeping the focus on one of them.
+    pub fn set_count(&mut self, count: usize) {
+        self.count = count;
+        self.focus = self.focus.min(count.saturating_sub(1));
+    }
+}
+
+/// An entry in the focus ring of the screen it's a child of.
+#[derive(Component)]
+pub struct Focusable(pub usize);
+
+/// Menu navigation, for screens that have to see keys before it does.
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct NavigateMenus;
+
+/// Sent when the focused entry is confirmed or a screen is backed out of.
+#[derive(Clone, Copy)]
+pub enum MenuAction {
+    Confirm(usize),
+    Cancel,
+}
+
+pub fn navigate_menus(
+    mut controls: Controls,
+    mut rings: Query<&mut FocusRing>,
+    mut actions: EventWriter<MenuAction>,
+) {
+    // The first player's directions move the focus
+    let is_down = controls.just_pressed(0, Action::Down);
+    let is_up = controls.just_pressed(0, Action::Up);
+
+    for mut ring in rings.iter_mut() {
+        if ring.count > 0 {
+            if is_down {
+                ring.focus = (ring.focus + 1) % ring.count;
+            }
+            else if is_up {
+                ring.focus = (ring.focus + ring.count - 1) % ring.count;
+            }
+        }
+
+        if controls.just_pressed(0, Action::Confirm) {
+            controls.clear_just_pressed(0, Action::Confirm);
+            actions.send(MenuAction::Confirm(ring.focus));
+        }
+        else if controls.just_pressed(0, Action::Cancel) {
+            controls.clear_just_pressed(0, Action::Cancel);
+            actions.send(MenuAction::Cancel);
+        }
+    }
+}
+
+/// Ease each entry toward the focused or unfocused color as focus moves.
+pub fn highlight_focus(
+    mut commands: Commands,
+    rings: Query<(&FocusRing, &Children), Changed<FocusRing>>,
+    entries: Query<(&Focusable, &Text)>,
+) {
+    for (ring, children) in rings.iter() {
+        for &child in children.iter() {
+            let (entry, text) = match entries.get(child) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            let from = text.sections[0].style.color;
+            let to = if entry.0 == ring.focus && ring.count > 0 { NAV_FOCUSED_COLOR } else { NAV_COLOR };
+            if from != to {
+                commands
+                    .entity(child)
+                    .insert(Tween::new(TweenTarget::TextColor { from, to }, Ease::QuadOut, NAV_FOCUS_SECONDS));
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Corpses and the necromancers that raise them. Blasted monsters leave a
+//! corpse behind for a while, within the remains limit. A necromancer that
+//! comes across one stops to channel, then raises the corpses around it as
+//! smaller, lighter monsters. Raised monsters leave nothing behind when they
+//! fall again.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::remains::{oldest_over_limit, RemainsLimit};
+use crate::tween::{Ease, Tween, TweenTarget};
+use crate::{
+    spawn_monster_kind, MonsterKind, MonsterStats, NewGameEvent, Position, Velocity,
+    MONSTER_BODY_RADIUS,
+};
+
+
+const CORPSE_SECONDS: f32      = 12.0;
+const CORPSE_ALPHA: f32        = 0.5;
+const CORPSE_DEPTH: f32        = -10.0;
+const CORPSE_FALL_SECONDS: f32 = 0.3;
+
+const NECROMANCY_RANGE: f32    = 120.0;
+const NECROMANCY_SECONDS: f32  = 2.0;
+const NECROMANCY_RAISES: usize = 3;
+const RAISED_SCALE: f32        = 0.8;
+
+/// What's left of a blasted monster until it fades away or is raised.
+#[derive(Component)]
+pub struct Corpse {
+    kind: MonsterKind,
+    lifetime: Timer,
+}
+
+/// A monster raised from a corpse. It leaves no corpse of its own.
+#[derive(Component)]
+pub struct Raised;
+
+/// A necromancer standing still while it raises the dead.
+#[derive(Component)]
+pub struct Channeling(Timer);
+
+pub fn spawn_corpse(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    kind: MonsterKind,
+    position: Vec2,
+) {
+    let scale = 2.0 * kind.radius() / MONSTER_BODY_RADIUS;
+
+    // Toppling onto its side, under everything still moving
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.monster(),
+                color: Color::rgba(0.4, 0.4, 0.4, CORPSE_ALPHA),
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, CORPSE_DEPTH),
+                rotation: Quat::IDENTITY,
+                scale: Vec3::new(scale, scale, 1.0),
+            },
+            ..Default::default()
+        })
+        .insert(Tween::new(
+            TweenTarget::Rotation { from: 0.0, to: FRAC_PI_2 },
+            Ease::QuadIn,
+            CORPSE_FALL_SECONDS,
+        ))
+        .insert(Corpse{
+            kind,
+            lifetime: Timer::from_seconds(CORPSE_SECONDS, false),
+        })
+        .insert(Position::new(position));
+}
+
+pub fn fade_corpses(
+    time: Res<Time>,
+    mut commands: Commands,
+    limit: Res<RemainsLimit>,
+    mut corpses: Query<(Entity, &mut Corpse, &mut TextureAtlasSprite)>,
+) {
+    let mut showing = Vec::new();
+    for (entity, mut corpse, mut sprite) in corpses.iter_mut() {
+        corpse.lifetime.tick(time.delta());
+        sprite.color.set_a(CORPSE_ALPHA * (1.0 - corpse.lifetime.percent()));
+
+        if corpse.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+        else {
+            showing.push((entity, corpse.lifetime.elapsed_secs()));
+        }
+    }
+
+    // Big fights drop more than the limit, so the oldest make way
+    for entity in oldest_over_limit(showing, 0, limit.get()) {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn clear_corpses(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    corpses: Query<Entity, With<Corpse>>,
+) {
+    if new_game_reader.iter().next().is_none() {
+        return;
+    }
+
+    for corpse in corpses.iter() {
+        commands.entity(corpse).despawn();
+    }
+}
+
+pub fn raise_dead(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut monster_stats: ResMut<MonsterStats>,
+    corpses: Query<(Entity, &Corpse, &Position)>,
+    mut necromancers: Query<(Entity, &MonsterKind, &Position, &mut Velocity, Option<&mut Channeling>)>,
+) {
+    // Each corpse can only be raised once, even with two necromancers on it
+    let mut raised = Vec::new();
+
+    for (entity, kind, p, mut v, channeling) in necromancers.iter_mut() {
+        if kind.behavior() != MonsterKind::Necromancer {
+            continue;
+        }
+
+        let nearby: Vec<(Entity, MonsterKind, Vec2)> = corpses
+            .iter()
+            .filter(|(corpse, _, _)| !raised.contains(corpse))
+            .filter(|(_, _, position)| position.current.distance_squared(p.current) <= NECROMANCY_RANGE.powi(2))
+            .map(|(corpse, c, position)| (corpse, c.kind, position.current))
+            .take(NECROMANCY_RAISES)
+            .collect();
+
+        let mut channeling = match channeling {
+            Some(channeling) => channeling,
+            None => {
+                if !nearby.is_empty() {
+                    commands.entity(entity).insert(Channeling(Timer::from_seconds(NECROMANCY_SECONDS, false)));
+                    v.direction = Vec2::ZERO;
+                }
+                continue;
+            }
+        };
+
+        v.direction = Vec2::ZERO;
+        if !channeling.0.tick(time.delta()).finished() {
+            continue;
+        }
+
+        commands.entity(entity).remove::<Channeling>();
+        for (corpse, kind, position) in nearby {
+            commands.entity(corpse).despawn();
+            let monster = spawn_monster_kind(&mut commands, &sprites, kind, position, RAISED_SCALE);
+            commands.entity(monster).insert(Raised);
+            monster_stats.record_spawn(kind);
+            raised.push(corpse);
+        }
+    }
+}
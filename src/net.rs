@@ -0,0 +1,724 @@
+//! LAN co-op over raw UDP. The host runs the whole simulation with the remote
+//! player as player two and streams snapshots of it at a fixed rate; the
+//! client only sends its input and draws whatever the latest snapshot
+//! contains. Each snapshot is split into numbered chunks small enough to
+//! cross the network unfragmented, and the client only draws one once every
+//! chunk of it has arrived. A snapshot missing a chunk is dropped, since
+//! another is never far behind. Hosting turns the run into a two player one,
+//! and the local player count is put back once the session closes.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use bevy::prelude::*;
+
+use crate::camera::CameraFocus;
+use crate::content::Content;
+use crate::input::{Action, Controls};
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::players::player_centroid;
+use crate::stage::Stages;
+use crate::{
+    AppState, Blast, Body, Monster, MonsterKind, Movement, NewGameEvent, Obstacle,
+    Player, PlayerCount, Position, RunDefaults, Velocity, WorldSeed, MONSTER_BODY_RADIUS,
+};
+
+
+const LAN_PORT: u16                 = 7777;
+const JOIN_RETRY_SECONDS: f32       = 1.0;
+const MAX_DATAGRAM_BYTES: usize     = 65_507;
+const SNAPSHOT_SECONDS: f32         = 0.05;
+/// Kept under the usual MTU so chunks aren't fragmented on the way.
+const SNAPSHOT_CHUNK_BYTES: usize   = 1200;
+const MAX_SNAPSHOT_CHUNKS: usize    = u8::MAX as usize;
+const SNAPSHOT_ENTRY_BYTES: usize   = 14;
+/// Message, seed, stage, sequence, chunk, chunk count and entry count.
+const SNAPSHOT_HEADER_BYTES: usize  = 16;
+const SNAPSHOT_CHUNK_ENTRIES: usize = (SNAPSHOT_CHUNK_BYTES - SNAPSHOT_HEADER_BYTES) / SNAPSHOT_ENTRY_BYTES;
+
+const MESSAGE_JOIN: u8     = 0;
+const MESSAGE_INPUT: u8    = 1;
+const MESSAGE_SNAPSHOT: u8 = 2;
+
+const REPLICA_PLAYER: u8   = 0;
+const REPLICA_MONSTER: u8  = 1;
+const REPLICA_BLAST: u8    = 2;
+const REPLICA_OBSTACLE: u8 = 3;
+
+const HINT_FONT_SIZE: f32 = 18.0;
+
+pub struct LanPlugin;
+
+impl Plugin for LanPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Menu)
+                    .with_system(spawn_lan_hint),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Menu)
+                    .with_system(lan_menu_input),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(
+                        host_receive
+                            .after(Movement::Input)
+                            .before(Movement::Player),
+                    )
+                    .with_system(
+                        host_send_snapshot
+                            .after(Movement::Spread),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing)
+                    .with_system(close_session),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Client)
+                    .with_system(client_send_input)
+                    .with_system(client_receive)
+                    .with_system(leave_client),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Client)
+                    .with_system(close_session)
+                    .with_system(despawn_replicas),
+            )
+            .insert_resource(LanSession::Offline)
+            .insert_resource(ReplicaPool::default());
+    }
+}
+
+pub enum LanSession {
+    Offline,
+    Host {
+        socket: UdpSocket,
+        peer: Option<SocketAddr>,
+        input: Vec2,
+        tick: Timer,
+        sequence: u16,
+        /// The player count before hosting, put back when the session closes.
+        local_players: usize,
+    },
+    Client {
+        socket: UdpSocket,
+        host: Option<SocketAddr>,
+        retry: Timer,
+        assembly: Assembly,
+    },
+}
+
+impl LanSession {
+    /// Host a two player run with the remote player as player two.
+    fn host(socket: UdpSocket, player_count: &mut PlayerCount) -> LanSession {
+        let local_players = player_count.0;
+        player_count.0 = 2;
+
+        LanSession::Host {
+            socket,
+            peer: None,
+            input: Vec2::ZERO,
+            tick: Timer::from_seconds(SNAPSHOT_SECONDS, true),
+            sequence: 0,
+            local_players,
+        }
+    }
+}
+
+/// One replicated thing in a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    replica: u8,
+    /// A monster's kind, by its place among every kind, so it can be tinted.
+    kind: u8,
+    position: Vec2,
+    radius: f32,
+}
+
+/// One chunk of a snapshot as it arrived.
+struct Chunk {
+    seed: u64,
+    stage: u8,
+    sequence: u16,
+    index: usize,
+    count: usize,
+    entries: Vec<Entry>,
+}
+
+impl Chunk {
+    fn parse(message: &[u8]) -> Option<Chunk> {
+        if message.first() != Some(&MESSAGE_SNAPSHOT) || message.len() < SNAPSHOT_HEADER_BYTES {
+            return None;
+        }
+
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&message[1..9]);
+        let entry_count = u16::from_le_bytes([message[14], message[15]]) as usize;
+        let entries = (0..entry_count)
+            .map_while(|i| {
+                let at = SNAPSHOT_HEADER_BYTES + i * SNAPSHOT_ENTRY_BYTES;
+                let (replica, kind) = (*message.get(at)?, *message.get(at + 1)?);
+                let (x, y, r) = (read_f32(message, at + 2)?, read_f32(message, at + 6)?, read_f32(message, at + 10)?);
+                Some(Entry{
+                    replica,
+                    kind,
+                    position: Vec2::new(x, y),
+                    radius: r,
+                })
+            })
+            .collect();
+
+        Some(Chunk{
+            seed: u64::from_le_bytes(seed),
+            stage: message[9],
+            sequence: u16::from_le_bytes([message[10], message[11]]),
+            index: message[12] as usize,
+            count: message[13] as usize,
+            entries,
+        })
+    }
+}
+
+/// The chunks of the newest snapshot so far.
+#[derive(Default)]
+pub struct Assembly {
+    sequence: Option<u16>,
+    chunks: Vec<Option<Chunk>>,
+}
+
+impl Assembly {
+    /// Take in a chunk, and hand back the whole snapshot if it was the last
+    /// one missing. Chunks of snapshots older than the one being put
+    /// together are ignored.
+    fn add(&mut self, chunk: Chunk) -> Option<Vec<Chunk>> {
+        if chunk.count == 0 || chunk.index >= chunk.count {
+            return None;
+        }
+
+        // Sequence numbers wrap, so newer means less than half the range ahead
+        let is_newer = self.sequence.is_none_or(|sequence| {
+            let ahead = chunk.sequence.wrapping_sub(sequence);
+            ahead != 0 && ahead < u16::MAX / 2
+        });
+        if is_newer {
+            self.sequence = Some(chunk.sequence);
+            self.chunks = (0..chunk.count).map(|_| None).collect();
+        }
+        else if self.sequence != Some(chunk.sequence) || self.chunks.len() != chunk.count {
+            return None;
+        }
+
+        let index = chunk.index;
+        self.chunks[index] = Some(chunk);
+        if self.chunks.iter().any(|chunk| chunk.is_none()) {
+            return None;
+        }
+
+        // Keep the sequence so late copies of its chunks aren't taken again
+        Some(self.chunks.drain(..).flatten().collect())
+    }
+}
+
+fn bind(port: u16) -> Option<UdpSocket> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))
+        .and_then(|s| s.set_nonblocking(true).map(|_| s))
+        .and_then(|s| s.set_broadcast(true).map(|_| s));
+
+    match socket {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            warn!("failed to open LAN socket on port {}: {}", port, e);
+            None
+        }
+    }
+}
+
+/// Drain every datagram waiting on a non-blocking socket.
+fn receive_all(socket: &UdpSocket) -> Vec<(Vec<u8>, SocketAddr)> {
+    let mut received = Vec::new();
+    let mut buffer = vec![0; MAX_DATAGRAM_BYTES];
+
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((size, from)) => received.push((buffer[..size].to_vec(), from)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("LAN receive failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    received
+}
+
+fn read_f32(bytes: &[u8], at: usize) -> Option<f32> {
+    bytes.get(at..at + 4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[derive(Component)]
+pub struct Replica;
+
+#[derive(Default)]
+pub struct ReplicaPool {
+    entities: HashMap<u8, Vec<Entity>>,
+}
+
+fn spawn_lan_hint(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(12.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "H: host LAN game    J: join LAN game",
+                TextStyle {
+                    font: assets.font(),
+                    font_size: HINT_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MenuRoot);
+}
+
+fn lan_menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut session: ResMut<LanSession>,
+    mut player_count: ResMut<PlayerCount>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::H) {
+        if let Some(socket) = bind(LAN_PORT) {
+            if state.set(AppState::Playing).is_ok() {
+                *session = LanSession::host(socket, &mut player_count);
+                new_game_writer.send(NewGameEvent::on_stage(stages.selected, &defaults));
+            }
+        }
+    }
+    else if keyboard_input.just_pressed(KeyCode::J) {
+        // Look for a host by broadcasting until one answers
+        if let Some(socket) = bind(0) {
+            if state.set(AppState::Client).is_ok() {
+                let mut retry = Timer::from_seconds(JOIN_RETRY_SECONDS, true);
+                retry.set_elapsed(retry.duration());
+
+                *session = LanSession::Client {
+                    socket,
+                    host: None,
+                    retry,
+                    assembly: Assembly::default(),
+                };
+            }
+        }
+    }
+}
+
+fn close_session(
+    mut session: ResMut<LanSession>,
+    mut player_count: ResMut<PlayerCount>,
+) {
+    // Runs after this one have nobody driving player two
+    if let LanSession::Host { local_players, .. } = *session {
+        player_count.0 = local_players;
+    }
+    *session = LanSession::Offline;
+}
+
+fn host_receive(
+    mut session: ResMut<LanSession>,
+    mut players: Query<(&Player, &mut Velocity)>,
+) {
+    let (socket, peer, input) = match &mut *session {
+        LanSession::Host { socket, peer, input, .. } => (socket, peer, input),
+        _ => return,
+    };
+
+    for (message, from) in receive_all(socket) {
+        match message.first() {
+            Some(&MESSAGE_JOIN) => {
+                if *peer != Some(from) {
+                    info!("LAN player joined from {}", from);
+                }
+                *peer = Some(from);
+            }
+            Some(&MESSAGE_INPUT) if *peer == Some(from) => {
+                if let (Some(x), Some(y)) = (read_f32(&message, 1), read_f32(&message, 5)) {
+                    *input = Vec2::new(x, y);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The remote player drives player two
+    for (player, mut v) in players.iter_mut() {
+        if player.index == 1 {
+            v.direction = input.normalize_or_zero();
+        }
+    }
+}
+
+fn host_send_snapshot(
+    time: Res<Time>,
+    mut session: ResMut<LanSession>,
+    world_seed: Res<WorldSeed>,
+    stages: Res<Stages>,
+    players: Query<(&Position, &Body), With<Player>>,
+    monsters: Query<(&Position, &Body, &MonsterKind), With<Monster>>,
+    blasts: Query<(&Position, &Blast)>,
+    obstacles: Query<(&Position, &Body), With<Obstacle>>,
+) {
+    let (socket, peer, sequence) = match &mut *session {
+        LanSession::Host { socket, peer: Some(peer), tick, sequence, .. } => {
+            if !tick.tick(time.delta()).just_finished() {
+                return;
+            }
+            (socket, *peer, sequence)
+        }
+        _ => return,
+    };
+    *sequence = sequence.wrapping_add(1);
+
+    let entry = |replica, kind, position: &Position, radius| Entry{
+        replica,
+        kind,
+        position: position.current,
+        radius,
+    };
+    let kinds = MonsterKind::all();
+    let kind_number = |kind: &MonsterKind| {
+        kinds.iter().position(|k| k == kind).and_then(|i| u8::try_from(i).ok()).unwrap_or(0)
+    };
+
+    let mut entries = Vec::new();
+    entries.extend(players.iter().map(|(p, b)| entry(REPLICA_PLAYER, 0, p, b.circle.radius)));
+    entries.extend(obstacles.iter().map(|(p, b)| entry(REPLICA_OBSTACLE, 0, p, b.circle.radius)));
+    entries.extend(monsters.iter().map(|(p, b, k)| entry(REPLICA_MONSTER, kind_number(k), p, b.circle.radius)));
+    entries.extend(blasts.iter().map(|(p, b)| entry(REPLICA_BLAST, 0, p, b.circle.radius)));
+
+    for message in snapshot_chunks(world_seed.value, stages.selected as u8, *sequence, &entries) {
+        if let Err(e) = socket.send_to(&message, peer) {
+            warn!("LAN snapshot send failed: {}", e);
+            return;
+        }
+    }
+}
+
+/// A snapshot of `entries` as datagrams, as many as can be numbered and
+/// dropping whatever doesn't fit.
+fn snapshot_chunks(seed: u64, stage: u8, sequence: u16, entries: &[Entry]) -> Vec<Vec<u8>> {
+    let entries = &entries[..entries.len().min(SNAPSHOT_CHUNK_ENTRIES * MAX_SNAPSHOT_CHUNKS)];
+    let count = entries.len().div_ceil(SNAPSHOT_CHUNK_ENTRIES).max(1);
+
+    (0..count)
+        .map(|index| {
+            let start = (index * SNAPSHOT_CHUNK_ENTRIES).min(entries.len());
+            let end = (start + SNAPSHOT_CHUNK_ENTRIES).min(entries.len());
+            let chunk = &entries[start..end];
+
+            let mut message = Vec::with_capacity(SNAPSHOT_HEADER_BYTES + chunk.len() * SNAPSHOT_ENTRY_BYTES);
+            message.push(MESSAGE_SNAPSHOT);
+            message.extend_from_slice(&seed.to_le_bytes());
+            message.push(stage);
+            message.extend_from_slice(&sequence.to_le_bytes());
+            message.push(index as u8);
+            message.push(count as u8);
+            message.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            for entry in chunk {
+                message.push(entry.replica);
+                message.push(entry.kind);
+                message.extend_from_slice(&entry.position.x.to_le_bytes());
+                message.extend_from_slice(&entry.position.y.to_le_bytes());
+                message.extend_from_slice(&entry.radius.to_le_bytes());
+            }
+            message
+        })
+        .collect()
+}
+
+fn client_send_input(
+    time: Res<Time>,
+    controls: Controls,
+    mut session: ResMut<LanSession>,
+) {
+    let (socket, host, retry) = match &mut *session {
+        LanSession::Client { socket, host, retry, .. } => (socket, host, retry),
+        _ => return,
+    };
+
+    // Keep announcing ourselves until a host starts sending snapshots
+    let host = match host {
+        Some(host) => *host,
+        None => {
+            retry.tick(time.delta());
+            if retry.just_finished() {
+                let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, LAN_PORT));
+                if let Err(e) = socket.send_to(&[MESSAGE_JOIN], broadcast) {
+                    warn!("LAN join broadcast failed: {}", e);
+                }
+            }
+            return;
+        }
+    };
+
+    let direction = controls.direction(0);
+
+    let mut message = vec![MESSAGE_INPUT];
+    message.extend_from_slice(&direction.x.to_le_bytes());
+    message.extend_from_slice(&direction.y.to_le_bytes());
+    if let Err(e) = socket.send_to(&message, host) {
+        warn!("LAN input send failed: {}", e);
+    }
+}
+
+fn spawn_replica(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    replica: u8,
+) -> Entity {
+    // Obstacles are plain squares, everything else comes from the sprite atlas
+    let mut entity = if replica == REPLICA_OBSTACLE {
+        commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite{
+                color: Color::rgb(0.35, 0.3, 0.25),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+    else {
+        let (index, scale) = match replica {
+            REPLICA_PLAYER => (sprites.player(), 4.0),
+            REPLICA_MONSTER => (sprites.monster(), 1.0),
+            _ => (sprites.blast(), 1.0),
+        };
+        commands.spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite::new(index),
+            transform: Transform::from_scale(Vec3::new(scale, scale, 1.0)),
+            ..Default::default()
+        })
+    };
+
+    entity
+        .insert(Replica)
+        .insert(Position::default())
+        .id()
+}
+
+fn client_receive(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut session: ResMut<LanSession>,
+    mut pool: ResMut<ReplicaPool>,
+    mut world_seed: ResMut<WorldSeed>,
+    mut stages: ResMut<Stages>,
+    mut focus: ResMut<CameraFocus>,
+    mut replicas: Query<(&mut Position, &mut Transform, Option<&mut TextureAtlasSprite>), With<Replica>>,
+) {
+    let (socket, host, assembly) = match &mut *session {
+        LanSession::Client { socket, host, assembly, .. } => (socket, host, assembly),
+        _ => return,
+    };
+
+    // Only the newest whole snapshot matters
+    let mut snapshot = None;
+    for (message, from) in receive_all(socket) {
+        if let Some(chunk) = Chunk::parse(&message) {
+            *host = Some(from);
+            if let Some(chunks) = assembly.add(chunk) {
+                snapshot = Some(chunks);
+            }
+        }
+    }
+    let chunks = match snapshot {
+        Some(chunks) => chunks,
+        None => return,
+    };
+
+    // Mirror the host's world so the ground and decorations match
+    world_seed.value = chunks[0].seed;
+    if (chunks[0].stage as usize) < stages.list.len() {
+        stages.selected = chunks[0].stage as usize;
+    }
+
+    let mut by_replica: HashMap<u8, Vec<Entry>> = HashMap::new();
+    for entry in chunks.into_iter().flat_map(|chunk| chunk.entries) {
+        by_replica.entry(entry.replica).or_default().push(entry);
+    }
+
+    let player_positions: Vec<Vec2> = by_replica
+        .get(&REPLICA_PLAYER)
+        .map(|entries| entries.iter().map(|entry| entry.position).collect())
+        .unwrap_or_default();
+    if let Some(center) = player_centroid(&player_positions) {
+        focus.center = center;
+    }
+
+    // Grow or shrink each pool to match, then move the replicas into place
+    let kinds = MonsterKind::all();
+    for replica in [REPLICA_PLAYER, REPLICA_MONSTER, REPLICA_BLAST, REPLICA_OBSTACLE] {
+        let entries = by_replica.remove(&replica).unwrap_or_default();
+        let entities = pool.entities.entry(replica).or_default();
+
+        while entities.len() > entries.len() {
+            commands.entity(entities.pop().unwrap()).despawn();
+        }
+        while entities.len() < entries.len() {
+            entities.push(spawn_replica(&mut commands, &sprites, replica));
+        }
+
+        for (entity, Entry { kind, position, radius, .. }) in entities.iter().zip(entries) {
+            if let Ok((mut p, mut t, sprite)) = replicas.get_mut(*entity) {
+                p.current = position;
+                match replica {
+                    // A kind from a mod the client lacks is drawn plain
+                    REPLICA_MONSTER => {
+                        let scale = 2.0 * radius / MONSTER_BODY_RADIUS;
+                        t.scale = Vec3::new(scale, scale, 1.0);
+                        if let Some(mut sprite) = sprite {
+                            sprite.color = kinds.get(kind as usize).map_or(Color::WHITE, |kind| kind.tint());
+                        }
+                    }
+                    REPLICA_BLAST => {
+                        if let Some(mut sprite) = sprite {
+                            sprite.custom_size = Some(Vec2::splat(radius * 2.0));
+                        }
+                    }
+                    // Obstacles are untextured, so one unit wide before scaling
+                    REPLICA_OBSTACLE => {
+                        t.scale = Vec3::new(radius * 2.0, radius * 2.0, 1.0);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn leave_client(
+    mut controls: Controls,
+    mut state: ResMut<State<AppState>>,
+) {
+    if controls.just_pressed(0, Action::Cancel) {
+        controls.clear_just_pressed(0, Action::Cancel);
+        if let Err(e) = state.set(AppState::Menu) {
+            warn!("ignoring the return to the menu: {}", e);
+        }
+    }
+}
+
+fn despawn_replicas(
+    mut commands: Commands,
+    mut pool: ResMut<ReplicaPool>,
+) {
+    for (_, entities) in pool.entities.drain() {
+        for entity in entities {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: usize) -> Vec<Entry> {
+        (0..count)
+            .map(|i| Entry{
+                replica: REPLICA_MONSTER,
+                kind: i as u8,
+                position: Vec2::new(i as f32, -(i as f32)),
+                radius: 8.0,
+            })
+            .collect()
+    }
+
+    fn chunks(sequence: u16, entries: &[Entry]) -> Vec<Chunk> {
+        snapshot_chunks(42, 1, sequence, entries)
+            .iter()
+            .map(|message| {
+                assert!(message.len() <= SNAPSHOT_CHUNK_BYTES);
+                Chunk::parse(message).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snapshots_come_back_whole_in_any_order() {
+        let sent = entries(SNAPSHOT_CHUNK_ENTRIES * 2 + 5);
+        let mut assembly = Assembly::default();
+
+        let mut snapshot = None;
+        for chunk in chunks(7, &sent).into_iter().rev() {
+            snapshot = assembly.add(chunk);
+        }
+
+        let received: Vec<_> = snapshot.unwrap().into_iter().flat_map(|chunk| chunk.entries).collect();
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn snapshots_missing_a_chunk_give_way_to_newer_ones() {
+        let mut assembly = Assembly::default();
+        let older = chunks(u16::MAX, &entries(SNAPSHOT_CHUNK_ENTRIES + 1));
+        assert!(assembly.add(older.into_iter().next().unwrap()).is_none());
+
+        // The sequence wraps around to zero
+        let newer = chunks(0, &entries(1));
+        assert!(assembly.add(newer.into_iter().next().unwrap()).is_some());
+
+        let late = chunks(u16::MAX, &entries(1));
+        assert!(assembly.add(late.into_iter().next().unwrap()).is_none());
+    }
+
+    #[test]
+    fn a_local_run_after_hosting_has_only_the_local_players() {
+        let mut world = World::new();
+        world.insert_resource(State::new(AppState::Menu));
+        world.insert_resource(LanSession::Offline);
+        world.insert_resource(PlayerCount(1));
+
+        let mut stage = SystemStage::parallel();
+        stage.add_system_set(State::<AppState>::get_driver());
+        stage.add_system_set(SystemSet::on_exit(AppState::Playing).with_system(close_session));
+        let mut go_to = |world: &mut World, next| {
+            world.get_resource_mut::<State<AppState>>().unwrap().set(next).unwrap();
+            stage.run(world);
+        };
+
+        // Host a run and lose it
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let session = LanSession::host(socket, &mut world.get_resource_mut::<PlayerCount>().unwrap());
+        world.insert_resource(session);
+        go_to(&mut world, AppState::Playing);
+        assert_eq!(world.get_resource::<PlayerCount>().unwrap().0, 2);
+        go_to(&mut world, AppState::GameOver);
+
+        // The next run from the menu is a local one again
+        go_to(&mut world, AppState::Menu);
+        go_to(&mut world, AppState::Playing);
+        assert!(matches!(world.get_resource::<LanSession>(), Some(LanSession::Offline)));
+        assert_eq!(world.get_resource::<PlayerCount>().unwrap().0, 1);
+    }
+}
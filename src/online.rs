@@ -0,0 +1,327 @@
+//! The online leaderboard. Each scored run is submitted to the server at
+//! `SWARM_LEADERBOARD_URL` along with a checksum over its result, and the menu
+//! shows the server's top runs for this version. Requests go out on their own
+//! thread so a slow or missing server never stalls a frame. Submissions that
+//! can't get through wait on disk and go out after the next request, oldest
+//! first, until one fails to get through again. While the server is out of
+//! reach the menu falls back to the local best runs.
+
+use std::env;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::leaderboard::{Leaderboard, ScoreEntry, GAME_VERSION};
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::run_stats::RUN_DIRECTORY;
+use crate::storage;
+use crate::AppState;
+
+
+const URL_VARIABLE: &str   = "SWARM_LEADERBOARD_URL";
+const PENDING_FILE: &str   = "pending-scores.json";
+const TIMEOUT_SECONDS: u64 = 5;
+const TOP_ENTRIES: usize   = 5;
+/// Mixed into the checksum, so it's more than a hash of the fields beside it.
+const CHECKSUM_SALT: u64   = 0x5377_6172_6d21;
+
+const PANEL_FONT_SIZE: f32 = 16.0;
+
+pub struct OnlinePlugin;
+
+impl Plugin for OnlinePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Menu)
+                    .with_system(spawn_online_panel),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Menu)
+                    .with_system(update_online_panel),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver)
+                    .with_system(submit_score),
+            )
+            .insert_resource(OnlineLeaderboard::connect());
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Submission {
+    version: String,
+    seed: u64,
+    stage: String,
+    score: u32,
+    duration_seconds: f32,
+    checksum: u64,
+}
+
+impl Submission {
+    fn of(entry: &ScoreEntry) -> Submission {
+        let fields = format!(
+            "{}|{}|{}|{}|{:.1}",
+            entry.version, entry.seed, entry.stage, entry.score, entry.duration_seconds,
+        );
+
+        // FNV-1a, so the server can check it without anything special
+        let checksum = fields
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325 ^ CHECKSUM_SALT, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+
+        Submission{
+            version: entry.version.clone(),
+            seed: entry.seed,
+            stage: entry.stage.clone(),
+            score: entry.score,
+            duration_seconds: entry.duration_seconds,
+            checksum,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TopEntry {
+    stage: String,
+    seed: u64,
+    score: u32,
+}
+
+enum Request {
+    Submit(Submission),
+    FetchTop,
+}
+
+pub struct OnlineLeaderboard {
+    sender: Option<Sender<Request>>,
+    /// The top runs as each fetch comes back, or `None` when it failed.
+    receiver: Option<Mutex<Receiver<Option<Vec<TopEntry>>>>>,
+}
+
+fn pending_file() -> String {
+    format!("{}/{}", RUN_DIRECTORY, PENDING_FILE)
+}
+
+fn load_pending() -> Vec<Submission> {
+    storage::read_to_string(&pending_file())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_pending(pending: &[Submission]) {
+    let saved = serde_json::to_string(pending)
+        .map_err(|e| e.to_string())
+        .and_then(|json| storage::write(&pending_file(), &json));
+    if let Err(e) = saved {
+        warn!("failed to save {}: {}", pending_file(), e);
+    }
+}
+
+/// Whether the server is done with `submission`. One it turned down won't
+/// get through on a retry either, so only failing to reach it keeps it
+/// waiting.
+fn submit(agent: &ureq::Agent, url: &str, submission: &Submission) -> bool {
+    let json = match serde_json::to_string(submission) {
+        Ok(json) => json,
+        Err(_) => return true,
+    };
+
+    match agent
+        .post(&format!("{}/scores", url))
+        .set("Content-Type", "application/json")
+        .send_string(&json)
+    {
+        Ok(_) => true,
+        Err(ureq::Error::Status(code, _)) => {
+            warn!("the leaderboard turned down a score with {}", code);
+            true
+        }
+        Err(e) => {
+            info!("leaderboard unreachable, keeping the score for later: {}", e);
+            false
+        }
+    }
+}
+
+/// Send what's waiting, oldest first, up to the first that can't get
+/// through, since the rest wouldn't either. Whether any went.
+fn flush(agent: &ureq::Agent, url: &str, pending: &mut Vec<Submission>) -> bool {
+    let sent = pending.iter().take_while(|submission| submit(agent, url, submission)).count();
+    pending.drain(..sent);
+    sent > 0
+}
+
+fn fetch_top(agent: &ureq::Agent, url: &str) -> Option<Vec<TopEntry>> {
+    let text = agent
+        .get(&format!("{}/scores/top", url))
+        .query("version", GAME_VERSION)
+        .query("limit", &TOP_ENTRIES.to_string())
+        .call()
+        .map_err(|e| e.to_string())
+        .and_then(|response| response.into_string().map_err(|e| e.to_string()))
+        .map_err(|e| info!("leaderboard unreachable: {}", e))
+        .ok()?;
+
+    serde_json::from_str(&text)
+        .map_err(|e| warn!("ignoring the leaderboard's top runs: {}", e))
+        .ok()
+}
+
+impl OnlineLeaderboard {
+    fn connect() -> OnlineLeaderboard {
+        let url = match env::var(URL_VARIABLE) {
+            Ok(url) => url.trim_end_matches('/').to_string(),
+            Err(_) => {
+                info!("{} is not set, online leaderboard disabled", URL_VARIABLE);
+                return OnlineLeaderboard{
+                    sender: None,
+                    receiver: None,
+                };
+            }
+        };
+
+        let (sender, requests) = mpsc::channel::<Request>();
+        let (results, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let agent = ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(TIMEOUT_SECONDS))
+                .build();
+            let mut pending = load_pending();
+
+            // Anything still waiting goes out after each request
+            for request in requests {
+                let mut is_changed = match request {
+                    Request::Submit(submission) => {
+                        pending.push(submission);
+                        true
+                    }
+                    // The menu is waiting on this one
+                    Request::FetchTop => {
+                        let _ = results.send(fetch_top(&agent, &url));
+                        false
+                    }
+                };
+
+                is_changed |= flush(&agent, &url, &mut pending);
+                if is_changed {
+                    save_pending(&pending);
+                }
+            }
+        });
+
+        OnlineLeaderboard{
+            sender: Some(sender),
+            receiver: Some(Mutex::new(receiver)),
+        }
+    }
+
+    fn send(&self, request: Request) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(request);
+        }
+    }
+
+    /// The top runs, if a fetch has come back since last asked.
+    fn top(&self) -> Option<Option<Vec<TopEntry>>> {
+        self.receiver.as_ref()?.lock().ok()?.try_recv().ok()
+    }
+}
+
+#[derive(Component)]
+pub struct OnlinePanelText;
+
+fn online_label(top: &[TopEntry]) -> String {
+    let mut label = String::from("Online top runs");
+    for (rank, entry) in top.iter().enumerate() {
+        label.push_str(&format!("\n{}. {} on {} (seed {})", rank + 1, entry.score, entry.stage, entry.seed));
+    }
+    label
+}
+
+fn offline_label(leaderboard: &Leaderboard) -> String {
+    let mut label = String::from("Best runs (offline)");
+    for (rank, entry) in leaderboard.on_version().take(TOP_ENTRIES).enumerate() {
+        label.push_str(&format!("\n{}. {} on {} (seed {})", rank + 1, entry.score, entry.stage, entry.seed));
+    }
+    label
+}
+
+fn spawn_online_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    online: Res<OnlineLeaderboard>,
+    leaderboard: Res<Leaderboard>,
+) {
+    let label = if online.sender.is_some() {
+        online.send(Request::FetchTop);
+        String::from("Online top runs\nloading...")
+    }
+    else {
+        offline_label(&leaderboard)
+    };
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(12.0),
+                    right: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                label,
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(OnlinePanelText)
+        .insert(MenuRoot);
+}
+
+fn update_online_panel(
+    online: Res<OnlineLeaderboard>,
+    leaderboard: Res<Leaderboard>,
+    mut texts: Query<&mut Text, With<OnlinePanelText>>,
+) {
+    let label = match online.top() {
+        Some(Some(top)) => online_label(&top),
+        Some(None) => offline_label(&leaderboard),
+        None => return,
+    };
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+}
+
+/// Send the run that just ended, once it's been scored.
+fn submit_score(
+    online: Res<OnlineLeaderboard>,
+    leaderboard: Res<Leaderboard>,
+    mut submitted: Local<Option<u64>>,
+) {
+    let entry = match leaderboard.latest() {
+        Some(entry) if *submitted != Some(entry.finished_at) => entry,
+        _ => return,
+    };
+
+    *submitted = Some(entry.finished_at);
+    online.send(Request::Submit(Submission::of(entry)));
+}
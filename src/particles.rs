@@ -0,0 +1,237 @@
+//! Short lived sprite particles: ichor when monsters are hurt or killed, a
+//! flash when one is executed, a puff when a blast goes off and dust kicked up
+//! by moving players. Particles are pooled. Spent ones are hidden and handed
+//! out again rather than despawned, and past the pool's size new bursts are
+//! cut short.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::hit_flash::MonsterHurtEvent;
+use crate::loading::GameAssets;
+use crate::{Blast, MonsterKind, Player, Position, Velocity};
+
+
+const PARTICLE_POOL_SIZE: usize = 512;
+const PARTICLE_DEPTH: f32       = 5.0;
+
+const ICHOR_PARTICLES: usize = 8;
+const ICHOR_SPEED: f32       = 90.0;
+const ICHOR_SIZE: f32        = 3.0;
+const ICHOR_SECONDS: f32     = 0.5;
+const ICHOR_GRAVITY: f32     = 240.0;
+
+const PUFF_PARTICLES: usize = 12;
+const PUFF_SPEED: f32       = 60.0;
+const PUFF_SIZE: f32        = 4.0;
+const PUFF_SECONDS: f32     = 0.4;
+
+const EXECUTION_PARTICLES: usize = 24;
+const EXECUTION_SPEED: f32       = 160.0;
+const EXECUTION_SIZE: f32        = 5.0;
+const EXECUTION_SECONDS: f32     = 0.6;
+
+const DUST_PERIOD_SECONDS: f32 = 0.25;
+const DUST_PARTICLES: usize    = 2;
+const DUST_SPEED: f32          = 15.0;
+const DUST_SIZE: f32           = 3.0;
+const DUST_SECONDS: f32        = 0.6;
+const DUST_GRAVITY: f32        = -10.0;
+
+/// Sent to throw out a handful of particles in every direction.
+#[derive(Clone, Copy)]
+pub struct ParticleBurst {
+    position: Vec2,
+    color: Color,
+    count: usize,
+    speed: f32,
+    size: f32,
+    seconds: f32,
+    /// Pull toward the bottom of the screen, or up when negative.
+    gravity: f32,
+}
+
+impl ParticleBurst {
+    pub fn ichor(position: Vec2, kind: MonsterKind) -> ParticleBurst {
+        ParticleBurst{
+            position,
+            color: kind.tint(),
+            count: ICHOR_PARTICLES,
+            speed: ICHOR_SPEED,
+            size: ICHOR_SIZE,
+            seconds: ICHOR_SECONDS,
+            gravity: ICHOR_GRAVITY,
+        }
+    }
+
+    fn puff(position: Vec2) -> ParticleBurst {
+        ParticleBurst{
+            position,
+            color: Color::rgba(1.0, 0.95, 0.8, 0.8),
+            count: PUFF_PARTICLES,
+            speed: PUFF_SPEED,
+            size: PUFF_SIZE,
+            seconds: PUFF_SECONDS,
+            gravity: 0.0,
+        }
+    }
+
+    /// A wide flash of white for a monster finished off by Execute.
+    pub fn execution(position: Vec2) -> ParticleBurst {
+        ParticleBurst{
+            position,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.9),
+            count: EXECUTION_PARTICLES,
+            speed: EXECUTION_SPEED,
+            size: EXECUTION_SIZE,
+            seconds: EXECUTION_SECONDS,
+            gravity: 0.0,
+        }
+    }
+
+    fn dust(position: Vec2) -> ParticleBurst {
+        ParticleBurst{
+            position,
+            color: Color::rgba(0.6, 0.5, 0.4, 0.5),
+            count: DUST_PARTICLES,
+            speed: DUST_SPEED,
+            size: DUST_SIZE,
+            seconds: DUST_SECONDS,
+            gravity: DUST_GRAVITY,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    gravity: f32,
+    lifetime: Timer,
+    alpha: f32,
+}
+
+pub struct DustTimer(Timer);
+
+impl Default for DustTimer {
+    fn default() -> DustTimer {
+        DustTimer(Timer::from_seconds(DUST_PERIOD_SECONDS, true))
+    }
+}
+
+/// Spent particles ready to be reused, and how many exist in all.
+#[derive(Default)]
+pub struct ParticlePool {
+    free: Vec<Entity>,
+    size: usize,
+}
+
+pub fn burst_on_hits(
+    mut hurt_reader: EventReader<MonsterHurtEvent>,
+    monsters: Query<(&Position, &MonsterKind)>,
+    blasts: Query<&Position, Added<Blast>>,
+    mut bursts: EventWriter<ParticleBurst>,
+) {
+    for MonsterHurtEvent(entity) in hurt_reader.iter() {
+        if let Ok((p, kind)) = monsters.get(*entity) {
+            bursts.send(ParticleBurst::ichor(p.current, *kind));
+        }
+    }
+
+    for p in blasts.iter() {
+        bursts.send(ParticleBurst::puff(p.current));
+    }
+}
+
+pub fn kick_up_dust(
+    time: Res<Time>,
+    mut timer: Local<DustTimer>,
+    players: Query<(&Position, &Velocity), With<Player>>,
+    mut bursts: EventWriter<ParticleBurst>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for (p, v) in players.iter().filter(|(_, v)| !v.is_zero()) {
+        bursts.send(ParticleBurst::dust(p.current - v.direction * DUST_SIZE));
+    }
+}
+
+pub fn emit_particles(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut pool: ResMut<ParticlePool>,
+    mut bursts: EventReader<ParticleBurst>,
+    mut particles: Query<(&mut Particle, &mut Position, &mut TextureAtlasSprite, &mut Visibility)>,
+) {
+    for burst in bursts.iter() {
+        for i in 0..burst.count {
+            // Spread evenly with a little jitter
+            let angle = (i as f32 + rand::random::<f32>()) / burst.count as f32 * TAU;
+            let speed = burst.speed * (0.5 + rand::random::<f32>() * 0.5);
+            let particle = Particle{
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                gravity: burst.gravity,
+                lifetime: Timer::from_seconds(burst.seconds, false),
+                alpha: burst.color.a(),
+            };
+            let sprite = TextureAtlasSprite{
+                index: sprites.blast(),
+                color: burst.color,
+                custom_size: Some(Vec2::splat(burst.size)),
+                ..Default::default()
+            };
+
+            // Reuse a spent particle, make a new one while the pool has room,
+            // or give up on the rest of the burst
+            if let Some(entity) = pool.free.pop() {
+                if let Ok((mut old, mut p, mut old_sprite, mut visibility)) = particles.get_mut(entity) {
+                    *old = particle;
+                    *p = Position::new(burst.position);
+                    *old_sprite = sprite;
+                    visibility.is_visible = true;
+                }
+            }
+            else if pool.size < PARTICLE_POOL_SIZE {
+                pool.size += 1;
+                commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: sprites.atlas(),
+                        sprite,
+                        transform: Transform::from_xyz(0.0, 0.0, PARTICLE_DEPTH),
+                        ..Default::default()
+                    })
+                    .insert(particle)
+                    .insert(Position::new(burst.position));
+            }
+            else {
+                break;
+            }
+        }
+    }
+}
+
+pub fn update_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(Entity, &mut Particle, &mut Position, &mut TextureAtlasSprite, &mut Visibility)>,
+) {
+    let seconds = time.delta_seconds();
+
+    for (entity, mut particle, mut p, mut sprite, mut visibility) in particles.iter_mut() {
+        if !visibility.is_visible {
+            continue;
+        }
+
+        if particle.lifetime.tick(time.delta()).finished() {
+            visibility.is_visible = false;
+            pool.free.push(entity);
+            continue;
+        }
+
+        particle.velocity.y -= particle.gravity * seconds;
+        p.apply(particle.velocity * seconds);
+        sprite.color.set_a(particle.alpha * (1.0 - particle.lifetime.percent()));
+    }
+}
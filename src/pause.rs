@@ -0,0 +1,135 @@
+//! The pause screen. Tab stops the run and lists what the players carry: the
+//! weapons in the loadout and every upgrade taken so far, with what the next
+//! level of each would be and the tag the build leans toward. The run also
+//! pauses itself when the window loses focus, and a click on the window picks
+//! it back up.
+
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+use crate::input::{Action, Controls};
+use crate::loading::GameAssets;
+use crate::menu::menu_text;
+use crate::nav::{FocusRing, MenuAction};
+use crate::progression::Progression;
+use crate::seed::{seed_label, SeedText};
+use crate::synergy::tag_label;
+use crate::{AppState, Loadout, WorldSeed};
+
+
+const PAUSE_TITLE_SIZE: f32   = 56.0;
+const PAUSE_HEADING_SIZE: f32 = 28.0;
+const PAUSE_ENTRY_SIZE: f32   = 20.0;
+const PAUSE_HINT_SIZE: f32    = 18.0;
+
+const PAUSE_MUTED_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
+
+#[derive(Component)]
+pub struct PauseRoot;
+
+pub fn pause_game(
+    mut controls: Controls,
+    mut focus_reader: EventReader<WindowFocused>,
+    mut state: ResMut<State<AppState>>,
+) {
+    // Alt-tabbing away shouldn't get anyone killed
+    let is_unfocused = focus_reader.iter().any(|event| !event.focused);
+    if (is_unfocused || controls.just_pressed(0, Action::Pause)) && state.push(AppState::Paused).is_ok() {
+        controls.clear_just_pressed(0, Action::Pause);
+    }
+}
+
+pub fn resume_game(
+    mut controls: Controls,
+    mut mouse_input: ResMut<Input<MouseButton>>,
+    mut actions: EventReader<MenuAction>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let is_cancelled = actions.iter().any(|action| matches!(action, MenuAction::Cancel));
+    let is_clicked = mouse_input.just_pressed(MouseButton::Left);
+    if (is_cancelled || is_clicked || controls.just_pressed(0, Action::Pause)) && state.pop().is_ok() {
+        controls.clear();
+        // Don't let the click also walk the player somewhere
+        mouse_input.reset(MouseButton::Left);
+    }
+}
+
+pub fn spawn_pause_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    loadout: Res<Loadout>,
+    progression: Res<Progression>,
+    world_seed: Res<WorldSeed>,
+) {
+    let font = assets.font();
+
+    let weapons: Vec<_> = loadout
+        .0
+        .iter()
+        .map(|weapon| {
+            format!(
+                "{} ({}){} - {}",
+                weapon.name(),
+                weapon.damage_type().name(),
+                tag_label(weapon.tags()),
+                weapon.description(),
+            )
+        })
+        .collect();
+
+    let mut upgrades: Vec<_> = progression
+        .upgrades()
+        .map(|(upgrade, level)| {
+            format!("{} {} - {} (next: level {})", upgrade.name(), level, upgrade.description(), level + 1)
+        })
+        .collect();
+    if upgrades.is_empty() {
+        upgrades.push("none yet".to_string());
+    }
+    if let Some(tag) = progression.archetype() {
+        upgrades.push(format!("building toward {}", tag.name()));
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(PauseRoot)
+        .insert(FocusRing::new(0, 0))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text("PAUSED", &font, PAUSE_TITLE_SIZE, Color::WHITE));
+
+            parent.spawn_bundle(menu_text("Weapons", &font, PAUSE_HEADING_SIZE, Color::WHITE));
+            for weapon in &weapons {
+                parent.spawn_bundle(menu_text(weapon, &font, PAUSE_ENTRY_SIZE, PAUSE_MUTED_COLOR));
+            }
+
+            parent.spawn_bundle(menu_text("Upgrades", &font, PAUSE_HEADING_SIZE, Color::WHITE));
+            for upgrade in &upgrades {
+                parent.spawn_bundle(menu_text(upgrade, &font, PAUSE_ENTRY_SIZE, PAUSE_MUTED_COLOR));
+            }
+
+            parent
+                .spawn_bundle(menu_text(&seed_label(world_seed.value), &font, PAUSE_HINT_SIZE, PAUSE_MUTED_COLOR))
+                .insert(SeedText);
+            parent.spawn_bundle(menu_text("Click, Tab or Escape to resume", &font, PAUSE_HINT_SIZE, PAUSE_MUTED_COLOR));
+        });
+}
+
+pub fn despawn_pause_screen(
+    mut commands: Commands,
+    roots: Query<Entity, With<PauseRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
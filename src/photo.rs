@@ -0,0 +1,121 @@
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+
+use crate::camera::{CameraFocus, MainCamera};
+use crate::capture::{save_png, FrameSource};
+use crate::input::{Action, Controls};
+use crate::AppState;
+
+
+const PHOTO_PAN_SPEED: f32  = 400.0;
+const PHOTO_ZOOM_RATE: f32  = 1.5;
+const PHOTO_WHEEL_STEP: f32 = 0.1;
+const PHOTO_MIN_ZOOM: f32   = 0.25;
+const PHOTO_MAX_ZOOM: f32   = 4.0;
+
+/// UI nodes hidden while photo mode is open.
+#[derive(Default)]
+pub struct HiddenHud(Vec<Entity>);
+
+pub fn enter_photo_mode(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::P) && state.push(AppState::PhotoMode).is_ok() {
+        keyboard_input.clear_just_pressed(KeyCode::P);
+    }
+}
+
+pub fn hide_hud(
+    mut hidden: ResMut<HiddenHud>,
+    mut nodes: Query<(Entity, &mut Visibility), With<Node>>,
+) {
+    for (entity, mut visibility) in nodes.iter_mut() {
+        if visibility.is_visible {
+            visibility.is_visible = false;
+            hidden.0.push(entity);
+        }
+    }
+}
+
+pub fn show_hud(
+    mut hidden: ResMut<HiddenHud>,
+    mut nodes: Query<&mut Visibility, With<Node>>,
+) {
+    for entity in hidden.0.drain(..) {
+        if let Ok(mut visibility) = nodes.get_mut(entity) {
+            visibility.is_visible = true;
+        }
+    }
+}
+
+pub fn photo_camera(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut focus: ResMut<CameraFocus>,
+    mut cameras: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    // Pan with either set of movement keys
+    let mut pan = Vec2::ZERO;
+    if keyboard_input.any_pressed([KeyCode::Right, KeyCode::D]) {
+        pan.x += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::Left, KeyCode::A]) {
+        pan.x -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::Up, KeyCode::W]) {
+        pan.y += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::Down, KeyCode::S]) {
+        pan.y -= 1.0;
+    }
+    let zoom = focus.zoom;
+    focus.center += pan.normalize_or_zero() * PHOTO_PAN_SPEED * zoom * time.delta_seconds();
+
+    // Drag the world around with the left mouse button
+    for motion in mouse_motion.iter() {
+        if mouse_input.pressed(MouseButton::Left) {
+            focus.center += Vec2::new(-motion.delta.x, motion.delta.y) * zoom;
+        }
+    }
+
+    // Zoom with the wheel or +/- keys
+    let mut factor = 1.0;
+    for wheel in mouse_wheel.iter() {
+        factor *= 1.0 - wheel.y * PHOTO_WHEEL_STEP;
+    }
+    if keyboard_input.any_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        factor *= 1.0 / (1.0 + PHOTO_ZOOM_RATE * time.delta_seconds());
+    }
+    if keyboard_input.any_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        factor *= 1.0 + PHOTO_ZOOM_RATE * time.delta_seconds();
+    }
+    focus.zoom = (focus.zoom * factor).clamp(PHOTO_MIN_ZOOM, PHOTO_MAX_ZOOM);
+
+    for mut projection in cameras.iter_mut() {
+        projection.scale = focus.zoom;
+    }
+}
+
+pub fn photo_controls(
+    mut controls: Controls,
+    mut state: ResMut<State<AppState>>,
+    frame_source: FrameSource,
+) {
+    if controls.keyboard().just_pressed(KeyCode::Space) {
+        if let Some(frame) = frame_source.capture(1.0) {
+            save_png(&frame, "photo");
+        }
+    }
+
+    if controls.keyboard().just_pressed(KeyCode::P) || controls.just_pressed(0, Action::Cancel) {
+        controls.keyboard().clear_just_pressed(KeyCode::P);
+        controls.clear_just_pressed(0, Action::Cancel);
+        if let Err(e) = state.pop() {
+            warn!("ignoring leaving photo mode: {}", e);
+        }
+    }
+}
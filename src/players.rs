@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+
+use crate::{Player, Position};
+
+
+/// Current positions of every player, which may be none at all.
+pub fn player_positions(
+    players: &Query<&Position, With<Player>>,
+) -> Vec<Vec2> {
+    players.iter().map(|p| p.current).collect()
+}
+
+/// Midpoint of all players.
+pub fn player_centroid(positions: &[Vec2]) -> Option<Vec2> {
+    if positions.is_empty() {
+        return None;
+    }
+
+    let sum = positions.iter().fold(Vec2::ZERO, |sum, p| sum + *p);
+    Some(sum / positions.len() as f32)
+}
+
+/// The player closest to a point.
+pub fn nearest_player(positions: &[Vec2], point: Vec2) -> Option<Vec2> {
+    positions
+        .iter()
+        .copied()
+        .min_by(|a, b| a.distance_squared(point).total_cmp(&b.distance_squared(point)))
+}
+
+/// Any one player, chosen at random so spawns spread across all of them.
+pub fn random_player(positions: &[Vec2]) -> Option<Vec2> {
+    if positions.is_empty() {
+        return None;
+    }
+
+    Some(positions[rand::random::<usize>() % positions.len()])
+}
@@ -0,0 +1,588 @@
+//! Experience, levels and the upgrades they bring. Every kill is worth a
+//! point of experience, shared by all players. Each level pauses the run on a
+//! screen offering a few upgrades to pick from. A limited number of times a
+//! run, the players can reroll the offer, skip it for bonus experience or
+//! banish an upgrade so it's never offered again. Fortune adds one of each.
+//! Daily runs can't reroll, so everyone playing the day picks from the same
+//! offers.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::args::Weapon;
+use crate::autopilot::DemoMode;
+use crate::content::{self, Content};
+use crate::daily::Daily;
+use crate::death::Dying;
+use crate::effective_stats::StatsChangedEvent;
+use crate::health::Health;
+use crate::input::{Action, Bindings, Controls};
+use crate::loading::GameAssets;
+use crate::menu::menu_text;
+use crate::mods::{self, PassiveDef};
+use crate::nav::{FocusRing, Focusable, MenuAction, NAV_COLOR};
+use crate::run_stats::RunStats;
+use crate::synergy::{tag_label, Offers, Tag};
+use crate::{AppState, Loadout, MonsterStats, NewGameEvent, Player};
+
+
+const FIRST_LEVEL_EXPERIENCE: u32 = 5;
+const LEVEL_EXPERIENCE_STEP: u32  = 5;
+const OFFER_SIZE: usize           = 3;
+/// How much more likely an offer is for each level or weapon sharing a tag
+/// with it.
+const SYNERGY_WEIGHT: f32         = 0.5;
+
+const BASE_REROLLS: u32    = 1;
+const BASE_SKIPS: u32      = 1;
+const BASE_BANISHES: u32   = 1;
+const SKIP_EXPERIENCE: u32 = 3;
+
+const SWIFTNESS_SPEED_BONUS: f32    = 0.1;
+const REACH_RADIUS_BONUS: f32       = 0.15;
+const VITALITY_HEALTH: f32          = 20.0;
+const SEEKER_RANGE_BONUS: f32       = 0.2;
+const VOLLEY_MAX_BLASTS: u32        = 4;
+const EXECUTE_THRESHOLD_STEP: f32   = 0.1;
+const EXECUTE_MAX_THRESHOLD: f32    = 0.3;
+const LIFESTEAL_FRACTION: f32       = 0.05;
+const LIFESTEAL_CAP_PER_SECOND: f32 = 4.0;
+const THORNS_PULSE_CHANCE: f32      = 0.25;
+
+const LEVEL_UP_TITLE_SIZE: f32 = 56.0;
+const LEVEL_UP_OFFER_SIZE: f32 = 28.0;
+const LEVEL_UP_HINT_SIZE: f32  = 18.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Upgrade {
+    Swiftness,
+    Reach,
+    Vitality,
+    Fortune,
+    Seeker,
+    Volley,
+    Execute,
+    Lifesteal,
+    Thorns,
+    /// A passive added by a mod, by its place among them.
+    Modded(u8),
+}
+
+impl Serialize for Upgrade {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        content::serialize(*self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Upgrade {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Upgrade, D::Error> {
+        content::deserialize(deserializer)
+    }
+}
+
+impl Content for Upgrade {
+    const SORT: &'static str = "upgrade";
+
+    fn id(self) -> &'static str {
+        match self {
+            Upgrade::Swiftness => "swiftness",
+            Upgrade::Reach => "reach",
+            Upgrade::Vitality => "vitality",
+            Upgrade::Fortune => "fortune",
+            Upgrade::Seeker => "seeker",
+            Upgrade::Volley => "volley",
+            Upgrade::Execute => "execute",
+            Upgrade::Lifesteal => "lifesteal",
+            Upgrade::Thorns => "thorns",
+            Upgrade::Modded(id) => &mods::passive(id).id,
+        }
+    }
+
+    /// The built-in upgrades and those from mods.
+    fn all() -> Vec<Upgrade> {
+        Upgrade::ALL.into_iter().chain(mods::passives()).collect()
+    }
+}
+
+impl Upgrade {
+    const ALL: [Upgrade; 9] = [
+        Upgrade::Swiftness,
+        Upgrade::Reach,
+        Upgrade::Vitality,
+        Upgrade::Fortune,
+        Upgrade::Seeker,
+        Upgrade::Volley,
+        Upgrade::Execute,
+        Upgrade::Lifesteal,
+        Upgrade::Thorns,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Upgrade::Swiftness => "Swiftness",
+            Upgrade::Reach => "Reach",
+            Upgrade::Vitality => "Vitality",
+            Upgrade::Fortune => "Fortune",
+            Upgrade::Seeker => "Seeker",
+            Upgrade::Volley => "Volley",
+            Upgrade::Execute => "Execute",
+            Upgrade::Lifesteal => "Lifesteal",
+            Upgrade::Thorns => "Thorns",
+            Upgrade::Modded(id) => &mods::passive(id).name,
+        }
+    }
+
+    /// The level past which taking it again does nothing, if there is one.
+    pub fn max_level(self) -> Option<u32> {
+        match self {
+            Upgrade::Volley => Some(VOLLEY_MAX_BLASTS - 1),
+            Upgrade::Execute => Some((EXECUTE_MAX_THRESHOLD / EXECUTE_THRESHOLD_STEP).round() as u32),
+            Upgrade::Thorns => Some((1.0 / THORNS_PULSE_CHANCE).ceil() as u32),
+            _ => None,
+        }
+    }
+
+    pub fn tags(self) -> &'static [Tag] {
+        match self {
+            Upgrade::Swiftness => &[Tag::Speed],
+            Upgrade::Reach => &[Tag::Area],
+            Upgrade::Vitality => &[Tag::Duration],
+            Upgrade::Fortune => &[],
+            Upgrade::Seeker => &[Tag::Projectile],
+            Upgrade::Volley => &[Tag::Projectile, Tag::Area],
+            Upgrade::Execute => &[],
+            Upgrade::Lifesteal => &[Tag::Duration],
+            Upgrade::Thorns => &[Tag::Area, Tag::Duration],
+            Upgrade::Modded(id) => &mods::passive(id).tags,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Upgrade::Swiftness => "move 10% faster",
+            Upgrade::Reach => "blasts 15% wider",
+            Upgrade::Vitality => "20 more max health",
+            Upgrade::Fortune => "one more reroll, skip and banish",
+            Upgrade::Seeker => "blasts drop on the biggest crowd nearby, reaching 20% further each level",
+            Upgrade::Volley => "one more blast in a ring around you, up to four",
+            Upgrade::Execute => "blasts finish off tough monsters under 10% more health, up to 30%",
+            Upgrade::Lifesteal => "heal 5% of blast damage dealt, up to 4 health a second",
+            Upgrade::Thorns => "monsters that hurt you take a hit back, with a 25% chance a level to throw back all close by",
+            Upgrade::Modded(id) => &mods::passive(id).description,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Progression {
+    experience: u32,
+    level: u32,
+    /// Kills already turned into experience.
+    counted_kills: u32,
+    upgrades: BTreeMap<Upgrade, u32>,
+    banished: Vec<Upgrade>,
+    rerolls: u32,
+    skips: u32,
+    banishes: u32,
+    offers: Vec<Upgrade>,
+    is_banishing: bool,
+}
+
+impl Progression {
+    fn new() -> Progression {
+        Progression{
+            rerolls: BASE_REROLLS,
+            skips: BASE_SKIPS,
+            banishes: BASE_BANISHES,
+            ..Default::default()
+        }
+    }
+
+    fn next_level_experience(&self) -> u32 {
+        FIRST_LEVEL_EXPERIENCE + self.level * LEVEL_EXPERIENCE_STEP
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// How far along to the next level, from zero to one.
+    pub fn experience_fraction(&self) -> f32 {
+        (self.experience as f32 / self.next_level_experience() as f32).min(1.0)
+    }
+
+    pub fn level_of(&self, upgrade: Upgrade) -> u32 {
+        self.upgrades.get(&upgrade).copied().unwrap_or_default()
+    }
+
+    fn is_maxed(&self, upgrade: Upgrade) -> bool {
+        upgrade.max_level().is_some_and(|max| self.level_of(upgrade) >= max)
+    }
+
+    /// A random upgrade with room to go, for chests, the merchant and the
+    /// sandbox. Most upgrades have no top level, so there's always one.
+    pub fn random_upgrade(&self) -> Upgrade {
+        let open: Vec<_> = Upgrade::all().into_iter().filter(|&u| !self.is_maxed(u)).collect();
+        open[rand::random::<usize>() % open.len()]
+    }
+
+    /// Every upgrade taken this run, with its level.
+    pub fn upgrades(&self) -> impl Iterator<Item = (Upgrade, u32)> + '_ {
+        self.upgrades.iter().map(|(&upgrade, &level)| (upgrade, level))
+    }
+
+    /// What the passives from mods add up to, one level at a time.
+    fn modded_bonus(&self, bonus: fn(&PassiveDef) -> f32) -> f32 {
+        self.upgrades
+            .iter()
+            .filter_map(|(&upgrade, &level)| match upgrade {
+                Upgrade::Modded(id) => Some(bonus(mods::passive(id)) * level as f32),
+                _ => None,
+            })
+            .sum()
+    }
+
+    pub fn move_speed_factor(&self) -> f32 {
+        1.0 + SWIFTNESS_SPEED_BONUS * self.level_of(Upgrade::Swiftness) as f32 + self.modded_bonus(|p| p.move_speed)
+    }
+
+    pub fn blast_radius_factor(&self) -> f32 {
+        1.0 + REACH_RADIUS_BONUS * self.level_of(Upgrade::Reach) as f32 + self.modded_bonus(|p| p.blast_radius)
+    }
+
+    /// How far past the usual aiming range blasts look for a crowd, once
+    /// Seeker has been taken.
+    pub fn seeker_range_factor(&self) -> Option<f32> {
+        match self.level_of(Upgrade::Seeker) {
+            0 => None,
+            level => Some(1.0 + SEEKER_RANGE_BONUS * (level - 1) as f32),
+        }
+    }
+
+    /// How many blasts each shot fires, counting the aimed one.
+    pub fn blast_count(&self) -> u32 {
+        (1 + self.level_of(Upgrade::Volley)).min(VOLLEY_MAX_BLASTS)
+    }
+
+    /// The share of its health under which a tough monster or horde dies to
+    /// any hit, or zero without Execute.
+    pub fn execute_threshold(&self) -> f32 {
+        (EXECUTE_THRESHOLD_STEP * self.level_of(Upgrade::Execute) as f32).min(EXECUTE_MAX_THRESHOLD)
+    }
+
+    /// The share of blast damage dealt that heals the player, and the most
+    /// it can heal each second.
+    pub fn lifesteal(&self) -> (f32, f32) {
+        let level = self.level_of(Upgrade::Lifesteal) as f32;
+        (LIFESTEAL_FRACTION * level, LIFESTEAL_CAP_PER_SECOND * level)
+    }
+
+    /// The chance that a hit on a player with Thorns also throws back the
+    /// monsters around them, or none without Thorns.
+    pub fn thorns_pulse_chance(&self) -> Option<f32> {
+        match self.level_of(Upgrade::Thorns) {
+            0 => None,
+            level => Some((THORNS_PULSE_CHANCE * level as f32).min(1.0)),
+        }
+    }
+
+    /// One more reroll for the level up screen, bought from the merchant.
+    pub fn add_reroll(&mut self) {
+        self.rerolls += 1;
+    }
+
+    /// How far the build has gone into `tag`, counting upgrade levels and
+    /// the weapons carried.
+    fn investment(&self, tag: Tag, weapons: &[Weapon]) -> u32 {
+        let levels: u32 = self.upgrades().filter(|(u, _)| u.tags().contains(&tag)).map(|(_, level)| level).sum();
+        levels + weapons.iter().filter(|w| w.tags().contains(&tag)).count() as u32
+    }
+
+    /// The tag the upgrades taken lean toward most, once there's a lean.
+    pub fn archetype(&self) -> Option<Tag> {
+        Tag::ALL
+            .into_iter()
+            .map(|tag| (tag, self.investment(tag, &[])))
+            .filter(|&(_, invested)| invested > 0)
+            .max_by_key(|&(_, invested)| invested)
+            .map(|(tag, _)| tag)
+    }
+
+    /// Offer a fresh pick of the upgrades that haven't been banished or
+    /// maxed out, leaning toward the build's tags unless `offers` says
+    /// otherwise.
+    fn roll_offers(&mut self, offers: Offers, weapons: &[Weapon]) {
+        let pool: Vec<_> = Upgrade::all()
+            .into_iter()
+            .filter(|&u| !self.banished.contains(&u) && !self.is_maxed(u))
+            .collect();
+        let weight = |upgrade: &Upgrade| match offers {
+            Offers::Synergy => {
+                let shared: u32 = upgrade.tags().iter().map(|&tag| self.investment(tag, weapons)).sum();
+                1.0 + SYNERGY_WEIGHT * shared as f32
+            }
+            Offers::Random => 1.0,
+        };
+
+        let mut rng = rand::thread_rng();
+        let mut picked: Vec<_> = match pool.choose_multiple_weighted(&mut rng, OFFER_SIZE, weight) {
+            Ok(picked) => picked.copied().collect(),
+            Err(_) => Vec::new(),
+        };
+        picked.shuffle(&mut rng);
+        self.offers = picked;
+        self.is_banishing = false;
+    }
+
+    fn take(&mut self, upgrade: Upgrade) {
+        *self.upgrades.entry(upgrade).or_default() += 1;
+        if upgrade == Upgrade::Fortune {
+            self.rerolls += 1;
+            self.skips += 1;
+            self.banishes += 1;
+        }
+        self.offers.clear();
+    }
+}
+
+/// Take an upgrade for every player and apply it straight away.
+pub fn grant_upgrade(
+    progression: &mut Progression,
+    upgrade: Upgrade,
+    players: &mut Query<&mut Health, With<Player>>,
+    stats_writer: &mut EventWriter<StatsChangedEvent>,
+) {
+    progression.take(upgrade);
+    let raise = match upgrade {
+        Upgrade::Vitality => VITALITY_HEALTH,
+        Upgrade::Modded(id) => mods::passive(id).max_health,
+        _ => 0.0,
+    };
+    if raise > 0.0 {
+        for mut health in players.iter_mut() {
+            health.raise_max(raise);
+        }
+    }
+    stats_writer.send(StatsChangedEvent);
+}
+
+#[derive(Component)]
+pub struct LevelUpRoot;
+
+#[derive(Component)]
+pub struct OfferText(usize);
+
+#[derive(Component)]
+pub struct LevelUpHint;
+
+fn offer_label(progression: &Progression, slot: usize) -> String {
+    match progression.offers.get(slot) {
+        Some(&upgrade) => format!(
+            "{}: {} {}{} - {}",
+            slot + 1,
+            upgrade.name(),
+            progression.level_of(upgrade) + 1,
+            tag_label(upgrade.tags()),
+            upgrade.description(),
+        ),
+        None => String::new(),
+    }
+}
+
+fn hint_label(progression: &Progression, daily: &Daily, bindings: &Bindings) -> String {
+    let choose = format!("{} or {}", bindings.choices_name(), bindings.key_name(Action::Confirm));
+    let banish = bindings.key_name(Action::Banish);
+    if progression.is_banishing {
+        return format!("Banish which? {}, or {} to cancel", choose, banish);
+    }
+
+    let skip = format!("{} to skip ({})", bindings.key_name(Action::Skip), progression.skips);
+    let banish = format!("{} to banish ({})", banish, progression.banishes);
+    if daily.run_day.is_some() {
+        format!("{} to choose, {}, {}", choose, skip, banish)
+    }
+    else {
+        let reroll = format!("{} to reroll ({})", bindings.key_name(Action::Reroll), progression.rerolls);
+        format!("{} to choose, {}, {}, {}", choose, reroll, skip, banish)
+    }
+}
+
+pub fn reset_progression(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut progression: ResMut<Progression>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        *progression = Progression::new();
+    }
+}
+
+pub fn gain_experience(
+    mut progression: ResMut<Progression>,
+    monster_stats: Res<MonsterStats>,
+    demo: Res<DemoMode>,
+    offers: Res<Offers>,
+    loadout: Res<Loadout>,
+    dying: Query<(), With<Dying>>,
+    mut state: ResMut<State<AppState>>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+    mut run_stats: ResMut<RunStats>,
+    mut players: Query<&mut Health, With<Player>>,
+) {
+    let kills = monster_stats.killed();
+    progression.experience += kills.saturating_sub(progression.counted_kills);
+    progression.counted_kills = kills;
+
+    let needed = progression.next_level_experience();
+    if progression.experience < needed || !dying.is_empty() {
+        return;
+    }
+
+    progression.experience -= needed;
+    progression.level += 1;
+    run_stats.record_level(progression.level);
+    progression.roll_offers(*offers, &loadout.0);
+
+    // Demo runs take the first offer rather than stopping for the screen
+    if demo.is_active() {
+        if let Some(&upgrade) = progression.offers.first() {
+            grant_upgrade(&mut progression, upgrade, &mut players, &mut stats_writer);
+        }
+        return;
+    }
+
+    if progression.offers.is_empty() || state.push(AppState::LevelUp).is_err() {
+        progression.offers.clear();
+    }
+}
+
+pub fn spawn_level_up_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    progression: Res<Progression>,
+    daily: Res<Daily>,
+    bindings: Res<Bindings>,
+) {
+    let font = assets.font();
+    let title = format!("LEVEL {}", progression.level);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.05, 0.2, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(LevelUpRoot)
+        .insert(FocusRing::new(0, progression.offers.len()))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text(&title, &font, LEVEL_UP_TITLE_SIZE, Color::WHITE));
+            for slot in 0..OFFER_SIZE {
+                parent
+                    .spawn_bundle(menu_text(&offer_label(&progression, slot), &font, LEVEL_UP_OFFER_SIZE, NAV_COLOR))
+                    .insert(OfferText(slot))
+                    .insert(Focusable(slot));
+            }
+            parent
+                .spawn_bundle(menu_text(&hint_label(&progression, &daily, &bindings), &font, LEVEL_UP_HINT_SIZE, Color::rgb(0.6, 0.6, 0.6)))
+                .insert(LevelUpHint);
+        });
+}
+
+pub fn despawn_level_up_screen(
+    mut commands: Commands,
+    roots: Query<Entity, With<LevelUpRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+pub fn level_up_input(
+    mut controls: Controls,
+    mut actions: EventReader<MenuAction>,
+    mut progression: ResMut<Progression>,
+    offers: Res<Offers>,
+    loadout: Res<Loadout>,
+    daily: Res<Daily>,
+    mut state: ResMut<State<AppState>>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+    mut run_stats: ResMut<RunStats>,
+    mut players: Query<&mut Health, With<Player>>,
+    mut rings: Query<&mut FocusRing, With<LevelUpRoot>>,
+    mut offer_texts: Query<(&OfferText, &mut Text), Without<LevelUpHint>>,
+    mut hints: Query<&mut Text, With<LevelUpHint>>,
+) {
+    // An offer's number picks it straight away, or Enter picks the focused one
+    let confirmed = actions.iter().find_map(|action| match action {
+        MenuAction::Confirm(slot) => Some(*slot),
+        MenuAction::Cancel => None,
+    });
+    let slot = (0..OFFER_SIZE)
+        .find(|&slot| controls.just_pressed(0, Action::Choose(slot)))
+        .or(confirmed)
+        .filter(|&slot| slot < progression.offers.len());
+
+    let is_done = match slot {
+        Some(slot) if progression.is_banishing => {
+            let upgrade = progression.offers[slot];
+            progression.banished.push(upgrade);
+            progression.banishes -= 1;
+            progression.roll_offers(*offers, &loadout.0);
+            progression.offers.is_empty()
+        }
+        Some(slot) => {
+            let upgrade = progression.offers[slot];
+            run_stats.record_upgrade(upgrade, progression.level);
+            grant_upgrade(&mut progression, upgrade, &mut players, &mut stats_writer);
+            true
+        }
+        None if controls.just_pressed(0, Action::Reroll) && progression.rerolls > 0 && daily.run_day.is_none() => {
+            progression.rerolls -= 1;
+            progression.roll_offers(*offers, &loadout.0);
+            false
+        }
+        None if controls.just_pressed(0, Action::Skip) && progression.skips > 0 => {
+            progression.skips -= 1;
+            progression.experience += SKIP_EXPERIENCE;
+            progression.offers.clear();
+            true
+        }
+        None if controls.just_pressed(0, Action::Banish) && (progression.banishes > 0 || progression.is_banishing) => {
+            progression.is_banishing = !progression.is_banishing;
+            false
+        }
+        // A pick that a transition already queued held open last frame
+        None if progression.offers.is_empty() => true,
+        None => return,
+    };
+
+    // Offers are cleared first, so a pick held open can't be taken twice and
+    // the screen closes next frame instead
+    if is_done {
+        progression.offers.clear();
+        match state.pop() {
+            Ok(()) => controls.clear(),
+            Err(e) => warn!("holding the level up screen open: {}", e),
+        }
+        return;
+    }
+
+    for mut ring in rings.iter_mut() {
+        ring.set_count(progression.offers.len());
+    }
+    for (offer, mut text) in offer_texts.iter_mut() {
+        text.sections[0].value = offer_label(&progression, offer.0);
+    }
+    for mut text in hints.iter_mut() {
+        text.sections[0].value = hint_label(&progression, &daily, controls.bindings());
+    }
+}
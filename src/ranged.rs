@@ -0,0 +1,134 @@
+//! Spitters and their spit. A spitter within range of a player it can see
+//! spits a slow glob at them. Obstacles block both the view and the spit, so
+//! cover keeps players safe from them.
+
+use bevy::prelude::*;
+
+use crate::death::Dying;
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::players::{nearest_player, player_positions};
+use crate::run_stats::RunStats;
+use crate::spatial::SpatialIndex;
+use crate::{collide_circles, Body, Circle, MonsterKind, NewGameEvent, Player, Position, Velocity};
+
+
+const SPIT_RANGE: f32            = 250.0;
+const SPIT_COOLDOWN_SECONDS: f32 = 2.5;
+const SPIT_SPEED: f32            = 150.0;
+const SPIT_RADIUS: f32           = 5.0;
+const SPIT_LIFETIME_SECONDS: f32 = 2.5;
+const SPIT_DAMAGE: f32           = 10.0;
+
+/// Time until a spitter can spit again.
+#[derive(Component)]
+pub struct SpitCooldown(Timer);
+
+impl SpitCooldown {
+    pub fn new() -> SpitCooldown {
+        SpitCooldown(Timer::from_seconds(SPIT_COOLDOWN_SECONDS, false))
+    }
+}
+
+#[derive(Component)]
+pub struct Spit {
+    circle: Circle,
+    lifetime: Timer,
+}
+
+pub fn spit_at_players(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    index: Res<SpatialIndex>,
+    players: Query<&Position, With<Player>>,
+    mut spitters: Query<(&Position, &mut SpitCooldown)>,
+) {
+    let targets = player_positions(&players);
+
+    for (p, mut cooldown) in spitters.iter_mut() {
+        if !cooldown.0.tick(time.delta()).finished() {
+            continue;
+        }
+
+        // Only spit at a player in range and in plain view
+        let target = match nearest_player(&targets, p.current) {
+            Some(target) if target.distance_squared(p.current) <= SPIT_RANGE.powi(2) => target,
+            _ => continue,
+        };
+        if !index.line_of_sight(p.current, target) {
+            continue;
+        }
+
+        cooldown.0.reset();
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.blast(),
+                    color: MonsterKind::Spitter.tint(),
+                    custom_size: Some(Vec2::splat(SPIT_RADIUS * 2.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Spit{
+                circle: Circle::new(SPIT_RADIUS),
+                lifetime: Timer::from_seconds(SPIT_LIFETIME_SECONDS, false),
+            })
+            .insert(Position::new(p.current))
+            .insert(Velocity::new((target - p.current).normalize_or_zero(), SPIT_SPEED));
+    }
+}
+
+pub fn spit_collision(
+    time: Res<Time>,
+    mut commands: Commands,
+    index: Res<SpatialIndex>,
+    mut spits: Query<(Entity, &mut Spit, &Position)>,
+    mut players: Query<(Entity, &Body, &Position, &mut Health), With<Player>>,
+    dying: Query<(), With<Dying>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    for (entity, mut spit, p) in spits.iter_mut() {
+        // Spit splats on obstacles and dries up after a while
+        let previous = p.current - p.change;
+        if spit.lifetime.tick(time.delta()).finished() || !index.line_of_sight(previous, p.current) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let hit = players.iter_mut().find(|(_, body, player_p, _)| {
+            collide_circles((&spit.circle, p.current), (&body.circle, player_p.current)).0
+        });
+        let (player, _, _, mut health) = match hit {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        commands.entity(entity).despawn();
+        if !dying.is_empty() || !health.can_be_hurt() {
+            continue;
+        }
+
+        run_stats.record_damage(SPIT_DAMAGE);
+        if health.hurt(SPIT_DAMAGE) {
+            commands.entity(player).insert(Dying::new());
+            run_stats.record_death(format!("{} spit", MonsterKind::Spitter.name()));
+        }
+    }
+}
+
+pub fn clear_spit(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    spits: Query<Entity, With<Spit>>,
+) {
+    if new_game_reader.iter().next().is_none() {
+        return;
+    }
+
+    for spit in spits.iter() {
+        commands.entity(spit).despawn();
+    }
+}
@@ -0,0 +1,239 @@
+//! A physics backend on `bevy_rapier2d`, in builds with the `rapier` feature.
+//! It stands in for `spread_collision` and nothing else: every `Body` gets a
+//! rapier collider of the same shape, layers and mass, the gameplay systems
+//! go on moving `Position` as before, and rapier settles the contacts. Each
+//! frame a body's move is handed to rapier as a velocity from where it
+//! started, and where rapier leaves it is written back as the collision's
+//! share of the move. Bodies without mass become fixed colliders, and bodies
+//! that hold their ground while still can't be pushed by the crowd.
+//!
+//! With real contacts come bounces off walls and each other, and the rapier
+//! query pipeline is there for sensors and ray casts.
+
+use bevy::prelude::*;
+use bevy_rapier2d::physics::wrapper::{
+    ColliderFlagsComponent, ColliderMassPropsComponent, ColliderShapeComponent, RigidBodyDominanceComponent,
+    RigidBodyPositionComponent, RigidBodyTypeComponent, RigidBodyVelocityComponent,
+};
+use bevy_rapier2d::physics::{
+    ColliderBundle, NoUserData, PhysicsSystems, RapierConfiguration, RapierPhysicsPlugin, RigidBodyBundle,
+};
+use bevy_rapier2d::prelude::{
+    ColliderFlags, ColliderMassProps, ColliderMaterial, ColliderShape, Isometry, InteractionGroups,
+    IntegrationParameters, Point, RigidBodyDominance, RigidBodyMassPropsFlags, RigidBodyType, Vector,
+};
+
+use crate::lod::Distant;
+use crate::{AppState, Body, Layers, Movement, Position, Shape};
+
+
+/// Game units in one rapier meter, so rapier's tolerances suit the sizes here.
+const UNITS_PER_METER: f32 = 32.0;
+/// Longest step rapier takes, so slow frames still move bodies their full way.
+const MAX_STEP_SECONDS: f32 = 0.1;
+const RESTITUTION: f32      = 0.3;
+
+pub struct RapierPlugin;
+
+impl Plugin for RapierPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+            .insert_resource(RapierConfiguration{
+                gravity: Vector::zeros(),
+                ..Default::default()
+            })
+            .insert_resource(IntegrationParameters{
+                dt: MAX_STEP_SECONDS,
+                ..Default::default()
+            })
+            .add_system(
+                pause_physics
+                    .before(PhysicsSystems::StepWorld),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(attach_colliders)
+                    .with_system(
+                        reshape_colliders
+                            .before(PhysicsSystems::StepWorld),
+                    )
+                    .with_system(
+                        hand_over_moves
+                            .after(Movement::Damage)
+                            .before(PhysicsSystems::StepWorld),
+                    )
+                    .with_system(
+                        take_back_positions
+                            .label(Movement::Spread)
+                            .after(PhysicsSystems::StepWorld),
+                    ),
+            );
+    }
+}
+
+fn to_rapier(point: Vec2) -> Vector<f32> {
+    Vector::new(point.x, point.y) / UNITS_PER_METER
+}
+
+fn isometry(point: Vec2) -> Isometry<f32> {
+    let point = to_rapier(point);
+    Isometry::translation(point.x, point.y)
+}
+
+fn from_rapier(vector: Vector<f32>) -> Vec2 {
+    Vec2::new(vector.x, vector.y) * UNITS_PER_METER
+}
+
+/// The collider for `body`, grown by its circle the way `collide_bodies`
+/// measures shapes.
+fn collider_shape(body: &Body) -> ColliderShape {
+    let radius = body.circle.radius / UNITS_PER_METER;
+    match body.shape {
+        Shape::Point => ColliderShape::ball(radius),
+        Shape::Segment(half) => {
+            let half = to_rapier(half);
+            ColliderShape::capsule(Point::from(-half), Point::from(half), radius)
+        }
+        Shape::Rect(half) => {
+            let half = to_rapier(half);
+            ColliderShape::round_cuboid(half.x, half.y, radius)
+        }
+    }
+}
+
+/// The density that gives `shape` the body's mass.
+fn density(body: &Body, shape: &ColliderShape) -> f32 {
+    let unit_mass = 1.0 / shape.mass_properties(1.0).inv_mass;
+    match body.mass {
+        Some(mass) if unit_mass > 0.0 => mass / unit_mass,
+        _ => 1.0,
+    }
+}
+
+/// Which bodies `body` collides with. Distant monsters still stay out of
+/// walls but pass through each other, as they do without rapier.
+fn collision_groups(body: &Body, is_distant: bool) -> InteractionGroups {
+    let mask = if is_distant {
+        Layers(body.mask.0 & !Layers::MONSTERS.0)
+    }
+    else {
+        body.mask
+    };
+    InteractionGroups::new(body.layer.0 as u32, mask.0 as u32)
+}
+
+/// Keep rapier still while the run is paused or over.
+fn pause_physics(
+    state: Res<State<AppState>>,
+    mut configuration: ResMut<RapierConfiguration>,
+) {
+    configuration.physics_pipeline_active = *state.current() == AppState::Playing;
+}
+
+fn attach_colliders(
+    mut commands: Commands,
+    bodies: Query<(Entity, &Body, &Position, Option<&Distant>), Added<Body>>,
+) {
+    for (entity, body, position, distant) in bodies.iter() {
+        let body_type = if body.mass.is_some() { RigidBodyType::Dynamic } else { RigidBodyType::Static };
+        let shape = collider_shape(body);
+        let density = density(body, &shape);
+
+        commands
+            .entity(entity)
+            .insert_bundle(RigidBodyBundle{
+                body_type: body_type.into(),
+                position: isometry(position.current).into(),
+                mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle{
+                shape: shape.into(),
+                material: ColliderMaterial{
+                    friction: 0.0,
+                    restitution: RESTITUTION,
+                    ..Default::default()
+                }
+                .into(),
+                flags: ColliderFlags{
+                    collision_groups: collision_groups(body, distant.is_some()),
+                    ..Default::default()
+                }
+                .into(),
+                mass_properties: ColliderMassProps::Density(density).into(),
+                ..Default::default()
+            });
+    }
+}
+
+pub type ReshapedColliders<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Body, &'static mut ColliderShapeComponent, &'static mut ColliderMassPropsComponent),
+    Changed<Body>,
+>;
+
+/// Follow bodies that grew or shrank, like hordes taking in members.
+fn reshape_colliders(mut colliders: ReshapedColliders) {
+    for (body, mut shape, mut mass_properties) in colliders.iter_mut() {
+        let reshaped = collider_shape(body);
+        *mass_properties = ColliderMassProps::Density(density(body, &reshaped)).into();
+        *shape = reshaped.into();
+    }
+}
+
+pub type MovedBodies<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Body,
+        &'static Position,
+        Option<&'static Distant>,
+        &'static mut RigidBodyPositionComponent,
+        &'static mut RigidBodyVelocityComponent,
+        &'static mut RigidBodyDominanceComponent,
+        &'static mut ColliderFlagsComponent,
+    ),
+>;
+
+/// Start each moving body where it was before this frame's move, heading
+/// for where the move took it.
+fn hand_over_moves(
+    time: Res<Time>,
+    mut bodies: MovedBodies,
+) {
+    let seconds = time.delta_seconds();
+    for (body, position, distant, mut rb_position, mut rb_velocity, mut dominance, mut flags) in
+        bodies.iter_mut()
+    {
+        let groups = collision_groups(body, distant.is_some());
+        if flags.collision_groups != groups {
+            flags.collision_groups = groups;
+        }
+
+        // Bodies that hold their ground while still can't be shoved by movers
+        let is_firm = body.is_holding_firm(position);
+        let wanted = RigidBodyDominance(if is_firm { 1 } else { 0 });
+        if dominance.0 != wanted {
+            dominance.0 = wanted;
+        }
+
+        rb_position.position = isometry(position.current - position.change);
+        rb_velocity.linvel = if seconds > 0.0 { to_rapier(position.change / seconds) } else { Vector::zeros() };
+    }
+}
+
+/// Write rapier's answer back as part of this frame's move.
+fn take_back_positions(
+    mut bodies: Query<(&mut Position, &RigidBodyPositionComponent, &RigidBodyTypeComponent)>,
+) {
+    for (mut position, rb_position, body_type) in bodies.iter_mut() {
+        if body_type.0 != RigidBodyType::Dynamic {
+            continue;
+        }
+
+        let settled = from_rapier(rb_position.position.translation.vector) - position.current;
+        position.apply_add(settled);
+    }
+}
@@ -0,0 +1,141 @@
+//! A debug probe, in debug builds only. F3 toggles a ray cast ahead of the
+//! first player that marks the monster it hits, to check the spatial index
+//! against what's on screen. It's only there while a run is being played.
+
+use bevy::prelude::*;
+
+use crate::camera::CameraFocus;
+use crate::spatial::{IndexMonsters, SpatialIndex};
+use crate::{AppState, Monster, Movement, Player, Position, Velocity};
+
+
+const PROBE_RANGE: f32       = 400.0;
+const PROBE_THICKNESS: f32   = 2.0;
+const PROBE_MARKER_SIZE: f32 = 8.0;
+const PROBE_DEPTH: f32       = 20.0;
+
+pub struct RayProbePlugin;
+
+impl Plugin for RayProbePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(spawn_ray_probe),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(
+                        probe_ray
+                            .after(IndexMonsters)
+                            .after(Movement::Spread),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing)
+                    .with_system(despawn_ray_probe),
+            );
+    }
+}
+
+/// The two parts of the ray probe: the ray itself, and a marker on the
+/// center of whatever it hit.
+#[derive(Component, Clone, Copy, PartialEq)]
+enum RayProbe {
+    Beam,
+    Marker,
+}
+
+/// Whether the probe is on, and the way it points while the player stands
+/// still.
+#[derive(Default)]
+struct ProbeState {
+    is_on: bool,
+    heading: Vec2,
+}
+
+fn spawn_ray_probe(
+    mut commands: Commands,
+) {
+    for part in [RayProbe::Beam, RayProbe::Marker] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                visibility: Visibility {
+                    is_visible: false,
+                },
+                ..Default::default()
+            })
+            .insert(part);
+    }
+}
+
+fn probe_ray(
+    keyboard_input: Res<Input<KeyCode>>,
+    index: Res<SpatialIndex>,
+    focus: Res<CameraFocus>,
+    mut state: Local<ProbeState>,
+    players: Query<(&Player, &Position, &Velocity)>,
+    monsters: Query<&Position, With<Monster>>,
+    mut probes: Query<(&RayProbe, &mut Transform, &mut Sprite, &mut Visibility)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        state.is_on = !state.is_on;
+    }
+
+    let first = players.iter().find(|(player, _, _)| player.index == 0);
+    let (_, p, v) = match first {
+        Some(first) if state.is_on => first,
+        _ => {
+            for (_, _, _, mut visibility) in probes.iter_mut() {
+                visibility.is_visible = false;
+            }
+            return;
+        }
+    };
+
+    if v.direction != Vec2::ZERO {
+        state.heading = v.direction.normalize();
+    }
+    let heading = if state.heading == Vec2::ZERO {
+        Vec2::X
+    }
+    else {
+        state.heading
+    };
+
+    // Yellow out to the full range, red up to whatever it hits
+    let hit = index.cast_ray(p.current, heading, PROBE_RANGE);
+    let (end, color) = match hit {
+        Some(hit) => (hit.point, Color::rgba(1.0, 0.2, 0.2, 0.8)),
+        None => (p.current + heading * PROBE_RANGE, Color::rgba(1.0, 1.0, 0.2, 0.6)),
+    };
+    let target = hit.and_then(|hit| monsters.get(hit.entity).ok());
+
+    for (part, mut transform, mut sprite, mut visibility) in probes.iter_mut() {
+        sprite.color = color;
+        match part {
+            RayProbe::Beam => {
+                transform.translation = ((p.current + end) / 2.0 - focus.center).extend(PROBE_DEPTH);
+                transform.rotation = Quat::from_rotation_z(heading.y.atan2(heading.x));
+                sprite.custom_size = Some(Vec2::new(end.distance(p.current), PROBE_THICKNESS));
+                visibility.is_visible = true;
+            }
+            RayProbe::Marker => {
+                if let Some(target) = target {
+                    transform.translation = (target.current - focus.center).extend(PROBE_DEPTH);
+                    sprite.custom_size = Some(Vec2::splat(PROBE_MARKER_SIZE));
+                }
+                visibility.is_visible = target.is_some();
+            }
+        }
+    }
+}
+
+fn despawn_ray_probe(
+    mut commands: Commands,
+    probes: Query<Entity, With<RayProbe>>,
+) {
+    for probe in probes.iter() {
+        commands.entity(probe).despawn();
+    }
+}
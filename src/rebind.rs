@@ -0,0 +1,197 @@
+//! The controls screen, opened from the menu with I. It lists every key that
+//! can be rebound: each player's own movement, fire, ability and dash keys,
+//! then the shared ones for pausing, menus and level ups. Confirming an entry
+//! waits for the next key pressed and binds it, and Escape gives up waiting
+//! instead. Backing out returns to the menu and saves the bindings to
+//! `controls.ron`, where gamepad buttons and the stick are still set by hand.
+
+use bevy::prelude::*;
+
+use crate::input::{Action, Bindings};
+use crate::loading::GameAssets;
+use crate::menu::{menu_text, MenuRoot};
+use crate::nav::{FocusRing, Focusable, MenuAction, NAV_COLOR};
+use crate::{AppState, MAX_PLAYERS};
+
+
+const REBIND_TITLE_SIZE: f32 = 48.0;
+const REBIND_ENTRY_SIZE: f32 = 16.0;
+const REBIND_HINT_SIZE: f32  = 18.0;
+const REBIND_ENTRY_GAP: f32  = 1.0;
+const PANEL_FONT_SIZE: f32   = 18.0;
+
+#[derive(Component)]
+pub struct RebindRoot {
+    /// The entry waiting for a key, if any.
+    waiting: Option<usize>,
+}
+
+#[derive(Component)]
+pub struct RebindEntry(usize);
+
+/// Every player's own actions, then the shared ones.
+fn entries() -> Vec<(usize, Action)> {
+    let own = (0..MAX_PLAYERS).flat_map(|player| Action::PER_PLAYER.map(|action| (player, action)));
+    own.chain(Action::SHARED.map(|action| (0, action))).collect()
+}
+
+fn entry_label(bindings: &Bindings, (player, action): (usize, Action), is_waiting: bool) -> String {
+    let key = if is_waiting {
+        "press a key".to_string()
+    }
+    else {
+        bindings.player_key_name(player, action)
+    };
+
+    if Action::SHARED.contains(&action) {
+        format!("{}: {}", action.name(), key)
+    }
+    else {
+        format!("player {} {}: {}", player + 1, action.name(), key)
+    }
+}
+
+pub fn spawn_controls_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(276.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "controls (I)",
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MenuRoot);
+}
+
+pub fn open_controls(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::I) {
+        return;
+    }
+
+    keyboard_input.clear_just_pressed(KeyCode::I);
+    if let Err(e) = state.set(AppState::Rebinding) {
+        warn!("ignoring the controls screen: {}", e);
+    }
+}
+
+pub fn spawn_rebind_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    bindings: Res<Bindings>,
+) {
+    let font = assets.font();
+    let entries = entries();
+    let hint = format!(
+        "{} to rebind, then the new key or Escape to keep the old one, {} to save and go back",
+        bindings.key_name(Action::Confirm), bindings.key_name(Action::Cancel),
+    );
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..Default::default()
+        })
+        .insert(RebindRoot{ waiting: None })
+        .insert(FocusRing::new(0, entries.len()))
+        .with_children(|parent| {
+            parent.spawn_bundle(menu_text("CONTROLS", &font, REBIND_TITLE_SIZE, Color::WHITE));
+            for (index, &entry) in entries.iter().enumerate() {
+                let mut text = menu_text(&entry_label(&bindings, entry, false), &font, REBIND_ENTRY_SIZE, NAV_COLOR);
+                text.style.margin = Rect::all(Val::Px(REBIND_ENTRY_GAP));
+                parent
+                    .spawn_bundle(text)
+                    .insert(Focusable(index))
+                    .insert(RebindEntry(index));
+            }
+            parent.spawn_bundle(menu_text(&hint, &font, REBIND_HINT_SIZE, NAV_COLOR));
+        });
+}
+
+pub fn despawn_rebind_screen(
+    mut commands: Commands,
+    roots: Query<Entity, With<RebindRoot>>,
+) {
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+/// Runs before menu navigation, so a key being bound doesn't also move the
+/// focus or back out of the screen.
+pub fn rebind_keys(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut button_input: ResMut<Input<GamepadButton>>,
+    mut bindings: ResMut<Bindings>,
+    mut actions: EventReader<MenuAction>,
+    mut state: ResMut<State<AppState>>,
+    mut roots: Query<&mut RebindRoot>,
+    mut texts: Query<(&RebindEntry, &mut Text)>,
+) {
+    let mut root = match roots.get_single_mut() {
+        Ok(root) => root,
+        Err(_) => return,
+    };
+    let entries = entries();
+
+    match root.waiting {
+        Some(index) => {
+            let key = keyboard_input.get_just_pressed().next().copied();
+            keyboard_input.clear();
+            button_input.clear();
+
+            match key {
+                Some(KeyCode::Escape) => root.waiting = None,
+                Some(key) => {
+                    let (player, action) = entries[index];
+                    bindings.set_key(player, action, key);
+                    root.waiting = None;
+                }
+                None => return,
+            }
+        }
+        None => match actions.iter().last() {
+            Some(&MenuAction::Confirm(index)) if index < entries.len() => root.waiting = Some(index),
+            Some(MenuAction::Cancel) => {
+                bindings.save();
+                if let Err(e) = state.set(AppState::Menu) {
+                    warn!("ignoring the return to the menu: {}", e);
+                }
+                return;
+            }
+            _ => return,
+        },
+    }
+
+    for (entry, mut text) in texts.iter_mut() {
+        let is_waiting = root.waiting == Some(entry.0);
+        text.sections[0].value = entry_label(&bindings, entries[entry.0], is_waiting);
+    }
+}
@@ -0,0 +1,102 @@
+//! How much of the fighting stays on the ground. Corpses and decals each keep
+//! at most this many on screen, dropping the oldest to make room, so long runs
+//! don't pile up entities. Set in the menu with [ and ], from nothing at all
+//! to plenty for fast machines.
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+const REMAINS_STEPS: [usize; 6] = [0, 50, 100, 150, 250, 400];
+const DEFAULT_STEP: usize       = 3;
+
+/// The most corpses, and separately the most decals, kept at once.
+pub struct RemainsLimit {
+    step: usize,
+}
+
+impl Default for RemainsLimit {
+    fn default() -> RemainsLimit {
+        RemainsLimit{
+            step: DEFAULT_STEP,
+        }
+    }
+}
+
+impl RemainsLimit {
+    pub fn get(&self) -> usize {
+        REMAINS_STEPS[self.step]
+    }
+}
+
+/// Which of `showing`, given as entities and how long each has been down,
+/// to drop so that `incoming` more fit under `limit`. The oldest go first.
+pub fn oldest_over_limit(mut showing: Vec<(Entity, f32)>, incoming: usize, limit: usize) -> Vec<Entity> {
+    showing.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let excess = (showing.len() + incoming).saturating_sub(limit);
+    showing.into_iter().take(excess).map(|(entity, _)| entity).collect()
+}
+
+#[derive(Component)]
+pub struct RemainsPanelText;
+
+fn panel_label(limit: &RemainsLimit) -> String {
+    let filled = "#".repeat(limit.step);
+    let empty = "-".repeat(REMAINS_STEPS.len() - 1 - limit.step);
+    format!("remains: [{}{}] {} ([ and ])", filled, empty, limit.get())
+}
+
+pub fn spawn_remains_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    limit: Res<RemainsLimit>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(156.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(&limit),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(RemainsPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn remains_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut limit: ResMut<RemainsLimit>,
+    mut texts: Query<&mut Text, With<RemainsPanelText>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::RBracket) {
+        limit.step = (limit.step + 1).min(REMAINS_STEPS.len() - 1);
+    }
+    else if keyboard_input.just_pressed(KeyCode::LBracket) {
+        limit.step = limit.step.saturating_sub(1);
+    }
+    else {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(&limit);
+    }
+}
@@ -0,0 +1,187 @@
+//! Per-run statistics, written to disk on game over so runs can be compared
+//! outside the game. Each run gets its own JSON file, with when each level
+//! was reached and which upgrade was picked at it, and a summary row is
+//! appended to a shared CSV.
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::autopilot::DemoMode;
+use crate::curses::Curses;
+use crate::daily::{date_label, Daily};
+use crate::progression::Upgrade;
+use crate::stage::Stages;
+use crate::storage;
+use crate::{MonsterStats, NewGameEvent, PlayerCount, WorldSeed};
+
+
+pub const RUN_DIRECTORY: &str  = "runs";
+const RUN_SUMMARY_FILE: &str   = "runs.csv";
+const DAILY_SUMMARY_FILE: &str = "daily.csv";
+const RUN_SUMMARY_HEADER: &str = "finished_at,stage,seed,players,duration_seconds,spawned,kills,damage_taken";
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RecordRun;
+
+/// Sent when the run ends because a player was caught.
+pub struct GameOverEvent;
+
+/// A level reached during the run.
+#[derive(Serialize)]
+struct LevelReached {
+    level: u32,
+    seconds: f32,
+}
+
+/// An upgrade picked on the level up screen.
+#[derive(Serialize)]
+struct UpgradePicked {
+    upgrade: Upgrade,
+    level: u32,
+    seconds: f32,
+}
+
+#[derive(Default, Serialize)]
+pub struct RunStats {
+    duration_seconds: f32,
+    /// Damage landed on all players together.
+    damage_taken: f32,
+    hits_taken: u32,
+    /// What landed the lethal hit.
+    cause_of_death: Option<String>,
+    levels: Vec<LevelReached>,
+    upgrades: Vec<UpgradePicked>,
+}
+
+impl RunStats {
+    pub fn duration_seconds(&self) -> f32 {
+        self.duration_seconds
+    }
+
+    pub fn record_damage(&mut self, amount: f32) {
+        self.damage_taken += amount;
+        self.hits_taken += 1;
+    }
+
+    pub fn record_level(&mut self, level: u32) {
+        self.levels.push(LevelReached{
+            level,
+            seconds: self.duration_seconds,
+        });
+    }
+
+    pub fn record_upgrade(&mut self, upgrade: Upgrade, level: u32) {
+        self.upgrades.push(UpgradePicked{
+            upgrade,
+            level,
+            seconds: self.duration_seconds,
+        });
+    }
+
+    pub fn record_death(&mut self, cause: String) {
+        self.cause_of_death = Some(cause);
+    }
+
+    pub fn cause_of_death(&self) -> Option<&str> {
+        self.cause_of_death.as_deref()
+    }
+}
+
+#[derive(Serialize)]
+struct RunReport<'a> {
+    finished_at: u64,
+    stage: &'a str,
+    seed: u64,
+    players: usize,
+    /// The date of the daily challenge, if this was one.
+    daily: Option<String>,
+    curses: Vec<&'static str>,
+    score_multiplier: f32,
+    score: u32,
+    #[serde(flatten)]
+    stats: &'a RunStats,
+    #[serde(flatten)]
+    monsters: &'a MonsterStats,
+}
+
+pub fn track_run_time(
+    time: Res<Time>,
+    mut stats: ResMut<RunStats>,
+) {
+    stats.duration_seconds += time.delta_seconds();
+}
+
+pub fn record_run(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut stats: ResMut<RunStats>,
+    monster_stats: Res<MonsterStats>,
+    demo: Res<DemoMode>,
+    stages: Res<Stages>,
+    world_seed: Res<WorldSeed>,
+    player_count: Res<PlayerCount>,
+    curses: Res<Curses>,
+    daily: Res<Daily>,
+) {
+    // Export before the restart clears the stats, skipping demo runs
+    if game_over_reader.iter().next().is_some() && !demo.is_active() {
+        let finished_at = storage::unix_millis();
+        let report = RunReport{
+            finished_at: finished_at / 1000,
+            stage: &stages.current().name,
+            seed: world_seed.value,
+            players: player_count.0,
+            daily: daily.run_day.map(date_label),
+            curses: curses.iter().map(|c| c.name()).collect(),
+            score_multiplier: curses.reward_multiplier(),
+            score: curses.score(monster_stats.killed()),
+            stats: &stats,
+            monsters: &monster_stats,
+        };
+
+        if let Err(e) = write_report(&report, finished_at) {
+            warn!("failed to save run stats: {}", e);
+        }
+    }
+
+    if new_game_reader.iter().next().is_some() {
+        *stats = RunStats::default();
+    }
+}
+
+fn write_report(report: &RunReport, finished_at_millis: u64) -> Result<(), String> {
+    // Full breakdown for this run, named to the millisecond so quick runs
+    // don't overwrite each other
+    let path = format!("{}/run-{}.json", RUN_DIRECTORY, finished_at_millis);
+    let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())?;
+    storage::write(&path, &json)?;
+    info!("saved {}", path);
+
+    // One summary row per run, with a header for new files. Daily runs are
+    // kept apart so they can be compared with each other
+    let summary_file = if report.daily.is_some() {
+        DAILY_SUMMARY_FILE
+    }
+    else {
+        RUN_SUMMARY_FILE
+    };
+    let summary = format!("{}/{}", RUN_DIRECTORY, summary_file);
+    let mut rows = String::new();
+    if !storage::exists(&summary) {
+        rows.push_str(RUN_SUMMARY_HEADER);
+        rows.push('\n');
+    }
+    rows.push_str(&format!(
+        "{},\"{}\",{},{},{:.1},{},{},{:.1}\n",
+        report.finished_at,
+        report.stage.replace('"', "\"\""),
+        report.seed,
+        report.players,
+        report.stats.duration_seconds,
+        report.monsters.spawned(),
+        report.monsters.killed(),
+        report.stats.damage_taken,
+    ));
+
+    storage::append(&summary, &rows)
+}
@@ -0,0 +1,237 @@
+//! The training arena, started with T from the menu on the selected stage.
+//! Nothing spawns on its own and players can't die. Number keys spawn each
+//! kind of monster and G grants a random upgrade. A training dummy next to
+//! the start counts the blasts that catch it, to compare builds by how often
+//! they land.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::director::Director;
+use crate::effective_stats::StatsChangedEvent;
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::merchant::MerchantVisits;
+use crate::players::player_positions;
+use crate::progression::{grant_upgrade, Progression};
+use crate::stage::{BossSchedule, Stages};
+use crate::{
+    collide_circles, random_unit, spawn_monster_kind, AppState, Blast, Circle, MonsterKind, MonsterSpawnTimer,
+    MonsterStats, NewGameEvent, Player, Position, RunDefaults, MONSTER_SPAWN_DISTANCE,
+};
+
+
+const DUMMY_HEIGHT: f32          = 60.0;
+const DUMMY_RADIUS: f32          = 24.0;
+const DUMMY_WINDOW_SECONDS: f32  = 5.0;
+const DUMMY_LABEL_OFFSET: f32    = 48.0;
+const DUMMY_LABEL_FONT_SIZE: f32 = 16.0;
+
+const SPAWN_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Whether the current run is in the training arena.
+#[derive(Default)]
+pub struct Sandbox {
+    is_active: bool,
+}
+
+/// Anything set up for the training arena, cleared with the run.
+#[derive(Component)]
+pub struct SandboxProp;
+
+#[derive(Component)]
+pub struct Dummy {
+    circle: Circle,
+    /// Blasts already counted, since each lasts a few frames.
+    struck_by: Vec<Entity>,
+    /// When each recent hit landed, in seconds since startup.
+    hits: VecDeque<f64>,
+}
+
+#[derive(Component)]
+pub struct DummyLabel;
+
+pub fn start_sandbox(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    stages: Res<Stages>,
+    defaults: Res<RunDefaults>,
+    mut state: ResMut<State<AppState>>,
+    mut new_game_writer: EventWriter<NewGameEvent>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::T) {
+        return;
+    }
+    if let Err(e) = state.set(AppState::Playing) {
+        warn!("ignoring the training arena: {}", e);
+        return;
+    }
+
+    keyboard_input.clear_just_pressed(KeyCode::T);
+    new_game_writer.send(NewGameEvent{
+        sandbox: true,
+        ..NewGameEvent::on_stage(stages.selected, &defaults)
+    });
+}
+
+pub fn setup_sandbox(
+    mut commands: Commands,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut sandbox: ResMut<Sandbox>,
+    sprites: Res<GameAssets>,
+    props: Query<Entity, With<SandboxProp>>,
+) {
+    let event = match new_game_reader.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    for prop in props.iter() {
+        commands.entity(prop).despawn();
+    }
+
+    sandbox.is_active = event.sandbox;
+    if !sandbox.is_active {
+        return;
+    }
+
+    // Players start around the origin, so the dummy stands just above them
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.blast(),
+                color: Color::rgb(0.8, 0.7, 0.5),
+                custom_size: Some(Vec2::splat(DUMMY_RADIUS * 2.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Dummy{
+            circle: Circle::new(DUMMY_RADIUS),
+            struck_by: Vec::new(),
+            hits: VecDeque::new(),
+        })
+        .insert(Position::new(Vec2::Y * DUMMY_HEIGHT))
+        .insert(SandboxProp);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: sprites.font(),
+                    font_size: DUMMY_LABEL_FONT_SIZE,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Center,
+                    vertical: VerticalAlign::Bottom,
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(Position::new(Vec2::Y * (DUMMY_HEIGHT + DUMMY_LABEL_OFFSET)))
+        .insert(DummyLabel)
+        .insert(SandboxProp);
+}
+
+/// Keep the automatic spawners from ever firing and players topped up.
+pub fn hold_sandbox(
+    sandbox: Res<Sandbox>,
+    mut spawn_timer: ResMut<MonsterSpawnTimer>,
+    mut director: ResMut<Director>,
+    mut boss_schedule: ResMut<BossSchedule>,
+    mut visits: ResMut<MerchantVisits>,
+    mut players: Query<&mut Health, With<Player>>,
+) {
+    if !sandbox.is_active {
+        return;
+    }
+
+    spawn_timer.0.pause();
+    director.hold();
+    visits.hold();
+    boss_schedule.clear();
+    for mut health in players.iter_mut() {
+        health.restore();
+    }
+}
+
+pub fn sandbox_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    sandbox: Res<Sandbox>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut progression: ResMut<Progression>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+    positions: Query<&Position, With<Player>>,
+    mut healths: Query<&mut Health, With<Player>>,
+) {
+    if !sandbox.is_active {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::G) {
+        let upgrade = progression.random_upgrade();
+        grant_upgrade(&mut progression, upgrade, &mut healths, &mut stats_writer);
+    }
+
+    // Spawn the chosen kind at the usual distance from the first player
+    let kind = SPAWN_KEYS
+        .iter()
+        .position(|&key| keyboard_input.just_pressed(key))
+        .map(|i| MonsterKind::ALL[i]);
+    let target = player_positions(&positions).first().copied();
+    if let (Some(kind), Some(target)) = (kind, target) {
+        let position = target + random_unit() * MONSTER_SPAWN_DISTANCE;
+        spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+        monster_stats.record_spawn(kind);
+    }
+}
+
+pub fn strike_dummies(
+    time: Res<Time>,
+    blasts: Query<(Entity, &Blast, &Position)>,
+    mut dummies: Query<(&mut Dummy, &Position)>,
+    mut labels: Query<&mut Text, With<DummyLabel>>,
+) {
+    let now = time.seconds_since_startup();
+
+    for (mut dummy, p) in dummies.iter_mut() {
+        // Forget blasts that have run out
+        dummy.struck_by.retain(|&blast| blasts.get(blast).is_ok());
+
+        for (entity, blast, blast_p) in blasts.iter() {
+            if dummy.struck_by.contains(&entity) {
+                continue;
+            }
+
+            let (did_collide, _) = collide_circles((&blast.circle, blast_p.current), (&dummy.circle, p.current));
+            if did_collide {
+                dummy.struck_by.push(entity);
+                dummy.hits.push_back(now);
+            }
+        }
+
+        while dummy.hits.front().is_some_and(|&hit| hit < now - DUMMY_WINDOW_SECONDS as f64) {
+            dummy.hits.pop_front();
+        }
+
+        let rate = dummy.hits.len() as f32 / DUMMY_WINDOW_SECONDS;
+        for mut text in labels.iter_mut() {
+            text.sections[0].value = format!("{:.1} hits/s\n1-9 spawn monsters, G for an upgrade", rate);
+        }
+    }
+}
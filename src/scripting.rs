@@ -0,0 +1,680 @@
+//! Scripts for mod content, in builds with the `scripting` feature. A monster
+//! kind from a mod can name a Rhai script whose `think(me)` runs a few times a
+//! second for each of its monsters, and a weapon one whose `fire(shot)` runs
+//! as each of its blasts goes off. Scripts only reach the game through the
+//! functions registered here:
+//!
+//! - `players()`, `nearest_player(x, y)` and `monsters_near(x, y, radius)`
+//!   give positions as maps with `x` and `y`, and `run_time()` the seconds
+//!   into the run.
+//! - `spawn_monster(id, x, y)` brings in a monster of the kind with that ID
+//!   if the spawn budget allows, kept near the monster or shot that asked.
+//!   Rhai reserves `spawn`, so it has the longer name.
+//! - `steer(x, y)` sets the heading of the monster thinking until it next
+//!   thinks, and `hurt_players(x, y, radius, amount)` hurts the players in
+//!   reach as if it had touched them. The reach is kept near the monster and
+//!   the amount to no more than a touch would do.
+//! - `blast(x, y, radius)` sets off another blast from the weapon firing, at
+//!   most a few times the size of the shot and not far from it.
+//!
+//! Every number passed in is a float, so it's `steer(1.0, 0.0)` and not
+//! `steer(1, 0)`. A call given a number that isn't finite, such as `1.0 / 0.0`,
+//! does nothing, and `spawn_monster` gives back `false`.
+//!
+//! Scripts can't import files, their strings, arrays and maps are capped, and
+//! each call is cut off after a set number of operations. One that fails is
+//! reported once and not run again. All the calls in a frame also share a
+//! budget of operations, and once it's spent the rest wait for the next frame.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+
+use crate::args::Weapon;
+use crate::content::Content;
+use crate::death::Dying;
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::mods;
+use crate::run_stats::RunStats;
+use crate::threat::Threat;
+use crate::{
+    spawn_blast_at, spawn_monster_kind, AppState, Blast, Monster, MonsterKind, MonsterStats, Movement,
+    Player, Position, Velocity, MONSTER_CONTACT_DAMAGE,
+};
+
+
+const THINK_SECONDS: f32       = 0.25;
+/// Operations a single call may take before it's cut off.
+const MAX_OPERATIONS: u64      = 50_000;
+/// Operations all the calls in a frame may take between them.
+const FRAME_OPERATIONS: u64    = 200_000;
+const MAX_CALL_LEVELS: usize   = 16;
+const MAX_STRING_SIZE: usize   = 4096;
+const MAX_ARRAY_SIZE: usize    = 1024;
+const MAX_MAP_SIZE: usize      = 256;
+/// Most monsters scripts can spawn between thoughts, all together.
+const SPAWN_LIMIT: usize       = 8;
+/// Most blasts a single blast's script can add.
+const BLAST_LIMIT: usize       = 4;
+/// Most monsters `monsters_near` hands back.
+const NEARBY_LIMIT: usize      = 32;
+/// How far from a monster its script can hurt players.
+const HURT_REACH: f32          = 96.0;
+/// How far from the monster or shot that asked a script can spawn.
+const SPAWN_REACH: f32         = 256.0;
+/// How far from the shot that set it off a script's blast can go.
+const BLAST_REACH: f32         = 256.0;
+/// Largest blast a script can add, next to the shot that set it off.
+const BLAST_RADIUS_FACTOR: f32 = 3.0;
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(
+                        refill_budget
+                            .before(RunScripts),
+                    )
+                    .with_system(
+                        think_monsters
+                            .label(RunScripts)
+                            .after(Movement::Monster)
+                            .before(Movement::Damage),
+                    )
+                    .with_system(
+                        fire_weapon_scripts
+                            .label(RunScripts),
+                    )
+                    .with_system(
+                        apply_script_actions
+                            .after(RunScripts),
+                    ),
+            )
+            .insert_resource(Scripts::compile());
+    }
+}
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+struct RunScripts;
+
+/// Who a running script acts for.
+#[derive(Clone, Copy)]
+enum Caller {
+    Monster {
+        entity: Entity,
+        kind: MonsterKind,
+        position: Vec2,
+    },
+    Weapon {
+        weapon: Weapon,
+        player: usize,
+        center: Vec2,
+        radius: f32,
+    },
+}
+
+impl Caller {
+    fn position(self) -> Vec2 {
+        match self {
+            Caller::Monster{ position, .. } => position,
+            Caller::Weapon{ center, .. } => center,
+        }
+    }
+}
+
+/// What a script asked for, carried out once the scripts are done.
+enum Action {
+    Spawn(MonsterKind, Vec2),
+    HurtPlayers {
+        kind: MonsterKind,
+        center: Vec2,
+        radius: f32,
+        amount: f32,
+    },
+    Blast {
+        weapon: Weapon,
+        player: usize,
+        center: Vec2,
+        radius: f32,
+    },
+}
+
+/// The game as the scripts see it, shared with the functions they call.
+#[derive(Default)]
+struct ScriptWorld {
+    seconds: f32,
+    players: Vec<Vec2>,
+    monsters: Vec<Vec2>,
+    caller: Option<Caller>,
+    steering: HashMap<Entity, Vec2>,
+    spawns: usize,
+    blasts: usize,
+    actions: Vec<Action>,
+}
+
+type SharedWorld = Arc<Mutex<ScriptWorld>>;
+
+/// Operations left to this frame's calls, and how many the running call
+/// has taken so far.
+#[derive(Default)]
+struct Budget {
+    left: AtomicU64,
+    used: AtomicU64,
+}
+
+fn point(position: Vec2) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("x".into(), Dynamic::from_float(position.x as FLOAT));
+    map.insert("y".into(), Dynamic::from_float(position.y as FLOAT));
+    map.into()
+}
+
+/// A number from a script, if it's still finite as an `f32`.
+fn scalar(value: FLOAT) -> Option<f32> {
+    Some(value as f32).filter(|value| value.is_finite())
+}
+
+fn at(x: FLOAT, y: FLOAT) -> Option<Vec2> {
+    Some(Vec2::new(scalar(x)?, scalar(y)?))
+}
+
+/// `to`, brought within `reach` of `from`.
+fn within(from: Vec2, to: Vec2, reach: f32) -> Vec2 {
+    from + (to - from).clamp_length_max(reach)
+}
+
+/// An engine that only reaches the game through `world`, and stops calls
+/// that go over `budget`.
+fn sandboxed_engine(world: &SharedWorld, budget: &Arc<Budget>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE)
+        .on_print(|text| info!("script: {}", text))
+        .on_debug(|text, _, _| debug!("script: {}", text));
+    engine.disable_symbol("eval");
+
+    let shared = budget.clone();
+    engine.on_progress(move |operations| {
+        shared.used.store(operations, Ordering::Relaxed);
+        (operations > shared.left.load(Ordering::Relaxed)).then_some(Dynamic::UNIT)
+    });
+
+    let shared = world.clone();
+    engine.register_fn("run_time", move || -> FLOAT {
+        shared.lock().unwrap().seconds as FLOAT
+    });
+
+    let shared = world.clone();
+    engine.register_fn("players", move || -> Array {
+        shared.lock().unwrap().players.iter().map(|&p| point(p)).collect()
+    });
+
+    let shared = world.clone();
+    engine.register_fn("nearest_player", move |x: FLOAT, y: FLOAT| -> Dynamic {
+        let from = match at(x, y) {
+            Some(from) => from,
+            None => return Dynamic::UNIT,
+        };
+        shared
+            .lock()
+            .unwrap()
+            .players
+            .iter()
+            .min_by(|a, b| a.distance_squared(from).total_cmp(&b.distance_squared(from)))
+            .map_or(Dynamic::UNIT, |&p| point(p))
+    });
+
+    let shared = world.clone();
+    engine.register_fn("monsters_near", move |x: FLOAT, y: FLOAT, radius: FLOAT| -> Array {
+        let (center, radius) = match (at(x, y), scalar(radius)) {
+            (Some(center), Some(radius)) => (center, radius),
+            _ => return Array::new(),
+        };
+        let reach = radius.powi(2);
+        shared
+            .lock()
+            .unwrap()
+            .monsters
+            .iter()
+            .filter(|p| p.distance_squared(center) <= reach)
+            .take(NEARBY_LIMIT)
+            .map(|&p| point(p))
+            .collect()
+    });
+
+    let shared = world.clone();
+    engine.register_fn("spawn_monster", move |id: &str, x: FLOAT, y: FLOAT| -> bool {
+        let mut world = shared.lock().unwrap();
+        let (kind, spot, caller) = match (MonsterKind::from_id(id), at(x, y), world.caller) {
+            (Some(kind), Some(spot), Some(caller)) if world.spawns < SPAWN_LIMIT => (kind, spot, caller),
+            _ => return false,
+        };
+        world.spawns += 1;
+        world.actions.push(Action::Spawn(kind, within(caller.position(), spot, SPAWN_REACH)));
+        true
+    });
+
+    let shared = world.clone();
+    engine.register_fn("steer", move |x: FLOAT, y: FLOAT| {
+        let mut world = shared.lock().unwrap();
+        if let (Some(Caller::Monster{ entity, .. }), Some(heading)) = (world.caller, at(x, y)) {
+            world.steering.insert(entity, heading.clamp_length_max(1.0));
+        }
+    });
+
+    let shared = world.clone();
+    engine.register_fn("hurt_players", move |x: FLOAT, y: FLOAT, radius: FLOAT, amount: FLOAT| {
+        let (center, radius, amount) = match (at(x, y), scalar(radius), scalar(amount)) {
+            (Some(center), Some(radius), Some(amount)) => (center, radius, amount),
+            _ => return,
+        };
+        let mut world = shared.lock().unwrap();
+        if let Some(Caller::Monster{ kind, position, .. }) = world.caller {
+            world.actions.push(Action::HurtPlayers{
+                kind,
+                center: within(position, center, HURT_REACH),
+                radius: radius.clamp(0.0, HURT_REACH),
+                amount: amount.clamp(0.0, MONSTER_CONTACT_DAMAGE),
+            });
+        }
+    });
+
+    let shared = world.clone();
+    engine.register_fn("blast", move |x: FLOAT, y: FLOAT, radius: FLOAT| {
+        let (spot, radius) = match (at(x, y), scalar(radius)) {
+            (Some(spot), Some(radius)) => (spot, radius),
+            _ => return,
+        };
+        let mut world = shared.lock().unwrap();
+        if let Some(Caller::Weapon{ weapon, player, center, radius: shot_radius }) = world.caller {
+            if world.blasts < BLAST_LIMIT {
+                world.blasts += 1;
+                world.actions.push(Action::Blast{
+                    weapon,
+                    player,
+                    center: within(center, spot, BLAST_REACH),
+                    radius: radius.clamp(0.0, shot_radius * BLAST_RADIUS_FACTOR),
+                });
+            }
+        }
+    });
+
+    engine
+}
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+    is_broken: bool,
+}
+
+impl Script {
+    fn compile(engine: &Engine, path: &Path) -> Option<Script> {
+        let compiled = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| engine.compile(text).map_err(|e| e.to_string()));
+
+        match compiled {
+            Ok(ast) => Some(Script{
+                path: path.to_path_buf(),
+                ast,
+                is_broken: false,
+            }),
+            Err(e) => {
+                warn!("skipping script {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Run `function` if the script has it and the frame's budget isn't
+    /// spent, giving up on the script for good the first time it fails.
+    fn call(&mut self, engine: &Engine, budget: &Budget, function: &str, argument: Dynamic) {
+        let left = budget.left.load(Ordering::Relaxed);
+        if self.is_broken || left == 0 || !self.ast.iter_functions().any(|f| f.name == function) {
+            return;
+        }
+
+        // Only the function, not the script's top level all over again
+        budget.used.store(0, Ordering::Relaxed);
+        let options = CallFnOptions::new().eval_ast(false);
+        let called = engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, function, (argument,));
+        budget.left.store(left.saturating_sub(budget.used.load(Ordering::Relaxed)), Ordering::Relaxed);
+
+        match called.map_err(|e| *e) {
+            Ok(_) => {}
+            // Out of this frame's budget, which isn't the script's fault
+            Err(EvalAltResult::ErrorTerminated(..)) => budget.left.store(0, Ordering::Relaxed),
+            Err(e) => {
+                warn!("stopping script {}: {}", self.path.display(), e);
+                self.is_broken = true;
+            }
+        }
+    }
+}
+
+pub struct Scripts {
+    engine: Engine,
+    world: SharedWorld,
+    budget: Arc<Budget>,
+    monsters: BTreeMap<MonsterKind, Script>,
+    weapons: BTreeMap<Weapon, Script>,
+    think: Timer,
+}
+
+impl Scripts {
+    fn compile() -> Scripts {
+        let world = SharedWorld::default();
+        let budget = Arc::new(Budget::default());
+        let engine = sandboxed_engine(&world, &budget);
+
+        let monsters = mods::monster_scripts()
+            .filter_map(|(kind, path)| Some((kind, Script::compile(&engine, path)?)))
+            .collect();
+        let weapons = mods::weapon_scripts()
+            .filter_map(|(weapon, path)| Some((weapon, Script::compile(&engine, path)?)))
+            .collect();
+
+        Scripts{
+            engine,
+            world,
+            budget,
+            monsters,
+            weapons,
+            think: Timer::from_seconds(THINK_SECONDS, true),
+        }
+    }
+
+    /// Catch the scripts' view up with the game before they run.
+    fn refresh(&self, seconds: f32, players: Vec<Vec2>, monsters: Vec<Vec2>) {
+        let mut world = self.world.lock().unwrap();
+        world.seconds = seconds;
+        world.players = players;
+        world.monsters = monsters;
+    }
+}
+
+fn refill_budget(scripts: Res<Scripts>) {
+    scripts.budget.left.store(FRAME_OPERATIONS, Ordering::Relaxed);
+}
+
+pub fn think_monsters(
+    time: Res<Time>,
+    run_stats: Res<RunStats>,
+    mut scripts: ResMut<Scripts>,
+    players: Query<&Position, With<Player>>,
+    mut monsters: Query<(Entity, &MonsterKind, &Position, &mut Velocity), With<Monster>>,
+) {
+    if scripts.monsters.is_empty() {
+        return;
+    }
+
+    if scripts.think.tick(time.delta()).just_finished() {
+        scripts.refresh(
+            run_stats.duration_seconds(),
+            players.iter().map(|p| p.current).collect(),
+            monsters.iter().map(|(_, _, p, _)| p.current).collect(),
+        );
+        {
+            let mut world = scripts.world.lock().unwrap();
+            world.steering.clear();
+            world.spawns = 0;
+        }
+
+        let scripts = &mut *scripts;
+        for (entity, kind, p, _) in monsters.iter() {
+            if let Some(script) = scripts.monsters.get_mut(kind) {
+                scripts.world.lock().unwrap().caller = Some(Caller::Monster{
+                    entity,
+                    kind: *kind,
+                    position: p.current,
+                });
+
+                let mut me = Map::new();
+                me.insert("x".into(), Dynamic::from_float(p.current.x as FLOAT));
+                me.insert("y".into(), Dynamic::from_float(p.current.y as FLOAT));
+                me.insert("kind".into(), kind.id().into());
+                script.call(&scripts.engine, &scripts.budget, "think", me.into());
+            }
+        }
+    }
+
+    // Steering holds between thoughts, over the usual chase
+    let world = scripts.world.lock().unwrap();
+    for (entity, _, _, mut v) in monsters.iter_mut() {
+        if let Some(&direction) = world.steering.get(&entity) {
+            v.direction = direction;
+        }
+    }
+}
+
+pub fn fire_weapon_scripts(
+    run_stats: Res<RunStats>,
+    mut scripts: ResMut<Scripts>,
+    players: Query<&Position, With<Player>>,
+    monsters: Query<&Position, With<Monster>>,
+    blasts: Query<(&Blast, &Position, Option<&ScriptBlast>), Added<Blast>>,
+) {
+    if scripts.weapons.is_empty() {
+        return;
+    }
+
+    let mut has_refreshed = false;
+    let scripts = &mut *scripts;
+    for (blast, p, from_script) in blasts.iter() {
+        let player = match blast.shooter {
+            Some(player) if from_script.is_none() && scripts.weapons.contains_key(&blast.weapon) => player,
+            _ => continue,
+        };
+
+        if !has_refreshed {
+            scripts.refresh(
+                run_stats.duration_seconds(),
+                players.iter().map(|p| p.current).collect(),
+                monsters.iter().map(|p| p.current).collect(),
+            );
+            has_refreshed = true;
+        }
+        {
+            let mut world = scripts.world.lock().unwrap();
+            world.caller = Some(Caller::Weapon{
+                weapon: blast.weapon,
+                player,
+                center: p.current,
+                radius: blast.circle.radius,
+            });
+            world.blasts = 0;
+        }
+
+        let mut shot = Map::new();
+        shot.insert("x".into(), Dynamic::from_float(p.current.x as FLOAT));
+        shot.insert("y".into(), Dynamic::from_float(p.current.y as FLOAT));
+        shot.insert("from_x".into(), Dynamic::from_float(blast.origin.x as FLOAT));
+        shot.insert("from_y".into(), Dynamic::from_float(blast.origin.y as FLOAT));
+        shot.insert("radius".into(), Dynamic::from_float(blast.circle.radius as FLOAT));
+        shot.insert("player".into(), Dynamic::from_int(player as INT));
+        if let Some(script) = scripts.weapons.get_mut(&blast.weapon) {
+            script.call(&scripts.engine, &scripts.budget, "fire", shot.into());
+        }
+    }
+}
+
+/// Marks blasts set off by scripts, which don't run the script again.
+#[derive(Component)]
+pub struct ScriptBlast;
+
+pub fn apply_script_actions(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    scripts: Res<Scripts>,
+    mut threat: ResMut<Threat>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut run_stats: ResMut<RunStats>,
+    mut players: Query<(Entity, &Position, &mut Health), With<Player>>,
+    dying: Query<(), With<Dying>>,
+) {
+    let actions = std::mem::take(&mut scripts.world.lock().unwrap().actions);
+    let mut is_over = !dying.is_empty();
+
+    for action in actions {
+        match action {
+            // Scripted spawns come out of the same budget as any other
+            Action::Spawn(kind, position) => {
+                if threat.can_afford(kind.threat()) {
+                    spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+                    monster_stats.record_spawn(kind);
+                    threat.spend(kind.threat());
+                }
+            }
+            Action::HurtPlayers{ kind, center, radius, amount } => {
+                if is_over {
+                    continue;
+                }
+                for (entity, p, mut health) in players.iter_mut() {
+                    if p.current.distance_squared(center) > radius.powi(2) || !health.can_be_hurt() {
+                        continue;
+                    }
+                    run_stats.record_damage(amount);
+                    if health.hurt(amount) {
+                        commands.entity(entity).insert(Dying::new());
+                        run_stats.record_death(kind.name().to_string());
+                        is_over = true;
+                        break;
+                    }
+                }
+            }
+            Action::Blast{ weapon, player, center, radius } => {
+                let blast = spawn_blast_at(&mut commands, &sprites, player, weapon, radius, center, center);
+                commands.entity(blast).insert(ScriptBlast);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monster() -> Caller {
+        Caller::Monster{
+            entity: Entity::from_raw(0),
+            kind: MonsterKind::Grunt,
+            position: Vec2::ZERO,
+        }
+    }
+
+    fn shot() -> Caller {
+        Caller::Weapon{
+            weapon: Weapon::Blast,
+            player: 0,
+            center: Vec2::ZERO,
+            radius: 10.0,
+        }
+    }
+
+    /// Run `code` for `caller` with a player and a monster about, and `nan`,
+    /// `inf` and `huge`, which is too big for an `f32`, in scope.
+    fn run(caller: Caller, code: &str) -> (Dynamic, ScriptWorld) {
+        let world = SharedWorld::default();
+        {
+            let mut world = world.lock().unwrap();
+            world.players = vec![Vec2::new(10.0, 0.0)];
+            world.monsters = vec![Vec2::new(0.0, 10.0)];
+            world.caller = Some(caller);
+        }
+        let budget = Arc::new(Budget::default());
+        budget.left.store(FRAME_OPERATIONS, Ordering::Relaxed);
+
+        let mut scope = Scope::new();
+        scope.push("nan", FLOAT::NAN);
+        scope.push("inf", FLOAT::INFINITY);
+        scope.push("huge", 1e300 as FLOAT);
+        let result = sandboxed_engine(&world, &budget).eval_with_scope::<Dynamic>(&mut scope, code).unwrap();
+
+        let world = std::mem::take(&mut *world.lock().unwrap());
+        (result, world)
+    }
+
+    #[test]
+    fn lookups_from_nowhere_find_nothing() {
+        for code in ["nearest_player(nan, 0.0)", "nearest_player(0.0, -inf)", "nearest_player(huge, 0.0)"] {
+            assert!(run(monster(), code).0.is_unit(), "{}", code);
+        }
+        for code in ["monsters_near(nan, 0.0, 50.0)", "monsters_near(0.0, inf, 50.0)", "monsters_near(0.0, 0.0, nan)", "monsters_near(0.0, 0.0, inf)"] {
+            assert_eq!(run(monster(), code).0.cast::<Array>().len(), 0, "{}", code);
+        }
+        assert_eq!(run(monster(), "monsters_near(0.0, 0.0, 50.0)").0.cast::<Array>().len(), 1);
+    }
+
+    #[test]
+    fn steering_nowhere_is_ignored() {
+        for code in ["steer(nan, 1.0)", "steer(1.0, -inf)", "steer(huge, 0.0)", "steer(1.0 / 0.0, 0.0)"] {
+            assert!(run(monster(), code).1.steering.is_empty(), "{}", code);
+        }
+        assert_eq!(run(monster(), "steer(3.0, 0.0)").1.steering[&Entity::from_raw(0)], Vec2::X);
+    }
+
+    #[test]
+    fn spawns_nowhere_are_refused() {
+        for code in ["spawn_monster(\"grunt\", nan, 0.0)", "spawn_monster(\"grunt\", 0.0, inf)", "spawn_monster(\"grunt\", huge, 0.0)"] {
+            let (spawned, world) = run(monster(), code);
+            assert!(!spawned.as_bool().unwrap(), "{}", code);
+            assert!(world.actions.is_empty(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn spawns_stay_near_the_caller() {
+        for caller in [monster(), shot()] {
+            let (spawned, world) = run(caller, "spawn_monster(\"grunt\", 1e9, 0.0)");
+            assert!(spawned.as_bool().unwrap());
+            assert!(matches!(world.actions[..], [Action::Spawn(_, spot)] if spot.length() <= SPAWN_REACH + 0.01));
+        }
+    }
+
+    #[test]
+    fn hurts_with_any_number_not_finite_are_ignored() {
+        for code in [
+            "hurt_players(nan, 0.0, 10.0, 1.0)",
+            "hurt_players(0.0, inf, 10.0, 1.0)",
+            "hurt_players(0.0, 0.0, nan, 1.0)",
+            "hurt_players(0.0, 0.0, inf, 1.0)",
+            "hurt_players(0.0, 0.0, 10.0, nan)",
+            "hurt_players(0.0, 0.0, 10.0, huge)",
+        ] {
+            assert!(run(monster(), code).1.actions.is_empty(), "{}", code);
+        }
+        assert_eq!(run(monster(), "hurt_players(0.0, 0.0, 10.0, 1.0)").1.actions.len(), 1);
+    }
+
+    #[test]
+    fn blasts_with_any_number_not_finite_are_ignored() {
+        for code in ["blast(nan, 0.0, 10.0)", "blast(0.0, -inf, 10.0)", "blast(0.0, 0.0, nan)", "blast(0.0, 0.0, inf)", "blast(huge, 0.0, 10.0)"] {
+            assert!(run(shot(), code).1.actions.is_empty(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn blasts_stay_near_the_shot() {
+        let (_, world) = run(shot(), "blast(0.0, -1e9, 1e9)");
+        assert!(matches!(
+            world.actions[..],
+            [Action::Blast{ center, radius, .. }] if center.length() <= BLAST_REACH + 0.01 && radius <= 10.0 * BLAST_RADIUS_FACTOR
+        ));
+    }
+}
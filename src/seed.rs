@@ -0,0 +1,153 @@
+//! Sharing seeds. The pause and game over screens show the run's seed, and
+//! C copies it to the clipboard. In the menu V pastes a seed for the next runs
+//! to use, and Backspace goes back to a fresh seed for each run. Browser builds
+//! have no clipboard access, so there the seed can only be read off the screen.
+
+use bevy::prelude::*;
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::{RunDefaults, WorldSeed};
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Clipboard(Option<arboard::Clipboard>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        Clipboard(
+            arboard::Clipboard::new()
+                .map_err(|e| warn!("no clipboard: {}", e))
+                .ok(),
+        )
+    }
+
+    /// Some platforms only serve what was copied for as long as the clipboard
+    /// that copied it is open, so one is kept for the whole session.
+    fn copy(&mut self, text: String) -> Result<(), String> {
+        let clipboard = self.0.as_mut().ok_or("no clipboard")?;
+        clipboard.set_text(text).map_err(|e| e.to_string())
+    }
+
+    fn paste(&mut self) -> Result<String, String> {
+        let clipboard = self.0.as_mut().ok_or("no clipboard")?;
+        clipboard.get_text().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct Clipboard;
+
+#[cfg(target_arch = "wasm32")]
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        Clipboard
+    }
+
+    fn copy(&mut self, _text: String) -> Result<(), String> {
+        Err("the clipboard isn't available in the browser".to_string())
+    }
+
+    fn paste(&mut self) -> Result<String, String> {
+        Err("the clipboard isn't available in the browser".to_string())
+    }
+}
+
+/// The run's seed on the pause or game over screen.
+#[derive(Component)]
+pub struct SeedText;
+
+#[derive(Component)]
+pub struct SeedPanelText;
+
+pub fn seed_label(seed: u64) -> String {
+    format!("Seed {} (C to copy)", seed)
+}
+
+fn panel_label(seed: Option<u64>) -> String {
+    match seed {
+        Some(seed) => format!("seed: {} (V to paste, Backspace for random)", seed),
+        None => "seed: random (V to paste)".to_string(),
+    }
+}
+
+pub fn copy_seed(
+    keyboard_input: Res<Input<KeyCode>>,
+    world_seed: Res<WorldSeed>,
+    mut clipboard: NonSendMut<Clipboard>,
+    mut texts: Query<&mut Text, With<SeedText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::C) {
+        return;
+    }
+
+    match clipboard.copy(world_seed.value.to_string()) {
+        Ok(()) => {
+            for mut text in texts.iter_mut() {
+                text.sections[0].value = format!("Seed {} copied", world_seed.value);
+            }
+        }
+        Err(e) => warn!("failed to copy the seed: {}", e),
+    }
+}
+
+pub fn spawn_seed_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    defaults: Res<RunDefaults>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(132.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(defaults.seed),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(SeedPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn seed_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut defaults: ResMut<RunDefaults>,
+    mut clipboard: NonSendMut<Clipboard>,
+    mut texts: Query<&mut Text, With<SeedPanelText>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::V) {
+        let pasted = clipboard
+            .paste()
+            .and_then(|text| text.trim().parse::<u64>().map_err(|_| format!("'{}' isn't a seed", text.trim())));
+        match pasted {
+            Ok(seed) => defaults.seed = Some(seed),
+            Err(e) => warn!("failed to paste a seed: {}", e),
+        }
+    }
+    else if keyboard_input.just_pressed(KeyCode::Back) {
+        defaults.seed = None;
+    }
+    else {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(defaults.seed);
+    }
+}
@@ -0,0 +1,87 @@
+//! Soft shadows under players and monsters to set them on the ground. A
+//! shadow is a dark ellipse kept in step with its owner's body, fading in and
+//! out with its sprite. Flyers cast theirs on the ground a little way below
+//! them, smaller for the height.
+
+use bevy::prelude::*;
+
+use crate::burrowing::Submerged;
+use crate::loading::GameAssets;
+use crate::{Body, MonsterKind, Position, Velocity};
+
+
+const SHADOW_ALPHA: f32        = 0.3;
+const SHADOW_SQUASH: f32       = 0.45;
+const SHADOW_DEPTH: f32        = -3.0;
+const SHADOW_DROP: f32         = 0.6;
+const SHADOW_FLYING_DROP: f32  = 2.0;
+const SHADOW_FLYING_SCALE: f32 = 0.7;
+
+#[derive(Component)]
+pub struct Shadow {
+    owner: Entity,
+    /// How far below the owner's center, in body radii.
+    drop: f32,
+    scale: f32,
+}
+
+pub type ShadowOwners<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Position, &'static Body, &'static TextureAtlasSprite, &'static Visibility, Option<&'static Submerged>),
+    Without<Shadow>,
+>;
+
+/// Everything that moves under its own power casts a shadow.
+pub fn attach_shadows(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    owners: Query<(Entity, &Position, &Body, Option<&MonsterKind>), Added<Velocity>>,
+) {
+    for (owner, p, _, kind) in owners.iter() {
+        let is_flying = kind.is_some_and(|k| k.is_flying());
+        let (drop, scale) = if is_flying { (SHADOW_FLYING_DROP, SHADOW_FLYING_SCALE) } else { (SHADOW_DROP, 1.0) };
+
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.blast(),
+                    color: Color::NONE,
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, SHADOW_DEPTH),
+                ..Default::default()
+            })
+            .insert(Shadow{
+                owner,
+                drop,
+                scale,
+            })
+            .insert(Position::new(p.current));
+    }
+}
+
+pub fn update_shadows(
+    mut commands: Commands,
+    owners: ShadowOwners,
+    mut shadows: Query<(Entity, &Shadow, &mut Position, &mut TextureAtlasSprite, &mut Visibility)>,
+) {
+    for (entity, shadow, mut p, mut sprite, mut visibility) in shadows.iter_mut() {
+        let (owner_p, body, owner_sprite, owner_visibility, submerged) = match owners.get(shadow.owner) {
+            Ok(owner) => owner,
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+
+        // Hordes grow and monsters fade in as they spawn, so follow both
+        let radius = body.circle.radius;
+        let width = 2.0 * radius * shadow.scale;
+        *p = Position::new(owner_p.current - Vec2::Y * radius * shadow.drop);
+        sprite.custom_size = Some(Vec2::new(width, width * SHADOW_SQUASH));
+        sprite.color.set_a(SHADOW_ALPHA * owner_sprite.color.a());
+        visibility.is_visible = owner_visibility.is_visible && submerged.is_none();
+    }
+}
@@ -0,0 +1,64 @@
+//! Shields for the Shielded kind. A shield covers an arc in front of its
+//! monster and soaks up blasts fired from that side, so the monster has to be
+//! hit from behind or have its shield worn down first. Each blocked blast
+//! wears it down by one and a broken shield is gone for good.
+
+use bevy::prelude::*;
+
+
+const SHIELD_DURABILITY: u32 = 2;
+const SHIELD_HALF_ARC: f32   = 1.0;
+
+#[derive(Component)]
+pub struct Shield {
+    durability: u32,
+    blocked: Vec<Entity>,
+}
+
+impl Shield {
+    pub fn new() -> Shield {
+        Shield{
+            durability: SHIELD_DURABILITY,
+            blocked: Vec::new(),
+        }
+    }
+
+    /// Whether the shield stops `blast`, fired from `from` at a monster at
+    /// `position` heading along `facing`. Blocking a blast wears the shield
+    /// down, and it keeps blocking that blast for as long as it lasts.
+    pub fn blocks(&mut self, blast: Entity, position: Vec2, facing: Vec2, from: Vec2) -> bool {
+        if self.blocked.contains(&blast) {
+            return true;
+        }
+        if self.durability == 0 {
+            return false;
+        }
+
+        // Only hits from inside the arc around the heading
+        let facing = facing.normalize_or_zero();
+        let toward = (from - position).normalize_or_zero();
+        if facing == Vec2::ZERO || toward == Vec2::ZERO || facing.dot(toward) < SHIELD_HALF_ARC.cos() {
+            return false;
+        }
+
+        self.blocked.push(blast);
+        self.durability -= 1;
+        true
+    }
+}
+
+/// Strip worn out shields, dimming their monster to show it's open to hits.
+pub fn break_shields(
+    mut commands: Commands,
+    mut shields: Query<(Entity, &Shield, &mut TextureAtlasSprite)>,
+) {
+    for (entity, shield, mut sprite) in shields.iter_mut() {
+        if shield.durability > 0 {
+            continue;
+        }
+
+        let alpha = sprite.color.a();
+        sprite.color = Color::rgba(0.55, 0.6, 0.7, alpha);
+        commands.entity(entity).remove::<Shield>();
+    }
+}
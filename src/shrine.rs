@@ -0,0 +1,194 @@
+//! Shrines, optional objectives the director puts up every so often. A shrine
+//! charges while a player stands inside its circle and slowly drains while
+//! nobody does. Channelling it draws monsters in from all around, and once it
+//! fills it leaves a chest behind holding a random upgrade and a handful of
+//! coins. Only one shrine is up at a time.
+
+use bevy::prelude::*;
+
+use crate::arena::Arena;
+use crate::director::Director;
+use crate::effective_stats::StatsChangedEvent;
+use crate::feed::Announcement;
+use crate::health::Health;
+use crate::loading::GameAssets;
+use crate::merchant::CoinDrop;
+use crate::players::{player_positions, random_player};
+use crate::progression::{grant_upgrade, Progression};
+use crate::stage::Stages;
+use crate::threat::Threat;
+use crate::{random_unit, spawn_monster_kind, MonsterStats, NewGameEvent, Player, Position};
+
+
+const SHRINE_DISTANCE: f32        = 300.0;
+const SHRINE_RADIUS: f32          = 70.0;
+const SHRINE_CHANNEL_SECONDS: f32 = 12.0;
+/// How much slower the charge drains than it fills.
+const SHRINE_DRAIN: f32           = 0.25;
+const SHRINE_COLOR: Color         = Color::rgb(0.5, 0.8, 1.0);
+const SHRINE_MIN_ALPHA: f32       = 0.15;
+const SHRINE_MAX_ALPHA: f32       = 0.6;
+/// Under the coins, over the ground.
+const SHRINE_DEPTH: f32           = -1.5;
+
+const CHANNEL_SPAWN_SECONDS: f32  = 1.5;
+const CHANNEL_SPAWN_GROUP: u32    = 4;
+const CHANNEL_SPAWN_DISTANCE: f32 = 360.0;
+
+const CHEST_SIZE: f32          = 20.0;
+const CHEST_COLOR: Color       = Color::rgb(0.9, 0.6, 0.2);
+const CHEST_PICKUP_RADIUS: f32 = 28.0;
+const CHEST_COINS: u32         = 10;
+
+#[derive(Component)]
+pub struct Shrine {
+    /// From 0 when it appears to 1 when it's done.
+    charge: f32,
+    spawn: Timer,
+}
+
+#[derive(Component)]
+pub struct Chest;
+
+/// Put up a shrine near a player when the director calls for one, and clear
+/// shrines and chests away for a new run.
+pub fn place_shrines(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut director: ResMut<Director>,
+    arena: Res<Arena>,
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut announcements: EventWriter<Announcement>,
+    players: Query<&Position, With<Player>>,
+    shrines: Query<Entity, With<Shrine>>,
+    chests: Query<Entity, With<Chest>>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        for entity in shrines.iter().chain(chests.iter()) {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    if !director.is_shrine_due(time.delta()) || !shrines.is_empty() {
+        return;
+    }
+
+    let target = if let Some(p) = random_player(&player_positions(&players)) {
+        p
+    }
+    else {
+        return
+    };
+
+    let position = target + random_unit().normalize_or_zero() * SHRINE_DISTANCE;
+    let mut color = SHRINE_COLOR;
+    color.set_a(SHRINE_MIN_ALPHA);
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: sprites.atlas(),
+            sprite: TextureAtlasSprite{
+                index: sprites.blast(),
+                color,
+                custom_size: Some(Vec2::splat(SHRINE_RADIUS * 2.0)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, SHRINE_DEPTH),
+            ..Default::default()
+        })
+        .insert(Shrine{
+            charge: 0.0,
+            spawn: Timer::from_seconds(CHANNEL_SPAWN_SECONDS, true),
+        })
+        .insert(Position::new(arena.clamp_inside(position, SHRINE_RADIUS)));
+    announcements.send(Announcement("A shrine has appeared".to_string()));
+}
+
+/// Charge shrines with players inside, send monsters at them while they do
+/// and swap a full shrine for its chest.
+pub fn charge_shrines(
+    time: Res<Time>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    stages: Res<Stages>,
+    arena: Res<Arena>,
+    mut threat: ResMut<Threat>,
+    mut monster_stats: ResMut<MonsterStats>,
+    mut announcements: EventWriter<Announcement>,
+    players: Query<&Position, With<Player>>,
+    mut shrines: Query<(Entity, &mut Shrine, &Position, &mut TextureAtlasSprite)>,
+) {
+    let positions = player_positions(&players);
+
+    for (entity, mut shrine, p, mut sprite) in shrines.iter_mut() {
+        let is_channelling = positions.iter().any(|player| player.distance(p.current) < SHRINE_RADIUS);
+        let rate = time.delta_seconds() / SHRINE_CHANNEL_SECONDS;
+        if !is_channelling {
+            shrine.charge = (shrine.charge - rate * SHRINE_DRAIN).max(0.0);
+        }
+        else {
+            shrine.charge += rate;
+
+            // Like the timeline, the channel's spawns go ahead past the budget
+            if shrine.spawn.tick(time.delta()).just_finished() {
+                let kind = stages.current().roll_monster();
+                for _ in 0..CHANNEL_SPAWN_GROUP {
+                    let position = arena
+                        .spawn_point()
+                        .unwrap_or_else(|| p.current + random_unit().normalize_or_zero() * CHANNEL_SPAWN_DISTANCE);
+                    spawn_monster_kind(&mut commands, &sprites, kind, position, 1.0);
+                    monster_stats.record_spawn(kind);
+                }
+                threat.spend(kind.threat() * CHANNEL_SPAWN_GROUP);
+            }
+        }
+
+        sprite.color.set_a(SHRINE_MIN_ALPHA + (SHRINE_MAX_ALPHA - SHRINE_MIN_ALPHA) * shrine.charge.min(1.0));
+        if shrine.charge < 1.0 {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: sprites.atlas(),
+                sprite: TextureAtlasSprite{
+                    index: sprites.blast(),
+                    color: CHEST_COLOR,
+                    custom_size: Some(Vec2::splat(CHEST_SIZE)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Chest)
+            .insert(Position::new(p.current));
+        announcements.send(Announcement("The shrine left a chest".to_string()));
+    }
+}
+
+pub fn open_chests(
+    mut commands: Commands,
+    mut progression: ResMut<Progression>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+    mut coin_drops: EventWriter<CoinDrop>,
+    mut announcements: EventWriter<Announcement>,
+    players: Query<&Position, With<Player>>,
+    mut healths: Query<&mut Health, With<Player>>,
+    chests: Query<(Entity, &Position), With<Chest>>,
+) {
+    for (chest, chest_p) in chests.iter() {
+        if !players.iter().any(|p| p.current.distance(chest_p.current) < CHEST_PICKUP_RADIUS) {
+            continue;
+        }
+
+        commands.entity(chest).despawn();
+        let upgrade = progression.random_upgrade();
+        grant_upgrade(&mut progression, upgrade, &mut healths, &mut stats_writer);
+        coin_drops.send(CoinDrop{
+            position: chest_p.current,
+            count: CHEST_COINS,
+        });
+        announcements.send(Announcement(format!("The chest held {}", upgrade.name())));
+    }
+}
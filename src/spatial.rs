@@ -0,0 +1,183 @@
+//! A coarse grid of monster bodies, rebuilt every frame after movement, and
+//! the obstacles alongside it. Lets systems ask what overlaps a circle, what a
+//! ray runs into or whether obstacles block the view without walking the whole
+//! swarm.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::prelude::*;
+
+use crate::{Body, Layers, Monster, Obstacle, Position, Shape};
+
+
+const SPATIAL_CELL_SIZE: f32      = 64.0;
+const LINE_OF_SIGHT_MIN_STEP: f32 = 4.0;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct IndexMonsters;
+
+/// Where a ray first meets a monster.
+#[derive(Clone, Copy)]
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+pub struct RayHit {
+    pub entity: Entity,
+    pub point: Vec2,
+    pub distance: f32,
+}
+
+#[derive(Default)]
+pub struct SpatialIndex {
+    cells: HashMap<IVec2, Vec<(Entity, Vec2, f32)>>,
+    /// The largest radius indexed, since bodies are filed by their center
+    /// but can reach into neighbouring cells.
+    max_radius: f32,
+    /// Few and far between, so kept in a plain list.
+    obstacles: Vec<(Vec2, f32, Shape)>,
+}
+
+impl SpatialIndex {
+    fn cell_of(point: Vec2) -> IVec2 {
+        (point / SPATIAL_CELL_SIZE).floor().as_ivec2()
+    }
+
+    fn overlapping(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Vec2, f32)> + '_ {
+        let reach = radius + self.max_radius;
+        let min = Self::cell_of(center - Vec2::splat(reach));
+        let max = Self::cell_of(center + Vec2::splat(reach));
+
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |(_, p, r)| p.distance_squared(center) <= (radius + r).powi(2))
+    }
+
+    /// Every indexed monster whose body overlaps the circle.
+    pub fn overlap_circle(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        self.overlapping(center, radius).map(|(entity, p, _)| (entity, p))
+    }
+
+    /// The middle of the biggest crowd within `range` of `center`, going by
+    /// how many monsters share each block of three by three cells.
+    pub fn densest_cluster(&self, center: Vec2, range: f32) -> Option<Vec2> {
+        // Kept in cell order so ties always go the same way
+        let mut blocks: BTreeMap<(i32, i32), (usize, Vec2)> = BTreeMap::new();
+        for (_, p, _) in self.overlapping(center, range) {
+            let cell = Self::cell_of(p);
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let block = blocks.entry((cell.x + x, cell.y + y)).or_default();
+                    block.0 += 1;
+                    block.1 += p;
+                }
+            }
+        }
+
+        blocks
+            .into_values()
+            .max_by_key(|&(count, _)| count)
+            .map(|(count, sum)| sum / count as f32)
+    }
+
+    /// The first indexed monster a ray runs into within `max_distance`. Only
+    /// the debug ray probe casts rays so far.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    pub fn cast_ray(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RayHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        // Step along the ray a cell at a time, checking bodies near each step
+        // until nothing closer than the best hit can turn up
+        let mut best: Option<RayHit> = None;
+        let steps = (max_distance / SPATIAL_CELL_SIZE).ceil() as u32;
+        for step in 0..=steps {
+            let along = (step as f32 * SPATIAL_CELL_SIZE).min(max_distance);
+            if best.is_some_and(|hit| hit.distance + SPATIAL_CELL_SIZE < along) {
+                break;
+            }
+
+            let point = origin + direction * along;
+            for (entity, center, radius) in self.overlapping(point, SPATIAL_CELL_SIZE) {
+                let distance = match ray_circle(origin, direction, center, radius) {
+                    Some(distance) if distance <= max_distance => distance,
+                    _ => continue,
+                };
+                if best.is_none_or(|hit| distance < hit.distance) {
+                    best = Some(RayHit{
+                        entity,
+                        point: origin + direction * distance,
+                        distance,
+                    });
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Whether no obstacle stands between two points.
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        !self.obstacles.iter().any(|(center, radius, shape)| {
+            // Sample the line finely enough not to skip over the obstacle
+            let spacing = (radius / 2.0).max(LINE_OF_SIGHT_MIN_STEP);
+            let steps = (from.distance(to) / spacing).ceil().max(1.0) as u32;
+            (0..=steps).any(|step| {
+                let point = from.lerp(to, step as f32 / steps as f32);
+                point.distance_squared(shape.closest_point(*center, point)) <= radius * radius
+            })
+        })
+    }
+}
+
+/// How far along a ray with a unit `direction` it enters a circle, or zero if
+/// it starts inside.
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+fn ray_circle(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+
+    let discriminant = b * b - c;
+    if discriminant < 0.0 || b > 0.0 {
+        return None;
+    }
+
+    Some(-b - discriminant.sqrt())
+}
+
+pub fn index_monsters(
+    mut index: ResMut<SpatialIndex>,
+    monsters: Query<(Entity, &Position, &Body), With<Monster>>,
+) {
+    // Keep each cell's allocation between frames
+    for entries in index.cells.values_mut() {
+        entries.clear();
+    }
+    index.max_radius = 0.0;
+
+    // Monsters off every monster layer, like submerged ones, can't be found
+    for (entity, p, body) in monsters.iter().filter(|(_, _, b)| b.layer.intersects(Layers::MONSTERS)) {
+        index.max_radius = index.max_radius.max(body.circle.radius);
+        index
+            .cells
+            .entry(SpatialIndex::cell_of(p.current))
+            .or_default()
+            .push((entity, p.current, body.circle.radius));
+    }
+}
+
+pub fn index_obstacles(
+    mut index: ResMut<SpatialIndex>,
+    obstacles: Query<(&Position, &Body), With<Obstacle>>,
+) {
+    index.obstacles.clear();
+    index
+        .obstacles
+        .extend(obstacles.iter().map(|(p, body)| (p.current, body.circle.radius, body.shape)));
+}
@@ -0,0 +1,402 @@
+//! Stages, loaded from the RON files in `assets/stages`. Each sets the ground,
+//! the obstacles, which monsters spawn and how often, and the bosses due at
+//! set times. A stage can also script spawns on a timeline, either inline or
+//! in a file of the same name under `assets/stages/timelines`, which the
+//! director plays out through the run, and fence the fight in as an arena.
+//! Monster kinds added by mods can be named like the built-in ones.
+//!
+//! Browser builds have no stage directory to read, so the shipped stages and
+//! timelines are built into them instead.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::arena::Arena;
+use crate::horde::Horde;
+use crate::loading::GameAssets;
+use crate::mods;
+use crate::players::{player_positions, random_player};
+use crate::{
+    random_unit, spawn_monster_kind, MonsterKind, MonsterStats, Player, Position,
+    MONSTER_SPAWN_DISTANCE,
+};
+
+
+const STAGE_DIRECTORY: &str    = "assets/stages";
+const TIMELINE_DIRECTORY: &str = "timelines";
+
+#[derive(Deserialize)]
+pub struct StageDef {
+    pub name: String,
+    pub ground: GroundDef,
+    pub obstacles: ObstacleDef,
+    pub spawn_table: Vec<SpawnEntry>,
+    #[serde(default)]
+    pub bosses: Vec<BossEntry>,
+    #[serde(default)]
+    pub timeline: Vec<TimelineEvent>,
+    /// Walls around the start, for a bounded arena.
+    #[serde(default)]
+    pub arena: Option<ArenaDef>,
+}
+
+#[derive(Deserialize)]
+pub struct GroundDef {
+    pub light: (f32, f32, f32),
+    pub dark: (f32, f32, f32),
+}
+
+/// The core an obstacle's radius is swept along. Capsules get a random
+/// heading and boxes stay lined up with the axes.
+#[derive(Deserialize, Clone, Copy, Default)]
+pub enum ObstacleShape {
+    #[default]
+    Circle,
+    Capsule {
+        length: f32,
+    },
+    Box {
+        width: f32,
+        height: f32,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct ObstacleDef {
+    pub count: u32,
+    #[serde(default)]
+    pub shape: ObstacleShape,
+    pub radius: f32,
+    pub spawn_radius: f32,
+    pub clear_radius: f32,
+    pub color: (f32, f32, f32),
+}
+
+#[derive(Deserialize)]
+pub struct ArenaDef {
+    pub width: f32,
+    pub height: f32,
+    /// How thick the walls are.
+    pub wall: f32,
+    pub gates_per_side: u32,
+    pub gate_width: f32,
+}
+
+#[derive(Deserialize)]
+pub struct SpawnEntry {
+    pub kind: MonsterKind,
+    pub weight: u32,
+}
+
+#[derive(Deserialize)]
+pub struct BossEntry {
+    pub at_seconds: f32,
+    pub kind: MonsterKind,
+    pub scale: f32,
+    /// Blasts it takes to bring down, just one if left out.
+    #[serde(default)]
+    pub hits: u32,
+}
+
+/// The side of the players a scripted group comes from.
+#[derive(Deserialize, Clone, Copy)]
+pub enum Side {
+    North,
+    South,
+    East,
+    West,
+    /// All around, at the usual spawn distance.
+    Around,
+}
+
+impl Side {
+    pub fn direction(self) -> Option<Vec2> {
+        match self {
+            Side::North => Some(Vec2::Y),
+            Side::South => Some(-Vec2::Y),
+            Side::East => Some(Vec2::X),
+            Side::West => Some(-Vec2::X),
+            Side::Around => None,
+        }
+    }
+}
+
+/// What a timeline event does when its time comes.
+#[derive(Deserialize)]
+pub enum TimelineAction {
+    Group {
+        kind: MonsterKind,
+        count: u32,
+        from: Side,
+    },
+    Boss {
+        kind: MonsterKind,
+        scale: f32,
+        /// Blasts it takes to bring down, just one if left out.
+        #[serde(default)]
+        hits: u32,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct TimelineEvent {
+    pub at_seconds: f32,
+    pub action: TimelineAction,
+}
+
+impl StageDef {
+    /// Built-in stage used when no stage files could be loaded.
+    fn fallback() -> StageDef {
+        StageDef{
+            name: String::from("Meadow"),
+            ground: GroundDef{
+                light: (0.22, 0.3, 0.2),
+                dark: (0.19, 0.27, 0.17),
+            },
+            obstacles: ObstacleDef{
+                count: 16,
+                shape: ObstacleShape::Circle,
+                radius: 24.0,
+                spawn_radius: 800.0,
+                clear_radius: 120.0,
+                color: (0.35, 0.3, 0.25),
+            },
+            spawn_table: vec![
+                SpawnEntry{
+                    kind: MonsterKind::Grunt,
+                    weight: 1,
+                },
+            ],
+            bosses: Vec::new(),
+            timeline: Vec::new(),
+            arena: None,
+        }
+    }
+
+    /// Pick a monster kind from the spawn table, weighted by entry.
+    pub fn roll_monster(&self) -> MonsterKind {
+        let total: u32 = self.spawn_table.iter().map(|e| e.weight).sum();
+        if total == 0 {
+            return MonsterKind::Grunt;
+        }
+
+        let mut roll = rand::random::<u32>() % total;
+        for entry in self.spawn_table.iter() {
+            if roll < entry.weight {
+                return entry.kind;
+            }
+            roll -= entry.weight;
+        }
+
+        MonsterKind::Grunt
+    }
+}
+
+pub struct Stages {
+    pub list: Vec<StageDef>,
+    pub selected: usize,
+}
+
+impl Stages {
+    pub fn load() -> Stages {
+        let mut list = load_stage_files(Path::new(STAGE_DIRECTORY));
+        if list.is_empty() {
+            warn!("no stages found in {}, using built-in stage", STAGE_DIRECTORY);
+            list.push(StageDef::fallback());
+        }
+
+        // Kinds from mods turn up everywhere
+        for stage in list.iter_mut() {
+            stage.spawn_table.extend(
+                mods::monster_weights()
+                    .filter(|&(_, weight)| weight > 0)
+                    .map(|(kind, weight)| SpawnEntry{ kind, weight }),
+            );
+        }
+
+        Stages{
+            list,
+            selected: 0,
+        }
+    }
+
+    pub fn current(&self) -> &StageDef {
+        &self.list[self.selected]
+    }
+}
+
+fn load_stage_files(directory: &Path) -> Vec<StageDef> {
+    let entries = match files::list(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("failed to read {}: {}", directory.display(), e);
+            return Vec::new();
+        }
+    };
+
+    // Sort by file name so the menu order is stable
+    let mut paths: Vec<_> = entries
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "ron"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            let parsed = files::read_to_string(path)
+                .and_then(|text| ron::from_str::<StageDef>(&text).map_err(|e| e.to_string()));
+
+            match parsed {
+                Ok(mut stage) => {
+                    if let Some(name) = path.file_name() {
+                        stage.timeline.extend(load_timeline(&directory.join(TIMELINE_DIRECTORY).join(name)));
+                    }
+                    stage.bosses.sort_by(|a, b| a.at_seconds.total_cmp(&b.at_seconds));
+                    stage.timeline.sort_by(|a, b| a.at_seconds.total_cmp(&b.at_seconds));
+                    Some(stage)
+                }
+                Err(e) => {
+                    warn!("skipping stage {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The events in a stage's timeline file, if it has one.
+fn load_timeline(path: &Path) -> Vec<TimelineEvent> {
+    if !files::exists(path) {
+        return Vec::new();
+    }
+
+    files::read_to_string(path)
+        .and_then(|text| ron::from_str::<Vec<TimelineEvent>>(&text).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            warn!("skipping timeline {}: {}", path.display(), e);
+            Vec::new()
+        })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod files {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Everything directly in `directory`.
+    pub fn list(directory: &Path) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(directory).map_err(|e| e.to_string())?;
+        Ok(entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+    }
+
+    pub fn exists(path: &Path) -> bool {
+        path.exists()
+    }
+
+    pub fn read_to_string(path: &Path) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod files {
+    use std::path::{Path, PathBuf};
+
+    /// The shipped stage files by path, with what's in them.
+    const BUILT_IN: &[(&str, &str)] = &[
+        ("assets/stages/01_meadow.ron", include_str!("../assets/stages/01_meadow.ron")),
+        ("assets/stages/02_badlands.ron", include_str!("../assets/stages/02_badlands.ron")),
+        ("assets/stages/03_colosseum.ron", include_str!("../assets/stages/03_colosseum.ron")),
+        ("assets/stages/timelines/02_badlands.ron", include_str!("../assets/stages/timelines/02_badlands.ron")),
+    ];
+
+    /// The built-in files directly in `directory`.
+    pub fn list(directory: &Path) -> Result<Vec<PathBuf>, String> {
+        Ok(BUILT_IN
+            .iter()
+            .map(|&(path, _)| PathBuf::from(path))
+            .filter(|path| path.parent() == Some(directory))
+            .collect())
+    }
+
+    pub fn exists(path: &Path) -> bool {
+        read_to_string(path).is_ok()
+    }
+
+    pub fn read_to_string(path: &Path) -> Result<String, String> {
+        BUILT_IN
+            .iter()
+            .find(|&&(built_in, _)| Path::new(built_in) == path)
+            .map(|&(_, text)| text.to_string())
+            .ok_or_else(|| format!("{} isn't built in", path.display()))
+    }
+}
+
+#[derive(Component)]
+pub struct Boss;
+
+#[derive(Default)]
+pub struct BossSchedule {
+    elapsed: f32,
+    next: usize,
+}
+
+impl BossSchedule {
+    pub fn clear(&mut self) {
+        self.elapsed = 0.0;
+        self.next = 0;
+    }
+}
+
+pub fn spawn_bosses(
+    time: Res<Time>,
+    mut schedule: ResMut<BossSchedule>,
+    stages: Res<Stages>,
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    players: Query<&Position, With<Player>>,
+    mut monster_stats: ResMut<MonsterStats>,
+    arena: Res<Arena>,
+) {
+    schedule.elapsed += time.delta_seconds();
+
+    let targets = player_positions(&players);
+
+    let bosses = &stages.current().bosses;
+    while let Some(boss) = bosses.get(schedule.next) {
+        if boss.at_seconds > schedule.elapsed {
+            break;
+        }
+
+        let target = if let Some(p) = random_player(&targets) {
+            p
+        }
+        else {
+            return
+        };
+
+        let position = arena
+            .spawn_point()
+            .unwrap_or_else(|| target + random_unit() * MONSTER_SPAWN_DISTANCE);
+        spawn_boss(&mut commands, &sprites, boss.kind, position, boss.scale, boss.hits);
+
+        monster_stats.record_spawn(boss.kind);
+        schedule.next += 1;
+    }
+}
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    sprites: &GameAssets,
+    kind: MonsterKind,
+    position: Vec2,
+    scale: f32,
+    hits: u32,
+) -> Entity {
+    let entity = spawn_monster_kind(commands, sprites, kind, position, scale);
+    commands.entity(entity).insert(Boss).insert(Horde::tough(hits.max(1)));
+    entity
+}
@@ -0,0 +1,224 @@
+//! Where the game keeps what it saves between sessions. Native builds read
+//! and write files relative to the working directory. Browser builds have no
+//! filesystem, so each file becomes a `localStorage` entry keyed by its path,
+//! and appending rewrites the whole entry. Captures are binary and stay
+//! native only.
+//!
+//! The clock lives here too, since `SystemTime` panics in the browser.
+//!
+//! What the game saves for itself, rather than files players edit like
+//! `controls.ron`, is JSON wrapped with the version of its layout. Changing a
+//! layout means adding a migration that brings saves from the version before
+//! up to date, and loading runs an old save through every migration since
+//! instead of throwing it away. Files from before versioning are version 0.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use backend::{append, exists, read_to_string, unix_millis, write};
+
+
+/// Brings a save's data up from one layout version to the next.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// Something saved with the version of its layout. The version is how many
+/// migrations there are, so each layout change adds one to the end.
+pub trait Versioned: Serialize + DeserializeOwned {
+    const MIGRATIONS: &'static [Migration];
+}
+
+#[derive(Serialize)]
+struct SavedOut<'a, T> {
+    version: usize,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedIn {
+    version: usize,
+    data: Value,
+}
+
+/// Read a versioned save, migrating it to the current layout.
+pub fn load<T: Versioned>(path: &str) -> Result<T, String> {
+    let text = read_to_string(path)?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+
+    // Saves from before versioning are the bare data
+    let (version, mut data) = match serde_json::from_value::<SavedIn>(value.clone()) {
+        Ok(saved) => (saved.version, saved.data),
+        Err(_) => (0, value),
+    };
+
+    let current = T::MIGRATIONS.len();
+    if version > current {
+        return Err(format!("saved in layout {} by a newer version, this one reads up to {}", version, current));
+    }
+    for migration in &T::MIGRATIONS[version..] {
+        data = migration(data)?;
+    }
+
+    serde_json::from_value(data).map_err(|e| e.to_string())
+}
+
+/// Replace a versioned save with `value` in the current layout.
+pub fn save<T: Versioned>(path: &str, value: &T) -> Result<(), String> {
+    let saved = SavedOut{
+        version: T::MIGRATIONS.len(),
+        data: value,
+    };
+    let json = serde_json::to_string(&saved).map_err(|e| e.to_string())?;
+    write(path, &json)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn create_parent(path: &str) -> Result<(), String> {
+        match Path::new(path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent).map_err(|e| e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn read_to_string(path: &str) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|e| e.to_string())
+    }
+
+    pub fn exists(path: &str) -> bool {
+        Path::new(path).exists()
+    }
+
+    /// Replace the file, creating its directory if needed.
+    pub fn write(path: &str, contents: &str) -> Result<(), String> {
+        create_parent(path)?;
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+
+    pub fn append(path: &str, contents: &str) -> Result<(), String> {
+        create_parent(path)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn unix_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use web_sys::Storage;
+
+    fn local_storage() -> Result<Storage, String> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| "local storage is unavailable".to_string())
+    }
+
+    pub fn read_to_string(path: &str) -> Result<String, String> {
+        local_storage()?
+            .get_item(path)
+            .map_err(|e| format!("{:?}", e))?
+            .ok_or_else(|| format!("{} not found", path))
+    }
+
+    pub fn exists(path: &str) -> bool {
+        read_to_string(path).is_ok()
+    }
+
+    pub fn write(path: &str, contents: &str) -> Result<(), String> {
+        local_storage()?
+            .set_item(path, contents)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    pub fn append(path: &str, contents: &str) -> Result<(), String> {
+        let existing = read_to_string(path).unwrap_or_default();
+        write(path, &(existing + contents))
+    }
+
+    pub fn unix_millis() -> u64 {
+        js_sys::Date::now() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A layout that went from `{"count": n}` to `{"total": n}` to
+    /// `{"total": n, "best": n}`.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Tally {
+        total: u32,
+        best: u32,
+    }
+
+    fn rename_count(mut data: Value) -> Result<Value, String> {
+        let count = data.get("count").cloned().ok_or("no count")?;
+        data["total"] = count;
+        data.as_object_mut().unwrap().remove("count");
+        Ok(data)
+    }
+
+    fn add_best(mut data: Value) -> Result<Value, String> {
+        data["best"] = data["total"].clone();
+        Ok(data)
+    }
+
+    impl Versioned for Tally {
+        const MIGRATIONS: &'static [Migration] = &[rename_count, add_best];
+    }
+
+    fn saved(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("swarm-storage-{}-{}.json", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_unwrapped_save_as_version_zero() {
+        let path = saved("unwrapped", r#"{"count": 3}"#);
+        assert_eq!(load::<Tally>(&path), Ok(Tally{ total: 3, best: 3 }));
+    }
+
+    #[test]
+    fn runs_every_migration_since_the_saved_version() {
+        let path = saved("chain", r#"{"version": 1, "data": {"total": 5}}"#);
+        assert_eq!(load::<Tally>(&path), Ok(Tally{ total: 5, best: 5 }));
+
+        let path = saved("current", r#"{"version": 2, "data": {"total": 5, "best": 9}}"#);
+        assert_eq!(load::<Tally>(&path), Ok(Tally{ total: 5, best: 9 }));
+    }
+
+    #[test]
+    fn rejects_save_from_newer_layout() {
+        let path = saved("newer", r#"{"version": 3, "data": {"total": 5, "best": 9}}"#);
+        assert!(load::<Tally>(&path).is_err());
+    }
+
+    #[test]
+    fn saves_in_current_layout() {
+        let path = saved("round_trip", "");
+        save(&path, &Tally{ total: 2, best: 7 }).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), r#"{"version":2,"data":{"total":2,"best":7}}"#);
+        assert_eq!(load::<Tally>(&path), Ok(Tally{ total: 2, best: 7 }));
+    }
+}
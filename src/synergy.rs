@@ -0,0 +1,151 @@
+//! Synergy tags. Weapons and upgrades carry tags for what they're good at,
+//! and the level up screen leans its offers toward the tags a build already
+//! has, so a run drifts into an archetype rather than a grab bag. Players who'd
+//! rather take their chances can switch the offers back to pure random in the
+//! menu with O, and the choice is kept between sessions.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::storage::{self, Versioned};
+
+
+const SETTING_FILE: &str = "offers.json";
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum Tag {
+    /// Hits more of the crowd at once.
+    Area,
+    /// Fires more or further.
+    Projectile,
+    /// Keeps the players going for longer.
+    Duration,
+    Speed,
+}
+
+impl Tag {
+    pub const ALL: [Tag; 4] = [Tag::Area, Tag::Projectile, Tag::Duration, Tag::Speed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::Area => "area",
+            Tag::Projectile => "projectile",
+            Tag::Duration => "duration",
+            Tag::Speed => "speed",
+        }
+    }
+}
+
+/// The tags in `tags` by name, like `[area, speed]`, or nothing if none.
+pub fn tag_label(tags: &[Tag]) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+
+    let names: Vec<_> = tags.iter().map(|tag| tag.name()).collect();
+    format!(" [{}]", names.join(", "))
+}
+
+/// How the level up screen picks its offers.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Offers {
+    /// Leaning toward the build's tags.
+    #[default]
+    Synergy,
+    Random,
+}
+
+impl Versioned for Offers {
+    const MIGRATIONS: &'static [storage::Migration] = &[];
+}
+
+impl Offers {
+    pub fn load() -> Offers {
+        if !storage::exists(SETTING_FILE) {
+            return Offers::default();
+        }
+
+        storage::load(SETTING_FILE).unwrap_or_else(|e| {
+            warn!("ignoring {}: {}", SETTING_FILE, e);
+            Offers::default()
+        })
+    }
+
+    fn save(self) {
+        if let Err(e) = storage::save(SETTING_FILE, &self) {
+            warn!("failed to save {}: {}", SETTING_FILE, e);
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Offers::Synergy => "lean toward your build",
+            Offers::Random => "random",
+        }
+    }
+
+    fn toggled(self) -> Offers {
+        match self {
+            Offers::Synergy => Offers::Random,
+            Offers::Random => Offers::Synergy,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OfferPanelText;
+
+fn panel_label(offers: Offers) -> String {
+    format!("level up offers: {} (O)", offers.name())
+}
+
+pub fn spawn_offer_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    offers: Res<Offers>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(228.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*offers),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(OfferPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn offer_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut offers: ResMut<Offers>,
+    mut texts: Query<&mut Text, With<OfferPanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::O) {
+        return;
+    }
+
+    *offers = offers.toggled();
+    offers.save();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*offers);
+    }
+}
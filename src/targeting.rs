@@ -0,0 +1,283 @@
+//! Where and when weapons fire. Each weapon has a targeting strategy, picked
+//! in the weapons panel on the title screen, that chooses one monster in range
+//! to aim at, or aims at the player themself or the mouse cursor. With nothing
+//! to aim at, weapons go off on the player. The Seeker upgrade overrides the
+//! strategy and drops blasts on the biggest crowd nearby instead. The panel
+//! also switches between automatic fire and firing on a key press.
+
+use std::collections::BTreeMap;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::args::Weapon;
+use crate::camera::{screen_to_world, CameraFocus};
+use crate::horde::Horde;
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::progression::Progression;
+use crate::spatial::SpatialIndex;
+use crate::RunDefaults;
+
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+const BLAST_AIM_RANGE: f32 = 150.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Targeting {
+    Nearest,
+    Strongest,
+    Densest,
+    Random,
+    OnSelf,
+    Cursor,
+}
+
+/// A monster a weapon could aim at.
+pub struct Target {
+    pub position: Vec2,
+    pub toughness: u32,
+}
+
+/// Everything a strategy gets to choose from.
+pub struct Aim<'a> {
+    pub origin: Vec2,
+    pub blast_radius: f32,
+    pub targets: Vec<Target>,
+    pub index: &'a SpatialIndex,
+    /// Where the mouse points, for the first player only.
+    pub cursor: Option<Vec2>,
+}
+
+impl Targeting {
+    const ALL: [Targeting; 6] = [
+        Targeting::Nearest,
+        Targeting::Strongest,
+        Targeting::Densest,
+        Targeting::Random,
+        Targeting::OnSelf,
+        Targeting::Cursor,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Targeting::Nearest => "nearest",
+            Targeting::Strongest => "strongest",
+            Targeting::Densest => "densest",
+            Targeting::Random => "random",
+            Targeting::OnSelf => "self",
+            Targeting::Cursor => "cursor",
+        }
+    }
+
+    fn next(self) -> Targeting {
+        let i = Targeting::ALL.iter().position(|&t| t == self).unwrap_or_default();
+        Targeting::ALL[(i + 1) % Targeting::ALL.len()]
+    }
+
+    fn selector(self) -> fn(&Aim) -> Option<Vec2> {
+        match self {
+            Targeting::Nearest => nearest,
+            Targeting::Strongest => strongest,
+            Targeting::Densest => densest,
+            Targeting::Random => random,
+            Targeting::OnSelf => on_self,
+            Targeting::Cursor => cursor,
+        }
+    }
+
+    pub fn pick(self, aim: &Aim) -> Option<Vec2> {
+        (self.selector())(aim)
+    }
+}
+
+fn nearest(aim: &Aim) -> Option<Vec2> {
+    aim.targets
+        .iter()
+        .map(|t| t.position)
+        .min_by(|a, b| a.distance_squared(aim.origin).total_cmp(&b.distance_squared(aim.origin)))
+}
+
+fn strongest(aim: &Aim) -> Option<Vec2> {
+    // Ties go to the nearest
+    aim.targets
+        .iter()
+        .max_by(|a, b| {
+            a.toughness.cmp(&b.toughness).then(
+                b.position.distance_squared(aim.origin).total_cmp(&a.position.distance_squared(aim.origin)),
+            )
+        })
+        .map(|t| t.position)
+}
+
+fn densest(aim: &Aim) -> Option<Vec2> {
+    aim.targets
+        .iter()
+        .map(|t| t.position)
+        .max_by_key(|&p| aim.index.overlap_circle(p, aim.blast_radius).count())
+}
+
+fn random(aim: &Aim) -> Option<Vec2> {
+    if aim.targets.is_empty() {
+        return None;
+    }
+
+    Some(aim.targets[rand::random::<usize>() % aim.targets.len()].position)
+}
+
+fn on_self(aim: &Aim) -> Option<Vec2> {
+    Some(aim.origin)
+}
+
+fn cursor(aim: &Aim) -> Option<Vec2> {
+    aim.cursor
+}
+
+/// What the blast weapon needs to decide where each blast goes off.
+#[derive(SystemParam)]
+pub struct BlastAiming<'w, 's> {
+    weapon_aim: Res<'w, WeaponAim>,
+    progression: Res<'w, Progression>,
+    index: Res<'w, SpatialIndex>,
+    windows: Res<'w, Windows>,
+    focus: Res<'w, CameraFocus>,
+    hordes: Query<'w, 's, &'static Horde>,
+}
+
+impl BlastAiming<'_, '_> {
+    /// Where a blast of `radius` from `weapon`, fired by `player` standing at
+    /// `origin`, goes off. Only monsters in range and in view are aimed at.
+    pub fn target(&self, weapon: Weapon, player: usize, origin: Vec2, radius: f32) -> Vec2 {
+        let index = &*self.index;
+
+        if let Some(range_factor) = self.progression.seeker_range_factor() {
+            let cluster = index
+                .densest_cluster(origin, BLAST_AIM_RANGE * range_factor)
+                .filter(|&cluster| index.line_of_sight(origin, cluster));
+            if let Some(cluster) = cluster {
+                return cluster;
+            }
+        }
+
+        let cursor = self
+            .windows
+            .get_primary()
+            .filter(|_| player == 0)
+            .and_then(|w| Some((w.cursor_position()?, Vec2::new(w.width(), w.height()))))
+            .map(|(cursor, size)| screen_to_world(cursor, size, &self.focus));
+
+        let aim = Aim{
+            origin,
+            blast_radius: radius,
+            targets: index
+                .overlap_circle(origin, BLAST_AIM_RANGE)
+                .filter(|(_, position)| index.line_of_sight(origin, *position))
+                .map(|(entity, position)| Target{
+                    position,
+                    toughness: self.hordes.get(entity).map(|h| h.health()).unwrap_or(1),
+                })
+                .collect(),
+            index,
+            cursor,
+        };
+        self.weapon_aim.of(weapon).pick(&aim).unwrap_or(origin)
+    }
+}
+
+/// Whether weapons go off on their own or wait for each player's fire key.
+/// Autopiloted players always fire automatically.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FireMode {
+    Auto,
+    Manual,
+}
+
+impl FireMode {
+    fn name(self) -> &'static str {
+        match self {
+            FireMode::Auto => "auto",
+            FireMode::Manual => "on key",
+        }
+    }
+
+    fn toggled(self) -> FireMode {
+        match self {
+            FireMode::Auto => FireMode::Manual,
+            FireMode::Manual => FireMode::Auto,
+        }
+    }
+}
+
+/// The targeting strategy picked for each weapon. Kept between runs.
+#[derive(Default)]
+pub struct WeaponAim(BTreeMap<Weapon, Targeting>);
+
+impl WeaponAim {
+    pub fn of(&self, weapon: Weapon) -> Targeting {
+        self.0.get(&weapon).copied().unwrap_or(Targeting::Nearest)
+    }
+}
+
+#[derive(Component)]
+pub struct WeaponPanelText;
+
+fn panel_label(weapon: Weapon, targeting: Targeting, fire_mode: FireMode) -> String {
+    format!("{} aims at: {} (Tab), fires: {} (M)", weapon.name(), targeting.name(), fire_mode.name())
+}
+
+pub fn spawn_weapon_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    defaults: Res<RunDefaults>,
+    aim: Res<WeaponAim>,
+    fire_mode: Res<FireMode>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(12.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(defaults.weapon, aim.of(defaults.weapon), *fire_mode),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(WeaponPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn weapon_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    defaults: Res<RunDefaults>,
+    mut aim: ResMut<WeaponAim>,
+    mut fire_mode: ResMut<FireMode>,
+    mut texts: Query<&mut Text, With<WeaponPanelText>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        let targeting = aim.of(defaults.weapon).next();
+        aim.0.insert(defaults.weapon, targeting);
+    }
+    else if keyboard_input.just_pressed(KeyCode::M) {
+        *fire_mode = fire_mode.toggled();
+    }
+    else {
+        return;
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(defaults.weapon, aim.of(defaults.weapon), *fire_mode);
+    }
+}
@@ -0,0 +1,278 @@
+//! Run telemetry for balance work, toggled in the menu with K. Each finished
+//! run is summed up without anything that says who played it: how long it
+//! lasted, the upgrades taken and what landed the lethal hit. By default the
+//! summaries only go to `runs/telemetry.jsonl` on this computer. Builds with
+//! the `online` feature can opt in to sharing them too, in batches sent to
+//! `SWARM_TELEMETRY_URL`, and a batch that can't be sent waits for the next.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive::Adaptive;
+use crate::autopilot::DemoMode;
+use crate::content::Content;
+use crate::escort::Escort;
+use crate::leaderboard::GAME_VERSION;
+use crate::loading::GameAssets;
+use crate::menu::MenuRoot;
+use crate::progression::Progression;
+use crate::run_stats::{GameOverEvent, RunStats, RUN_DIRECTORY};
+use crate::stage::Stages;
+use crate::storage::{self, Versioned};
+use crate::{MonsterStats, PlayerCount};
+
+
+const SETTING_FILE: &str     = "telemetry.json";
+/// Where the setting was kept before saves were versioned.
+const OLD_SETTING_FILE: &str = "telemetry.ron";
+const LOG_FILE: &str         = "telemetry.jsonl";
+const BATCH_FILE: &str       = "telemetry-batch.json";
+/// Runs gathered before a batch is sent.
+const BATCH_SIZE: usize      = 10;
+
+const PANEL_FONT_SIZE: f32 = 18.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Telemetry {
+    Off,
+    /// Kept on this computer only.
+    #[default]
+    Local,
+    /// Kept here and sent for balance analysis too.
+    Shared,
+}
+
+impl Versioned for Telemetry {
+    const MIGRATIONS: &'static [storage::Migration] = &[];
+}
+
+impl Telemetry {
+    pub fn load() -> Telemetry {
+        if !storage::exists(SETTING_FILE) {
+            return Telemetry::load_old().unwrap_or_default();
+        }
+
+        storage::load(SETTING_FILE).unwrap_or_else(|e| {
+            warn!("ignoring {}: {}", SETTING_FILE, e);
+            Telemetry::default()
+        })
+    }
+
+    /// The setting from before it moved to JSON, carried over once so that
+    /// players who opted out stay out.
+    fn load_old() -> Option<Telemetry> {
+        let text = storage::read_to_string(OLD_SETTING_FILE).ok()?;
+        let telemetry: Telemetry = ron::from_str(&text)
+            .map_err(|e| warn!("ignoring {}: {}", OLD_SETTING_FILE, e))
+            .ok()?;
+        telemetry.save();
+        Some(telemetry)
+    }
+
+    fn save(self) {
+        if let Err(e) = storage::save(SETTING_FILE, &self) {
+            warn!("failed to save {}: {}", SETTING_FILE, e);
+        }
+    }
+
+    /// Sharing is only on offer in builds that can send.
+    fn next(self) -> Telemetry {
+        match self {
+            Telemetry::Off => Telemetry::Local,
+            Telemetry::Local if cfg!(feature = "online") => Telemetry::Shared,
+            _ => Telemetry::Off,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Telemetry::Off => "off",
+            Telemetry::Local => "kept on this computer",
+            Telemetry::Shared => "shared for balancing",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunSample {
+    version: String,
+    stage: String,
+    players: usize,
+    escort: bool,
+    /// Whether adaptive difficulty was on.
+    #[serde(default)]
+    adaptive: bool,
+    duration_seconds: f32,
+    level: u32,
+    kills: u32,
+    upgrades: Vec<(String, u32)>,
+    cause_of_death: Option<String>,
+}
+
+fn log_file() -> String {
+    format!("{}/{}", RUN_DIRECTORY, LOG_FILE)
+}
+
+fn batch_file() -> String {
+    format!("{}/{}", RUN_DIRECTORY, BATCH_FILE)
+}
+
+fn load_batch() -> Vec<RunSample> {
+    storage::read_to_string(&batch_file())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_batch(batch: &[RunSample]) {
+    let saved = serde_json::to_string(batch)
+        .map_err(|e| e.to_string())
+        .and_then(|json| storage::write(&batch_file(), &json));
+    if let Err(e) = saved {
+        warn!("failed to save {}: {}", batch_file(), e);
+    }
+}
+
+/// Send a full batch off on its own thread, putting it back to wait for the
+/// next one if it doesn't get through.
+#[cfg(feature = "online")]
+fn send_batch(batch: Vec<RunSample>) {
+    const URL_VARIABLE: &str = "SWARM_TELEMETRY_URL";
+
+    let url = match std::env::var(URL_VARIABLE) {
+        Ok(url) => url,
+        Err(_) => {
+            info!("{} is not set, keeping telemetry for later", URL_VARIABLE);
+            save_batch(&batch);
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        let json = match serde_json::to_string(&batch) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        let sent = ureq::post(&url)
+            .set("Content-Type", "application/json")
+            .send_string(&json);
+        if let Err(e) = sent {
+            info!("failed to send telemetry, keeping it for later: {}", e);
+            let mut waiting = load_batch();
+            waiting.extend(batch);
+            save_batch(&waiting);
+        }
+    });
+}
+
+#[cfg(not(feature = "online"))]
+fn send_batch(batch: Vec<RunSample>) {
+    save_batch(&batch);
+}
+
+pub fn record_telemetry(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    telemetry: Res<Telemetry>,
+    demo: Res<DemoMode>,
+    escort: Res<Escort>,
+    adaptive: Res<Adaptive>,
+    stages: Res<Stages>,
+    player_count: Res<PlayerCount>,
+    progression: Res<Progression>,
+    monster_stats: Res<MonsterStats>,
+    run_stats: Res<RunStats>,
+) {
+    if game_over_reader.iter().next().is_none() || demo.is_active() || *telemetry == Telemetry::Off {
+        return;
+    }
+
+    let sample = RunSample{
+        version: GAME_VERSION.to_string(),
+        stage: stages.current().name.clone(),
+        players: player_count.0,
+        escort: escort.is_active,
+        adaptive: *adaptive == Adaptive::On,
+        duration_seconds: run_stats.duration_seconds(),
+        level: progression.level(),
+        kills: monster_stats.killed(),
+        upgrades: progression
+            .upgrades()
+            .map(|(upgrade, level)| (upgrade.id().to_string(), level))
+            .collect(),
+        cause_of_death: run_stats.cause_of_death().map(str::to_string),
+    };
+
+    let logged = serde_json::to_string(&sample)
+        .map_err(|e| e.to_string())
+        .and_then(|json| storage::append(&log_file(), &format!("{}\n", json)));
+    if let Err(e) = logged {
+        warn!("failed to save telemetry: {}", e);
+    }
+
+    if *telemetry != Telemetry::Shared {
+        return;
+    }
+    let mut batch = load_batch();
+    batch.push(sample);
+    if batch.len() < BATCH_SIZE {
+        save_batch(&batch);
+    }
+    else {
+        save_batch(&[]);
+        send_batch(batch);
+    }
+}
+
+#[derive(Component)]
+pub struct TelemetryPanelText;
+
+fn panel_label(telemetry: Telemetry) -> String {
+    format!("telemetry: {} (K)", telemetry.description())
+}
+
+pub fn spawn_telemetry_panel(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    telemetry: Res<Telemetry>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(204.0),
+                    left: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                panel_label(*telemetry),
+                TextStyle {
+                    font: assets.font(),
+                    font_size: PANEL_FONT_SIZE,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(TelemetryPanelText)
+        .insert(MenuRoot);
+}
+
+pub fn telemetry_panel_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut telemetry: ResMut<Telemetry>,
+    mut texts: Query<&mut Text, With<TelemetryPanelText>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::K) {
+        return;
+    }
+
+    *telemetry = telemetry.next();
+    telemetry.save();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = panel_label(*telemetry);
+    }
+}
@@ -0,0 +1,59 @@
+//! The spawn budget. Rather than capping how many monsters are out, each kind
+//! costs threat points by how dangerous it is, and the spawners only add
+//! monsters while the total stays within the budget. A boss costs as much as
+//! a crowd of small monsters, so the trickle thins out while one is up, and a
+//! budget spent on brutes leaves less room for grunts.
+
+use bevy::prelude::*;
+
+use crate::horde::Horde;
+use crate::stage::Boss;
+use crate::{Monster, MonsterKind};
+
+
+const THREAT_BUDGET: u32       = 500;
+const BOSS_THREAT_PER_HIT: u32 = 2;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct MeasureThreat;
+
+/// The threat of every monster out, counting what's been spawned since it
+/// was last measured.
+#[derive(Default)]
+pub struct Threat {
+    current: u32,
+}
+
+impl Threat {
+    pub fn can_afford(&self, cost: u32) -> bool {
+        self.current + cost <= THREAT_BUDGET
+    }
+
+    pub fn spend(&mut self, cost: u32) {
+        self.current += cost;
+    }
+}
+
+pub type ThreatSources<'w, 's> = Query<
+    'w,
+    's,
+    (&'static MonsterKind, Option<&'static Horde>, Option<&'static Boss>),
+    With<Monster>,
+>;
+
+pub fn measure_threat(
+    mut threat: ResMut<Threat>,
+    monsters: ThreatSources,
+) {
+    threat.current = monsters
+        .iter()
+        .map(|(kind, horde, boss)| {
+            let weight = match (horde, boss) {
+                (Some(horde), Some(_)) => BOSS_THREAT_PER_HIT * horde.max_health(),
+                (Some(horde), None) => horde.members(),
+                (None, _) => 1,
+            };
+            kind.threat() * weight
+        })
+        .sum();
+}
@@ -0,0 +1,153 @@
+//! Touch controls for phones and tablets. They switch on by themselves the
+//! first time the screen is touched. A finger put down on the left half of
+//! the screen becomes a virtual stick that steers the first player, drawn
+//! where the finger landed. A quick tap on the right half makes them dash.
+
+use bevy::prelude::*;
+
+use crate::autopilot::Autopilot;
+use crate::{Player, Velocity};
+
+
+const STICK_RADIUS: f32 = 60.0;
+const KNOB_SIZE: f32    = 48.0;
+/// A touch that moves less than this before lifting counts as a tap.
+const TAP_DISTANCE: f32 = 12.0;
+
+/// Sent when the first player taps to dash.
+pub struct TouchTap;
+
+#[derive(Default)]
+pub struct TouchControls {
+    is_active: bool,
+    /// The finger on the stick and where it came down.
+    stick: Option<(u64, Vec2)>,
+    direction: Vec2,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum StickPart {
+    Base,
+    Knob,
+}
+
+fn stick_node(size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            ..Default::default()
+        },
+        color: color.into(),
+        visibility: Visibility{
+            is_visible: false,
+        },
+        ..Default::default()
+    }
+}
+
+pub fn spawn_touch_stick(
+    mut commands: Commands,
+) {
+    commands
+        .spawn_bundle(stick_node(STICK_RADIUS * 2.0, Color::rgba(1.0, 1.0, 1.0, 0.15)))
+        .insert(StickPart::Base);
+    commands
+        .spawn_bundle(stick_node(KNOB_SIZE, Color::rgba(1.0, 1.0, 1.0, 0.4)))
+        .insert(StickPart::Knob);
+}
+
+pub fn despawn_touch_stick(
+    mut commands: Commands,
+    nodes: Query<Entity, With<StickPart>>,
+) {
+    for node in nodes.iter() {
+        commands.entity(node).despawn_recursive();
+    }
+}
+
+pub fn read_touches(
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+    mut controls: ResMut<TouchControls>,
+    mut taps: EventWriter<TouchTap>,
+) {
+    let width = match windows.get_primary() {
+        Some(window) => window.width(),
+        None => return,
+    };
+    if !controls.is_active && touches.iter().next().is_some() {
+        controls.is_active = true;
+    }
+
+    // Let go of the stick when its finger lifts
+    if let Some((id, _)) = controls.stick {
+        if touches.get_pressed(id).is_none() {
+            controls.stick = None;
+        }
+    }
+
+    for touch in touches.iter_just_pressed() {
+        if controls.stick.is_none() && touch.start_position().x < width / 2.0 {
+            controls.stick = Some((touch.id(), touch.start_position()));
+        }
+    }
+
+    for touch in touches.iter_just_released() {
+        let is_tap = touch.start_position().distance(touch.position()) < TAP_DISTANCE;
+        if is_tap && touch.start_position().x >= width / 2.0 {
+            taps.send(TouchTap);
+        }
+    }
+
+    // Touches are measured down from the top of the window
+    controls.direction = match controls.stick.and_then(|(id, origin)| Some((touches.get_pressed(id)?, origin))) {
+        Some((touch, origin)) => {
+            let offset = (touch.position() - origin) * Vec2::new(1.0, -1.0);
+            (offset / STICK_RADIUS).clamp_length_max(1.0)
+        }
+        None => Vec2::ZERO,
+    };
+}
+
+/// Runs after the keys and gamepads, and steers the first player while the
+/// stick is held.
+pub fn touch_to_move(
+    controls: Res<TouchControls>,
+    mut players: Query<(&Player, &mut Velocity), Without<Autopilot>>,
+) {
+    if controls.direction == Vec2::ZERO {
+        return;
+    }
+
+    for (_, mut v) in players.iter_mut().filter(|(player, _)| player.index == 0) {
+        v.direction = controls.direction.normalize_or_zero();
+    }
+}
+
+pub fn draw_touch_stick(
+    controls: Res<TouchControls>,
+    windows: Res<Windows>,
+    mut parts: Query<(&StickPart, &mut Style, &mut Visibility)>,
+) {
+    let height = match windows.get_primary() {
+        Some(window) => window.height(),
+        None => return,
+    };
+
+    let origin = controls.stick.filter(|_| controls.is_active).map(|(_, origin)| origin);
+    let knob = origin.map(|origin| origin + controls.direction * Vec2::new(1.0, -1.0) * STICK_RADIUS);
+
+    for (part, mut style, mut visibility) in parts.iter_mut() {
+        let (center, size) = match part {
+            StickPart::Base => (origin, STICK_RADIUS * 2.0),
+            StickPart::Knob => (knob, KNOB_SIZE),
+        };
+        visibility.is_visible = center.is_some();
+        if let Some(center) = center {
+            // UI is laid out from the bottom left corner
+            style.position.left = Val::Px(center.x - size / 2.0);
+            style.position.bottom = Val::Px(height - center.y - size / 2.0);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Fading trails behind things moving fast: spit in flight, dashing players
+//! and monsters thrown back by a shockwave. A trail remembers where its owner was over the
+//! last few frames and draws a shrinking, fading sprite at each spot.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::abilities::Knockback;
+use crate::dash::Dashing;
+use crate::loading::GameAssets;
+use crate::ranged::Spit;
+use crate::{MonsterKind, Player, Position};
+
+
+const TRAIL_POINTS: usize = 8;
+const TRAIL_DEPTH: f32    = -1.0;
+const TRAIL_ALPHA: f32    = 0.5;
+
+const SPIT_TRAIL_WIDTH: f32      = 8.0;
+const KNOCKBACK_TRAIL_WIDTH: f32 = 14.0;
+const DASH_TRAIL_WIDTH: f32      = 20.0;
+
+#[derive(Component)]
+pub struct Trail {
+    color: Color,
+    width: f32,
+    /// Where the owner was, newest first.
+    points: VecDeque<Vec2>,
+    segments: Vec<Entity>,
+}
+
+impl Trail {
+    fn new(color: Color, width: f32) -> Trail {
+        Trail{
+            color,
+            width,
+            points: VecDeque::with_capacity(TRAIL_POINTS),
+            segments: Vec::with_capacity(TRAIL_POINTS),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TrailSegment {
+    owner: Entity,
+}
+
+pub fn attach_trails(
+    mut commands: Commands,
+    spits: Query<Entity, Added<Spit>>,
+    knocked: Query<(Entity, &MonsterKind), Added<Knockback>>,
+    dashing: Query<Entity, (With<Player>, Added<Dashing>)>,
+) {
+    for spit in spits.iter() {
+        commands.entity(spit).insert(Trail::new(MonsterKind::Spitter.tint(), SPIT_TRAIL_WIDTH));
+    }
+    for (monster, kind) in knocked.iter() {
+        commands.entity(monster).insert(Trail::new(kind.tint(), KNOCKBACK_TRAIL_WIDTH));
+    }
+    for player in dashing.iter() {
+        commands.entity(player).insert(Trail::new(Color::WHITE, DASH_TRAIL_WIDTH));
+    }
+}
+
+/// Monsters only leave a trail while they're being thrown back, and players
+/// only while they're dashing.
+pub fn detach_trails(
+    mut commands: Commands,
+    settled: Query<(Entity, &MonsterKind, &Trail), Without<Knockback>>,
+    walking: Query<(Entity, &Player, &Trail), Without<Dashing>>,
+) {
+    for (monster, _, _) in settled.iter() {
+        commands.entity(monster).remove::<Trail>();
+    }
+    for (player, _, _) in walking.iter() {
+        commands.entity(player).remove::<Trail>();
+    }
+}
+
+pub fn update_trails(
+    mut commands: Commands,
+    sprites: Res<GameAssets>,
+    mut trails: Query<(Entity, &mut Trail, &Position)>,
+    mut segments: Query<(&mut Position, &mut TextureAtlasSprite), Without<Trail>>,
+) {
+    for (owner, mut trail, p) in trails.iter_mut() {
+        trail.points.push_front(p.current);
+        trail.points.truncate(TRAIL_POINTS);
+
+        // Grow the trail a segment at a time until it's full length
+        if trail.segments.len() < trail.points.len() {
+            let segment = commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: sprites.atlas(),
+                    sprite: TextureAtlasSprite{
+                        index: sprites.blast(),
+                        color: Color::NONE,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, TRAIL_DEPTH),
+                    ..Default::default()
+                })
+                .insert(TrailSegment{ owner })
+                .insert(Position::new(p.current))
+                .id();
+            trail.segments.push(segment);
+        }
+
+        // Older points are thinner and fainter
+        for (i, (&segment, &point)) in trail.segments.iter().zip(trail.points.iter()).enumerate() {
+            if let Ok((mut segment_p, mut sprite)) = segments.get_mut(segment) {
+                let fresh = 1.0 - i as f32 / TRAIL_POINTS as f32;
+                *segment_p = Position::new(point);
+                sprite.color = trail.color;
+                sprite.color.set_a(TRAIL_ALPHA * fresh);
+                sprite.custom_size = Some(Vec2::splat(trail.width * fresh));
+            }
+        }
+    }
+}
+
+/// Clear away the trails of owners that are gone or have stopped leaving one.
+pub fn clear_trails(
+    mut commands: Commands,
+    segments: Query<(Entity, &TrailSegment)>,
+    trails: Query<&Trail>,
+) {
+    for (entity, segment) in segments.iter() {
+        let is_kept = trails.get(segment.owner).is_ok_and(|t| t.segments.contains(&entity));
+        if !is_kept {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trail_stage() -> SystemStage {
+        SystemStage::single_threaded()
+            .with_system(attach_trails)
+            .with_system(detach_trails)
+    }
+
+    #[test]
+    fn a_player_leaves_a_trail_only_while_dashing() {
+        let mut world = World::new();
+        let mut stage = trail_stage();
+        let player = world
+            .spawn()
+            .insert(Player{ index: 0 })
+            .insert(Position::new(Vec2::ZERO))
+            .id();
+
+        stage.run(&mut world);
+        assert!(world.get::<Trail>(player).is_none());
+
+        world.entity_mut(player).insert(Dashing::new(Vec2::X));
+        stage.run(&mut world);
+        assert!(world.get::<Trail>(player).is_some());
+
+        world.entity_mut(player).remove::<Dashing>();
+        stage.run(&mut world);
+        assert!(world.get::<Trail>(player).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+//! Tweens: a value on an entity eased from one setting to another over a
+//! set time. A tween can drive a transform's scale or rotation, a sprite's
+//! color, a UI node's color or a text's color. An entity has at most one
+//! tween at a time, and a new one replaces whatever was playing.
+
+use bevy::prelude::*;
+
+
+/// How a tween's progress maps to how far along its value is.
+#[derive(Clone, Copy)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    CubicOut,
+}
+
+impl Ease {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Ease::CubicOut => 1.0 - (1.0 - t).powi(3),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum TweenTarget {
+    Scale { from: Vec3, to: Vec3 },
+    /// Around the z axis, in radians.
+    Rotation { from: f32, to: f32 },
+    SpriteColor { from: Color, to: Color },
+    UiColor { from: Color, to: Color },
+    /// The first section of a text.
+    TextColor { from: Color, to: Color },
+}
+
+#[derive(Component)]
+pub struct Tween {
+    target: TweenTarget,
+    ease: Ease,
+    timer: Timer,
+    despawn_when_done: bool,
+}
+
+impl Tween {
+    pub fn new(target: TweenTarget, ease: Ease, seconds: f32) -> Tween {
+        Tween{
+            target,
+            ease,
+            timer: Timer::from_seconds(seconds, false),
+            despawn_when_done: false,
+        }
+    }
+
+    /// Despawn the entity once the tween finishes.
+    pub fn then_despawn(mut self) -> Tween {
+        self.despawn_when_done = true;
+        self
+    }
+
+    /// Advance by `delta` and return the eased progress.
+    fn step(&mut self, delta: std::time::Duration) -> f32 {
+        self.ease.apply(self.timer.tick(delta).percent())
+    }
+
+    /// Drop the tween, or its entity, once it's done.
+    fn finish(&self, commands: &mut Commands, entity: Entity) {
+        if !self.timer.finished() {
+            return;
+        }
+
+        if self.despawn_when_done {
+            commands.entity(entity).despawn_recursive();
+        }
+        else {
+            commands.entity(entity).remove::<Tween>();
+        }
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = Vec4::from(from.as_rgba_f32());
+    let to = Vec4::from(to.as_rgba_f32());
+    let c = from.lerp(to, t);
+    Color::rgba(c.x, c.y, c.z, c.w)
+}
+
+pub fn tween_transforms(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut tweens: Query<(Entity, &mut Tween, &mut Transform)>,
+) {
+    for (entity, mut tween, mut transform) in tweens.iter_mut() {
+        match tween.target {
+            TweenTarget::Scale { from, to } => {
+                transform.scale = from.lerp(to, tween.step(time.delta()));
+            }
+            TweenTarget::Rotation { from, to } => {
+                let t = tween.step(time.delta());
+                transform.rotation = Quat::from_rotation_z(from + (to - from) * t);
+            }
+            _ => continue,
+        }
+        tween.finish(&mut commands, entity);
+    }
+}
+
+pub fn tween_sprite_colors(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut tweens: Query<(Entity, &mut Tween, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut tween, mut sprite) in tweens.iter_mut() {
+        if let TweenTarget::SpriteColor { from, to } = tween.target {
+            sprite.color = lerp_color(from, to, tween.step(time.delta()));
+            tween.finish(&mut commands, entity);
+        }
+    }
+}
+
+pub fn tween_ui_colors(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut tweens: Query<(Entity, &mut Tween, &mut UiColor)>,
+) {
+    for (entity, mut tween, mut color) in tweens.iter_mut() {
+        if let TweenTarget::UiColor { from, to } = tween.target {
+            color.0 = lerp_color(from, to, tween.step(time.delta()));
+            tween.finish(&mut commands, entity);
+        }
+    }
+}
+
+pub fn tween_text_colors(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut tweens: Query<(Entity, &mut Tween, &mut Text)>,
+) {
+    for (entity, mut tween, mut text) in tweens.iter_mut() {
+        if let TweenTarget::TextColor { from, to } = tween.target {
+            text.sections[0].style.color = lerp_color(from, to, tween.step(time.delta()));
+            tween.finish(&mut commands, entity);
+        }
+    }
+}
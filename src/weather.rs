@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+
+use crate::effective_stats::StatsChangedEvent;
+use crate::loading::GameAssets;
+use crate::NewGameEvent;
+
+
+const WEATHER_PERIOD_SECONDS: f32 = 45.0;
+const WEATHER_BANNER_SECONDS: f32 = 3.0;
+
+const RAIN_MONSTER_SPEED_FACTOR: f32 = 0.75;
+const FOG_BLAST_RADIUS_FACTOR: f32   = 0.7;
+
+const BANNER_FONT_SIZE: f32 = 40.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum WeatherKind {
+    Clear,
+    Rain,
+    Fog,
+}
+
+impl WeatherKind {
+    fn roll() -> WeatherKind {
+        match rand::random::<u32>() % 4 {
+            0 => WeatherKind::Rain,
+            1 => WeatherKind::Fog,
+            _ => WeatherKind::Clear,
+        }
+    }
+
+    fn overlay(self) -> Color {
+        match self {
+            WeatherKind::Clear => Color::NONE,
+            WeatherKind::Rain => Color::rgba(0.2, 0.25, 0.4, 0.25),
+            WeatherKind::Fog => Color::rgba(0.8, 0.8, 0.85, 0.35),
+        }
+    }
+
+    fn announcement(self) -> &'static str {
+        match self {
+            WeatherKind::Clear => "The skies clear",
+            WeatherKind::Rain => "Rain: monsters slowed",
+            WeatherKind::Fog => "Fog: blast radius reduced",
+        }
+    }
+}
+
+pub struct Weather {
+    kind: WeatherKind,
+    timer: Timer,
+}
+
+impl Weather {
+    pub fn new() -> Weather {
+        Weather{
+            kind: WeatherKind::Clear,
+            timer: Timer::from_seconds(WEATHER_PERIOD_SECONDS, true),
+        }
+    }
+
+    pub fn monster_speed_factor(&self) -> f32 {
+        if self.kind == WeatherKind::Rain {
+            RAIN_MONSTER_SPEED_FACTOR
+        }
+        else {
+            1.0
+        }
+    }
+
+    pub fn blast_radius_factor(&self) -> f32 {
+        if self.kind == WeatherKind::Fog {
+            FOG_BLAST_RADIUS_FACTOR
+        }
+        else {
+            1.0
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct WeatherOverlay;
+
+#[derive(Component)]
+pub struct WeatherBanner {
+    timer: Timer,
+}
+
+pub fn spawn_weather_hud(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(WeatherOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(48.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: assets.font(),
+                            font_size: BANNER_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(WeatherBanner{
+                    timer: Timer::from_seconds(WEATHER_BANNER_SECONDS, false),
+                });
+        });
+}
+
+pub fn despawn_weather_hud(
+    mut commands: Commands,
+    overlays: Query<Entity, With<WeatherOverlay>>,
+) {
+    for overlay in overlays.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+}
+
+pub fn reset_weather(
+    mut new_game_reader: EventReader<NewGameEvent>,
+    mut weather: ResMut<Weather>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+) {
+    if new_game_reader.iter().next().is_some() {
+        *weather = Weather::new();
+        stats_writer.send(StatsChangedEvent);
+    }
+}
+
+pub fn change_weather(
+    time: Res<Time>,
+    mut weather: ResMut<Weather>,
+    mut overlays: Query<&mut UiColor, With<WeatherOverlay>>,
+    mut banners: Query<(&mut WeatherBanner, &mut Text)>,
+    mut stats_writer: EventWriter<StatsChangedEvent>,
+) {
+    weather.timer.tick(time.delta());
+
+    // Roll new weather each period and announce it if it changed
+    if weather.timer.just_finished() {
+        let kind = WeatherKind::roll();
+        if kind != weather.kind {
+            weather.kind = kind;
+            stats_writer.send(StatsChangedEvent);
+
+            for (mut banner, mut text) in banners.iter_mut() {
+                banner.timer.reset();
+                text.sections[0].value = kind.announcement().to_string();
+            }
+        }
+    }
+
+    for mut color in overlays.iter_mut() {
+        color.0 = weather.kind.overlay();
+    }
+
+    // Fade the banner out over its lifetime
+    for (mut banner, mut text) in banners.iter_mut() {
+        banner.timer.tick(time.delta());
+        let alpha = 1.0 - banner.timer.percent();
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
@@ -0,0 +1,66 @@
+//! The game window: its title, size limits and icon. Size and vsync come from
+//! the launch options, and everything else is fixed here rather than left to
+//! Bevy's defaults.
+
+// Browser pages set their own icon
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+use bevy::prelude::*;
+use bevy::window::{WindowId, WindowMode, WindowResizeConstraints};
+use bevy::winit::WinitWindows;
+use winit::window::Icon;
+
+use crate::args::LaunchArgs;
+
+
+const WINDOW_TITLE: &str = "Swarm";
+const WINDOW_ICON: &str  = "assets/icon.png";
+
+const DEFAULT_WIDTH: f32  = 1280.0;
+const DEFAULT_HEIGHT: f32 = 720.0;
+/// Small enough for a split laptop screen, big enough to keep the HUD legible.
+const MIN_WIDTH: f32  = 640.0;
+const MIN_HEIGHT: f32 = 360.0;
+
+pub fn window_descriptor(args: &LaunchArgs) -> WindowDescriptor {
+    let (width, height) = args.window_size.unwrap_or((DEFAULT_WIDTH, DEFAULT_HEIGHT));
+
+    WindowDescriptor {
+        title: WINDOW_TITLE.to_string(),
+        width: width.max(MIN_WIDTH),
+        height: height.max(MIN_HEIGHT),
+        resize_constraints: WindowResizeConstraints {
+            min_width: MIN_WIDTH,
+            min_height: MIN_HEIGHT,
+            ..Default::default()
+        },
+        resizable: true,
+        vsync: !args.no_vsync,
+        mode: WindowMode::Windowed,
+        ..Default::default()
+    }
+}
+
+/// Bevy has no setting for the icon, so it's handed to the winit window once
+/// that exists.
+pub fn set_window_icon(
+    windows: NonSend<WinitWindows>,
+) {
+    let window = match windows.get_window(WindowId::primary()) {
+        Some(window) => window,
+        None => return,
+    };
+
+    let icon = image::open(WINDOW_ICON)
+        .map_err(|e| e.to_string())
+        .and_then(|image| {
+            let image = image.into_rgba8();
+            let (width, height) = image.dimensions();
+            Icon::from_rgba(image.into_raw(), width, height).map_err(|e| e.to_string())
+        });
+
+    match icon {
+        Ok(icon) => window.set_window_icon(Some(icon)),
+        Err(e) => warn!("failed to load {}: {}", WINDOW_ICON, e),
+    }
+}
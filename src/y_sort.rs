@@ -0,0 +1,47 @@
+//! Draw order by height on screen. Within its layer, whatever stands lower
+//! on the screen is drawn in front, so bodies overlap the way they would
+//! seen from above and to the front. Layers stay apart: the sort only moves
+//! a sprite a little way either side of its layer's depth.
+//!
+//! Sprites are ranked rather than given a depth straight from their height,
+//! so sprites at the same height keep the same order frame to frame and the
+//! steps between them stay well clear of float precision.
+
+use bevy::prelude::*;
+
+use crate::Position;
+
+
+/// How far either side of its layer a sprite can move.
+const Y_SORT_RANGE: f32 = 0.4;
+
+/// Sorts the entity by its position within `layer`.
+#[derive(Component)]
+pub struct SortByY {
+    pub layer: f32,
+}
+
+pub fn sort_by_y(
+    mut sorted: Query<(Entity, &SortByY, &Position, &mut Transform)>,
+) {
+    // Back to front: highest on screen first, then by entity among ties
+    let mut order: Vec<_> = sorted
+        .iter()
+        .map(|(entity, sort, p, _)| (sort.layer, p.current.y, entity))
+        .collect();
+    order.sort_by(|a, b| {
+        a.0.total_cmp(&b.0)
+            .then(b.1.total_cmp(&a.1))
+            .then(a.2.to_bits().cmp(&b.2.to_bits()))
+    });
+
+    // Spread each layer evenly over its range
+    for layer in order.chunk_by(|a, b| a.0 == b.0) {
+        let step = 2.0 * Y_SORT_RANGE / layer.len() as f32;
+        for (rank, &(depth, _, entity)) in layer.iter().enumerate() {
+            if let Ok((_, _, _, mut t)) = sorted.get_mut(entity) {
+                t.translation.z = depth - Y_SORT_RANGE + (rank as f32 + 0.5) * step;
+            }
+        }
+    }
+}